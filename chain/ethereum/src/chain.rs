@@ -22,15 +22,15 @@ use graph::{
     firehose::bstream,
     log::factory::{ComponentLoggerConfig, ElasticComponentLoggerConfig},
     prelude::{
-        async_trait, error, lazy_static, o, serde_json as json, web3::types::H256, BlockNumber,
-        ChainStore, EthereumBlockWithCalls, Future01CompatExt, Logger, LoggerFactory,
+        async_trait, error, futures03, info, lazy_static, o, serde_json as json, web3::types::H256,
+        BlockNumber, ChainStore, EthereumBlockWithCalls, Future01CompatExt, Logger, LoggerFactory,
         MetricsRegistry, NodeId, SubgraphStore,
     },
 };
 use prost::Message;
 use std::collections::HashSet;
 use std::iter::FromIterator;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use crate::data_source::DataSourceTemplate;
 use crate::data_source::UnresolvedDataSourceTemplate;
@@ -280,6 +280,12 @@ impl Blockchain for Chain {
             true => 0,
         };
 
+        // The operator can override how many blocks we scan per batch of
+        // provider requests for just this deployment
+        let max_block_range_size = graph::settings::for_deployment(&deployment.hash)
+            .block_range_size()
+            .unwrap_or(*MAX_BLOCK_RANGE_SIZE);
+
         Ok(Box::new(PollingBlockStream::new(
             writable,
             chain_store,
@@ -292,7 +298,7 @@ impl Blockchain for Chain {
             reorg_threshold,
             logger,
             metrics,
-            *MAX_BLOCK_RANGE_SIZE,
+            max_block_range_size,
             *TARGET_TRIGGERS_PER_BLOCK_RANGE,
             unified_api_version,
             subgraph_start_block,
@@ -315,9 +321,11 @@ impl Blockchain for Chain {
 
         let adapter = IngestorAdapter {
             eth_adapter,
+            eth_adapters: self.eth_adapters.clone(),
             logger,
             ancestor_count: self.ancestor_count,
             chain_store: self.chain_store.clone(),
+            poisoned_blocks: Mutex::new(HashSet::new()),
         };
         Arc::new(adapter)
     }
@@ -626,7 +634,40 @@ pub struct IngestorAdapter {
     logger: Logger,
     ancestor_count: i32,
     eth_adapter: Arc<EthereumAdapter>,
+    eth_adapters: Arc<EthereumNetworkAdapters>,
     chain_store: Arc<dyn ChainStore>,
+
+    /// Hashes of blocks a provider served with corrupt contents. Kept so
+    /// that repeated incidents can be told apart from one-off glitches in
+    /// the logs; quarantined blocks are refetched on the next poll since an
+    /// alternate provider may serve a good copy.
+    poisoned_blocks: Mutex<HashSet<H256>>,
+}
+
+impl IngestorAdapter {
+    /// Check that the block a provider served is consistent with the hash
+    /// we asked for. Providers occasionally serve corrupt blocks, e.g. from
+    /// a stale cache during a reorg; ingesting them would poison the chain
+    /// store and every subgraph indexing this chain.
+    fn verify_block(block_hash: &H256, block: &LightEthereumBlock) -> Result<(), String> {
+        match block.hash {
+            Some(hash) if hash == *block_hash => {}
+            hash => {
+                return Err(format!(
+                    "requested block {:x} but provider returned block with hash {:?}",
+                    block_hash, hash
+                ))
+            }
+        }
+        if block.number.is_none() {
+            return Err("provider returned a pending block".to_owned());
+        }
+        if block.number.map(|number| number.as_u64()) > Some(0) && block.parent_hash == H256::zero()
+        {
+            return Err("provider returned a block without a parent link".to_owned());
+        }
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -647,6 +688,10 @@ impl IngestorAdapterTrait<Chain> for IngestorAdapter {
             .map(|block| block.into())
     }
 
+    async fn subscribe_new_heads(&self) -> Option<futures03::stream::BoxStream<'static, ()>> {
+        self.eth_adapter.subscribe_new_heads(&self.logger).await
+    }
+
     async fn ingest_block(
         &self,
         block_hash: &BlockHash,
@@ -655,12 +700,44 @@ impl IngestorAdapterTrait<Chain> for IngestorAdapter {
         let block_hash = H256::from_slice(block_hash.as_slice());
 
         // Get the fully populated block
-        let block = self
+        let mut block = self
             .eth_adapter
             .block_by_hash(&self.logger, block_hash)
             .compat()
             .await?
             .ok_or_else(|| IngestorError::BlockUnavailable(block_hash))?;
+
+        // Quarantine corrupt blocks instead of ingesting them. If another
+        // provider is configured, give it a chance to serve a good copy
+        // before giving up on this poll.
+        if let Err(reason) = Self::verify_block(&block_hash, &block) {
+            let repeated = !self.poisoned_blocks.lock().unwrap().insert(block_hash);
+            error!(self.logger, "Quarantining corrupt block from provider";
+                "block_hash" => format!("{:x}", block_hash),
+                "reason" => &reason,
+                "repeated" => repeated,
+                "provider" => self.eth_adapter.provider(),
+            );
+
+            let alternate = self
+                .eth_adapters
+                .alternate(self.eth_adapter.provider())
+                .ok_or_else(|| IngestorError::PoisonBlock(block_hash, reason.clone()))?;
+            block = alternate
+                .block_by_hash(&self.logger, block_hash)
+                .compat()
+                .await?
+                .ok_or_else(|| IngestorError::BlockUnavailable(block_hash))?;
+            Self::verify_block(&block_hash, &block)
+                .map_err(|reason| IngestorError::PoisonBlock(block_hash, reason))?;
+            info!(self.logger, "Alternate provider served a good copy of quarantined block";
+                "block_hash" => format!("{:x}", block_hash),
+                "provider" => alternate.provider(),
+            );
+        } else {
+            self.poisoned_blocks.lock().unwrap().remove(&block_hash);
+        }
+
         let ethereum_block = self
             .eth_adapter
             .load_full_block(&self.logger, block)
@@ -52,6 +52,11 @@ impl<'a> Into<EthereumCall> for CallAt<'a> {
             block_number: self.block.number as i32,
             transaction_hash: Some(H256::from_slice(&self.trace.hash)),
             transaction_index: self.trace.index as u64,
+            // Firehose reports the position of a call in its transaction
+            // as a flat preorder index rather than a path into the trace
+            // tree. Both order calls the same way, which is all
+            // `trace_address` is used for
+            trace_address: vec![self.call.index as u64],
         }
     }
 }
@@ -16,7 +16,7 @@ use graph::{
     blockchain::{self, Blockchain},
     prelude::{
         async_trait,
-        ethabi::{Address, Contract, Event, Function, LogParam, ParamType, RawLog},
+        ethabi::{Address, Contract, Event, Function, LogParam, ParamType, RawLog, Token},
         info, serde_json,
         web3::types::{Log, Transaction, H256},
         BlockNumber, CheapClone, DataSourceTemplateInfo, Deserialize, EthereumCall,
@@ -24,7 +24,9 @@ use graph::{
     },
 };
 
-use graph::data::subgraph::{calls_host_fn, DataSourceContext, Source};
+use graph::data::subgraph::{
+    calls_host_fn, DataSourceContext, Source, SPEC_VERSION_0_0_5, SPEC_VERSION_0_0_6,
+};
 
 use crate::chain::Chain;
 use crate::trigger::{EthereumBlockTriggerType, EthereumTrigger, MappingTrigger};
@@ -159,9 +161,19 @@ impl blockchain::DataSource<Chain> for DataSource {
         })
     }
 
-    fn validate(&self) -> Vec<Error> {
+    fn validate(&self, spec_version: &semver::Version) -> Vec<Error> {
         let mut errors = vec![];
 
+        // A context can only be set on a data source from spec version 0.0.5 on
+        if self.context.is_some() && spec_version < &SPEC_VERSION_0_0_5 {
+            errors.push(anyhow!(
+                "data source has a `context`, which requires a specVersion of at least {}, \
+                 but the manifest declares {}",
+                SPEC_VERSION_0_0_5,
+                spec_version
+            ))
+        }
+
         if !ETHEREUM_KINDS.contains(&self.kind.as_str()) {
             errors.push(anyhow!(
                 "data source has invalid `kind`, expected `ethereum` but found {}",
@@ -197,6 +209,54 @@ impl blockchain::DataSource<Chain> for DataSource {
             errors.push(anyhow!("data source has duplicated block handlers"));
         }
 
+        // Event predicates can only be used from spec version 0.0.6 on.
+        // Older graph-node versions do not know the key and would ignore
+        // it, processing every event; tying predicates to a spec version
+        // those versions reject keeps the PoI the same across indexers
+        if spec_version < &SPEC_VERSION_0_0_6
+            && self
+                .mapping
+                .event_handlers
+                .iter()
+                .any(|handler| !handler.predicates.is_empty())
+        {
+            errors.push(anyhow!(
+                "data source has event handlers with `predicates`, which requires a \
+                 specVersion of at least {}, but the manifest declares {}",
+                SPEC_VERSION_0_0_6,
+                spec_version
+            ))
+        }
+
+        // Validate that event predicates compare against something we can
+        // evaluate, so that a typo fails the deployment instead of the
+        // first matching event
+        for handler in &self.mapping.event_handlers {
+            for predicate in &handler.predicates {
+                if let Err(e) = predicate.expected_address(self.source.address) {
+                    errors.push(e.context(format!("handler `{}`", handler.handler)));
+                }
+            }
+        }
+
+        // Block handlers can only ask for the transactions of the block with
+        // apiVersion 0.0.7 mappings since older `graph-ts` releases do not
+        // have a block class with a transaction list; with an older
+        // apiVersion the handler would compile but see no transactions
+        if self
+            .mapping
+            .block_handlers
+            .iter()
+            .any(|handler| handler.transactions)
+            && self.mapping.api_version < semver::Version::new(0, 0, 7)
+        {
+            errors.push(anyhow!(
+                "block handlers with `transactions` require an apiVersion of at least 0.0.7, \
+                 but the mapping declares {}",
+                self.mapping.api_version
+            ))
+        }
+
         errors
     }
 
@@ -251,6 +311,46 @@ impl DataSource {
         Ok(handlers)
     }
 
+    /// Evaluate the predicates of `handler` against the decoded `params`.
+    /// A return of `Ok(false)` means a predicate failed and the event must
+    /// not be passed to the handler
+    fn event_predicates_match(
+        &self,
+        handler: &MappingEventHandler,
+        params: &[LogParam],
+    ) -> Result<bool, Error> {
+        for predicate in &handler.predicates {
+            let param = params
+                .iter()
+                .find(|param| param.name == predicate.param)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "event `{}` has no parameter `{}` for the predicate of handler `{}`",
+                        handler.event,
+                        predicate.param,
+                        handler.handler
+                    )
+                })?;
+            let address = match &param.value {
+                Token::Address(address) => *address,
+                _ => {
+                    return Err(anyhow!(
+                        "the predicate on parameter `{}` of handler `{}` requires \
+                         an address parameter, but `{}` is not an address",
+                        predicate.param,
+                        handler.handler,
+                        predicate.param
+                    ))
+                }
+            };
+            match predicate.expected_address(self.source.address)? {
+                Some(expected) if expected != address => return Ok(false),
+                _ => (),
+            }
+        }
+        Ok(true)
+    }
+
     fn handler_for_call(&self, call: &EthereumCall) -> Result<Option<MappingCallHandler>, Error> {
         // First four bytes of the input for the call are the first four
         // bytes of hash of the function signature
@@ -464,7 +564,10 @@ impl DataSource {
                     None => return Ok(None),
                 };
                 Ok(Some(TriggerWithHandler::new(
-                    MappingTrigger::Block { block },
+                    MappingTrigger::Block {
+                        block,
+                        include_transactions: handler.transactions,
+                    },
                     handler.handler,
                 )))
             }
@@ -538,6 +641,13 @@ impl DataSource {
                     )
                 );
 
+                // Evaluate the predicates of the handler natively so that
+                // events the data source does not care about never reach
+                // the mappings
+                if !self.event_predicates_match(&event_handler, &params)? {
+                    return Ok(None);
+                }
+
                 // Special case: In Celo, there are Epoch Rewards events, which do not have an
                 // associated transaction and instead have `transaction_hash == block.hash`,
                 // in which case we pass a dummy transaction to the mappings.
@@ -963,6 +1073,11 @@ impl UnresolvedMappingABI {
 pub struct MappingBlockHandler {
     pub handler: String,
     pub filter: Option<BlockHandlerFilter>,
+    /// Pass the block together with its transactions to the handler. The
+    /// transactions are only decoded for handlers that set this so that
+    /// handlers that just need the header do not pay for the decoding
+    #[serde(default)]
+    pub transactions: bool,
 }
 
 #[derive(Clone, Debug, Hash, Eq, PartialEq, Deserialize)]
@@ -984,6 +1099,13 @@ pub struct MappingEventHandler {
     pub event: String,
     pub topic0: Option<H256>,
     pub handler: String,
+    /// Predicates on the event parameters that are evaluated natively
+    /// after decoding the event and before calling into the mappings.
+    /// When any of them fails, the event is not passed to the handler at
+    /// all. For high-volume shared events like ERC-20 `Transfer` across
+    /// many dynamic data sources, this avoids most handler invocations
+    #[serde(default)]
+    pub predicates: Vec<MappingEventPredicate>,
 }
 
 impl MappingEventHandler {
@@ -993,6 +1115,37 @@ impl MappingEventHandler {
     }
 }
 
+/// A predicate requiring that the event parameter named `param` equals
+/// `equals`, which is either the literal `dataSource.address` or a hex
+/// address like `0xdeadbeef...`. Only address parameters can be
+/// constrained this way
+#[derive(Clone, Debug, Hash, Eq, PartialEq, Deserialize)]
+pub struct MappingEventPredicate {
+    pub param: String,
+    pub equals: String,
+}
+
+impl MappingEventPredicate {
+    /// The address that the parameter must equal; `None` means the
+    /// predicate refers to `dataSource.address` and the data source does
+    /// not have an address, in which case the predicate matches anything
+    fn expected_address(&self, ds_address: Option<Address>) -> Result<Option<Address>, Error> {
+        match self.equals.as_str() {
+            "dataSource.address" => Ok(ds_address),
+            literal => Address::from_str(literal.trim_start_matches("0x"))
+                .map(Some)
+                .map_err(|_| {
+                    anyhow!(
+                        "the predicate on parameter `{}` compares against `{}`, \
+                         which is neither `dataSource.address` nor a hex address",
+                        self.param,
+                        self.equals
+                    )
+                }),
+        }
+    }
+}
+
 /// Hashes a string to a H256 hash.
 fn string_to_h256(s: &str) -> H256 {
     let mut result = [0u8; 32];
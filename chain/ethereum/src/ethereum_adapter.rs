@@ -2,6 +2,7 @@ use futures::future;
 use futures::prelude::*;
 use graph::blockchain::BlockHash;
 use graph::blockchain::ChainIdentifier;
+use graph::components::provider::PROVIDER_REGISTRY;
 use graph::components::transaction_receipt::LightTransactionReceipt;
 use graph::data::subgraph::UnifiedMappingApiVersion;
 use graph::prelude::ethabi::ParamType;
@@ -48,6 +49,7 @@ use crate::{
         EthereumCallFilter, EthereumContractCall, EthereumContractCallError, EthereumLogFilter,
         ProviderEthRpcMetrics, SubgraphEthRpcMetrics,
     },
+    provider_errors::ProviderErrorKind,
     transport::Transport,
     trigger::{EthereumBlockTriggerType, EthereumTrigger},
     TriggerFilter,
@@ -57,6 +59,8 @@ use crate::{
 pub struct EthereumAdapter {
     logger: Logger,
     url_hostname: Arc<String>,
+    /// The network this adapter serves, e.g. `mainnet`
+    network: String,
     /// The label for the provider from the configuration
     provider: String,
     web3: Arc<Web3<Transport>>,
@@ -74,12 +78,12 @@ lazy_static! {
     /// contract address, only event signature, and are therefore expensive.
     ///
     /// According to Ethereum node operators, size 500 is reasonable here.
-    static ref MAX_EVENT_ONLY_RANGE: BlockNumber = std::env::var("GRAPH_ETHEREUM_MAX_EVENT_ONLY_RANGE")
+    pub(crate) static ref MAX_EVENT_ONLY_RANGE: BlockNumber = std::env::var("GRAPH_ETHEREUM_MAX_EVENT_ONLY_RANGE")
         .unwrap_or("500".into())
         .parse::<BlockNumber>()
         .expect("invalid number of parallel Ethereum block ranges to scan");
 
-    static ref BLOCK_BATCH_SIZE: usize = std::env::var("ETHEREUM_BLOCK_BATCH_SIZE")
+    pub(crate) static ref BLOCK_BATCH_SIZE: usize = std::env::var("ETHEREUM_BLOCK_BATCH_SIZE")
             .unwrap_or("10".into())
             .parse::<usize>()
             .expect("invalid ETHEREUM_BLOCK_BATCH_SIZE env var");
@@ -147,6 +151,7 @@ impl CheapClone for EthereumAdapter {
 impl EthereumAdapter {
     pub async fn new(
         logger: Logger,
+        network: String,
         provider: String,
         url: &str,
         transport: Transport,
@@ -173,6 +178,7 @@ impl EthereumAdapter {
 
         EthereumAdapter {
             logger,
+            network,
             provider,
             url_hostname: Arc::new(hostname),
             web3,
@@ -281,16 +287,13 @@ impl EthereumAdapter {
         from: BlockNumber,
         to: BlockNumber,
         filter: Arc<EthGetLogsFilter>,
-        too_many_logs_fingerprints: &'static [&'static str],
     ) -> Result<Vec<Log>, TimeoutError<web3::error::Error>> {
         let eth_adapter = self.clone();
 
         retry("eth_getLogs RPC call", &logger)
             .when(move |res: &Result<_, web3::error::Error>| match res {
                 Ok(_) => false,
-                Err(e) => !too_many_logs_fingerprints
-                    .iter()
-                    .any(|f| e.to_string().contains(f)),
+                Err(e) => ProviderErrorKind::classify(e).is_retryable(),
             })
             .limit(*REQUEST_RETRIES)
             .timeout_secs(*JSON_RPC_TIMEOUT)
@@ -384,14 +387,6 @@ impl EthereumAdapter {
         to: BlockNumber,
         filter: EthGetLogsFilter,
     ) -> DynTryFuture<'static, Vec<Log>, Error> {
-        // Codes returned by Ethereum node providers if an eth_getLogs request is too heavy.
-        // The first one is for Infura when it hits the log limit, the rest for Alchemy timeouts.
-        const TOO_MANY_LOGS_FINGERPRINTS: &[&str] = &[
-            "ServerError(-32005)",
-            "503 Service Unavailable",
-            "ServerError(-32000)",
-        ];
-
         if from > to {
             panic!(
                 "cannot produce a log stream on a backwards block range (from={}, to={})",
@@ -435,7 +430,6 @@ impl EthereumAdapter {
                         start,
                         end,
                         filter.cheap_clone(),
-                        TOO_MANY_LOGS_FINGERPRINTS,
                     )
                     .await;
 
@@ -445,9 +439,8 @@ impl EthereumAdapter {
 
                         // If the step is already 0, the request is too heavy even for a single
                         // block. We hope this never happens, but if it does, make sure to error.
-                        if TOO_MANY_LOGS_FINGERPRINTS
-                            .iter()
-                            .any(|f| string_err.contains(f))
+                        if ProviderErrorKind::classify_str(&string_err)
+                            == ProviderErrorKind::ResponseTooLarge
                             && step > 0
                         {
                             // The range size for a request is `step + 1`. So it's ok if the step
@@ -857,6 +850,40 @@ impl EthereumAdapter {
         )
         .map_err(Error::msg)
     }
+
+    /// Subscribe to `newHeads` notifications when the provider is reached
+    /// over a websocket connection. HTTP providers do not support
+    /// subscriptions, and for them the caller has to fall back to polling
+    pub async fn subscribe_new_heads(
+        &self,
+        logger: &Logger,
+    ) -> Option<futures03::stream::BoxStream<'static, ()>> {
+        let ws = match self.web3.transport() {
+            Transport::WS(ws) => ws.clone(),
+            #[cfg(unix)]
+            Transport::IPC(_) => return None,
+            Transport::RPC(_) => return None,
+            // Subscriptions are not part of a recording
+            Transport::Recorded { .. } => return None,
+        };
+
+        match Web3::new(ws).eth_subscribe().subscribe_new_heads().await {
+            Ok(heads) => Some(
+                heads
+                    .filter_map(|head| async move { head.ok().map(|_| ()) })
+                    .boxed(),
+            ),
+            Err(e) => {
+                warn!(
+                    logger,
+                    "Failed to subscribe to newHeads notifications, \
+                     falling back to polling: {}",
+                    e
+                );
+                None
+            }
+        }
+    }
 }
 
 #[async_trait]
@@ -919,20 +946,29 @@ impl EthereumAdapterTrait for EthereumAdapter {
         logger: &Logger,
     ) -> Box<dyn Future<Item = web3::types::Block<H256>, Error = IngestorError> + Send> {
         let web3 = self.web3.clone();
+        // This request runs on every poll for the chain head, which makes
+        // it a good place to collect rolling latency stats for the provider
+        let network = self.network.clone();
+        let provider = self.provider.clone();
         Box::new(
             retry("eth_getBlockByNumber(latest) no txs RPC call", logger)
                 .no_limit()
                 .timeout_secs(*JSON_RPC_TIMEOUT)
                 .run(move || {
                     let web3 = web3.cheap_clone();
+                    let network = network.clone();
+                    let provider = provider.clone();
                     async move {
+                        let start = Instant::now();
                         let block_opt = web3
                             .eth()
                             .block(Web3BlockNumber::Latest.into())
                             .await
                             .map_err(|e| {
+                                PROVIDER_REGISTRY.record_error(&network, &provider, &e.to_string());
                                 anyhow!("could not get latest block from Ethereum: {}", e)
                             })?;
+                        PROVIDER_REGISTRY.record_latency(&network, &provider, start.elapsed());
 
                         block_opt
                             .ok_or_else(|| anyhow!("no latest block returned from Ethereum").into())
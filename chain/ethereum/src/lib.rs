@@ -3,11 +3,14 @@ mod capabilities;
 pub mod codec;
 mod data_source;
 mod ethereum_adapter;
+mod provider_errors;
+mod rpc_recorder;
 pub mod runtime;
 mod transport;
 
 pub use self::capabilities::NodeCapabilities;
 pub use self::ethereum_adapter::EthereumAdapter;
+pub use self::provider_errors::ProviderErrorKind;
 pub use self::runtime::RuntimeAdapter;
 pub use self::transport::Transport;
 
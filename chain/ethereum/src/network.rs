@@ -4,6 +4,7 @@ use graph::prelude::rand::{self, seq::IteratorRandom};
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use graph::components::provider::PROVIDER_REGISTRY;
 pub use graph::impl_slog_value;
 use graph::prelude::Error;
 
@@ -56,6 +57,16 @@ impl EthereumNetworkAdapters {
             .map(|ethereum_network_adapter| ethereum_network_adapter.adapter.clone())
     }
 
+    /// An adapter from a different provider than `provider`, used to
+    /// cross-check blocks that failed verification. Returns `None` if no
+    /// other provider is configured.
+    pub fn alternate(&self, provider: &str) -> Option<Arc<EthereumAdapter>> {
+        self.adapters
+            .iter()
+            .find(|adapter| adapter.adapter.provider() != provider)
+            .map(|adapter| adapter.adapter.clone())
+    }
+
     pub fn remove(&mut self, provider: &str) {
         self.adapters
             .retain(|adapter| adapter.adapter.provider() != provider);
@@ -80,6 +91,13 @@ impl EthereumNetworks {
         capabilities: NodeCapabilities,
         adapter: Arc<EthereumAdapter>,
     ) {
+        PROVIDER_REGISTRY.register(
+            &name,
+            adapter.provider(),
+            capabilities.to_string(),
+            *crate::ethereum_adapter::MAX_EVENT_ONLY_RANGE,
+            *crate::ethereum_adapter::BLOCK_BATCH_SIZE as i32,
+        );
         let network_adapters = self
             .networks
             .entry(name)
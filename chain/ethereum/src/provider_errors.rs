@@ -0,0 +1,163 @@
+use graph::prelude::web3;
+
+/// A normalized category for the errors different Ethereum providers return.
+///
+/// Infura, Alchemy, Erigon and Nethermind report the same underlying
+/// conditions with different error codes and messages. Matching on the raw
+/// error at every call site led to retry behavior that depended on which
+/// provider happened to serve the request; this module is the single place
+/// where raw errors are fingerprinted into categories that drive retries and
+/// provider fallback.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProviderErrorKind {
+    /// The provider has pruned the state needed to serve the request, or is
+    /// not an archive node. Retrying against the same provider is pointless;
+    /// only falling back to a provider with more history can help.
+    MissingTrieNode,
+
+    /// The response would exceed the provider's size, item count or execution
+    /// time limits. The caller should narrow the request (e.g. reduce the
+    /// block range of an `eth_getLogs` call) rather than retry it as-is.
+    ResponseTooLarge,
+
+    /// The provider is throttling us. The request is safe to retry after a
+    /// backoff.
+    RateLimited,
+
+    /// Anything we could not classify. Treated as transient and retried,
+    /// which matches the behavior before errors were categorized.
+    Other,
+}
+
+/// Message fragments that indicate pruned or unavailable state. The first two
+/// are geth/Infura, then Erigon, OpenEthereum and Nethermind.
+const MISSING_STATE_FINGERPRINTS: &[&str] = &[
+    "missing trie node",
+    "header not found",
+    "required historical state unavailable",
+    "request is not supported because your node is running with state pruning",
+    "state is not available",
+];
+
+/// Message fragments for responses that hit a provider limit. Infura returns
+/// `-32005` with a "query returned more than" message, Alchemy responds with
+/// a 503 or its own log limit message, Erigon and Nethermind mention the
+/// response size directly.
+const RESPONSE_TOO_LARGE_FINGERPRINTS: &[&str] = &[
+    "ServerError(-32005)",
+    "503 Service Unavailable",
+    "ServerError(-32000)",
+    "query returned more than",
+    "Log response size exceeded",
+    "response too large",
+    "result is too big",
+];
+
+/// Message fragments for throttling responses. The status code 429 is only
+/// matched in anchored forms since a bare `429` also appears in block
+/// numbers, hashes and result counts.
+const RATE_LIMIT_FINGERPRINTS: &[&str] = &[
+    "HTTP status 429",
+    "status code 429",
+    "429 Too Many Requests",
+    "Too Many Requests",
+    "too many requests",
+    "rate limit",
+    "daily request count exceeded",
+    "exceeded its compute units",
+];
+
+impl ProviderErrorKind {
+    /// Classify a raw `web3` error. Falls back to `Other` for anything that
+    /// does not match a known provider fingerprint.
+    pub fn classify(err: &web3::Error) -> Self {
+        Self::classify_str(&err.to_string())
+    }
+
+    /// Classify based on the string rendering of an error. Useful for call
+    /// sites where the original `web3::Error` has already been wrapped.
+    pub fn classify_str(msg: &str) -> Self {
+        let matches = |fingerprints: &[&str]| fingerprints.iter().any(|f| msg.contains(f));
+
+        // Rate limiting is checked first: providers such as Infura reuse
+        // `-32005` for both throttling and log limits, but include a
+        // distinctive message for the former.
+        if matches(RATE_LIMIT_FINGERPRINTS) {
+            ProviderErrorKind::RateLimited
+        } else if matches(MISSING_STATE_FINGERPRINTS) {
+            ProviderErrorKind::MissingTrieNode
+        } else if matches(RESPONSE_TOO_LARGE_FINGERPRINTS) {
+            ProviderErrorKind::ResponseTooLarge
+        } else {
+            ProviderErrorKind::Other
+        }
+    }
+
+    /// Whether retrying the same request against the same provider can
+    /// succeed. Requests that hit a provider limit or pruned state need the
+    /// caller to change the request or the provider instead.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ProviderErrorKind::MissingTrieNode => false,
+            ProviderErrorKind::ResponseTooLarge => false,
+            ProviderErrorKind::RateLimited => true,
+            ProviderErrorKind::Other => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProviderErrorKind;
+
+    #[test]
+    fn classifies_provider_specific_messages() {
+        // Infura log limit vs. geth missing state, both `-32000`-ish shapes.
+        assert_eq!(
+            ProviderErrorKind::classify_str(
+                "RPC error: ServerError(-32005): query returned more than 10000 results"
+            ),
+            ProviderErrorKind::ResponseTooLarge
+        );
+        assert_eq!(
+            ProviderErrorKind::classify_str(
+                "RPC error: ServerError(-32000): missing trie node 1234abcd"
+            ),
+            ProviderErrorKind::MissingTrieNode
+        );
+        // Erigon's pruned-state message.
+        assert_eq!(
+            ProviderErrorKind::classify_str("required historical state unavailable"),
+            ProviderErrorKind::MissingTrieNode
+        );
+        // Alchemy throttling.
+        assert_eq!(
+            ProviderErrorKind::classify_str("HTTP status 429 Too Many Requests"),
+            ProviderErrorKind::RateLimited
+        );
+        assert_eq!(
+            ProviderErrorKind::classify_str("connection reset by peer"),
+            ProviderErrorKind::Other
+        );
+        // A bare `429` inside a result count, block number or hash is not
+        // throttling.
+        assert_eq!(
+            ProviderErrorKind::classify_str(
+                "RPC error: ServerError(-32005): query returned more than 4298 results"
+            ),
+            ProviderErrorKind::ResponseTooLarge
+        );
+        assert_eq!(
+            ProviderErrorKind::classify_str("missing trie node at block 14290000"),
+            ProviderErrorKind::MissingTrieNode
+        );
+    }
+
+    #[test]
+    fn retry_behavior_follows_category() {
+        assert!(ProviderErrorKind::RateLimited.is_retryable());
+        assert!(ProviderErrorKind::Other.is_retryable());
+        assert!(!ProviderErrorKind::MissingTrieNode.is_retryable());
+        assert!(!ProviderErrorKind::ResponseTooLarge.is_retryable());
+    }
+}
@@ -0,0 +1,180 @@
+//! Optional record-and-replay of Ethereum RPC responses.
+//!
+//! When `GRAPH_ETHEREUM_RPC_RECORD_DIR` is set, every response a transport
+//! receives is written to a per-network directory under it, gzip-compressed
+//! and keyed by a hash of the request. When `GRAPH_ETHEREUM_RPC_REPLAY_DIR`
+//! is set, responses are served from such a recording instead of asking the
+//! providers, which makes reindexing bit-identical to the run that produced
+//! the recording; a request that is not in the recording is an error.
+
+use std::fs;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use jsonrpc_core::types::Call;
+use jsonrpc_core::Value;
+use serde::{Deserialize, Serialize};
+
+use graph::prelude::{lazy_static, serde_json, web3};
+
+lazy_static! {
+    static ref RECORD_DIR: Option<PathBuf> =
+        std::env::var_os("GRAPH_ETHEREUM_RPC_RECORD_DIR").map(PathBuf::from);
+    static ref REPLAY_DIR: Option<PathBuf> =
+        std::env::var_os("GRAPH_ETHEREUM_RPC_REPLAY_DIR").map(PathBuf::from);
+}
+
+#[derive(Debug)]
+enum Mode {
+    Record,
+    Replay,
+}
+
+/// Records the responses for one network, or serves them back, depending
+/// on which environment variable the recorder was created from
+#[derive(Debug)]
+pub struct RpcRecorder {
+    mode: Mode,
+    dir: PathBuf,
+}
+
+/// The deterministic part of a response: either the result or the error
+/// the provider sent back. Transport and decoder failures are transient
+/// and never recorded
+#[derive(Serialize, Deserialize)]
+enum Recorded {
+    #[serde(rename = "ok")]
+    Ok(Value),
+    #[serde(rename = "rpc_error")]
+    RpcError(jsonrpc_core::types::error::Error),
+}
+
+fn method_name(request: &Call) -> &str {
+    match request {
+        Call::MethodCall(call) => &call.method,
+        Call::Notification(notification) => &notification.method,
+        Call::Invalid { .. } => "invalid",
+    }
+}
+
+impl RpcRecorder {
+    /// The recorder for `network` according to the environment, if any
+    pub fn from_env(network: &str) -> Option<Self> {
+        let (mode, dir) = match (&*RECORD_DIR, &*REPLAY_DIR) {
+            (Some(_), Some(_)) => panic!(
+                "only one of GRAPH_ETHEREUM_RPC_RECORD_DIR and \
+                 GRAPH_ETHEREUM_RPC_REPLAY_DIR may be set"
+            ),
+            (Some(dir), None) => (Mode::Record, dir),
+            (None, Some(dir)) => (Mode::Replay, dir),
+            (None, None) => return None,
+        };
+        let dir = dir.join(network);
+        if let Mode::Record = mode {
+            fs::create_dir_all(&dir).unwrap_or_else(|e| {
+                panic!(
+                    "failed to create recording directory {}: {}",
+                    dir.display(),
+                    e
+                )
+            });
+        }
+        Some(RpcRecorder { mode, dir })
+    }
+
+    pub fn is_replay(&self) -> bool {
+        matches!(self.mode, Mode::Replay)
+    }
+
+    /// The file that holds the response for `request`. Requests are
+    /// identified by their method and params; the request id is ignored
+    /// since it changes from run to run
+    fn path(&self, request: &Call) -> Option<PathBuf> {
+        let (method, params) = match request {
+            Call::MethodCall(call) => (&call.method, &call.params),
+            Call::Notification(_) | Call::Invalid { .. } => return None,
+        };
+        let key = serde_json::to_vec(&(method, params)).expect("requests are serializable");
+        let hash = tiny_keccak::keccak256(&key);
+        Some(self.dir.join(format!("{}.json.gz", hex::encode(hash))))
+    }
+
+    /// Add the response for `request` to the recording unless it is
+    /// already there. Failing to write is an error since a recording
+    /// with holes can not guarantee a faithful replay
+    pub fn record(
+        &self,
+        request: &Call,
+        response: &Result<Value, web3::Error>,
+    ) -> Result<(), web3::Error> {
+        let recorded = match response {
+            Ok(value) => Recorded::Ok(value.clone()),
+            Err(web3::Error::Rpc(e)) => Recorded::RpcError(e.clone()),
+            Err(_) => return Ok(()),
+        };
+        let path = match self.path(request) {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if path.exists() {
+            return Ok(());
+        }
+
+        let write = || -> Result<(), std::io::Error> {
+            let bytes = serde_json::to_vec(&recorded)?;
+            // Write to a temporary file first so that a response never
+            // appears in the recording half-written
+            let tmp = path.with_extension("tmp");
+            let mut encoder = GzEncoder::new(fs::File::create(&tmp)?, Compression::default());
+            encoder.write_all(&bytes)?;
+            encoder.finish()?;
+            fs::rename(&tmp, &path)
+        };
+        write().map_err(|e| {
+            web3::Error::Transport(format!(
+                "failed to record the response for {} at {}: {}",
+                method_name(request),
+                path.display(),
+                e
+            ))
+        })
+    }
+
+    /// Look the response for `request` up in the recording
+    pub fn replay(&self, request: &Call) -> Result<Value, web3::Error> {
+        let path = self.path(request).ok_or_else(|| {
+            web3::Error::Transport(format!(
+                "only method calls can be replayed, not {}",
+                method_name(request)
+            ))
+        })?;
+        let file = fs::File::open(&path).map_err(|_| {
+            web3::Error::Transport(format!(
+                "the recording has no response for {} (expected it at {})",
+                method_name(request),
+                path.display()
+            ))
+        })?;
+        let mut bytes = Vec::new();
+        GzDecoder::new(file).read_to_end(&mut bytes).map_err(|e| {
+            web3::Error::Transport(format!(
+                "failed to read the recorded response at {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        match serde_json::from_slice(&bytes).map_err(|e| {
+            web3::Error::Transport(format!(
+                "failed to decode the recorded response at {}: {}",
+                path.display(),
+                e
+            ))
+        })? {
+            Recorded::Ok(value) => Ok(value),
+            Recorded::RpcError(e) => Err(web3::Error::Rpc(e)),
+        }
+    }
+}
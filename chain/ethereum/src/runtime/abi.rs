@@ -1,3 +1,4 @@
+use graph::prelude::web3::types::{AccessListItem, H256};
 use graph::prelude::{ethabi, BigInt};
 use graph::runtime::{asc_get, asc_new, AscPtr, DeterministicHostError, FromAscObj, ToAscObj};
 use graph::runtime::{AscHeap, AscIndexId, AscType, IndexForAscTypeId};
@@ -9,6 +10,7 @@ use semver::Version;
 
 use crate::trigger::{
     EthereumBlockData, EthereumCallData, EthereumEventData, EthereumTransactionData,
+    FullEthereumBlockData,
 };
 
 use super::runtime_adapter::UnresolvedContractCall;
@@ -195,6 +197,140 @@ impl AscIndexId for AscEthereumTransaction_0_0_6 {
     const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::EthereumTransaction;
 }
 
+#[repr(C)]
+#[derive(AscType)]
+pub(crate) struct AscEthereumTransaction_0_0_7 {
+    pub hash: AscPtr<AscH256>,
+    pub index: AscPtr<AscBigInt>,
+    pub from: AscPtr<AscH160>,
+    pub to: AscPtr<AscH160>,
+    pub value: AscPtr<AscBigInt>,
+    pub gas_limit: AscPtr<AscBigInt>,
+    pub gas_price: AscPtr<AscBigInt>,
+    pub input: AscPtr<Uint8Array>,
+    pub nonce: AscPtr<AscBigInt>,
+
+    // Typed transaction (EIP-2718/1559/2930) fields; null for legacy
+    // transactions
+    pub max_fee_per_gas: AscPtr<AscBigInt>,
+    pub max_priority_fee_per_gas: AscPtr<AscBigInt>,
+    pub tx_type: AscPtr<AscBigInt>,
+    pub access_list: AscPtr<AscAccessTupleArray>,
+}
+
+impl AscIndexId for AscEthereumTransaction_0_0_7 {
+    const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::EthereumTransaction;
+}
+
+/// An Ethereum block with its transaction list appended to the end of the
+/// class so that mappings that treat it as a plain block keep working. Only
+/// used for block handlers that set `transactions: true` in the manifest
+#[repr(C)]
+#[derive(AscType)]
+pub(crate) struct AscFullEthereumBlock<T>
+where
+    T: AscType,
+{
+    pub hash: AscPtr<AscH256>,
+    pub parent_hash: AscPtr<AscH256>,
+    pub uncles_hash: AscPtr<AscH256>,
+    pub author: AscPtr<AscH160>,
+    pub state_root: AscPtr<AscH256>,
+    pub transactions_root: AscPtr<AscH256>,
+    pub receipts_root: AscPtr<AscH256>,
+    pub number: AscPtr<AscBigInt>,
+    pub gas_used: AscPtr<AscBigInt>,
+    pub gas_limit: AscPtr<AscBigInt>,
+    pub timestamp: AscPtr<AscBigInt>,
+    pub difficulty: AscPtr<AscBigInt>,
+    pub total_difficulty: AscPtr<AscBigInt>,
+    pub size: AscPtr<AscBigInt>,
+    pub base_fee_per_block: AscPtr<AscBigInt>,
+    pub transactions: AscPtr<AscEthereumTransactionArray<T>>,
+}
+
+impl<T> AscIndexId for AscFullEthereumBlock<T>
+where
+    T: AscType,
+{
+    const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::FullEthereumBlock;
+}
+
+pub struct AscEthereumTransactionArray<T>(Array<AscPtr<T>>)
+where
+    T: AscType;
+
+impl<T> AscType for AscEthereumTransactionArray<T>
+where
+    T: AscType,
+{
+    fn to_asc_bytes(&self) -> Result<Vec<u8>, DeterministicHostError> {
+        self.0.to_asc_bytes()
+    }
+    fn from_asc_bytes(
+        asc_obj: &[u8],
+        api_version: &Version,
+    ) -> Result<Self, DeterministicHostError> {
+        Ok(Self(Array::from_asc_bytes(asc_obj, api_version)?))
+    }
+}
+
+impl<T> AscIndexId for AscEthereumTransactionArray<T>
+where
+    T: AscType,
+{
+    const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::ArrayEthereumTransaction;
+}
+
+/// One entry of an EIP-2930 access list: an address and the storage keys the
+/// transaction declares it will touch.
+#[repr(C)]
+#[derive(AscType)]
+pub(crate) struct AscAccessTuple {
+    pub address: AscPtr<AscAddress>,
+    pub storage_keys: AscPtr<AscH256Array>,
+}
+
+impl AscIndexId for AscAccessTuple {
+    const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::AccessTuple;
+}
+
+pub struct AscAccessTupleArray(Array<AscPtr<AscAccessTuple>>);
+
+impl AscType for AscAccessTupleArray {
+    fn to_asc_bytes(&self) -> Result<Vec<u8>, DeterministicHostError> {
+        self.0.to_asc_bytes()
+    }
+    fn from_asc_bytes(
+        asc_obj: &[u8],
+        api_version: &Version,
+    ) -> Result<Self, DeterministicHostError> {
+        Ok(Self(Array::from_asc_bytes(asc_obj, api_version)?))
+    }
+}
+
+impl AscIndexId for AscAccessTupleArray {
+    const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::ArrayAccessTuple;
+}
+
+pub struct AscH256Array(Array<AscPtr<AscH256>>);
+
+impl AscType for AscH256Array {
+    fn to_asc_bytes(&self) -> Result<Vec<u8>, DeterministicHostError> {
+        self.0.to_asc_bytes()
+    }
+    fn from_asc_bytes(
+        asc_obj: &[u8],
+        api_version: &Version,
+    ) -> Result<Self, DeterministicHostError> {
+        Ok(Self(Array::from_asc_bytes(asc_obj, api_version)?))
+    }
+}
+
+impl AscIndexId for AscH256Array {
+    const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::ArrayH256;
+}
+
 #[repr(C)]
 #[derive(AscType)]
 pub(crate) struct AscEthereumEvent<T, B>
@@ -223,6 +359,10 @@ impl AscIndexId for AscEthereumEvent<AscEthereumTransaction_0_0_6, AscEthereumBl
     const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::EthereumEvent;
 }
 
+impl AscIndexId for AscEthereumEvent<AscEthereumTransaction_0_0_7, AscEthereumBlock_0_0_6> {
+    const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::EthereumEvent;
+}
+
 #[repr(C)]
 #[derive(AscType)]
 pub(crate) struct AscLogParam {
@@ -329,6 +469,59 @@ impl ToAscObj<AscEthereumBlock_0_0_6> for EthereumBlockData {
     }
 }
 
+impl<T> ToAscObj<AscFullEthereumBlock<T>> for FullEthereumBlockData
+where
+    T: AscType + AscIndexId,
+    EthereumTransactionData: ToAscObj<T>,
+{
+    fn to_asc_obj<H: AscHeap + ?Sized>(
+        &self,
+        heap: &mut H,
+    ) -> Result<AscFullEthereumBlock<T>, DeterministicHostError> {
+        let block = &self.block;
+        Ok(AscFullEthereumBlock {
+            hash: asc_new(heap, &block.hash)?,
+            parent_hash: asc_new(heap, &block.parent_hash)?,
+            uncles_hash: asc_new(heap, &block.uncles_hash)?,
+            author: asc_new(heap, &block.author)?,
+            state_root: asc_new(heap, &block.state_root)?,
+            transactions_root: asc_new(heap, &block.transactions_root)?,
+            receipts_root: asc_new(heap, &block.receipts_root)?,
+            number: asc_new(heap, &BigInt::from(block.number))?,
+            gas_used: asc_new(heap, &BigInt::from_unsigned_u256(&block.gas_used))?,
+            gas_limit: asc_new(heap, &BigInt::from_unsigned_u256(&block.gas_limit))?,
+            timestamp: asc_new(heap, &BigInt::from_unsigned_u256(&block.timestamp))?,
+            difficulty: asc_new(heap, &BigInt::from_unsigned_u256(&block.difficulty))?,
+            total_difficulty: asc_new(heap, &BigInt::from_unsigned_u256(&block.total_difficulty))?,
+            size: block
+                .size
+                .map(|size| asc_new(heap, &BigInt::from_unsigned_u256(&size)))
+                .unwrap_or(Ok(AscPtr::null()))?,
+            base_fee_per_block: block
+                .base_fee_per_gas
+                .map(|base_fee| asc_new(heap, &BigInt::from_unsigned_u256(&base_fee)))
+                .unwrap_or(Ok(AscPtr::null()))?,
+            transactions: asc_new(heap, &self.transactions)?,
+        })
+    }
+}
+
+impl<T> ToAscObj<AscEthereumTransactionArray<T>> for Vec<EthereumTransactionData>
+where
+    T: AscType + AscIndexId,
+    EthereumTransactionData: ToAscObj<T>,
+{
+    fn to_asc_obj<H: AscHeap + ?Sized>(
+        &self,
+        heap: &mut H,
+    ) -> Result<AscEthereumTransactionArray<T>, DeterministicHostError> {
+        let content: Result<Vec<_>, _> =
+            self.iter().map(|tx| asc_new::<T, _, _>(heap, tx)).collect();
+        let content = content?;
+        Ok(AscEthereumTransactionArray(Array::new(&*content, heap)?))
+    }
+}
+
 impl ToAscObj<AscEthereumTransaction_0_0_1> for EthereumTransactionData {
     fn to_asc_obj<H: AscHeap + ?Sized>(
         &self,
@@ -392,6 +585,79 @@ impl ToAscObj<AscEthereumTransaction_0_0_6> for EthereumTransactionData {
     }
 }
 
+impl ToAscObj<AscEthereumTransaction_0_0_7> for EthereumTransactionData {
+    fn to_asc_obj<H: AscHeap + ?Sized>(
+        &self,
+        heap: &mut H,
+    ) -> Result<AscEthereumTransaction_0_0_7, DeterministicHostError> {
+        Ok(AscEthereumTransaction_0_0_7 {
+            hash: asc_new(heap, &self.hash)?,
+            index: asc_new(heap, &BigInt::from(self.index))?,
+            from: asc_new(heap, &self.from)?,
+            to: self
+                .to
+                .map(|to| asc_new(heap, &to))
+                .unwrap_or(Ok(AscPtr::null()))?,
+            value: asc_new(heap, &BigInt::from_unsigned_u256(&self.value))?,
+            gas_limit: asc_new(heap, &BigInt::from_unsigned_u256(&self.gas_limit))?,
+            gas_price: asc_new(heap, &BigInt::from_unsigned_u256(&self.gas_price))?,
+            input: asc_new(heap, &*self.input)?,
+            nonce: asc_new(heap, &BigInt::from_unsigned_u256(&self.nonce))?,
+            max_fee_per_gas: self
+                .max_fee_per_gas
+                .map(|fee| asc_new(heap, &BigInt::from_unsigned_u256(&fee)))
+                .unwrap_or(Ok(AscPtr::null()))?,
+            max_priority_fee_per_gas: self
+                .max_priority_fee_per_gas
+                .map(|fee| asc_new(heap, &BigInt::from_unsigned_u256(&fee)))
+                .unwrap_or(Ok(AscPtr::null()))?,
+            tx_type: self
+                .transaction_type
+                .map(|kind| asc_new(heap, &BigInt::from(kind)))
+                .unwrap_or(Ok(AscPtr::null()))?,
+            access_list: self
+                .access_list
+                .as_ref()
+                .map(|list| asc_new(heap, list))
+                .unwrap_or(Ok(AscPtr::null()))?,
+        })
+    }
+}
+
+impl ToAscObj<AscAccessTuple> for AccessListItem {
+    fn to_asc_obj<H: AscHeap + ?Sized>(
+        &self,
+        heap: &mut H,
+    ) -> Result<AscAccessTuple, DeterministicHostError> {
+        Ok(AscAccessTuple {
+            address: asc_new(heap, &self.address)?,
+            storage_keys: asc_new(heap, &self.storage_keys)?,
+        })
+    }
+}
+
+impl ToAscObj<AscAccessTupleArray> for Vec<AccessListItem> {
+    fn to_asc_obj<H: AscHeap + ?Sized>(
+        &self,
+        heap: &mut H,
+    ) -> Result<AscAccessTupleArray, DeterministicHostError> {
+        let content: Result<Vec<_>, _> = self.iter().map(|x| asc_new(heap, x)).collect();
+        let content = content?;
+        Ok(AscAccessTupleArray(Array::new(&*content, heap)?))
+    }
+}
+
+impl ToAscObj<AscH256Array> for Vec<H256> {
+    fn to_asc_obj<H: AscHeap + ?Sized>(
+        &self,
+        heap: &mut H,
+    ) -> Result<AscH256Array, DeterministicHostError> {
+        let content: Result<Vec<_>, _> = self.iter().map(|x| asc_new(heap, x)).collect();
+        let content = content?;
+        Ok(AscH256Array(Array::new(&*content, heap)?))
+    }
+}
+
 impl<T, B> ToAscObj<AscEthereumEvent<T, B>> for EthereumEventData
 where
     T: AscType + AscIndexId,
@@ -479,6 +745,27 @@ impl ToAscObj<AscEthereumCall_0_0_3<AscEthereumTransaction_0_0_6, AscEthereumBlo
     }
 }
 
+impl ToAscObj<AscEthereumCall_0_0_3<AscEthereumTransaction_0_0_7, AscEthereumBlock_0_0_6>>
+    for EthereumCallData
+{
+    fn to_asc_obj<H: AscHeap + ?Sized>(
+        &self,
+        heap: &mut H,
+    ) -> Result<
+        AscEthereumCall_0_0_3<AscEthereumTransaction_0_0_7, AscEthereumBlock_0_0_6>,
+        DeterministicHostError,
+    > {
+        Ok(AscEthereumCall_0_0_3 {
+            to: asc_new(heap, &self.to)?,
+            from: asc_new(heap, &self.from)?,
+            block: asc_new(heap, &self.block)?,
+            transaction: asc_new(heap, &self.transaction)?,
+            inputs: asc_new(heap, &self.inputs)?,
+            outputs: asc_new(heap, &self.outputs)?,
+        })
+    }
+}
+
 impl ToAscObj<AscLogParam> for ethabi::LogParam {
     fn to_asc_obj<H: AscHeap + ?Sized>(
         &self,
@@ -31,15 +31,19 @@ fn test_trigger_ordering() {
 
     let mut call2 = EthereumCall::default();
     call2.transaction_index = 2;
+    call2.trace_address = vec![2];
     let call2 = EthereumTrigger::Call(Arc::new(call2));
 
     let mut call3 = EthereumCall::default();
     call3.transaction_index = 3;
     let call3 = EthereumTrigger::Call(Arc::new(call3));
 
-    // Call with the same tx index as call2
+    // Call with the same tx index as call2, but an earlier trace address;
+    // must come before call2 no matter in which order the provider
+    // returned the two
     let mut call4 = EthereumCall::default();
     call4.transaction_index = 2;
+    call4.trace_address = vec![0, 1];
     let call4 = EthereumTrigger::Call(Arc::new(call4));
 
     fn create_log(tx_index: u64, log_index: u64) -> Arc<Log> {
@@ -71,7 +75,7 @@ fn test_trigger_ordering() {
     let log3 = EthereumTrigger::Log(create_log(2, 5));
 
     let triggers = vec![
-        // Call triggers; these should be in the order 1, 2, 4, 3 after sorting
+        // Call triggers; these should be in the order 1, 4, 2, 3 after sorting
         call3.clone(),
         call1.clone(),
         call2.clone(),
@@ -92,6 +96,6 @@ fn test_trigger_ordering() {
 
     assert_eq!(
         block_with_triggers.trigger_data,
-        vec![log1, log2, call1, log3, call2, call4, call3, block2, block1]
+        vec![log1, log2, call1, log3, call4, call2, call3, block2, block1]
     );
 }
@@ -1,18 +1,32 @@
 use jsonrpc_core::types::Call;
 use jsonrpc_core::Value;
 
-use web3::transports::{http, ipc, ws};
+#[cfg(unix)]
+use web3::transports::ipc;
+use web3::transports::{http, ws};
 use web3::RequestId;
 
 use graph::prelude::*;
 use std::future::Future;
+use std::sync::Arc;
 
-/// Abstraction over the different web3 transports.
+use crate::rpc_recorder::RpcRecorder;
+
+/// Abstraction over the different web3 transports. The IPC transport uses
+/// Unix domain sockets and only exists on Unix-like platforms. The
+/// `Recorded` transport wraps one of the others and records its responses,
+/// or replays them from an earlier recording; see the `rpc_recorder`
+/// module.
 #[derive(Clone, Debug)]
 pub enum Transport {
     RPC(http::Http),
+    #[cfg(unix)]
     IPC(ipc::Ipc),
     WS(ws::WebSocket),
+    Recorded {
+        inner: Box<Transport>,
+        recorder: Arc<RpcRecorder>,
+    },
 }
 
 impl Transport {
@@ -25,6 +39,13 @@ impl Transport {
             .expect("Failed to connect to Ethereum IPC")
     }
 
+    /// IPC transports use Unix domain sockets; configuring one on other
+    /// platforms is an error.
+    #[cfg(not(unix))]
+    pub async fn new_ipc(_ipc: &str) -> Self {
+        panic!("IPC transports are only supported on Unix-like platforms")
+    }
+
     /// Creates a WebSocket transport.
     pub async fn new_ws(ws: &str) -> Self {
         ws::WebSocket::new(ws)
@@ -42,6 +63,19 @@ impl Transport {
             .map(|transport| Transport::RPC(transport))
             .expect("Failed to connect to Ethereum RPC")
     }
+
+    /// Wrap the transport so that its responses are recorded, or replayed
+    /// from an earlier recording, when the environment asks for it through
+    /// `GRAPH_ETHEREUM_RPC_RECORD_DIR` or `GRAPH_ETHEREUM_RPC_REPLAY_DIR`.
+    pub fn recorded(self, network: &str) -> Self {
+        match RpcRecorder::from_env(network) {
+            Some(recorder) => Transport::Recorded {
+                inner: Box::new(self),
+                recorder: Arc::new(recorder),
+            },
+            None => self,
+        }
+    }
 }
 
 impl web3::Transport for Transport {
@@ -50,16 +84,32 @@ impl web3::Transport for Transport {
     fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
         match self {
             Transport::RPC(http) => http.prepare(method, params),
+            #[cfg(unix)]
             Transport::IPC(ipc) => ipc.prepare(method, params),
             Transport::WS(ws) => ws.prepare(method, params),
+            Transport::Recorded { inner, .. } => inner.prepare(method, params),
         }
     }
 
     fn send(&self, id: RequestId, request: Call) -> Self::Out {
         match self {
             Transport::RPC(http) => Box::new(http.send(id, request)),
+            #[cfg(unix)]
             Transport::IPC(ipc) => Box::new(ipc.send(id, request)),
             Transport::WS(ws) => Box::new(ws.send(id, request)),
+            Transport::Recorded { inner, recorder } => {
+                if recorder.is_replay() {
+                    Box::new(futures03::future::ready(recorder.replay(&request)))
+                } else {
+                    let out = inner.send(id, request.clone());
+                    let recorder = recorder.cheap_clone();
+                    Box::new(Box::pin(async move {
+                        let response = out.await;
+                        recorder.record(&request, &response)?;
+                        response
+                    }))
+                }
+            }
         }
     }
 }
@@ -77,8 +127,29 @@ impl web3::BatchTransport for Transport {
     {
         match self {
             Transport::RPC(http) => Box::new(http.send_batch(requests)),
+            #[cfg(unix)]
             Transport::IPC(ipc) => Box::new(ipc.send_batch(requests)),
             Transport::WS(ws) => Box::new(ws.send_batch(requests)),
+            Transport::Recorded { inner, recorder } => {
+                let requests: Vec<(RequestId, Call)> = requests.into_iter().collect();
+                if recorder.is_replay() {
+                    let responses = requests
+                        .iter()
+                        .map(|(_, request)| recorder.replay(request))
+                        .collect();
+                    Box::new(futures03::future::ready(Ok(responses)))
+                } else {
+                    let out = inner.send_batch(requests.clone());
+                    let recorder = recorder.cheap_clone();
+                    Box::new(Box::pin(async move {
+                        let responses = out.await?;
+                        for ((_, request), response) in requests.iter().zip(responses.iter()) {
+                            recorder.record(request, response)?;
+                        }
+                        Ok(responses)
+                    }))
+                }
+            }
         }
     }
 }
@@ -1,5 +1,5 @@
 use graph::blockchain;
-use graph::blockchain::TriggerData;
+use graph::blockchain::{TransactionTrigger, TriggerData, TriggerOrderKey};
 use graph::prelude::ethabi::ethereum_types::H160;
 use graph::prelude::ethabi::ethereum_types::H256;
 use graph::prelude::ethabi::ethereum_types::U128;
@@ -8,6 +8,7 @@ use graph::prelude::ethabi::ethereum_types::U64;
 use graph::prelude::ethabi::Address;
 use graph::prelude::ethabi::Bytes;
 use graph::prelude::ethabi::LogParam;
+use graph::prelude::web3::types::AccessList;
 use graph::prelude::web3::types::Block;
 use graph::prelude::web3::types::Log;
 use graph::prelude::web3::types::Transaction;
@@ -21,7 +22,7 @@ use graph::runtime::DeterministicHostError;
 use graph::semver::Version;
 use std::convert::TryFrom;
 use std::ops::Deref;
-use std::{cmp::Ordering, sync::Arc};
+use std::sync::Arc;
 
 use crate::runtime::abi::AscEthereumBlock;
 use crate::runtime::abi::AscEthereumBlock_0_0_6;
@@ -31,6 +32,8 @@ use crate::runtime::abi::AscEthereumEvent;
 use crate::runtime::abi::AscEthereumTransaction_0_0_1;
 use crate::runtime::abi::AscEthereumTransaction_0_0_2;
 use crate::runtime::abi::AscEthereumTransaction_0_0_6;
+use crate::runtime::abi::AscEthereumTransaction_0_0_7;
+use crate::runtime::abi::AscFullEthereumBlock;
 
 // ETHDEP: This should be defined in only one place.
 type LightEthereumBlock = Block<Transaction>;
@@ -51,6 +54,10 @@ pub enum MappingTrigger {
     },
     Block {
         block: Arc<LightEthereumBlock>,
+        /// Pass the transactions of the block to the handler. Set from the
+        /// `transactions` flag of the matching block handler; we only decode
+        /// the transaction list for handlers that ask for it
+        include_transactions: bool,
     },
 }
 
@@ -96,7 +103,7 @@ impl std::fmt::Debug for MappingTrigger {
                 _inputs: inputs.clone(),
                 _outputs: outputs.clone(),
             },
-            MappingTrigger::Block { block: _ } => MappingTriggerWithoutBlock::Block,
+            MappingTrigger::Block { .. } => MappingTriggerWithoutBlock::Block,
         };
 
         write!(f, "{:?}", trigger_without_block)
@@ -122,7 +129,14 @@ impl blockchain::MappingTrigger for MappingTrigger {
                     params,
                 };
                 let api_version = heap.api_version();
-                if api_version >= Version::new(0, 0, 6) {
+                if api_version >= Version::new(0, 0, 7) {
+                    asc_new::<
+                        AscEthereumEvent<AscEthereumTransaction_0_0_7, AscEthereumBlock_0_0_6>,
+                        _,
+                        _,
+                    >(heap, &ethereum_event_data)?
+                    .erase()
+                } else if api_version >= Version::new(0, 0, 6) {
                     asc_new::<
                         AscEthereumEvent<AscEthereumTransaction_0_0_6, AscEthereumBlock_0_0_6>,
                         _,
@@ -158,7 +172,14 @@ impl blockchain::MappingTrigger for MappingTrigger {
                     inputs,
                     outputs,
                 };
-                if heap.api_version() >= Version::new(0, 0, 6) {
+                if heap.api_version() >= Version::new(0, 0, 7) {
+                    asc_new::<
+                        AscEthereumCall_0_0_3<AscEthereumTransaction_0_0_7, AscEthereumBlock_0_0_6>,
+                        _,
+                        _,
+                    >(heap, &call)?
+                    .erase()
+                } else if heap.api_version() >= Version::new(0, 0, 6) {
                     asc_new::<
                         AscEthereumCall_0_0_3<AscEthereumTransaction_0_0_6, AscEthereumBlock_0_0_6>,
                         _,
@@ -176,12 +197,26 @@ impl blockchain::MappingTrigger for MappingTrigger {
                     asc_new::<AscEthereumCall, _, _>(heap, &call)?.erase()
                 }
             }
-            MappingTrigger::Block { block } => {
-                let block = EthereumBlockData::from(block.as_ref());
-                if heap.api_version() >= Version::new(0, 0, 6) {
-                    asc_new::<AscEthereumBlock_0_0_6, _, _>(heap, &block)?.erase()
+            MappingTrigger::Block {
+                block,
+                include_transactions,
+            } => {
+                // Manifest validation only allows `transactions` with
+                // apiVersion 0.0.7 and up, where `graph-ts` has a block
+                // class with a transaction list
+                if include_transactions && heap.api_version() >= Version::new(0, 0, 7) {
+                    let block = FullEthereumBlockData::from(block.as_ref());
+                    asc_new::<AscFullEthereumBlock<AscEthereumTransaction_0_0_7>, _, _>(
+                        heap, &block,
+                    )?
+                    .erase()
                 } else {
-                    asc_new::<AscEthereumBlock, _, _>(heap, &block)?.erase()
+                    let block = EthereumBlockData::from(block.as_ref());
+                    if heap.api_version() >= Version::new(0, 0, 6) {
+                        asc_new::<AscEthereumBlock_0_0_6, _, _>(heap, &block)?.erase()
+                    } else {
+                        asc_new::<AscEthereumBlock, _, _>(heap, &block)?.erase()
+                    }
                 }
             }
         })
@@ -239,52 +274,6 @@ impl EthereumTrigger {
     }
 }
 
-impl Ord for EthereumTrigger {
-    fn cmp(&self, other: &Self) -> Ordering {
-        match (self, other) {
-            // Keep the order when comparing two block triggers
-            (Self::Block(..), Self::Block(..)) => Ordering::Equal,
-
-            // Block triggers always come last
-            (Self::Block(..), _) => Ordering::Greater,
-            (_, Self::Block(..)) => Ordering::Less,
-
-            // Calls are ordered by their tx indexes
-            (Self::Call(a), Self::Call(b)) => a.transaction_index.cmp(&b.transaction_index),
-
-            // Events are ordered by their log index
-            (Self::Log(a), Self::Log(b)) => a.log_index.cmp(&b.log_index),
-
-            // Calls vs. events are logged by their tx index;
-            // if they are from the same transaction, events come first
-            (Self::Call(a), Self::Log(b))
-                if a.transaction_index == b.transaction_index.unwrap().as_u64() =>
-            {
-                Ordering::Greater
-            }
-            (Self::Log(a), Self::Call(b))
-                if a.transaction_index.unwrap().as_u64() == b.transaction_index =>
-            {
-                Ordering::Less
-            }
-            (Self::Call(a), Self::Log(b)) => a
-                .transaction_index
-                .cmp(&b.transaction_index.unwrap().as_u64()),
-            (Self::Log(a), Self::Call(b)) => a
-                .transaction_index
-                .unwrap()
-                .as_u64()
-                .cmp(&b.transaction_index),
-        }
-    }
-}
-
-impl PartialOrd for EthereumTrigger {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
-}
-
 impl TriggerData for EthereumTrigger {
     fn error_context(&self) -> std::string::String {
         let transaction_id = match self {
@@ -303,6 +292,20 @@ impl TriggerData for EthereumTrigger {
             None => String::new(),
         }
     }
+
+    fn order_key(&self) -> TriggerOrderKey {
+        match self {
+            EthereumTrigger::Log(log) => TriggerOrderKey::Transaction(
+                log.transaction_index.unwrap().as_u64(),
+                TransactionTrigger::Log(log.log_index.unwrap().as_u64()),
+            ),
+            EthereumTrigger::Call(call) => TriggerOrderKey::Transaction(
+                call.transaction_index,
+                TransactionTrigger::Call(call.trace_address.clone()),
+            ),
+            EthereumTrigger::Block(..) => TriggerOrderKey::Block,
+        }
+    }
 }
 
 /// Ethereum block data.
@@ -347,6 +350,28 @@ impl<'a, T> From<&'a Block<T>> for EthereumBlockData {
     }
 }
 
+/// Ethereum block data together with the transactions the block contains.
+/// Only materialized for block handlers that set `transactions: true` in
+/// the manifest
+#[derive(Clone, Debug)]
+pub struct FullEthereumBlockData {
+    pub block: EthereumBlockData,
+    pub transactions: Vec<EthereumTransactionData>,
+}
+
+impl<'a> From<&'a Block<Transaction>> for FullEthereumBlockData {
+    fn from(block: &'a Block<Transaction>) -> FullEthereumBlockData {
+        FullEthereumBlockData {
+            block: EthereumBlockData::from(block),
+            transactions: block
+                .transactions
+                .iter()
+                .map(EthereumTransactionData::from)
+                .collect(),
+        }
+    }
+}
+
 /// Ethereum transaction data.
 #[derive(Clone, Debug)]
 pub struct EthereumTransactionData {
@@ -359,6 +384,14 @@ pub struct EthereumTransactionData {
     pub gas_price: U256,
     pub input: Bytes,
     pub nonce: U256,
+
+    // EIP-1559 and EIP-2930 fields. These are `None` for legacy transactions
+    // and on networks that have not adopted typed transactions; providers for
+    // such networks simply omit them from the RPC response.
+    pub max_fee_per_gas: Option<U256>,
+    pub max_priority_fee_per_gas: Option<U256>,
+    pub transaction_type: Option<U64>,
+    pub access_list: Option<AccessList>,
 }
 
 impl From<&'_ Transaction> for EthereumTransactionData {
@@ -376,6 +409,10 @@ impl From<&'_ Transaction> for EthereumTransactionData {
             gas_price: tx.gas_price,
             input: tx.input.0.clone(),
             nonce: tx.nonce.clone(),
+            max_fee_per_gas: tx.max_fee_per_gas,
+            max_priority_fee_per_gas: tx.max_priority_fee_per_gas,
+            transaction_type: tx.transaction_type,
+            access_list: tx.access_list.clone(),
         }
     }
 }
@@ -135,7 +135,7 @@ impl blockchain::DataSource<Chain> for DataSource {
         todo!()
     }
 
-    fn validate(&self) -> Vec<Error> {
+    fn validate(&self, _spec_version: &semver::Version) -> Vec<Error> {
         let mut errors = Vec::new();
 
         if self.kind != NEAR_KIND {
@@ -1,6 +1,6 @@
 use graph::blockchain;
 use graph::blockchain::Block;
-use graph::blockchain::TriggerData;
+use graph::blockchain::{TriggerData, TriggerOrderKey};
 use graph::cheap_clone::CheapClone;
 use graph::prelude::hex;
 use graph::prelude::web3::types::H256;
@@ -9,7 +9,7 @@ use graph::runtime::asc_new;
 use graph::runtime::AscHeap;
 use graph::runtime::AscPtr;
 use graph::runtime::DeterministicHostError;
-use std::{cmp::Ordering, sync::Arc};
+use std::sync::Arc;
 
 use crate::codec;
 
@@ -91,29 +91,6 @@ impl NearTrigger {
     }
 }
 
-impl Ord for NearTrigger {
-    fn cmp(&self, other: &Self) -> Ordering {
-        match (self, other) {
-            // Keep the order when comparing two block triggers
-            (Self::Block(..), Self::Block(..)) => Ordering::Equal,
-
-            // Block triggers always come last
-            (Self::Block(..), _) => Ordering::Greater,
-            (_, Self::Block(..)) => Ordering::Less,
-
-            // Execution outcomes have no intrinsic ordering information, so we keep the order in
-            // which they are included in the `receipt_execution_outcomes` field of `IndexerShard`.
-            (Self::Receipt(..), Self::Receipt(..)) => Ordering::Equal,
-        }
-    }
-}
-
-impl PartialOrd for NearTrigger {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
-}
-
 impl TriggerData for NearTrigger {
     fn error_context(&self) -> std::string::String {
         match self {
@@ -130,6 +107,15 @@ impl TriggerData for NearTrigger {
             }
         }
     }
+
+    fn order_key(&self) -> TriggerOrderKey {
+        match self {
+            // Execution outcomes have no intrinsic ordering information, so we keep the order in
+            // which they are included in the `receipt_execution_outcomes` field of `IndexerShard`.
+            NearTrigger::Receipt(..) => TriggerOrderKey::Unordered,
+            NearTrigger::Block(..) => TriggerOrderKey::Block,
+        }
+    }
 }
 
 pub struct ReceiptWithOutcome {
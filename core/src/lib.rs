@@ -1,7 +1,11 @@
 mod link_resolver;
 mod metrics;
+mod search_sync;
 mod subgraph;
 
 pub use crate::link_resolver::LinkResolver;
 pub use crate::metrics::MetricsRegistry;
-pub use crate::subgraph::{SubgraphAssignmentProvider, SubgraphInstanceManager, SubgraphRegistrar};
+pub use crate::subgraph::{
+    replay_block, BlockReplayOutcome, EntityDiff, SubgraphAssignmentProvider,
+    SubgraphInstanceManager, SubgraphRegistrar,
+};
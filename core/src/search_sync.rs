@@ -0,0 +1,468 @@
+//! Mirror selected entity types of a deployment into an external search
+//! engine as blocks are processed, so that applications get typo-tolerant
+//! full text search without scraping GraphQL.
+//!
+//! The mirror is configured through a JSON file whose path is given in
+//! `GRAPH_SEARCH_SYNC_CONFIG`. The file maps deployment ids to the engine
+//! that should receive their changes:
+//!
+//! ```json
+//! {
+//!     "QmSubgraph...": {
+//!         "engine": "elasticsearch",
+//!         "endpoint": "http://localhost:9200",
+//!         "entity_types": ["Token", "Pair"],
+//!         "index_prefix": "tokens",
+//!         "api_key": "..."
+//!     }
+//! }
+//! ```
+//!
+//! Every document carries the block number at which it was written in a
+//! `block` field. When a block is reverted, documents written at or after
+//! the reverted block are deleted; entities whose latest change was
+//! reverted to an older version reappear in the index the next time they
+//! change. Synchronization is best effort and never fails or stalls the
+//! subgraph: operations are queued for a background task, requests to
+//! the search engine time out, and errors are logged while indexing
+//! continues. When the search engine falls so far behind that the queue
+//! fills up, operations are dropped and the index needs a full resync.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use graph::prelude::{
+    anyhow::{anyhow, Context as _, Error},
+    reqwest, serde_json,
+    tokio::sync::mpsc,
+    warn, BlockNumber, BlockPtr, DeploymentHash, Entity, EntityModification, Logger, Value,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+/// How many reverted documents to look up in one query when reverting a
+/// Meilisearch index; anything beyond that is left to a full resync
+const MEILISEARCH_REVERT_LIMIT: u32 = 10_000;
+
+/// How long a request to the search engine may take before it is
+/// abandoned, so that a hung engine can not block the background task
+/// forever
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How many operations may wait in the queue to the background task
+/// before further ones are dropped
+const QUEUE_SIZE: usize = 64;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Engine {
+    Elasticsearch,
+    Meilisearch,
+}
+
+/// The per-deployment entry in the configuration file
+#[derive(Debug, Deserialize)]
+struct Config {
+    engine: Engine,
+    endpoint: String,
+    /// The entity types that should be mirrored
+    entity_types: Vec<String>,
+    /// The prefix for index names; defaults to the lowercased deployment
+    /// id. Each entity type goes into the index `{prefix}-{entity_type}`
+    #[serde(default)]
+    index_prefix: Option<String>,
+    /// Sent as `X-Meili-API-Key` for Meilisearch and verbatim in the
+    /// `Authorization` header for Elasticsearch
+    #[serde(default)]
+    api_key: Option<String>,
+}
+
+/// One operation against the search engine, derived from an
+/// `EntityModification` before the modification is handed to the store
+enum SearchOp {
+    Upsert {
+        index: String,
+        id: String,
+        doc: serde_json::Value,
+    },
+    Delete {
+        index: String,
+        id: String,
+    },
+}
+
+/// The search operations for one block, built with `SearchSyncer::batch`
+/// and applied with `SearchSyncer::sync` once the block has been
+/// transacted into the store
+pub struct SearchBatch {
+    ops: Vec<SearchOp>,
+}
+
+/// One message to the background task that talks to the search engine
+enum SyncMsg {
+    Batch(SearchBatch),
+    Revert(BlockNumber),
+}
+
+pub struct SearchSyncer {
+    logger: Logger,
+    mirror: Arc<Mirror>,
+    /// Operations are handed to a background task through this bounded
+    /// queue so that a slow or hung search engine can not stall block
+    /// processing
+    sender: mpsc::Sender<SyncMsg>,
+}
+
+/// The connection to the search engine, shared between the `SearchSyncer`
+/// and the background task that applies the queued operations
+struct Mirror {
+    logger: Logger,
+    engine: Engine,
+    endpoint: String,
+    index_prefix: String,
+    entity_types: HashSet<String>,
+    api_key: Option<String>,
+    client: reqwest::Client,
+    /// Meilisearch indexes whose settings have been adjusted so that
+    /// reverts can filter on the `block` attribute
+    prepared_indexes: Mutex<HashSet<String>>,
+}
+
+/// Render `value` as the JSON that is stored in the search document.
+/// Numeric types that do not fit into JSON numbers use their canonical
+/// string form
+fn value_to_json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::String(s) => s.as_str().into(),
+        Value::Int(i) => (*i).into(),
+        Value::BigDecimal(d) => d.to_string().into(),
+        Value::Bool(b) => (*b).into(),
+        Value::List(values) => serde_json::Value::Array(values.iter().map(value_to_json).collect()),
+        Value::Null => serde_json::Value::Null,
+        Value::Bytes(bytes) => bytes.to_string().into(),
+        Value::BigInt(number) => number.to_string().into(),
+    }
+}
+
+impl SearchSyncer {
+    /// The syncer for `deployment` according to the file that
+    /// `GRAPH_SEARCH_SYNC_CONFIG` points at; `None` if the variable is
+    /// not set or the file has no entry for `deployment`
+    pub fn for_deployment(
+        logger: &Logger,
+        deployment: &DeploymentHash,
+    ) -> Result<Option<Arc<Self>>, Error> {
+        let path = match std::env::var("GRAPH_SEARCH_SYNC_CONFIG") {
+            Ok(path) => path,
+            Err(_) => return Ok(None),
+        };
+        let file = File::open(&path)
+            .with_context(|| format!("can not open GRAPH_SEARCH_SYNC_CONFIG file `{}`", path))?;
+        let mut configs: HashMap<String, Config> = serde_json::from_reader(file)
+            .with_context(|| format!("invalid search sync configuration in `{}`", path))?;
+        let config = match configs.remove(deployment.as_str()) {
+            Some(config) => config,
+            None => return Ok(None),
+        };
+        if config.entity_types.is_empty() {
+            return Err(anyhow!(
+                "the search sync configuration for `{}` does not list any entity types",
+                deployment
+            ));
+        }
+        let index_prefix = config
+            .index_prefix
+            .unwrap_or_else(|| deployment.as_str().to_lowercase());
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()?;
+        let mirror = Arc::new(Mirror {
+            logger: logger.clone(),
+            engine: config.engine,
+            endpoint: config.endpoint.trim_end_matches('/').to_string(),
+            index_prefix,
+            entity_types: config.entity_types.into_iter().collect(),
+            api_key: config.api_key,
+            client,
+            prepared_indexes: Mutex::new(HashSet::new()),
+        });
+        // The background task exits when the syncer, and with it the
+        // sender, is dropped
+        let (sender, mut receiver) = mpsc::channel(QUEUE_SIZE);
+        {
+            let mirror = mirror.clone();
+            graph::spawn(async move {
+                while let Some(msg) = receiver.recv().await {
+                    match msg {
+                        SyncMsg::Batch(batch) => mirror.sync(batch).await,
+                        SyncMsg::Revert(block) => mirror.revert_to(block).await,
+                    }
+                }
+            });
+        }
+        Ok(Some(Arc::new(SearchSyncer {
+            logger: logger.clone(),
+            mirror,
+            sender,
+        })))
+    }
+
+    /// The search operations for the modifications of one block. This is
+    /// separate from `sync` so that it can run before the modifications
+    /// are moved into the store transaction
+    pub fn batch(&self, block_ptr: &BlockPtr, mods: &[EntityModification]) -> SearchBatch {
+        self.mirror.batch(block_ptr, mods)
+    }
+
+    /// Queue `batch` for the search engine. Errors are logged by the
+    /// background task and never returned since the mirror must not
+    /// stall indexing; when the queue is full, the batch is dropped
+    pub fn sync(&self, batch: SearchBatch) {
+        if batch.ops.is_empty() {
+            return;
+        }
+        if self.sender.try_send(SyncMsg::Batch(batch)).is_err() {
+            warn!(
+                self.logger,
+                "The search mirror can not keep up and entity changes were \
+                 dropped; the index needs a full resync"
+            );
+        }
+    }
+
+    /// Queue the removal of documents that were written after `block`
+    /// because the blocks they came from were reverted
+    pub fn revert_to(&self, block: BlockNumber) {
+        if self.sender.try_send(SyncMsg::Revert(block)).is_err() {
+            warn!(self.logger, "The search mirror can not keep up and a revert was \
+                   dropped; it may contain stale documents";
+                  "block" => block);
+        }
+    }
+}
+
+impl Mirror {
+    fn index(&self, entity_type: &str) -> String {
+        format!("{}-{}", self.index_prefix, entity_type.to_lowercase())
+    }
+
+    fn request(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match (&self.api_key, self.engine) {
+            (Some(key), Engine::Meilisearch) => builder.header("X-Meili-API-Key", key),
+            (Some(key), Engine::Elasticsearch) => builder.header("Authorization", key),
+            (None, _) => builder,
+        }
+    }
+
+    fn doc(&self, block: BlockNumber, data: &Entity) -> serde_json::Value {
+        let mut doc: serde_json::Map<String, serde_json::Value> = data
+            .iter()
+            .map(|(name, value)| (name.clone(), value_to_json(value)))
+            .collect();
+        doc.insert("block".to_string(), block.into());
+        doc.into()
+    }
+
+    fn batch(&self, block_ptr: &BlockPtr, mods: &[EntityModification]) -> SearchBatch {
+        use EntityModification::*;
+
+        let ops = mods
+            .iter()
+            .filter(|entity_mod| {
+                self.entity_types
+                    .contains(entity_mod.entity_key().entity_type.as_str())
+            })
+            .map(|entity_mod| {
+                let key = entity_mod.entity_key();
+                let index = self.index(key.entity_type.as_str());
+                let id = key.entity_id.clone();
+                match entity_mod {
+                    Insert { data, .. } | Overwrite { data, .. } => SearchOp::Upsert {
+                        index,
+                        id,
+                        doc: self.doc(block_ptr.number, data),
+                    },
+                    Remove { .. } => SearchOp::Delete { index, id },
+                }
+            })
+            .collect();
+        SearchBatch { ops }
+    }
+
+    /// Apply `batch` to the search engine. Errors are logged but not
+    /// returned since the mirror is best effort
+    async fn sync(&self, batch: SearchBatch) {
+        if batch.ops.is_empty() {
+            return;
+        }
+        let res = match self.engine {
+            Engine::Elasticsearch => self.sync_elasticsearch(&batch).await,
+            Engine::Meilisearch => self.sync_meilisearch(&batch).await,
+        };
+        if let Err(e) = res {
+            warn!(self.logger, "Failed to mirror entity changes to the search engine";
+                  "error" => e.to_string());
+        }
+    }
+
+    /// Remove documents that were written after `block` from all mirrored
+    /// indexes because the blocks they came from were reverted
+    async fn revert_to(&self, block: BlockNumber) {
+        let res = match self.engine {
+            Engine::Elasticsearch => self.revert_elasticsearch(block).await,
+            Engine::Meilisearch => self.revert_meilisearch(block).await,
+        };
+        if let Err(e) = res {
+            warn!(self.logger, "Failed to revert the search mirror; it may contain stale documents";
+                  "block" => block,
+                  "error" => e.to_string());
+        }
+    }
+
+    async fn sync_elasticsearch(&self, batch: &SearchBatch) -> Result<(), Error> {
+        let mut body = String::new();
+        for op in &batch.ops {
+            match op {
+                SearchOp::Upsert { index, id, doc } => {
+                    body.push_str(&json!({ "index": { "_index": index, "_id": id } }).to_string());
+                    body.push('\n');
+                    body.push_str(&doc.to_string());
+                    body.push('\n');
+                }
+                SearchOp::Delete { index, id } => {
+                    body.push_str(&json!({ "delete": { "_index": index, "_id": id } }).to_string());
+                    body.push('\n');
+                }
+            }
+        }
+        let url = format!("{}/_bulk", self.endpoint);
+        self.request(self.client.post(&url))
+            .header("content-type", "application/x-ndjson")
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    async fn revert_elasticsearch(&self, block: BlockNumber) -> Result<(), Error> {
+        for entity_type in &self.entity_types {
+            let url = format!(
+                "{}/{}/_delete_by_query",
+                self.endpoint,
+                self.index(entity_type)
+            );
+            let response = self
+                .request(self.client.post(&url))
+                .json(&json!({ "query": { "range": { "block": { "gt": block } } } }))
+                .send()
+                .await?;
+            // The index does not exist until the first document is
+            // written to it
+            if response.status() != reqwest::StatusCode::NOT_FOUND {
+                response.error_for_status()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Make sure `index` allows filtering on `block` so that reverts work
+    async fn prepare_meilisearch_index(&self, index: &str) -> Result<(), Error> {
+        if self.prepared_indexes.lock().unwrap().contains(index) {
+            return Ok(());
+        }
+        let url = format!("{}/indexes/{}/settings", self.endpoint, index);
+        self.request(self.client.post(&url))
+            .json(&json!({ "filterableAttributes": ["block"] }))
+            .send()
+            .await?
+            .error_for_status()?;
+        self.prepared_indexes
+            .lock()
+            .unwrap()
+            .insert(index.to_string());
+        Ok(())
+    }
+
+    async fn sync_meilisearch(&self, batch: &SearchBatch) -> Result<(), Error> {
+        let mut upserts: HashMap<&str, Vec<&serde_json::Value>> = HashMap::new();
+        let mut deletes: HashMap<&str, Vec<&str>> = HashMap::new();
+        for op in &batch.ops {
+            match op {
+                SearchOp::Upsert { index, doc, .. } => {
+                    upserts.entry(index).or_default().push(doc);
+                }
+                SearchOp::Delete { index, id } => {
+                    deletes.entry(index).or_default().push(id);
+                }
+            }
+        }
+        for (index, docs) in upserts {
+            self.prepare_meilisearch_index(index).await?;
+            let url = format!("{}/indexes/{}/documents", self.endpoint, index);
+            self.request(self.client.post(&url))
+                .json(&docs)
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        for (index, ids) in deletes {
+            let url = format!("{}/indexes/{}/documents/delete-batch", self.endpoint, index);
+            self.request(self.client.post(&url))
+                .json(&ids)
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        Ok(())
+    }
+
+    async fn revert_meilisearch(&self, block: BlockNumber) -> Result<(), Error> {
+        #[derive(Deserialize)]
+        struct Hit {
+            id: String,
+        }
+        #[derive(Deserialize)]
+        struct SearchResponse {
+            hits: Vec<Hit>,
+        }
+
+        for entity_type in &self.entity_types {
+            let index = self.index(entity_type);
+            let url = format!("{}/indexes/{}/search", self.endpoint, index);
+            let response = self
+                .request(self.client.post(&url))
+                .json(&json!({
+                    "q": "",
+                    "filter": format!("block > {}", block),
+                    "limit": MEILISEARCH_REVERT_LIMIT,
+                    "attributesToRetrieve": ["id"],
+                }))
+                .send()
+                .await?;
+            if response.status() == reqwest::StatusCode::NOT_FOUND {
+                continue;
+            }
+            let response: SearchResponse = response.error_for_status()?.json().await?;
+            if response.hits.len() as u32 == MEILISEARCH_REVERT_LIMIT {
+                warn!(self.logger, "The search mirror has more reverted documents than can be \
+                       removed in one pass; the index needs a full resync";
+                      "index" => &index);
+            }
+            if response.hits.is_empty() {
+                continue;
+            }
+            let ids: Vec<&str> = response.hits.iter().map(|hit| hit.id.as_str()).collect();
+            let url = format!("{}/indexes/{}/documents/delete-batch", self.endpoint, index);
+            self.request(self.client.post(&url))
+                .json(&ids)
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,272 @@
+//! Fetch a missing graft base from another graph-node.
+//!
+//! When a grafted subgraph is deployed and its base is not in the local
+//! store, the deployment normally fails validation. With
+//! `GRAPH_GRAFT_BASE_FETCH_URL` set, the base is instead downloaded from
+//! the index node endpoint of another graph-node through its `/export`
+//! endpoint, checked against the entity count and keccak-256 checksum
+//! from the export, and stored as an unnamed local deployment that the
+//! graft then copies from like any other base.
+
+use std::env;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use graph::components::store::EntityType;
+use graph::data::subgraph::schema::{
+    SubgraphDeploymentEntity, SubgraphHealth, SubgraphManifestEntity,
+};
+use graph::data::subgraph::{Graft, Link};
+use graph::prelude::{
+    anyhow, hex, info, lazy_static, reqwest, serde_json, serde_yaml, tiny_keccak,
+    web3::types::H256, BlockPtr, Entity, Error, LinkResolver, Logger, Schema,
+    SubgraphRegistrarError, SubgraphStore,
+};
+
+lazy_static! {
+    /// The URL of the index node endpoint of another graph-node, e.g.
+    /// `https://other-node:8030`, from which missing graft bases are
+    /// fetched. When it is not set, grafting onto a base that is not in
+    /// the local store fails validation as usual
+    static ref FETCH_URL: Option<String> = env::var("GRAPH_GRAFT_BASE_FETCH_URL")
+        .ok()
+        .map(|url| url.trim_end_matches('/').to_string());
+    /// Refuse to download graft bases whose export is bigger than this
+    /// many bytes. Defaults to 1 GB
+    static ref FETCH_MAX_BYTES: usize = env::var("GRAPH_GRAFT_BASE_FETCH_MAX_BYTES")
+        .ok()
+        .map(|s| {
+            usize::from_str(&s).unwrap_or_else(|_| {
+                panic!(
+                    "GRAPH_GRAFT_BASE_FETCH_MAX_BYTES must be a number, but is `{}`",
+                    s
+                )
+            })
+        })
+        .unwrap_or(1_000_000_000);
+}
+
+/// If `raw` grafts onto a base that is not present in `store`, and
+/// `GRAPH_GRAFT_BASE_FETCH_URL` is set, fetch the base from that node and
+/// store it locally so that manifest validation and the graft itself can
+/// proceed. Do nothing when the manifest does not declare a graft or when
+/// the base is already present
+pub(super) async fn fetch_base_if_missing<S, L>(
+    logger: &Logger,
+    store: Arc<S>,
+    resolver: &Arc<L>,
+    raw: &serde_yaml::Mapping,
+) -> Result<(), SubgraphRegistrarError>
+where
+    S: SubgraphStore,
+    L: LinkResolver,
+{
+    let url = match &*FETCH_URL {
+        Some(url) => url,
+        None => return Ok(()),
+    };
+    let graft = match raw.get(&serde_yaml::Value::String("graft".to_string())) {
+        Some(graft) => serde_yaml::from_value::<Graft>(graft.clone())
+            .map_err(|e| SubgraphRegistrarError::ResolveError(e.into()))?,
+        None => return Ok(()),
+    };
+
+    // `Graft::validate` treats an error here the same as a missing base
+    if let Ok(Some(_)) = store.least_block_ptr(&graft.base) {
+        return Ok(());
+    }
+
+    info!(logger, "Fetching graft base from remote node";
+          "base" => graft.base.to_string(),
+          "block" => graft.block,
+          "url" => url);
+
+    fetch_base(logger, store, resolver, url, &graft)
+        .await
+        .map_err(|e| {
+            SubgraphRegistrarError::Unknown(
+                e.context(format!("failed to fetch graft base `{}`", graft.base)),
+            )
+        })
+}
+
+async fn fetch_base<S, L>(
+    logger: &Logger,
+    store: Arc<S>,
+    resolver: &Arc<L>,
+    url: &str,
+    graft: &Graft,
+) -> Result<(), Error>
+where
+    S: SubgraphStore,
+    L: LinkResolver,
+{
+    // The base manifest tells us the schema and network of the base;
+    // its hash is an IPFS hash just like that of the subgraph we are
+    // deploying
+    let raw: serde_yaml::Mapping = {
+        let file_bytes = resolver.cat(logger, &graft.base.to_ipfs_link()).await?;
+        serde_yaml::from_slice(&file_bytes)?
+    };
+    let schema_text = {
+        let link = raw
+            .get(&serde_yaml::Value::String("schema".to_string()))
+            .and_then(|schema| schema.get(&serde_yaml::Value::String("file".to_string())))
+            .cloned()
+            .ok_or_else(|| anyhow!("the manifest of the graft base has no schema file"))?;
+        let link = serde_yaml::from_value::<Link>(link)?;
+        String::from_utf8(resolver.cat(logger, &link).await?)?
+    };
+    let schema = Schema::parse(&schema_text, graft.base.clone())?;
+    let network = raw
+        .get(&serde_yaml::Value::String("dataSources".to_string()))
+        .and_then(|data_sources| data_sources.as_sequence())
+        .and_then(|data_sources| data_sources.first())
+        .and_then(|data_source| data_source.get(&serde_yaml::Value::String("network".to_string())))
+        .and_then(|network| network.as_str())
+        .ok_or_else(|| anyhow!("the manifest of the graft base has no network"))?
+        .to_string();
+    let string_value = |key: &str| {
+        raw.get(&serde_yaml::Value::String(key.to_string()))
+            .and_then(|value| value.as_str())
+            .map(String::from)
+    };
+    let manifest = SubgraphManifestEntity {
+        spec_version: string_value("specVersion")
+            .ok_or_else(|| anyhow!("the manifest of the graft base has no specVersion"))?,
+        description: string_value("description"),
+        repository: string_value("repository"),
+        features: raw
+            .get(&serde_yaml::Value::String("features".to_string()))
+            .and_then(|features| features.as_sequence())
+            .map(|features| {
+                features
+                    .iter()
+                    .filter_map(|feature| feature.as_str())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default(),
+        schema: schema_text,
+    };
+
+    let export_url = format!("{}/export/{}?block={}", url, graft.base, graft.block);
+    let mut resp = reqwest::Client::new()
+        .get(&export_url)
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status())?;
+    let mut data: Vec<u8> = Vec::new();
+    while let Some(chunk) = resp.chunk().await? {
+        if data.len() + chunk.len() > *FETCH_MAX_BYTES {
+            return Err(anyhow!(
+                "the export is bigger than GRAPH_GRAFT_BASE_FETCH_MAX_BYTES ({} bytes)",
+                *FETCH_MAX_BYTES
+            ));
+        }
+        data.extend_from_slice(&chunk);
+    }
+
+    let mut lines = data.split(|b| *b == b'\n').filter(|line| !line.is_empty());
+    let header: serde_json::Value = serde_json::from_slice(
+        lines
+            .next()
+            .ok_or_else(|| anyhow!("the export from `{}` is empty", export_url))?,
+    )?;
+    if header["deployment"].as_str() != Some(graft.base.as_str()) {
+        return Err(anyhow!(
+            "the export is for deployment `{}`, not for the graft base",
+            header["deployment"]
+        ));
+    }
+    if header["block"].as_i64() != Some(graft.block as i64) {
+        return Err(anyhow!(
+            "the export is at block {}, but the graft needs block {}",
+            header["block"],
+            graft.block
+        ));
+    }
+    let block_hash = header["blockHash"]
+        .as_str()
+        .map(|hash| {
+            let bytes = hex::decode(hash.trim_start_matches("0x"))?;
+            if bytes.len() != 32 {
+                return Err(anyhow!("the block hash `{}` is not 32 bytes long", hash));
+            }
+            Ok(H256::from_slice(&bytes))
+        })
+        .transpose()?;
+
+    // The checksum covers the raw bytes of the entity lines, including
+    // their newlines but excluding the header, exactly as the remote
+    // node hashed them before sending
+    let mut keccak = tiny_keccak::Keccak::new_keccak256();
+    let mut count: u64 = 0;
+    let mut entities: Vec<(EntityType, Vec<Entity>)> = Vec::new();
+    let mut trailer: Option<serde_json::Value> = None;
+    for line in lines {
+        if trailer.is_some() {
+            return Err(anyhow!("the export continues after the trailer"));
+        }
+        let value: serde_json::Value = serde_json::from_slice(line)?;
+        match value.get("entityType").and_then(|typ| typ.as_str()) {
+            Some(entity_type) => {
+                keccak.update(line);
+                keccak.update(b"\n");
+                count += 1;
+                let entity: Entity = serde_json::from_value(value["data"].clone())?;
+                // The export is grouped by entity type
+                match entities.last_mut() {
+                    Some((typ, batch)) if typ.as_str() == entity_type => batch.push(entity),
+                    _ => entities.push((EntityType::new(entity_type.to_string()), vec![entity])),
+                }
+            }
+            None => trailer = Some(value),
+        }
+    }
+    let trailer =
+        trailer.ok_or_else(|| anyhow!("the export has no trailer and was probably truncated"))?;
+    if trailer["entityCount"].as_u64() != Some(count) {
+        return Err(anyhow!(
+            "the export should contain {} entities, but has {}",
+            trailer["entityCount"],
+            count
+        ));
+    }
+    let mut hash = [0u8; 32];
+    keccak.finalize(&mut hash);
+    let checksum = format!("0x{}", hex::encode(hash));
+    if trailer["keccak256"].as_str() != Some(checksum.as_str()) {
+        return Err(anyhow!(
+            "the keccak-256 checksum of the downloaded entities does not match the trailer"
+        ));
+    }
+
+    // Mark the copy as synced since it will never index anything; the
+    // graft only needs its data and block pointer. Without a block hash
+    // in the export we have to make do with a zero hash, which is fine
+    // for copying since only the block number matters there
+    let latest_block = BlockPtr::from((block_hash.unwrap_or_else(H256::zero), graft.block));
+    let deployment = SubgraphDeploymentEntity {
+        manifest,
+        failed: false,
+        health: SubgraphHealth::Healthy,
+        synced: true,
+        fatal_error: None,
+        non_fatal_errors: vec![],
+        earliest_block: Some(latest_block.clone()),
+        latest_block: Some(latest_block),
+        graft_base: None,
+        graft_block: None,
+        reorg_count: 0,
+        current_reorg_depth: 0,
+        max_reorg_depth: 0,
+    };
+    let locator = store.import_graft_base(&schema, deployment, network, entities)?;
+
+    info!(logger, "Fetched graft base from remote node";
+          "base" => graft.base.to_string(),
+          "entities" => count,
+          "locator" => locator.to_string());
+    Ok(())
+}
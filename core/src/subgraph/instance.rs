@@ -4,6 +4,7 @@ use lazy_static::lazy_static;
 use std::collections::HashMap;
 use std::env;
 use std::str::FromStr;
+use std::time::Instant;
 
 use graph::{blockchain::DataSource, prelude::*};
 use graph::{
@@ -11,6 +12,8 @@ use graph::{
     components::subgraph::{MappingError, SharedProofOfIndexing},
 };
 
+use super::instance_manager::BlockProcessingSummary;
+
 lazy_static! {
     static ref MAX_DATA_SOURCES: Option<usize> = env::var("GRAPH_SUBGRAPH_MAX_DATA_SOURCES")
         .ok()
@@ -113,6 +116,7 @@ where
         state: BlockState<C>,
         proof_of_indexing: SharedProofOfIndexing,
         causality_region: &str,
+        summary: &mut BlockProcessingSummary,
     ) -> Result<BlockState<C>, MappingError> {
         Self::process_trigger_in_runtime_hosts(
             logger,
@@ -122,6 +126,7 @@ where
             state,
             proof_of_indexing,
             causality_region,
+            summary,
         )
         .await
     }
@@ -134,6 +139,7 @@ where
         mut state: BlockState<C>,
         proof_of_indexing: SharedProofOfIndexing,
         causality_region: &str,
+        summary: &mut BlockProcessingSummary,
     ) -> Result<BlockState<C>, MappingError> {
         let error_count = state.deterministic_errors.len();
 
@@ -153,6 +159,8 @@ where
                     None => continue,
                 };
 
+            let handler = mapping_trigger.handler_name().to_owned();
+            let start = Instant::now();
             state = host
                 .process_mapping_trigger(
                     logger,
@@ -162,6 +170,7 @@ where
                     proof_of_indexing.cheap_clone(),
                 )
                 .await?;
+            summary.observe_handler(&handler, start.elapsed());
         }
 
         if let Some(proof_of_indexing) = &proof_of_indexing {
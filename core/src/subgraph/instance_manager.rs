@@ -1,19 +1,22 @@
 use super::loader::load_dynamic_data_sources;
+use super::match_blockchain_kind;
 use super::SubgraphInstance;
+use crate::search_sync::SearchSyncer;
 use atomic_refcell::AtomicRefCell;
 use fail::fail_point;
 use graph::blockchain::{BlockchainKind, DataSource};
+use graph::components::sync_progress::SYNC_PROGRESS;
 use graph::data::store::scalar::Bytes;
 use graph::data::subgraph::{UnifiedMappingApiVersion, MAX_SPEC_VERSION};
 use graph::prelude::TryStreamExt;
 use graph::prelude::{SubgraphInstanceManager as SubgraphInstanceManagerTrait, *};
-use graph::util::{backoff::ExponentialBackoff, lfu_cache::LfuCache};
+use graph::util::{backoff::ExponentialBackoff, lfu_cache::LfuCache, maintenance};
 use graph::{blockchain::block_stream::BlockStreamMetrics, components::store::WritableStore};
 use graph::{blockchain::block_stream::BlockWithTriggers, data::subgraph::SubgraphFeature};
 use graph::{
     blockchain::NodeCapabilities,
     blockchain::TriggersAdapter,
-    data::subgraph::schema::{SubgraphError, SubgraphHealth, POI_OBJECT},
+    data::subgraph::schema::{ErrorClass, SubgraphError, SubgraphHealth, POI_OBJECT},
 };
 use graph::{
     blockchain::{block_stream::BlockStreamEvent, Blockchain, TriggerFilter as _},
@@ -52,10 +55,74 @@ lazy_static! {
             .parse::<u64>()
             .map(Duration::from_secs)
             .expect("invalid GRAPH_SUBGRAPH_ERROR_RETRY_CEIL_SECS");
+
+    /// With a write-back cache policy (the `write_back_blocks` setting in
+    /// `graph::settings`), stop buffering and write after every block once
+    /// the deployment is within this many blocks of the chain head, so
+    /// that every block that might get reorged can be reverted
+    /// individually. Must be at least the reorg threshold of the chain
+    /// that is being indexed.
+    pub static ref WRITE_BACK_SAFETY_MARGIN: i32 =
+        std::env::var("GRAPH_WRITE_BACK_SAFETY_MARGIN")
+            .unwrap_or("250".into())
+            .parse::<i32>()
+            .expect("invalid GRAPH_WRITE_BACK_SAFETY_MARGIN");
 }
 
 type SharedInstanceKeepAliveMap = Arc<RwLock<HashMap<DeploymentId, CancelGuard>>>;
 
+/// Accumulates trigger counts and handler timings while a block is being
+/// processed, and is logged as a single structured summary line once the
+/// block has been transacted. Emitted at `debug` level so that operators can
+/// turn the per-block breakdown on and off with the log level.
+#[derive(Default)]
+pub(crate) struct BlockProcessingSummary {
+    /// Number of triggers processed and total time spent, per handler name.
+    handlers: HashMap<String, (u64, Duration)>,
+}
+
+impl BlockProcessingSummary {
+    pub(crate) fn observe_handler(&mut self, handler: &str, duration: Duration) {
+        let entry = self
+            .handlers
+            .entry(handler.to_owned())
+            .or_insert((0, Duration::from_secs(0)));
+        entry.0 += 1;
+        entry.1 += duration;
+    }
+
+    fn trigger_count(&self) -> u64 {
+        self.handlers.values().map(|(count, _)| count).sum()
+    }
+
+    fn handler_time(&self) -> Duration {
+        self.handlers.values().map(|(_, time)| *time).sum()
+    }
+
+    /// Render the per-handler breakdown as `handler: count (total time)`
+    /// pairs, slowest handler first.
+    fn breakdown(&self) -> String {
+        let mut handlers: Vec<_> = self.handlers.iter().collect();
+        handlers.sort_by(|a, b| b.1 .1.cmp(&a.1 .1));
+        handlers
+            .iter()
+            .map(|(name, (count, time))| format!("{}: {} ({:?})", name, count, time))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn log(&self, logger: &Logger, eth_calls: u64, store_ops: usize, transact_time: Duration) {
+        debug!(logger, "Block processing summary";
+            "triggers" => self.trigger_count(),
+            "handlers" => self.breakdown(),
+            "handler_time_ms" => self.handler_time().as_millis(),
+            "eth_calls" => eth_calls,
+            "store_ops" => store_ops,
+            "transact_time_ms" => transact_time.as_millis(),
+        );
+    }
+}
+
 struct IndexingInputs<C: Blockchain> {
     deployment: DeploymentLocator,
     features: BTreeSet<SubgraphFeature>,
@@ -65,6 +132,9 @@ struct IndexingInputs<C: Blockchain> {
     chain: Arc<C>,
     templates: Arc<Vec<C::DataSourceTemplate>>,
     unified_api_version: UnifiedMappingApiVersion,
+    /// When set, entity changes are mirrored into an external search
+    /// engine; see the `search_sync` module
+    search_sync: Option<Arc<SearchSyncer>>,
 }
 
 struct IndexingState<T: RuntimeHostBuilder<C>, C: Blockchain> {
@@ -73,6 +143,26 @@ struct IndexingState<T: RuntimeHostBuilder<C>, C: Blockchain> {
     instances: SharedInstanceKeepAliveMap,
     filter: C::TriggerFilter,
     entity_lfu_cache: LfuCache<EntityKey, Option<Entity>>,
+    /// Entity changes from already processed blocks that have not been
+    /// written to the store yet because the deployment uses a write-back
+    /// cache policy (the `write_back_blocks` setting in `graph::settings`)
+    pending_writes: Option<PendingWrites>,
+}
+
+/// The entity changes buffered under a write-back cache policy. The
+/// buffered cache reads through to the store, so handlers see the
+/// changes from buffered blocks even though they are not in the store
+/// yet
+struct PendingWrites {
+    entity_cache: EntityCache,
+    /// The number of blocks whose changes are buffered in `entity_cache`
+    blocks: u32,
+    /// The last block whose changes are in `entity_cache`, and its
+    /// firehose cursor. If the next block fails with a deterministic
+    /// error, the buffer is written at this block so that the changes
+    /// from the already successful blocks are not lost
+    block_ptr: BlockPtr,
+    firehose_cursor: Option<String>,
 }
 
 struct IndexingContext<T: RuntimeHostBuilder<C>, C: Blockchain> {
@@ -198,26 +288,18 @@ where
         let instance_manager = self.cheap_clone();
 
         let subgraph_start_future = async move {
-            match BlockchainKind::from_manifest(&manifest)? {
-                BlockchainKind::Ethereum => {
-                    instance_manager
-                        .start_subgraph_inner::<graph_chain_ethereum::Chain>(logger, loc, manifest)
-                        .await
-                }
-
-                BlockchainKind::Near => {
-                    instance_manager
-                        .start_subgraph_inner::<graph_chain_near::Chain>(logger, loc, manifest)
-                        .await
-                }
-            }
+            match_blockchain_kind!(BlockchainKind::from_manifest(&manifest)?, <C> {
+                instance_manager
+                    .start_subgraph_inner::<C>(logger, loc, manifest)
+                    .await
+            })
         };
         // Perform the actual work of starting the subgraph in a separate
         // task. If the subgraph is a graft or a copy, starting it will
         // perform the actual work of grafting/copying, which can take
         // hours. Running it in the background makes sure the instance
         // manager does not hang because of that work.
-        graph::spawn(async move {
+        graph::spawn_index(async move {
             match subgraph_start_future.await {
                 Ok(()) => self.manager_metrics.subgraph_count.inc(),
                 Err(err) => error!(
@@ -238,6 +320,7 @@ where
         let mut instances = self.instances.write().unwrap();
         instances.remove(&loc.id);
 
+        SYNC_PROGRESS.remove(loc.hash.as_str());
         self.manager_metrics.subgraph_count.dec();
     }
 }
@@ -403,6 +486,7 @@ where
 
         let features = manifest.features.clone();
         let unified_api_version = manifest.unified_mapping_api_version()?;
+        let search_sync = SearchSyncer::for_deployment(&logger, &deployment.hash)?;
         let instance =
             SubgraphInstance::from_manifest(&logger, manifest, host_builder, host_metrics.clone())?;
 
@@ -417,6 +501,7 @@ where
                 chain,
                 templates,
                 unified_api_version,
+                search_sync,
             },
             state: IndexingState {
                 logger: logger.cheap_clone(),
@@ -424,6 +509,7 @@ where
                 instances: self.instances.cheap_clone(),
                 filter,
                 entity_lfu_cache: LfuCache::new(),
+                pending_writes: None,
             },
             subgraph_metrics,
             host_metrics,
@@ -458,6 +544,12 @@ where
     }
 }
 
+/// Whether `deployment` pauses during a maintenance window, i.e.,
+/// whether its `priority` setting is unset or zero
+fn pauses_for_maintenance(deployment: &DeploymentHash) -> bool {
+    graph::settings::for_deployment(deployment).priority() == 0
+}
+
 async fn run_subgraph<T, C>(mut ctx: IndexingContext<T, C>) -> Result<(), Error>
 where
     T: RuntimeHostBuilder<C>,
@@ -475,7 +567,26 @@ where
     // increasing its timeout exponentially until it reaches the ceiling.
     let mut backoff = ExponentialBackoff::new(MINUTE * 2, *SUBGRAPH_ERROR_RETRY_CEIL_SECS);
 
+    // Initialize the health state set from the stored health so that
+    // alert rules see the right state right after a restart
+    ctx.block_stream_metrics
+        .track_health(ctx.inputs.store.health(&ctx.inputs.deployment.hash).await?);
+
     loop {
+        // While a maintenance window is open, low-priority deployments
+        // stay paused so that maintenance work like vacuuming and
+        // backups gets the I/O that indexing would otherwise use. The
+        // `priority` setting is checked on every poll so that raising it
+        // through `subgraph_setting` resumes the deployment without
+        // waiting for the window to close
+        if maintenance::in_window() && pauses_for_maintenance(&ctx.inputs.deployment.hash) {
+            info!(logger, "Pausing subgraph for maintenance window");
+            while maintenance::in_window() && pauses_for_maintenance(&ctx.inputs.deployment.hash) {
+                tokio::time::sleep(MINUTE).await;
+            }
+            info!(logger, "Resuming subgraph paused for maintenance");
+        }
+
         debug!(logger, "Starting or restarting subgraph");
 
         let block_stream_canceler = CancelGuard::new();
@@ -534,6 +645,22 @@ where
                         "block_hash" => format!("{}", subgraph_ptr.hash)
                     );
 
+                    // With a write-back cache policy, the changes for the
+                    // reverted block, and possibly for blocks before it, are
+                    // only buffered in memory and the store is still at an
+                    // earlier block. Throwing the buffer away reverts them;
+                    // restart the block stream from the last block that was
+                    // actually written to the store
+                    if ctx.state.pending_writes.take().is_some() {
+                        ctx.state.entity_lfu_cache = LfuCache::new();
+                        ctx.state
+                            .instances
+                            .write()
+                            .unwrap()
+                            .remove(&ctx.inputs.deployment.id);
+                        break;
+                    }
+
                     // We would like to revert the DB state to the parent of the current block.
                     match optional_parent_ptr {
                         Some(parent_ptr) => {
@@ -584,6 +711,12 @@ where
                         .reverted_blocks
                         .set(subgraph_ptr.number as f64);
 
+                    // Drop what the search mirror has for the reverted
+                    // block and everything after it; this is best effort
+                    if let Some(search_sync) = &ctx.inputs.search_sync {
+                        search_sync.revert_to(subgraph_ptr.number - 1);
+                    }
+
                     // Revert the in-memory state:
                     // - Remove hosts for reverted dynamic data sources.
                     // - Clear the entity cache.
@@ -665,6 +798,15 @@ where
 
             match res {
                 Ok(needs_restart) => {
+                    // Track how fast the deployment head moves, for the
+                    // sync ETA in the indexing status API
+                    SYNC_PROGRESS.record(ctx.inputs.deployment.hash.as_str(), block_ptr.number);
+                    if let Some(bps) =
+                        SYNC_PROGRESS.blocks_per_second(ctx.inputs.deployment.hash.as_str())
+                    {
+                        ctx.block_stream_metrics.blocks_per_second.set(bps);
+                    }
+
                     // Keep trying to unfail subgraph for everytime it advances block(s) until it's
                     // health is not Failed anymore.
                     if should_try_unfail_non_deterministic {
@@ -680,15 +822,28 @@ where
                                 // `should_try_unfail_non_deterministic` as `true` until it's
                                 // actually unfailed.
                             }
-                            SubgraphHealth::Healthy | SubgraphHealth::Unhealthy => {
+                            health @ (SubgraphHealth::Healthy | SubgraphHealth::Unhealthy) => {
                                 // Stop trying to unfail.
                                 should_try_unfail_non_deterministic = false;
                                 deployment_failed.set(0.0);
+                                ctx.block_stream_metrics.track_health(health);
                                 backoff.reset();
                             }
                         };
                     }
 
+                    // If a maintenance window opened while the deployment
+                    // was streaming, restart it so that the block stream
+                    // and its provider connections get dropped; the check
+                    // at the top of the outer loop then keeps the
+                    // deployment paused until the window closes. Skip this
+                    // while entity changes are buffered since a restart
+                    // would lose them
+                    let needs_restart = needs_restart
+                        || (ctx.state.pending_writes.is_none()
+                            && maintenance::in_window()
+                            && pauses_for_maintenance(&ctx.inputs.deployment.hash));
+
                     if needs_restart {
                         // Cancel the stream for real
                         ctx.state
@@ -709,10 +864,14 @@ where
                 // Handle unexpected stream errors by marking the subgraph as failed.
                 Err(e) => {
                     deployment_failed.set(1.0);
+                    ctx.block_stream_metrics.fatal_errors.inc();
+                    ctx.block_stream_metrics
+                        .track_health(SubgraphHealth::Failed);
 
                     let message = format!("{:#}", e).replace("\n", "\t");
                     let err = anyhow!("{}, code: {}", message, LogCode::SubgraphSyncingFailure);
                     let deterministic = e.is_deterministic();
+                    let class = e.classify();
 
                     let error = SubgraphError {
                         subgraph_id: id_for_err.clone(),
@@ -728,7 +887,7 @@ where
                             // - Change status/health.
                             // - Save the error to the database.
                             store_for_err
-                                .fail_subgraph(error)
+                                .fail_subgraph(error, class)
                                 .await
                                 .context("Failed to set subgraph status to `failed`")?;
 
@@ -749,7 +908,7 @@ where
                                 // - Change status/health.
                                 // - Save the error to the database.
                                 store_for_err
-                                    .fail_subgraph(error)
+                                    .fail_subgraph(error, class)
                                     .await
                                     .context("Failed to set subgraph status to `failed`")?;
                             }
@@ -800,6 +959,32 @@ impl BlockProcessingError {
     fn is_deterministic(&self) -> bool {
         matches!(self, BlockProcessingError::Deterministic(_))
     }
+
+    /// Classify the error for the benefit of operators deciding whether a
+    /// retry can succeed. Walks the error chain since the interesting
+    /// cause is usually wrapped in layers of context
+    fn classify(&self) -> ErrorClass {
+        use BlockProcessingError::*;
+
+        match self {
+            Deterministic(_) => ErrorClass::Deterministic,
+            Canceled => ErrorClass::Unknown,
+            Unknown(e) => {
+                if e.chain()
+                    .any(|cause| cause.downcast_ref::<StoreError>().is_some())
+                {
+                    ErrorClass::Store
+                } else if e
+                    .chain()
+                    .any(|cause| cause.downcast_ref::<web3::Error>().is_some())
+                {
+                    ErrorClass::Provider
+                } else {
+                    ErrorClass::Unknown
+                }
+            }
+        }
+    }
 }
 
 impl From<Error> for BlockProcessingError {
@@ -862,20 +1047,51 @@ async fn process_block<T: RuntimeHostBuilder<C>, C: Blockchain>(
     // There are currently no other causality regions since offchain data is not supported.
     let causality_region = CausalityRegion::from_network(ctx.state.instance.network());
 
+    let mut summary = BlockProcessingSummary::default();
+    let eth_calls_before = ctx.host_metrics.eth_call_count();
+
+    // With a write-back cache policy, the entity changes from the blocks
+    // since the last write are still buffered; continue with that buffer
+    // instead of a fresh cache
+    let (initial_state, buffered_blocks, buffered) = match ctx.state.pending_writes.take() {
+        Some(PendingWrites {
+            entity_cache,
+            blocks,
+            block_ptr,
+            firehose_cursor,
+        }) => {
+            // Keep a snapshot of the buffered changes; if this block
+            // fails with a deterministic error, they are written out on
+            // their own since they are from successful blocks
+            let snapshot = entity_cache.snapshot();
+            (
+                BlockState::with_cache(entity_cache),
+                blocks,
+                Some((snapshot, block_ptr, firehose_cursor)),
+            )
+        }
+        None => (
+            BlockState::new(
+                ctx.inputs.store.clone(),
+                std::mem::take(&mut ctx.state.entity_lfu_cache),
+            ),
+            0,
+            None,
+        ),
+    };
+
     // Process events one after the other, passing in entity operations
     // collected previously to every new event being processed
     let mut block_state = match process_triggers(
         &logger,
-        BlockState::new(
-            ctx.inputs.store.clone(),
-            std::mem::take(&mut ctx.state.entity_lfu_cache),
-        ),
+        initial_state,
         proof_of_indexing.cheap_clone(),
         ctx.subgraph_metrics.clone(),
         &ctx.state.instance,
         &block,
         triggers,
         &causality_region,
+        &mut summary,
     )
     .await
     {
@@ -965,6 +1181,7 @@ async fn process_block<T: RuntimeHostBuilder<C>, C: Blockchain>(
                 block_state,
                 proof_of_indexing.cheap_clone(),
                 &causality_region,
+                &mut summary,
             )
             .await
             .map_err(|e| {
@@ -1005,6 +1222,32 @@ async fn process_block<T: RuntimeHostBuilder<C>, C: Blockchain>(
         .await?;
     }
 
+    // With a write-back cache policy, hold on to the entity changes
+    // instead of writing them after every block. Errors and new data
+    // sources are written out right away since handling them relies on
+    // the store, and so is everything close to the chain head: within
+    // the safety margin, every block needs to be written individually so
+    // that it can be reverted individually
+    let write_back_blocks = graph::settings::for_deployment(&ctx.inputs.deployment.hash)
+        .write_back_blocks()
+        .unwrap_or(1);
+    let buffered_blocks = buffered_blocks + 1;
+    if buffered_blocks < write_back_blocks
+        && !has_errors
+        && !needs_restart
+        && ctx.block_stream_metrics.blocks_behind.get() as i32 > *WRITE_BACK_SAFETY_MARGIN
+    {
+        debug!(&logger, "Buffering entity changes";
+            "buffered_blocks" => buffered_blocks);
+        ctx.state.pending_writes = Some(PendingWrites {
+            entity_cache: block_state.entity_cache,
+            blocks: buffered_blocks,
+            block_ptr,
+            firehose_cursor,
+        });
+        return Ok(false);
+    }
+
     let section = ctx.host_metrics.stopwatch.start_section("as_modifications");
     let ModificationsAndCache {
         modifications: mut mods,
@@ -1020,7 +1263,11 @@ async fn process_block<T: RuntimeHostBuilder<C>, C: Blockchain>(
         .host_metrics
         .stopwatch
         .start_section("entity_cache_evict");
-    cache.evict(*ENTITY_CACHE_SIZE);
+    cache.evict(
+        graph::settings::for_deployment(&ctx.inputs.deployment.hash)
+            .entity_cache_size()
+            .unwrap_or(*ENTITY_CACHE_SIZE),
+    );
     section.end();
 
     // Put the cache back in the ctx, asserting that the placeholder cache was not used.
@@ -1053,6 +1300,28 @@ async fn process_block<T: RuntimeHostBuilder<C>, C: Blockchain>(
         let is_poi_entity = |entity_mod: &EntityModification| {
             entity_mod.entity_key().entity_type.as_str() == "Poi$"
         };
+        // The buffered changes are from blocks that were processed
+        // successfully; write them at the block they cover before
+        // recording the failure so that failing this block does not
+        // throw them away. Their PoI is written below together with the
+        // error since the PoI modification spans the whole buffer
+        if let Some((snapshot, buffered_ptr, buffered_cursor)) = buffered {
+            let ModificationsAndCache {
+                modifications: mut flushed,
+                ..
+            } = snapshot
+                .as_modifications()
+                .map_err(|e| BlockProcessingError::Unknown(e.into()))?;
+            flushed.retain(|entity_mod| !is_poi_entity(entity_mod));
+            store.transact_block_operations(
+                buffered_ptr,
+                buffered_cursor,
+                flushed,
+                stopwatch.clone(),
+                vec![],
+                vec![],
+            )?;
+        }
         mods.retain(is_poi_entity);
         // Confidence check
         assert!(
@@ -1067,6 +1336,16 @@ async fn process_block<T: RuntimeHostBuilder<C>, C: Blockchain>(
     } = block_state;
 
     let first_error = deterministic_errors.first().cloned();
+    let error_count = deterministic_errors.len();
+    let store_ops = mods.len();
+
+    // Collect what the search mirror needs before the modifications are
+    // moved into the store transaction
+    let search_batch = ctx
+        .inputs
+        .search_sync
+        .as_ref()
+        .map(|search_sync| search_sync.batch(&block_ptr, &mods));
 
     match store.transact_block_operations(
         block_ptr,
@@ -1092,6 +1371,26 @@ async fn process_block<T: RuntimeHostBuilder<C>, C: Blockchain>(
             let elapsed = start.elapsed().as_secs_f64();
             metrics.block_ops_transaction_duration.observe(elapsed);
 
+            // Any errors at this point are non-fatal since the subgraph
+            // has the `nonFatalErrors` feature; the deployment is now
+            // unhealthy
+            if has_errors {
+                ctx.block_stream_metrics
+                    .non_fatal_errors
+                    .inc_by(error_count as f64);
+                ctx.block_stream_metrics
+                    .track_health(SubgraphHealth::Unhealthy);
+            }
+
+            let eth_calls = ctx.host_metrics.eth_call_count() - eth_calls_before;
+            summary.log(&logger, eth_calls, store_ops, start.elapsed());
+
+            // Now that the block is in the store, queue its changes for
+            // the search engine; the syncer logs its own errors
+            if let (Some(search_sync), Some(batch)) = (&ctx.inputs.search_sync, search_batch) {
+                search_sync.sync(batch);
+            }
+
             // To prevent a buggy pending version from replacing a current version, if errors are
             // present the subgraph will be unassigned.
             if has_errors && !*DISABLE_FAIL_FAST && !store.is_deployment_synced().await? {
@@ -1113,7 +1412,7 @@ async fn process_block<T: RuntimeHostBuilder<C>, C: Blockchain>(
 
 /// Transform the proof of indexing changes into entity updates that will be
 /// inserted when as_modifications is called.
-async fn update_proof_of_indexing(
+pub(super) async fn update_proof_of_indexing(
     proof_of_indexing: ProofOfIndexing,
     stopwatch: &StopwatchMetrics,
     deployment_id: &DeploymentHash,
@@ -1167,6 +1466,7 @@ async fn process_triggers<C: Blockchain>(
     block: &Arc<C::Block>,
     triggers: Vec<C::TriggerData>,
     causality_region: &str,
+    summary: &mut BlockProcessingSummary,
 ) -> Result<BlockState<C>, MappingError> {
     use graph::blockchain::TriggerData;
 
@@ -1180,6 +1480,7 @@ async fn process_triggers<C: Blockchain>(
                 block_state,
                 proof_of_indexing.cheap_clone(),
                 causality_region,
+                summary,
             )
             .await
             .map_err(move |mut e| {
@@ -1,10 +1,35 @@
+mod graft_base;
 mod instance;
 mod instance_manager;
 mod loader;
 mod provider;
 mod registrar;
+mod replay;
+
+/// Run `$body` with the type variable `$chain` bound to the `Blockchain`
+/// implementation for `$kind`. All the places where `core` needs to go
+/// from the blockchain kind in a manifest to a concrete chain type
+/// dispatch through this macro, so that a new chain integration only
+/// needs to add one arm here
+macro_rules! match_blockchain_kind {
+    ($kind:expr, <$chain:ident> $body:block) => {
+        match $kind {
+            graph::blockchain::BlockchainKind::Ethereum => {
+                type $chain = graph_chain_ethereum::Chain;
+                $body
+            }
+            graph::blockchain::BlockchainKind::Near => {
+                type $chain = graph_chain_near::Chain;
+                $body
+            }
+        }
+    };
+}
+
+pub(crate) use match_blockchain_kind;
 
 pub use self::instance::SubgraphInstance;
 pub use self::instance_manager::SubgraphInstanceManager;
 pub use self::provider::SubgraphAssignmentProvider;
 pub use self::registrar::SubgraphRegistrar;
+pub use self::replay::{replay_block, BlockReplayOutcome, EntityDiff};
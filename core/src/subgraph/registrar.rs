@@ -1,11 +1,13 @@
 use std::collections::HashSet;
 use std::time::Instant;
 
+use super::{graft_base, match_blockchain_kind};
 use async_trait::async_trait;
 use graph::blockchain::Blockchain;
 use graph::blockchain::BlockchainKind;
 use graph::blockchain::BlockchainMap;
 use graph::components::store::{DeploymentId, DeploymentLocator, SubscriptionManager};
+use graph::data::subgraph::features::SubgraphFeature;
 use graph::data::subgraph::schema::SubgraphDeploymentEntity;
 use graph::data::subgraph::MAX_SPEC_VERSION;
 use graph::prelude::{
@@ -60,7 +62,7 @@ where
         }
     }
 
-    pub fn start(&self) -> impl Future<Item = (), Error = Error> {
+    pub async fn start(&self) -> Result<(), Error> {
         let logger_clone1 = self.logger.clone();
         let logger_clone2 = self.logger.clone();
         let provider = self.provider.clone();
@@ -94,40 +96,40 @@ where
         let assignment_event_stream = self.assignment_events();
 
         // Deploy named subgraphs found in store
-        self.start_assigned_subgraphs().and_then(move |()| {
-            // Spawn a task to handle assignment events.
-            // Blocking due to store interactions. Won't be blocking after #905.
-            graph::spawn_blocking(
-                assignment_event_stream
-                    .compat()
-                    .map_err(SubgraphAssignmentProviderError::Unknown)
-                    .map_err(CancelableError::Error)
-                    .cancelable(&assignment_event_stream_cancel_handle, || {
-                        Err(CancelableError::Cancel)
-                    })
-                    .compat()
-                    .for_each(move |assignment_event| {
-                        assert_eq!(assignment_event.node_id(), &node_id);
-                        handle_assignment_event(
-                            assignment_event,
-                            provider.clone(),
-                            logger_clone1.clone(),
-                        )
-                        .boxed()
-                        .compat()
-                    })
-                    .map_err(move |e| match e {
-                        CancelableError::Cancel => panic!("assignment event stream canceled"),
-                        CancelableError::Error(e) => {
-                            error!(logger_clone2, "Assignment event stream failed: {}", e);
-                            panic!("assignment event stream failed: {}", e);
-                        }
-                    })
-                    .compat(),
-            );
+        self.start_assigned_subgraphs().await?;
+
+        // Spawn a task to handle assignment events.
+        // Blocking due to store interactions. Won't be blocking after #905.
+        graph::spawn_blocking(async move {
+            let mut assignment_events = assignment_event_stream
+                .compat()
+                .map_err(SubgraphAssignmentProviderError::Unknown)
+                .map_err(CancelableError::Error)
+                .cancelable(&assignment_event_stream_cancel_handle, || {
+                    Err(CancelableError::Cancel)
+                });
+
+            while let Some(event) = assignment_events.next().await {
+                let result = match event {
+                    Ok(event) => {
+                        assert_eq!(event.node_id(), &node_id);
+                        handle_assignment_event(event, provider.clone(), logger_clone1.clone())
+                            .await
+                    }
+                    Err(e) => Err(e),
+                };
+                match result {
+                    Ok(()) => (),
+                    Err(CancelableError::Cancel) => panic!("assignment event stream canceled"),
+                    Err(CancelableError::Error(e)) => {
+                        error!(logger_clone2, "Assignment event stream failed: {}", e);
+                        panic!("assignment event stream failed: {}", e);
+                    }
+                }
+            }
+        });
 
-            Ok(())
-        })
+        Ok(())
     }
 
     pub fn assignment_events(&self) -> impl Stream<Item = AssignmentEvent, Error = Error> + Send {
@@ -209,36 +211,108 @@ where
             .flatten()
     }
 
-    fn start_assigned_subgraphs(&self) -> impl Future<Item = (), Error = Error> {
+    async fn start_assigned_subgraphs(&self) -> Result<(), Error> {
         let provider = self.provider.clone();
         let logger = self.logger.clone();
         let node_id = self.node_id.clone();
 
-        future::result(self.store.assignments(&self.node_id))
-            .map_err(|e| anyhow!("Error querying subgraph assignments: {}", e))
-            .and_then(move |deployments| {
-                // This operation should finish only after all subgraphs are
-                // started. We wait for the spawned tasks to complete by giving
-                // each a `sender` and waiting for all of them to be dropped, so
-                // the receiver terminates without receiving anything.
-                let deployments = HashSet::<DeploymentLocator>::from_iter(deployments);
-                let deployments_len = deployments.len();
-                let (sender, receiver) = futures01::sync::mpsc::channel::<()>(1);
-                for id in deployments {
-                    let sender = sender.clone();
-                    let logger = logger.clone();
-
-                    graph::spawn(
-                        start_subgraph(id, provider.clone(), logger).map(move |()| drop(sender)),
-                    );
-                }
-                drop(sender);
-                receiver.collect().then(move |_| {
-                    info!(logger, "Started all assigned subgraphs";
-                                  "count" => deployments_len, "node_id" => &node_id);
-                    future::ok(())
-                })
-            })
+        let deployments = self
+            .store
+            .assignments(&self.node_id)
+            .map_err(|e| anyhow!("Error querying subgraph assignments: {}", e))?;
+
+        // This operation should finish only after all subgraphs are
+        // started. Each subgraph starts in its own task; wait for all of
+        // those tasks to finish
+        let deployments = HashSet::<DeploymentLocator>::from_iter(deployments);
+        let deployments_len = deployments.len();
+        let tasks: Vec<_> = deployments
+            .into_iter()
+            .map(|id| graph::spawn(start_subgraph(id, provider.clone(), logger.clone())))
+            .collect();
+        // A task can only fail if it panics, and starting the remaining
+        // subgraphs is still worthwhile then
+        let _ = futures03::future::join_all(tasks).await;
+
+        info!(logger, "Started all assigned subgraphs";
+              "count" => deployments_len, "node_id" => &node_id);
+        Ok(())
+    }
+
+    /// Fetch the raw manifest `hash` from IPFS and parse it as YAML
+    async fn resolve_raw_manifest(
+        &self,
+        logger: &Logger,
+        hash: &DeploymentHash,
+    ) -> Result<serde_yaml::Mapping, SubgraphRegistrarError> {
+        let file_bytes = self
+            .resolver
+            .cat(logger, &hash.to_ipfs_link())
+            .await
+            .map_err(|e| {
+                SubgraphRegistrarError::ResolveError(SubgraphManifestResolveError::ResolveError(e))
+            })?;
+
+        serde_yaml::from_slice(&file_bytes)
+            .map_err(|e| SubgraphRegistrarError::ResolveError(e.into()))
+    }
+
+    /// Validate the raw manifest and deploy it as a new version of `name`
+    /// assigned to `node_id`
+    async fn deploy_subgraph_version(
+        &self,
+        logger: &Logger,
+        name: SubgraphName,
+        hash: DeploymentHash,
+        raw: serde_yaml::Mapping,
+        node_id: NodeId,
+    ) -> Result<(), SubgraphRegistrarError> {
+        let kind = BlockchainKind::from_manifest(&raw).map_err(|e| {
+            SubgraphRegistrarError::ResolveError(SubgraphManifestResolveError::ResolveError(e))
+        })?;
+
+        // If the manifest grafts onto a base that we do not have locally,
+        // try to fetch the base from the node configured through
+        // `GRAPH_GRAFT_BASE_FETCH_URL` before validation looks for it
+        graft_base::fetch_base_if_missing(logger, self.store.clone(), &self.resolver, &raw).await?;
+
+        match_blockchain_kind!(kind, <C> {
+            create_subgraph_version::<C, _, _>(
+                logger,
+                self.store.clone(),
+                self.chains.cheap_clone(),
+                name.clone(),
+                hash.cheap_clone(),
+                raw,
+                node_id,
+                self.version_switching_mode,
+                self.resolver.cheap_clone(),
+            )
+            .await?
+        });
+
+        Ok(())
+    }
+
+    /// Look up the deployment for `hash` and error unless there is exactly
+    /// one
+    fn locate_unique(
+        &self,
+        hash: &DeploymentHash,
+    ) -> Result<DeploymentLocator, SubgraphRegistrarError> {
+        let locations = self.store.locators(hash)?;
+        match locations.len() {
+            0 => Err(SubgraphRegistrarError::DeploymentNotFound(hash.to_string())),
+            1 => Ok(locations[0].clone()),
+            _ => Err(SubgraphRegistrarError::StoreError(
+                anyhow!(
+                    "there are {} different deployments with id {}",
+                    locations.len(),
+                    hash.as_str()
+                )
+                .into(),
+            )),
+        }
     }
 }
 
@@ -274,62 +348,72 @@ where
             .logger_factory
             .subgraph_logger(&DeploymentLocator::new(DeploymentId(0), hash.clone()));
 
-        let raw: serde_yaml::Mapping = {
-            let file_bytes = self
-                .resolver
-                .cat(&logger, &hash.to_ipfs_link())
-                .await
-                .map_err(|e| {
-                    SubgraphRegistrarError::ResolveError(
-                        SubgraphManifestResolveError::ResolveError(e),
-                    )
-                })?;
+        let raw = self.resolve_raw_manifest(&logger, &hash).await?;
 
-            serde_yaml::from_slice(&file_bytes)
-                .map_err(|e| SubgraphRegistrarError::ResolveError(e.into()))?
-        };
+        self.deploy_subgraph_version(&logger, name.clone(), hash.clone(), raw, node_id)
+            .await?;
 
-        let kind = BlockchainKind::from_manifest(&raw).map_err(|e| {
-            SubgraphRegistrarError::ResolveError(SubgraphManifestResolveError::ResolveError(e))
-        })?;
+        debug!(
+            &logger,
+            "Wrote new subgraph version to store";
+            "subgraph_name" => name.to_string(),
+            "subgraph_hash" => hash.to_string(),
+        );
 
-        match kind {
-            BlockchainKind::Ethereum => {
-                create_subgraph_version::<graph_chain_ethereum::Chain, _, _>(
-                    &logger,
-                    self.store.clone(),
-                    self.chains.cheap_clone(),
-                    name.clone(),
-                    hash.cheap_clone(),
-                    raw,
-                    node_id,
-                    self.version_switching_mode,
-                    self.resolver.cheap_clone(),
-                )
-                .await?
-            }
+        Ok(())
+    }
 
-            BlockchainKind::Near => {
-                create_subgraph_version::<graph_chain_near::Chain, _, _>(
-                    &logger,
-                    self.store.clone(),
-                    self.chains.cheap_clone(),
-                    name.clone(),
-                    hash.cheap_clone(),
-                    raw,
-                    node_id,
-                    self.version_switching_mode,
-                    self.resolver.cheap_clone(),
-                )
-                .await?
+    /// Deploy the manifest `hash` under `name` as a clone of the
+    /// deployment `base`, as if the manifest grafted onto `base` at
+    /// `block`: the entity data of `base` up to `block` is copied into
+    /// the new deployment, which then indexes forward with its own
+    /// mappings
+    async fn clone_subgraph_version(
+        &self,
+        name: SubgraphName,
+        hash: DeploymentHash,
+        node_id: NodeId,
+        base: DeploymentHash,
+        block: BlockNumber,
+    ) -> Result<(), SubgraphRegistrarError> {
+        let logger = self
+            .logger_factory
+            .subgraph_logger(&DeploymentLocator::new(DeploymentId(0), hash.clone()));
+
+        let mut raw = self.resolve_raw_manifest(&logger, &hash).await?;
+
+        // Turn the manifest into one that grafts onto `base` at `block`,
+        // replacing any graft declaration the manifest itself carries
+        let mut graft = serde_yaml::Mapping::new();
+        graft.insert("base".into(), base.to_string().into());
+        graft.insert("block".into(), block.into());
+        raw.insert("graft".into(), serde_yaml::Value::Mapping(graft));
+
+        // Declare the grafting feature on behalf of the manifest so that
+        // feature validation does not reject the injected graft
+        let features_key = serde_yaml::Value::from("features");
+        let grafting = serde_yaml::Value::from(SubgraphFeature::Grafting.to_string());
+        match raw.get_mut(&features_key) {
+            Some(serde_yaml::Value::Sequence(features)) => {
+                if !features.contains(&grafting) {
+                    features.push(grafting);
+                }
             }
-        };
+            _ => {
+                raw.insert(features_key, serde_yaml::Value::Sequence(vec![grafting]));
+            }
+        }
+
+        self.deploy_subgraph_version(&logger, name.clone(), hash.clone(), raw, node_id)
+            .await?;
 
         debug!(
             &logger,
-            "Wrote new subgraph version to store";
+            "Cloned deployment";
             "subgraph_name" => name.to_string(),
             "subgraph_hash" => hash.to_string(),
+            "base" => base.to_string(),
+            "block" => block,
         );
 
         Ok(())
@@ -371,6 +455,92 @@ where
 
         Ok(())
     }
+
+    /// Reassign several deployments in one metadata transaction; either all
+    /// of the assignments take effect or none do
+    async fn reassign_subgraphs(
+        &self,
+        assignments: &[(DeploymentHash, NodeId)],
+    ) -> Result<(), SubgraphRegistrarError> {
+        let mut located = Vec::with_capacity(assignments.len());
+        for (hash, node_id) in assignments {
+            let locations = self.store.locators(hash)?;
+            let deployment = match locations.len() {
+                0 => return Err(SubgraphRegistrarError::DeploymentNotFound(hash.to_string())),
+                1 => locations[0].clone(),
+                _ => {
+                    return Err(SubgraphRegistrarError::StoreError(
+                        anyhow!(
+                            "there are {} different deployments with id {}",
+                            locations.len(),
+                            hash.as_str()
+                        )
+                        .into(),
+                    ))
+                }
+            };
+            located.push((deployment, node_id.clone()));
+        }
+        self.store.reassign_subgraphs(&located)?;
+
+        Ok(())
+    }
+
+    /// Clear a non-deterministic failure and resume indexing from the
+    /// failed block.
+    async fn retry_subgraph(&self, hash: &DeploymentHash) -> Result<(), SubgraphRegistrarError> {
+        let deployment = self.locate_unique(hash)?;
+        self.store.retry_subgraph(&deployment, None)?;
+
+        debug!(self.logger, "Retrying subgraph"; "subgraph_hash" => hash.to_string());
+
+        Ok(())
+    }
+
+    /// Clear the failure, deterministic or not, and resume indexing from
+    /// `block`, which must be the block the deployment failed at.
+    async fn retry_subgraph_from(
+        &self,
+        hash: &DeploymentHash,
+        block: BlockNumber,
+    ) -> Result<(), SubgraphRegistrarError> {
+        let deployment = self.locate_unique(hash)?;
+        self.store.retry_subgraph(&deployment, Some(block))?;
+
+        debug!(
+            self.logger,
+            "Retrying subgraph";
+            "subgraph_hash" => hash.to_string(),
+            "block" => block,
+        );
+
+        Ok(())
+    }
+
+    /// Attach a per-deployment override for a global tunable; see
+    /// `graph::settings` for the recognized names.
+    async fn set_deployment_setting(
+        &self,
+        hash: &DeploymentHash,
+        name: &str,
+        value: &str,
+    ) -> Result<(), SubgraphRegistrarError> {
+        graph::settings::validate(name, value).map_err(SubgraphRegistrarError::InvalidSetting)?;
+        if self.store.locators(hash)?.is_empty() {
+            return Err(SubgraphRegistrarError::DeploymentNotFound(hash.to_string()));
+        }
+        self.store.set_deployment_setting(hash, name, value)?;
+
+        debug!(
+            self.logger,
+            "Set deployment setting";
+            "subgraph_hash" => hash.to_string(),
+            "name" => name.to_string(),
+            "value" => value.to_string(),
+        );
+
+        Ok(())
+    }
 }
 
 async fn handle_assignment_event(
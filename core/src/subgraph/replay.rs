@@ -0,0 +1,266 @@
+use std::sync::Arc;
+
+use atomic_refcell::AtomicRefCell;
+use graph::anyhow::{bail, ensure};
+use graph::blockchain::{Blockchain, NodeCapabilities, TriggerFilter as _, TriggersAdapter};
+use graph::components::store::{DeploymentLocator, EntityModification, ModificationsAndCache};
+use graph::components::subgraph::{CausalityRegion, MappingError, ProofOfIndexing};
+use graph::data::subgraph::schema::SubgraphError;
+use graph::data::subgraph::MAX_SPEC_VERSION;
+use graph::prelude::*;
+use graph::util::lfu_cache::LfuCache;
+
+use super::instance_manager::{update_proof_of_indexing, BlockProcessingSummary};
+use super::loader::load_dynamic_data_sources;
+use super::SubgraphInstance;
+
+/// One entity change produced by a replay, together with the entity as it
+/// currently exists in the store so that callers can render a diff
+pub struct EntityDiff {
+    pub modification: EntityModification,
+    pub previous: Option<Entity>,
+}
+
+/// The result of replaying a single block in a sandbox. The diffs are what
+/// processing the block would write to the store; unless `committed` is
+/// set, none of them have been applied
+pub struct BlockReplayOutcome {
+    pub block_ptr: BlockPtr,
+    pub trigger_count: usize,
+    pub diffs: Vec<EntityDiff>,
+    pub deterministic_errors: Vec<SubgraphError>,
+    /// Number of dynamic data sources that handlers created. The replay
+    /// does not instantiate them since that would require reprocessing
+    /// the block with a wider trigger filter
+    pub created_data_sources: usize,
+    pub committed: bool,
+}
+
+/// Replay the triggers of one block against the current mappings of
+/// `deployment` without going through the block stream. The triggers are
+/// fetched and processed exactly like during indexing, but the resulting
+/// entity operations are only returned, not written to the store, unless
+/// `commit` is set. A commit also advances the deployment head and
+/// therefore requires that the head currently is the parent of the
+/// replayed block; `trigger` restricts the replay to the trigger with
+/// that index within the block
+pub async fn replay_block<C, S, M, L>(
+    logger: &Logger,
+    subgraph_store: Arc<S>,
+    chain: Arc<C>,
+    link_resolver: Arc<L>,
+    registry: Arc<M>,
+    deployment: DeploymentLocator,
+    raw_manifest: serde_yaml::Mapping,
+    block_number: BlockNumber,
+    trigger: Option<usize>,
+    commit: bool,
+) -> Result<BlockReplayOutcome, Error>
+where
+    C: Blockchain,
+    S: SubgraphStore,
+    M: MetricsRegistry,
+    L: LinkResolver + Clone,
+{
+    let store = subgraph_store
+        .cheap_clone()
+        .writable(logger.clone(), deployment.id)
+        .await?;
+
+    // Assemble the manifest, including dynamic data sources, the same way
+    // the instance manager does when it starts the deployment
+    let manifest: SubgraphManifest<C> = {
+        let mut manifest = SubgraphManifest::resolve_from_raw(
+            deployment.hash.cheap_clone(),
+            raw_manifest,
+            &link_resolver.as_ref().clone().with_retries(),
+            logger,
+            MAX_SPEC_VERSION.clone(),
+        )
+        .await
+        .context("Failed to resolve subgraph from IPFS")?;
+
+        let data_sources = load_dynamic_data_sources::<C>(
+            store.clone(),
+            logger.clone(),
+            manifest.templates.clone(),
+        )
+        .await
+        .context("Failed to load dynamic data sources")?;
+
+        manifest.data_sources.extend(data_sources);
+        manifest
+    };
+
+    let required_capabilities = C::NodeCapabilities::from_data_sources(&manifest.data_sources);
+    let filter = C::TriggerFilter::from_data_sources(manifest.data_sources.iter());
+    let unified_api_version = manifest.unified_mapping_api_version()?;
+
+    let stopwatch = StopwatchMetrics::new(
+        logger.clone(),
+        deployment.hash.clone(),
+        registry.cheap_clone(),
+    );
+    let triggers_adapter = chain
+        .triggers_adapter(
+            &deployment,
+            &required_capabilities,
+            unified_api_version,
+            stopwatch.clone(),
+        )
+        .map_err(|e| {
+            anyhow!(
+                "expected triggers adapter that matches deployment {} with required capabilities: {}: {}",
+                &deployment,
+                &required_capabilities,
+                e
+            )
+        })?;
+
+    // Fetch the block and its triggers the same way a block stream would
+    let block = triggers_adapter
+        .scan_triggers(block_number, block_number, &filter)
+        .await?
+        .into_iter()
+        .find(|block| block.ptr().number == block_number)
+        .ok_or_else(|| anyhow!("block {} is not available from the providers", block_number))?;
+
+    let block_ptr = block.ptr();
+    let mut triggers = block.trigger_data;
+    let block = Arc::new(block.block);
+
+    if let Some(index) = trigger {
+        if index >= triggers.len() {
+            bail!(
+                "block {} only has {} trigger(s); there is no trigger {}",
+                block_number,
+                triggers.len(),
+                index
+            );
+        }
+        triggers = vec![triggers.swap_remove(index)];
+    }
+    let trigger_count = triggers.len();
+
+    let host_metrics = Arc::new(HostMetrics::new(
+        registry.cheap_clone(),
+        deployment.hash.as_str(),
+        stopwatch.clone(),
+    ));
+    let host_builder = graph_runtime_wasm::RuntimeHostBuilder::new(
+        chain.runtime_adapter(),
+        link_resolver.cheap_clone(),
+        subgraph_store,
+    );
+    let instance = SubgraphInstance::from_manifest(logger, manifest, host_builder, host_metrics)?;
+
+    let proof_of_indexing = if store.clone().supports_proof_of_indexing().await? {
+        Some(Arc::new(AtomicRefCell::new(ProofOfIndexing::new(
+            block_ptr.number,
+        ))))
+    } else {
+        None
+    };
+    let causality_region = CausalityRegion::from_network(instance.network());
+
+    let mut block_state = BlockState::new(store.clone(), LfuCache::new());
+    let mut summary = BlockProcessingSummary::default();
+    for trigger in triggers.iter() {
+        block_state = instance
+            .process_trigger(
+                logger,
+                &block,
+                trigger,
+                block_state,
+                proof_of_indexing.cheap_clone(),
+                &causality_region,
+                &mut summary,
+            )
+            .await
+            .map_err(|e| match e {
+                MappingError::Unknown(e) | MappingError::PossibleReorg(e) => {
+                    e.context("failed to process trigger".to_string())
+                }
+            })?;
+    }
+
+    let created_data_sources = block_state.drain_created_data_sources().len();
+
+    if let Some(proof_of_indexing) = proof_of_indexing {
+        let proof_of_indexing = Arc::try_unwrap(proof_of_indexing).unwrap().into_inner();
+        update_proof_of_indexing(
+            proof_of_indexing,
+            &stopwatch,
+            &deployment.hash,
+            &mut block_state.entity_cache,
+        )
+        .await?;
+    }
+
+    let BlockState {
+        entity_cache,
+        deterministic_errors,
+        ..
+    } = block_state;
+
+    let ModificationsAndCache { modifications, .. } =
+        entity_cache.as_modifications().map_err(Error::from)?;
+
+    // Look the current version of each changed entity up so that callers
+    // can show what the replay changed; this has to happen before a commit
+    let mut diffs = Vec::with_capacity(modifications.len());
+    for modification in modifications {
+        let previous = match &modification {
+            EntityModification::Insert { .. } => None,
+            EntityModification::Overwrite { .. } | EntityModification::Remove { .. } => {
+                store.get(modification.entity_key())?
+            }
+        };
+        diffs.push(EntityDiff {
+            modification,
+            previous,
+        });
+    }
+
+    let committed = if commit {
+        ensure!(
+            deterministic_errors.is_empty(),
+            "refusing to commit a replay that produced deterministic errors"
+        );
+        ensure!(
+            created_data_sources == 0,
+            "refusing to commit a replay that created dynamic data sources"
+        );
+        match store.block_ptr()? {
+            Some(head) if head.number + 1 == block_ptr.number => (),
+            head => bail!(
+                "the deployment head is at {} and only a replay of the block \
+                 right after it can be committed; rewind the deployment first",
+                head.map(|head| head.number.to_string())
+                    .unwrap_or_else(|| "the genesis block".to_string())
+            ),
+        }
+
+        let mods = diffs.iter().map(|diff| diff.modification.clone()).collect();
+        store.transact_block_operations(
+            block_ptr.cheap_clone(),
+            None,
+            mods,
+            stopwatch,
+            vec![],
+            deterministic_errors.clone(),
+        )?;
+        true
+    } else {
+        false
+    };
+
+    Ok(BlockReplayOutcome {
+        block_ptr,
+        trigger_count,
+        diffs,
+        deterministic_errors,
+        created_data_sources,
+        committed,
+    })
+}
@@ -36,7 +36,7 @@ async fn insert_and_query(
 
     let document = graphql_parser::parse_query(query).unwrap().into_static();
     let target = QueryTarget::Deployment(subgraph_id);
-    let query = Query::new(document, None);
+    let query = Query::new(document, None, false);
     Ok(execute_subgraph_query(query, target)
         .await
         .first()
@@ -40,6 +40,19 @@ where
     }
 
     pub async fn into_polling_stream(self) {
+        use futures03::StreamExt;
+
+        // Providers that support `newHeads` subscriptions wake us up as
+        // soon as a new head appears; the polling interval then only acts
+        // as a safety net in case the subscription drops notifications
+        let mut new_heads = self.adapter.subscribe_new_heads().await;
+        if new_heads.is_some() {
+            info!(
+                self.logger,
+                "Using newHeads subscription for chain head updates"
+            );
+        }
+
         loop {
             match self.do_poll().await {
                 // Some polls will fail due to transient issues
@@ -55,6 +68,12 @@ where
                         "Trying again after block polling failed: {}", err
                     );
                 }
+                Err(err @ IngestorError::PoisonBlock(_, _)) => {
+                    warn!(
+                        self.logger,
+                        "Quarantined corrupt block from provider: {}", err
+                    );
+                }
                 Err(IngestorError::Unknown(inner_err)) => {
                     warn!(
                         self.logger,
@@ -68,7 +87,27 @@ where
                 self.cleanup_cached_blocks()
             }
 
-            tokio::time::sleep(self.polling_interval).await;
+            // Wait for the next head notification, but never longer than
+            // the polling interval
+            let subscription_ended = match new_heads.as_mut() {
+                Some(stream) => {
+                    tokio::select! {
+                        head = stream.next() => head.is_none(),
+                        _ = tokio::time::sleep(self.polling_interval) => false,
+                    }
+                }
+                None => {
+                    tokio::time::sleep(self.polling_interval).await;
+                    false
+                }
+            };
+            if subscription_ended {
+                warn!(
+                    self.logger,
+                    "The newHeads subscription ended; falling back to polling"
+                );
+                new_heads = None;
+            }
         }
     }
 
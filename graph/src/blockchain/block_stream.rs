@@ -3,8 +3,9 @@ use futures03::Stream;
 use std::sync::Arc;
 use thiserror::Error;
 
-use super::{Block, BlockPtr, Blockchain};
+use super::{Block, BlockPtr, Blockchain, TriggerData};
 use crate::components::store::BlockNumber;
+use crate::data::subgraph::schema::SubgraphHealth;
 use crate::firehose::bstream;
 use crate::{prelude::*, prometheus::labels};
 
@@ -22,8 +23,10 @@ pub struct BlockWithTriggers<C: Blockchain> {
 
 impl<C: Blockchain> BlockWithTriggers<C> {
     pub fn new(block: C::Block, mut trigger_data: Vec<C::TriggerData>) -> Self {
-        // This is where triggers get sorted.
-        trigger_data.sort();
+        // This is where triggers get sorted. The sort is stable, so
+        // triggers with equal keys keep the order in which the chain
+        // delivered them
+        trigger_data.sort_by_key(TriggerData::order_key);
         Self {
             block,
             trigger_data,
@@ -113,6 +116,14 @@ pub struct BlockStreamMetrics {
     pub deployment_head: Box<Gauge>,
     pub deployment_failed: Box<Gauge>,
     pub reverted_blocks: Box<Gauge>,
+    pub blocks_per_second: Box<Gauge>,
+    /// State set with one gauge per health state; the gauge for the
+    /// current state is 1 and the others are 0, so alert rules can match
+    /// on the `state` label
+    pub health: Box<GaugeVec>,
+    pub blocks_behind: Box<Gauge>,
+    pub fatal_errors: Box<Counter>,
+    pub non_fatal_errors: Box<Counter>,
     pub stopwatch: StopwatchMetrics,
 }
 
@@ -147,16 +158,69 @@ impl BlockStreamMetrics {
             .new_gauge(
                 "deployment_failed",
                 "Boolean gauge to indicate whether the deployment has failed (1 == failed)",
-                labels,
+                labels.clone(),
             )
             .expect("failed to create `deployment_failed` gauge");
+        let blocks_per_second = registry
+            .new_gauge(
+                "deployment_blocks_per_second",
+                "Track the speed at which a deployment processes blocks, \
+                 measured over a sliding window",
+                labels.clone(),
+            )
+            .expect("failed to create `deployment_blocks_per_second` gauge");
+        let health = registry
+            .new_deployment_gauge_vec(
+                "subgraph_health",
+                "Indicate the health of a deployment; the gauge for the \
+                 current state is 1 and the others are 0",
+                deployment_id.as_str(),
+                vec![String::from("state")],
+            )
+            .expect("failed to create `subgraph_health` gauge");
+        let blocks_behind = registry
+            .new_gauge(
+                "subgraph_blocks_behind_chain_head",
+                "Track how many blocks a deployment is behind the chain head",
+                labels,
+            )
+            .expect("failed to create `subgraph_blocks_behind_chain_head` gauge");
+        let fatal_errors = registry
+            .new_deployment_counter(
+                "subgraph_fatal_errors",
+                "Count the fatal errors a deployment hit while indexing",
+                deployment_id.as_str(),
+            )
+            .expect("failed to create `subgraph_fatal_errors` counter");
+        let non_fatal_errors = registry
+            .new_deployment_counter(
+                "subgraph_non_fatal_errors",
+                "Count the non-fatal errors a deployment hit while indexing",
+                deployment_id.as_str(),
+            )
+            .expect("failed to create `subgraph_non_fatal_errors` counter");
         Self {
             deployment_head,
             deployment_failed,
             reverted_blocks,
+            blocks_per_second,
+            health,
+            blocks_behind,
+            fatal_errors,
+            non_fatal_errors,
             stopwatch,
         }
     }
+
+    /// Reflect `health` in the `subgraph_health` state set
+    pub fn track_health(&self, health: SubgraphHealth) {
+        use SubgraphHealth::*;
+
+        for state in [Healthy, Unhealthy, Failed] {
+            let value = if state == health { 1.0 } else { 0.0 };
+            self.health.with_label_values(&[state.as_str()]).set(value);
+        }
+    }
 }
 
 /// Notifications about the chain head advancing. The block ingestor sends
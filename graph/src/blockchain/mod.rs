@@ -86,8 +86,11 @@ pub trait Blockchain: Debug + Sized + Send + Sync + Unpin + 'static {
 
     type TriggersAdapter: TriggersAdapter<Self>;
 
-    /// Trigger data as parsed from the triggers adapter.
-    type TriggerData: TriggerData + Ord;
+    /// Trigger data as parsed from the triggers adapter. Ordering of
+    /// triggers within a block happens solely through
+    /// `TriggerData::order_key` so that all chains follow the same
+    /// canonical order.
+    type TriggerData: TriggerData;
 
     /// Decoded trigger ready to be processed by the mapping.
     /// New implementations should have this be the same as `TriggerData`.
@@ -157,6 +160,12 @@ pub enum IngestorError {
     #[error("Receipt for tx {1:?} unavailable, block was likely uncled (block hash = {0:?})")]
     ReceiptUnavailable(H256, H256),
 
+    /// The provider served a block whose contents contradict the hash we
+    /// requested or the cached chain. The block is quarantined instead of
+    /// being ingested into the chain store.
+    #[error("Provider returned a corrupt block (block hash = {0:?}): {1}")]
+    PoisonBlock(H256, String),
+
     /// An unexpected error occurred.
     #[error("Ingestor error: {0}")]
     Unknown(Error),
@@ -197,6 +206,15 @@ pub trait IngestorAdapter<C: Blockchain> {
     /// to the block streams of subgraphs
     fn chain_head_ptr(&self) -> Result<Option<BlockPtr>, Error>;
 
+    /// Subscribe to push-based head update notifications from the
+    /// provider, if the provider supports them. The block ingestor uses
+    /// the notifications as wakeups instead of waiting out the full
+    /// polling interval, and keeps polling as a fallback. The default is
+    /// to rely on polling alone
+    async fn subscribe_new_heads(&self) -> Option<futures03::stream::BoxStream<'static, ()>> {
+        None
+    }
+
     /// Remove old blocks from the database cache and return a pair
     /// containing the number of the oldest block retained and the number of
     /// blocks deleted if anything was removed. This is generally only used
@@ -253,7 +271,9 @@ pub trait DataSource<C: Blockchain>:
     ) -> Result<Self, Error>;
 
     /// Used as part of manifest validation. If there are no errors, return an empty vector.
-    fn validate(&self) -> Vec<Error>;
+    /// Features that are only available from a certain manifest spec version on must be
+    /// checked against `spec_version` here.
+    fn validate(&self, spec_version: &semver::Version) -> Vec<Error>;
 }
 
 #[async_trait]
@@ -288,6 +308,41 @@ pub trait TriggerData {
     /// If there is an error when processing this trigger, this will called to add relevant context.
     /// For example an useful return is: `"block #<N> (<hash>), transaction <tx_hash>".
     fn error_context(&self) -> String;
+
+    /// The canonical position of this trigger within its block; see
+    /// [`TriggerOrderKey`] for the guarantees implementations must uphold.
+    fn order_key(&self) -> TriggerOrderKey;
+}
+
+/// The canonical position of a trigger within its block. The triggers of a
+/// block run in ascending order of this key, and that order feeds into the
+/// proof of indexing: all chains, and all versions of `graph-node`, must
+/// therefore assign the same key to the same trigger. Triggers that compare
+/// equal keep the order in which the chain delivered them.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TriggerOrderKey {
+    /// A trigger tied to a specific transaction. Transactions run in the
+    /// order in which they appear in the block; within one transaction,
+    /// events run first, in log index order, followed by calls in trace
+    /// address order.
+    Transaction(u64, TransactionTrigger),
+
+    /// A trigger for which the chain defines no intrinsic position within
+    /// the block, like a NEAR receipt execution outcome. These run after
+    /// all transaction triggers, in delivery order.
+    Unordered,
+
+    /// Block triggers run last.
+    Block,
+}
+
+/// The position of a transaction-scoped trigger within its transaction.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TransactionTrigger {
+    /// An event, ordered by its log index.
+    Log(u64),
+    /// A call, ordered by its trace address.
+    Call(Vec<u64>),
 }
 
 pub trait MappingTrigger: Send + Sync {
@@ -455,3 +510,35 @@ impl<C: Blockchain> TriggerWithHandler<C> {
         self.trigger.to_asc_ptr(heap)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{TransactionTrigger, TriggerOrderKey};
+
+    #[test]
+    fn canonical_trigger_order() {
+        use TransactionTrigger::*;
+        use TriggerOrderKey::*;
+
+        // Within a block: events by log index, then calls by trace
+        // address, both grouped by transaction; unordered triggers and
+        // block triggers at the end
+        let ordered = vec![
+            Transaction(0, Log(0)),
+            Transaction(0, Log(7)),
+            Transaction(0, Call(vec![])),
+            Transaction(0, Call(vec![0])),
+            Transaction(0, Call(vec![0, 3])),
+            Transaction(0, Call(vec![1])),
+            Transaction(1, Log(8)),
+            Transaction(1, Call(vec![0])),
+            Unordered,
+            Block,
+        ];
+
+        let mut sorted = ordered.clone();
+        sorted.reverse();
+        sorted.sort();
+        assert_eq!(ordered, sorted);
+    }
+}
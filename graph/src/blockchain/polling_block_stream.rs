@@ -263,10 +263,14 @@ where
         // subgraph_ptr > head_ptr shouldn't happen, but if it does, it's safest to just stop.
         if let Some(ptr) = &subgraph_ptr {
             if ptr.number >= head_ptr.number {
+                self.metrics.blocks_behind.set(0.0);
                 return Ok(ReconciliationStep::Done);
             }
 
             self.metrics.deployment_head.set(ptr.number as f64);
+            self.metrics
+                .blocks_behind
+                .set((head_ptr.number - ptr.number) as f64);
         }
 
         // Subgraph ptr is behind head ptr.
@@ -402,6 +406,16 @@ where
                 blocks, range_size,
             ))
         } else {
+            // When the deployment only wants final blocks, we stop at the
+            // reorg threshold and wait for the chain head to move instead
+            // of following the probabilistic head. Everything the
+            // deployment processes then goes through the branch above,
+            // which never goes past `head_ptr.number - reorg_threshold`,
+            // so its data never gets reverted
+            if self.final_blocks_only() {
+                return Ok(ReconciliationStep::Done);
+            }
+
             // The subgraph ptr is not too far behind the head ptr.
             // This means a few things.
             //
@@ -487,13 +501,26 @@ where
         Ok(ptr)
     }
 
+    /// Look the setting up every time so that operators can flip it
+    /// without a restart; it takes effect with the next reconciliation
+    fn final_blocks_only(&self) -> bool {
+        crate::settings::for_deployment(&self.subgraph_id).final_blocks_only()
+    }
+
     /// Set subgraph deployment entity synced flag if and only if the subgraph block pointer is
-    /// caught up to the head block pointer.
+    /// caught up to the head block pointer, or, for deployments that only
+    /// process final blocks, caught up to the reorg threshold.
     fn update_subgraph_synced_status(&self) -> Result<(), StoreError> {
         let head_ptr_opt = self.chain_store.chain_head_ptr()?;
         let subgraph_ptr = self.current_block.clone();
 
-        if head_ptr_opt != subgraph_ptr || head_ptr_opt.is_none() || subgraph_ptr.is_none() {
+        let synced = match (&head_ptr_opt, &subgraph_ptr) {
+            (Some(head_ptr), Some(subgraph_ptr)) if self.final_blocks_only() => {
+                subgraph_ptr.number + self.reorg_threshold >= head_ptr.number
+            }
+            _ => head_ptr_opt == subgraph_ptr && head_ptr_opt.is_some(),
+        };
+        if !synced {
             // Not synced yet
             Ok(())
         } else {
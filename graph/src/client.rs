@@ -0,0 +1,441 @@
+//! Typed clients for the operator-facing APIs of `graph-node`.
+//!
+//! Orchestration tools that manage a fleet of nodes generally talk to two
+//! endpoints: the admin JSON-RPC API for changing what a node indexes, and
+//! the GraphQL API of the index node server for observing how indexing is
+//! going. [`AdminClient`] and [`StatusClient`] wrap these two endpoints
+//! with typed requests and responses so that such tools do not have to
+//! assemble and pick apart raw JSON themselves.
+//!
+//! Both clients are thin wrappers around a `reqwest::Client` and can be
+//! cloned cheaply; errors from the server are turned into plain `Error`s
+//! that carry the server's error message.
+
+use std::str::FromStr;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::json;
+
+use crate::components::store::BlockNumber;
+use crate::data::subgraph::schema::SubgraphHealth;
+use crate::prelude::{
+    anyhow::{anyhow, bail, Context as _},
+    CreateSubgraphResult, DeploymentHash, Error, NodeId, SubgraphName,
+};
+
+/// A client for the admin JSON-RPC API that `graph-node` serves on its
+/// admin port (8020 by default), with one method for each RPC method
+#[derive(Clone)]
+pub struct AdminClient {
+    url: String,
+    client: reqwest::Client,
+}
+
+/// The parameters of the `subgraph_deploy` method, and of each entry of
+/// `subgraph_deploy_batch`
+#[derive(Clone, Debug, Serialize)]
+pub struct DeployParams {
+    pub name: SubgraphName,
+    pub ipfs_hash: DeploymentHash,
+    /// The node that should index the deployment. When it is omitted, the
+    /// node that handles the request assigns the deployment to itself
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node_id: Option<NodeId>,
+}
+
+/// The parameters of the `subgraph_clone` method
+#[derive(Clone, Debug, Serialize)]
+pub struct CloneParams {
+    pub name: SubgraphName,
+    pub ipfs_hash: DeploymentHash,
+    /// The deployment whose data the clone starts from
+    pub base: DeploymentHash,
+    /// The block up to which the data of `base` is copied
+    pub block: BlockNumber,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node_id: Option<NodeId>,
+}
+
+/// The parameters of the `subgraph_reassign` method, and of each entry of
+/// `subgraph_reassign_batch`
+#[derive(Clone, Debug, Serialize)]
+pub struct ReassignParams {
+    pub ipfs_hash: DeploymentHash,
+    pub node_id: NodeId,
+}
+
+/// The URLs under which a subgraph can be queried, as returned by
+/// `subgraph_deploy`
+#[derive(Clone, Debug, Deserialize)]
+pub struct SubgraphRoutes {
+    pub playground: String,
+    pub queries: String,
+    pub subscriptions: String,
+}
+
+#[derive(Serialize)]
+struct JsonRpcRequest<'a, T> {
+    jsonrpc: &'static str,
+    id: u32,
+    method: &'a str,
+    params: T,
+}
+
+#[derive(Deserialize)]
+struct JsonRpcResponse {
+    result: Option<serde_json::Value>,
+    error: Option<JsonRpcError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonRpcError {
+    code: i64,
+    message: String,
+}
+
+impl AdminClient {
+    /// Create a client for the admin JSON-RPC API at `url`, e.g.
+    /// `http://localhost:8020`
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    async fn call<P: Serialize, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: P,
+    ) -> Result<R, Error> {
+        let request = JsonRpcRequest {
+            jsonrpc: "2.0",
+            id: 1,
+            method,
+            params,
+        };
+        let response: JsonRpcResponse = self
+            .client
+            .post(&self.url)
+            .json(&request)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .with_context(|| format!("`{}` returned an invalid JSON-RPC response", method))?;
+        if let Some(e) = response.error {
+            bail!("`{}` failed with code {}: {}", method, e.code, e.message);
+        }
+        serde_json::from_value(response.result.unwrap_or(serde_json::Value::Null))
+            .with_context(|| format!("`{}` returned an unexpected result", method))
+    }
+
+    pub async fn subgraph_create(
+        &self,
+        name: &SubgraphName,
+    ) -> Result<CreateSubgraphResult, Error> {
+        self.call("subgraph_create", json!({ "name": name })).await
+    }
+
+    pub async fn subgraph_deploy(&self, params: &DeployParams) -> Result<SubgraphRoutes, Error> {
+        self.call("subgraph_deploy", params).await
+    }
+
+    /// Deploy several subgraphs in order. Deployments before a failing one
+    /// remain applied; the error names the deployment that failed
+    pub async fn subgraph_deploy_batch(
+        &self,
+        deployments: &[DeployParams],
+    ) -> Result<Vec<SubgraphRoutes>, Error> {
+        self.call(
+            "subgraph_deploy_batch",
+            json!({ "deployments": deployments }),
+        )
+        .await
+    }
+
+    /// Deploy a manifest as a clone of an existing deployment: the data of
+    /// the base deployment up to the given block is copied into the new
+    /// one, which then indexes forward with its own mappings
+    pub async fn subgraph_clone(&self, params: &CloneParams) -> Result<SubgraphRoutes, Error> {
+        self.call("subgraph_clone", params).await
+    }
+
+    pub async fn subgraph_remove(&self, name: &SubgraphName) -> Result<(), Error> {
+        self.call("subgraph_remove", json!({ "name": name })).await
+    }
+
+    pub async fn subgraph_reassign(&self, params: &ReassignParams) -> Result<(), Error> {
+        self.call("subgraph_reassign", params).await
+    }
+
+    /// Reassign several deployments in one transaction; either all of them
+    /// move to their new node or none of them do
+    pub async fn subgraph_reassign_batch(
+        &self,
+        reassignments: &[ReassignParams],
+    ) -> Result<(), Error> {
+        self.call(
+            "subgraph_reassign_batch",
+            json!({ "reassignments": reassignments }),
+        )
+        .await
+    }
+
+    pub async fn subgraph_retry(&self, ipfs_hash: &DeploymentHash) -> Result<(), Error> {
+        self.call("subgraph_retry", json!({ "ipfs_hash": ipfs_hash }))
+            .await
+    }
+
+    pub async fn subgraph_retry_from(
+        &self,
+        ipfs_hash: &DeploymentHash,
+        block: BlockNumber,
+    ) -> Result<(), Error> {
+        self.call(
+            "subgraph_retry_from",
+            json!({ "ipfs_hash": ipfs_hash, "block": block }),
+        )
+        .await
+    }
+
+    /// Set the deployment setting `name` to `value`; an empty `value`
+    /// removes the setting. See the module docs of [`crate::settings`] for
+    /// the recognized settings
+    pub async fn subgraph_setting(
+        &self,
+        ipfs_hash: &DeploymentHash,
+        name: &str,
+        value: &str,
+    ) -> Result<(), Error> {
+        self.call(
+            "subgraph_setting",
+            json!({ "ipfs_hash": ipfs_hash, "name": name, "value": value }),
+        )
+        .await
+    }
+}
+
+/// A client for the status queries of the index node server (port 8030 by
+/// default)
+#[derive(Clone)]
+pub struct StatusClient {
+    url: String,
+    client: reqwest::Client,
+}
+
+/// The indexing status of one deployment, as returned by the
+/// `indexingStatuses` family of queries
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexingStatus {
+    /// The deployment hash
+    pub subgraph: String,
+    pub synced: bool,
+    #[serde(deserialize_with = "deserialize_health")]
+    pub health: SubgraphHealth,
+    pub fatal_error: Option<IndexingError>,
+    pub non_fatal_errors: Vec<IndexingError>,
+    pub chains: Vec<ChainStatus>,
+    #[serde(deserialize_with = "deserialize_big_int")]
+    pub entity_count: u64,
+    /// The first block for which the deployment has history; time-travel
+    /// queries for earlier blocks return empty results
+    pub earliest_block_number: BlockNumber,
+    /// How many blocks of history the deployment retains
+    pub history_blocks: Option<BlockNumber>,
+    /// Whether history before `earliest_block_number` is currently being
+    /// removed
+    pub prune_in_progress: bool,
+    /// The node the deployment is assigned to
+    pub node: Option<String>,
+}
+
+/// An indexing error of a deployment
+#[derive(Clone, Debug, Deserialize)]
+pub struct IndexingError {
+    pub message: String,
+    /// The block at which the error occurred
+    pub block: Option<Block>,
+    /// The handler that caused the error
+    pub handler: Option<String>,
+    pub deterministic: bool,
+}
+
+/// How far a deployment has come in indexing its chain
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainStatus {
+    pub network: String,
+    pub chain_head_block: Option<Block>,
+    pub earliest_block: Option<Block>,
+    pub latest_block: Option<Block>,
+    pub last_healthy_block: Option<Block>,
+}
+
+/// A block pointer in a status response
+#[derive(Clone, Debug, Deserialize)]
+pub struct Block {
+    /// The block hash as a hex string with a `0x` prefix
+    pub hash: String,
+    #[serde(deserialize_with = "deserialize_big_int")]
+    pub number: BlockNumber,
+}
+
+/// Deserialize a GraphQL `BigInt`, which appears as a string in the JSON
+/// response
+fn deserialize_big_int<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    let s = String::deserialize(deserializer)?;
+    s.parse().map_err(serde::de::Error::custom)
+}
+
+fn deserialize_health<'de, D>(deserializer: D) -> Result<SubgraphHealth, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    s.parse().map_err(serde::de::Error::custom)
+}
+
+#[derive(Deserialize)]
+struct GraphqlResponse {
+    data: Option<serde_json::Value>,
+    errors: Option<Vec<GraphqlError>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphqlError {
+    message: String,
+}
+
+/// The fields the status queries select for each `SubgraphIndexingStatus`
+const STATUS_FIELDS: &str = "\
+    subgraph synced health \
+    fatalError { message block { hash number } handler deterministic } \
+    nonFatalErrors { message block { hash number } handler deterministic } \
+    chains { network \
+             chainHeadBlock { hash number } \
+             earliestBlock { hash number } \
+             latestBlock { hash number } \
+             lastHealthyBlock { hash number } } \
+    entityCount earliestBlockNumber historyBlocks pruneInProgress node";
+
+impl StatusClient {
+    /// Create a client for the index node server at `url`, e.g.
+    /// `http://localhost:8030`
+    pub fn new(url: impl Into<String>) -> Self {
+        let url = format!("{}/graphql", url.into().trim_end_matches('/'));
+        Self {
+            url,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Run `query` and deserialize the field `field` of the response data
+    async fn query<R: DeserializeOwned>(
+        &self,
+        query: String,
+        variables: serde_json::Value,
+        field: &str,
+    ) -> Result<R, Error> {
+        let response: GraphqlResponse = self
+            .client
+            .post(&self.url)
+            .json(&json!({ "query": query, "variables": variables }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .with_context(|| format!("`{}` returned an invalid GraphQL response", field))?;
+        if let Some(errors) = response.errors {
+            if !errors.is_empty() {
+                let messages: Vec<_> = errors.into_iter().map(|e| e.message).collect();
+                bail!("`{}` failed: {}", field, messages.join("; "));
+            }
+        }
+        let value = response
+            .data
+            .and_then(|mut data| data.get_mut(field).map(serde_json::Value::take))
+            .ok_or_else(|| anyhow!("`{}` returned no data", field))?;
+        serde_json::from_value(value)
+            .with_context(|| format!("`{}` returned an unexpected result", field))
+    }
+
+    /// The indexing statuses of the given deployments, or of all
+    /// deployments when `subgraphs` is `None`
+    pub async fn indexing_statuses(
+        &self,
+        subgraphs: Option<Vec<String>>,
+    ) -> Result<Vec<IndexingStatus>, Error> {
+        let query = format!(
+            "query($subgraphs: [String!]) {{ \
+               indexingStatuses(subgraphs: $subgraphs) {{ {} }} }}",
+            STATUS_FIELDS
+        );
+        self.query(query, json!({ "subgraphs": subgraphs }), "indexingStatuses")
+            .await
+    }
+
+    /// The indexing status of the current version of the named subgraph
+    pub async fn indexing_status_for_current_version(
+        &self,
+        name: &SubgraphName,
+    ) -> Result<Option<IndexingStatus>, Error> {
+        let query = format!(
+            "query($name: String!) {{ \
+               indexingStatusForCurrentVersion(subgraphName: $name) {{ {} }} }}",
+            STATUS_FIELDS
+        );
+        self.query(
+            query,
+            json!({ "name": name }),
+            "indexingStatusForCurrentVersion",
+        )
+        .await
+    }
+
+    /// The indexing status of the pending version of the named subgraph,
+    /// if there is one
+    pub async fn indexing_status_for_pending_version(
+        &self,
+        name: &SubgraphName,
+    ) -> Result<Option<IndexingStatus>, Error> {
+        let query = format!(
+            "query($name: String!) {{ \
+               indexingStatusForPendingVersion(subgraphName: $name) {{ {} }} }}",
+            STATUS_FIELDS
+        );
+        self.query(
+            query,
+            json!({ "name": name }),
+            "indexingStatusForPendingVersion",
+        )
+        .await
+    }
+
+    /// The indexing statuses of all versions of the named subgraph
+    pub async fn indexing_statuses_for_subgraph_name(
+        &self,
+        name: &SubgraphName,
+    ) -> Result<Vec<IndexingStatus>, Error> {
+        let query = format!(
+            "query($name: String!) {{ \
+               indexingStatusesForSubgraphName(subgraphName: $name) {{ {} }} }}",
+            STATUS_FIELDS
+        );
+        self.query(
+            query,
+            json!({ "name": name }),
+            "indexingStatusesForSubgraphName",
+        )
+        .await
+    }
+}
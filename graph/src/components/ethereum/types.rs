@@ -116,6 +116,12 @@ pub struct EthereumCall {
     pub block_hash: H256,
     pub transaction_hash: Option<H256>,
     pub transaction_index: u64,
+    /// The position of the call in the trace tree of its transaction, in
+    /// execution (preorder) terms. Used to order calls deterministically
+    /// within a transaction; the numbers themselves carry no meaning
+    /// beyond that, and different providers may report different numbers
+    /// for the same call as long as the relative order is preserved.
+    pub trace_address: Vec<u64>,
 }
 
 impl EthereumCall {
@@ -152,6 +158,7 @@ impl EthereumCall {
             block_hash: trace.block_hash,
             transaction_hash: trace.transaction_hash,
             transaction_index,
+            trace_address: trace.trace_address.iter().map(|i| *i as u64).collect(),
         })
     }
 }
@@ -3,7 +3,7 @@ use futures::prelude::*;
 use crate::data::query::{CacheStatus, Query, QueryTarget};
 use crate::data::subscription::{Subscription, SubscriptionError, SubscriptionResult};
 use crate::data::{graphql::effort::LoadManager, query::QueryResults};
-use crate::prelude::DeploymentHash;
+use crate::prelude::{BlockNumber, DeploymentHash};
 
 use async_trait::async_trait;
 use std::sync::Arc;
@@ -35,6 +35,16 @@ pub trait GraphQlRunner: Send + Sync + 'static {
         max_skip: Option<u32>,
     ) -> QueryResults;
 
+    /// Runs a GraphQL query with all block constraints in the query
+    /// overridden to the given block number, so that queries against
+    /// several deployments can be served from the same consistent snapshot.
+    async fn run_query_at_block(
+        self: Arc<Self>,
+        query: Query,
+        target: QueryTarget,
+        block: BlockNumber,
+    ) -> QueryResults;
+
     /// Runs a GraphQL subscription and returns a stream of results.
     async fn run_subscription(
         self: Arc<Self>,
@@ -75,6 +75,27 @@ impl StopwatchMetrics {
         }
     }
 
+    /// A stopwatch that discards all measurements. This is for one-off
+    /// store operations that need to pass a stopwatch along but happen
+    /// outside the indexing pipeline, like importing a graft base
+    pub fn disabled(logger: Logger) -> Self {
+        let counter = CounterVec::new(
+            crate::components::metrics::Opts::new("disabled_stopwatch", "unused"),
+            &["section"],
+        )
+        .expect("failed to create disabled stopwatch counter");
+
+        StopwatchMetrics {
+            disabled: Arc::new(AtomicBool::new(true)),
+            inner: Arc::new(Mutex::new(StopwatchInner {
+                counter,
+                logger,
+                section_stack: Vec::new(),
+                timer: Instant::now(),
+            })),
+        }
+    }
+
     pub fn start_section(&self, id: &str) -> Section {
         let id = id.to_owned();
         if !self.disabled.load(Ordering::SeqCst) {
@@ -55,6 +55,12 @@ pub mod link_resolver;
 /// Components dealing with collecting metrics
 pub mod metrics;
 
+/// A registry of configured chain providers and their health
+pub mod provider;
+
+/// Indexing speed per deployment, for sync ETAs
+pub mod sync_progress;
+
 /// A component that receives events of type `T`.
 pub trait EventConsumer<E> {
     /// Get the event sink.
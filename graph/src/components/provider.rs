@@ -0,0 +1,151 @@
+//! A registry of the chain providers a node is configured with, together
+//! with health information collected while the node talks to them. The
+//! index node exposes the registry through the `providerStatuses` query so
+//! operators can validate their provider mix from one place.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+
+/// How many latency samples to keep per provider for the rolling stats
+const LATENCY_WINDOW: usize = 100;
+
+/// A snapshot of what we know about one provider for one network
+#[derive(Clone, Debug)]
+pub struct ProviderStatus {
+    pub network: String,
+    pub provider: String,
+    /// The capabilities the provider was configured with, rendered the way
+    /// the chain renders them, e.g. `archive, traces`
+    pub capabilities: String,
+    /// The largest block range the provider is asked to scan in one
+    /// `eth_getLogs` call
+    pub get_logs_range_limit: i32,
+    /// The number of blocks fetched from the provider in one batch
+    pub batch_size_limit: i32,
+    /// The message of the most recent request that failed
+    pub last_error: Option<String>,
+    /// Mean latency over the most recent requests, in milliseconds
+    pub latency_avg_ms: Option<u64>,
+    /// 90th percentile latency over the most recent requests
+    pub latency_p90_ms: Option<u64>,
+    /// The number of latency samples the stats are based on
+    pub latency_samples: usize,
+}
+
+struct Entry {
+    capabilities: String,
+    get_logs_range_limit: i32,
+    batch_size_limit: i32,
+    last_error: Option<String>,
+    latencies: VecDeque<Duration>,
+}
+
+/// Tracks all registered providers, keyed by network and provider name
+/// since the same provider label is commonly reused across networks.
+/// Chains register their providers on startup and record request
+/// latencies and errors as they happen
+pub struct ProviderRegistry {
+    entries: Mutex<BTreeMap<(String, String), Entry>>,
+}
+
+impl ProviderRegistry {
+    fn new() -> Self {
+        Self {
+            entries: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn register(
+        &self,
+        network: &str,
+        provider: &str,
+        capabilities: String,
+        get_logs_range_limit: i32,
+        batch_size_limit: i32,
+    ) {
+        self.entries.lock().unwrap().insert(
+            (network.to_owned(), provider.to_owned()),
+            Entry {
+                capabilities,
+                get_logs_range_limit,
+                batch_size_limit,
+                last_error: None,
+                latencies: VecDeque::with_capacity(LATENCY_WINDOW),
+            },
+        );
+    }
+
+    /// Record the latency of a successful request against `provider` on
+    /// `network`. Unregistered providers are ignored
+    pub fn record_latency(&self, network: &str, provider: &str, latency: Duration) {
+        if let Some(entry) = self
+            .entries
+            .lock()
+            .unwrap()
+            .get_mut(&(network.to_owned(), provider.to_owned()))
+        {
+            if entry.latencies.len() >= LATENCY_WINDOW {
+                entry.latencies.pop_front();
+            }
+            entry.latencies.push_back(latency);
+        }
+    }
+
+    /// Record that a request against `provider` on `network` failed.
+    /// Unregistered providers are ignored
+    pub fn record_error(&self, network: &str, provider: &str, error: &str) {
+        if let Some(entry) = self
+            .entries
+            .lock()
+            .unwrap()
+            .get_mut(&(network.to_owned(), provider.to_owned()))
+        {
+            entry.last_error = Some(error.to_owned());
+        }
+    }
+
+    /// A snapshot of all registered providers and their stats
+    pub fn status(&self) -> Vec<ProviderStatus> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|((network, provider), entry)| {
+                let mut millis: Vec<u64> = entry
+                    .latencies
+                    .iter()
+                    .map(|latency| latency.as_millis() as u64)
+                    .collect();
+                millis.sort_unstable();
+                let latency_avg_ms = if millis.is_empty() {
+                    None
+                } else {
+                    Some(millis.iter().sum::<u64>() / millis.len() as u64)
+                };
+                let latency_p90_ms = if millis.is_empty() {
+                    None
+                } else {
+                    Some(millis[(millis.len() - 1) * 9 / 10])
+                };
+                ProviderStatus {
+                    network: network.clone(),
+                    provider: provider.clone(),
+                    capabilities: entry.capabilities.clone(),
+                    get_logs_range_limit: entry.get_logs_range_limit,
+                    batch_size_limit: entry.batch_size_limit,
+                    last_error: entry.last_error.clone(),
+                    latency_avg_ms,
+                    latency_p90_ms,
+                    latency_samples: millis.len(),
+                }
+            })
+            .collect()
+    }
+}
+
+lazy_static! {
+    pub static ref PROVIDER_REGISTRY: ProviderRegistry = ProviderRegistry::new();
+}
@@ -7,6 +7,8 @@ use crate::prelude::{BlockNumber, Schema};
 #[derive(Debug)]
 /// This is only needed to support the explorer API
 pub struct VersionInfo {
+    /// When this node first saw the version, as wall-clock time; purely
+    /// informational and different between indexers
     pub created_at: String,
     pub deployment_id: String,
     pub latest_ethereum_block_number: Option<BlockNumber>,
@@ -211,6 +211,13 @@ impl EntityFilter {
     }
 }
 
+/// The direction in which to order entities for one sort key.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OrderDirection {
+    Ascending,
+    Descending,
+}
+
 /// The order in which entities should be restored from a store.
 #[derive(Clone, Debug, PartialEq)]
 pub enum EntityOrder {
@@ -218,6 +225,10 @@ pub enum EntityOrder {
     Ascending(String, ValueType),
     /// Order descending by the given attribute. Use `id` as a tie-breaker
     Descending(String, ValueType),
+    /// Order by several attributes, each with its own direction, in the
+    /// given priority. Unless `id` is one of the attributes, it is used as
+    /// the final tie-breaker
+    Multiple(Vec<(String, ValueType, OrderDirection)>),
     /// Order by the `id` of the entities
     Default,
     /// Do not order at all. This speeds up queries where we know that
@@ -400,6 +411,15 @@ pub struct EntityQuery {
 
     pub query_id: Option<String>,
 
+    /// When set, the SQL statements that the query causes are recorded
+    /// here so they can be reported back to the client
+    pub trace: Option<Arc<Trace>>,
+
+    /// When set, the handle becomes canceled if the client that caused
+    /// this query goes away, and the store cancels the SQL statements it
+    /// runs for the query
+    pub cancel: Option<CancelHandle>,
+
     _force_use_of_new: (),
 }
 
@@ -418,6 +438,8 @@ impl EntityQuery {
             range: EntityRange::first(100),
             logger: None,
             query_id: None,
+            trace: None,
+            cancel: None,
             _force_use_of_new: (),
         }
     }
@@ -794,6 +816,32 @@ pub enum StoreError {
     DatabaseUnavailable,
 }
 
+impl StoreError {
+    /// Return `true` if the error indicates that the database connection
+    /// was lost or the database can not be reached, for example, because a
+    /// HA setup failed over to a new primary. Operations that fail with
+    /// such an error ran in a transaction that did not commit and can
+    /// safely be retried on a fresh connection
+    pub fn is_unavailable(&self) -> bool {
+        const CONNECTION_ERRORS: [&str; 5] = [
+            "server closed the connection",
+            "connection reset by peer",
+            "broken pipe",
+            "terminating connection",
+            "the database system is in recovery mode",
+        ];
+
+        match self {
+            StoreError::DatabaseUnavailable => true,
+            StoreError::Unknown(e) => {
+                let msg = e.to_string();
+                CONNECTION_ERRORS.iter().any(|s| msg.contains(s))
+            }
+            _ => false,
+        }
+    }
+}
+
 // Convenience to report a constraint violation
 #[macro_export]
 macro_rules! constraint_violation {
@@ -848,6 +896,31 @@ pub struct StoredDynamicDataSource {
     pub creation_block: Option<BlockNumber>,
 }
 
+/// A sample of how the chain head of a network moved, taken every time the
+/// chain head is updated. Dashboards use these to plot how far and how
+/// evenly a node's view of the chain advances
+#[derive(Debug, Clone)]
+pub struct HeadLagSample {
+    /// The chain head at the time the sample was taken
+    pub head_number: BlockNumber,
+    /// How many blocks the chain head moved with this update
+    pub blocks_advanced: i64,
+    /// When the sample was taken, as an ISO timestamp
+    pub sampled_at: String,
+}
+
+///// A reorg that was detected while updating the chain head of a network:
+/// the old chain head turned out not to be an ancestor of the new one
+#[derive(Debug, Clone)]
+pub struct ReorgEvent {
+    pub from_hash: String,
+    pub from_number: BlockNumber,
+    pub to_hash: String,
+    pub to_number: BlockNumber,
+    /// When the reorg was detected, as an ISO timestamp
+    pub detected_at: String,
+}
+
 pub trait SubscriptionManager: Send + Sync + 'static {
     /// Subscribe to changes for specific subgraphs and entities.
     ///
@@ -948,6 +1021,22 @@ pub trait SubgraphStore: Send + Sync + 'static {
     /// their assignment, but keep the deployments themselves around
     fn remove_subgraph(&self, name: SubgraphName) -> Result<(), StoreError>;
 
+    /// Atomically rename the subgraph `old` to `new`. If `alias_old` is
+    /// set, keep the old name resolvable by turning it into an alias for
+    /// the new name. It is an error if `old` does not exist or if `new` is
+    /// already taken
+    fn rename_subgraph(
+        &self,
+        old: SubgraphName,
+        new: SubgraphName,
+        alias_old: bool,
+    ) -> Result<(), StoreError>;
+
+    /// Make `alias` another name for the subgraph `target`, pointing at the
+    /// versions that are current and pending for `target` right now. It is
+    /// an error if `target` does not exist or if `alias` is already taken
+    fn alias_subgraph(&self, target: SubgraphName, alias: SubgraphName) -> Result<(), StoreError>;
+
     /// Assign the subgraph with `id` to the node `node_id`. If there is no
     /// assignment for the given deployment, report an error.
     fn reassign_subgraph(
@@ -956,6 +1045,44 @@ pub trait SubgraphStore: Send + Sync + 'static {
         node_id: &NodeId,
     ) -> Result<(), StoreError>;
 
+    /// Assign each deployment to the node paired with it, in one metadata
+    /// transaction: either all assignments take effect or none do
+    fn reassign_subgraphs(
+        &self,
+        assignments: &[(DeploymentLocator, NodeId)],
+    ) -> Result<(), StoreError>;
+
+    /// Clear the fatal error of `deployment` and poke the assigned node so
+    /// that indexing resumes at the block where it failed. With a `block`
+    /// of `None`, refuses to retry a deterministic failure since that
+    /// would fail again; passing the number of the failed block forces the
+    /// retry, e.g. after upgrading to a node that fixes a mapping bug
+    fn retry_subgraph(
+        &self,
+        deployment: &DeploymentLocator,
+        block: Option<BlockNumber>,
+    ) -> Result<(), StoreError>;
+
+    /// Remove the assignment for `deployment` so that no node indexes it
+    /// anymore; the deployment stays queryable. Indexing resumes when the
+    /// deployment is assigned to a node again with `reassign_subgraph`
+    fn pause_subgraph(&self, deployment: &DeploymentLocator) -> Result<(), StoreError>;
+
+    /// Rewind the deployment `id` to `block_ptr_to`, undoing all entity
+    /// changes after that block. The deployment must not be actively
+    /// indexing while it is rewound
+    fn rewind(&self, id: DeploymentHash, block_ptr_to: BlockPtr) -> Result<(), StoreError>;
+
+    /// Persist the per-deployment override `name` for `deployment` and
+    /// apply it on this node; an empty `value` clears the override. The
+    /// recognized names are listed in `crate::settings`
+    fn set_deployment_setting(
+        &self,
+        deployment: &DeploymentHash,
+        name: &str,
+        value: &str,
+    ) -> Result<(), StoreError>;
+
     fn assigned_node(&self, deployment: &DeploymentLocator) -> Result<Option<NodeId>, StoreError>;
 
     fn assignments(&self, node: &NodeId) -> Result<Vec<DeploymentLocator>, StoreError>;
@@ -999,6 +1126,33 @@ pub trait SubgraphStore: Send + Sync + 'static {
 
     /// Find the deployment locators for the subgraph with the given hash
     fn locators(&self, hash: &str) -> Result<Vec<DeploymentLocator>, StoreError>;
+
+    /// Stream all entities of `entity_type` in `deployment` that are live
+    /// at `block` to `sink` in batches of at most `batch_size` entities,
+    /// ordered by id, and return how many entities were streamed. This is
+    /// meant for export tooling, not for the query path
+    fn export_entities(
+        &self,
+        deployment: &DeploymentHash,
+        entity_type: &EntityType,
+        block: BlockNumber,
+        batch_size: u32,
+        sink: &mut dyn FnMut(Vec<Entity>) -> Result<(), StoreError>,
+    ) -> Result<usize, StoreError>;
+
+    /// Create a local copy of the deployment `schema.id` from externally
+    /// fetched data so that it can serve as the base of a graft. The
+    /// `entities` must be the entities of the base that were live at
+    /// `deployment.latest_block` on the node they were fetched from. The
+    /// copy is not named or assigned to any node; it is never indexed and
+    /// only read when a graft copies from it
+    fn import_graft_base(
+        &self,
+        schema: &Schema,
+        deployment: SubgraphDeploymentEntity,
+        network: String,
+        entities: Vec<(EntityType, Vec<Entity>)>,
+    ) -> Result<DeploymentLocator, StoreError>;
 }
 
 /// A view of the store for indexing. All indexing-related operations need
@@ -1036,7 +1190,13 @@ pub trait WritableStore: Send + Sync + 'static {
     fn unfail_non_deterministic_error(&self, current_ptr: &BlockPtr) -> Result<(), StoreError>;
 
     /// Set subgraph status to failed with the given error as the cause.
-    async fn fail_subgraph(&self, error: SubgraphError) -> Result<(), StoreError>;
+    /// The `class` records what kind of failure this was so that operators
+    /// can tell whether a retry can succeed
+    async fn fail_subgraph(
+        &self,
+        error: SubgraphError,
+        class: ErrorClass,
+    ) -> Result<(), StoreError>;
 
     async fn supports_proof_of_indexing(&self) -> Result<bool, StoreError>;
 
@@ -1064,6 +1224,10 @@ pub trait WritableStore: Send + Sync + 'static {
         ids_for_type: BTreeMap<&EntityType, Vec<&str>>,
     ) -> Result<BTreeMap<EntityType, Vec<Entity>>, StoreError>;
 
+    /// Return the ids of up to `first` entities of the given type as of the
+    /// latest block, in ascending order of id
+    fn entity_ids(&self, entity_type: &EntityType, first: u32) -> Result<Vec<String>, StoreError>;
+
     /// The deployment `id` finished syncing, mark it as synced in the database
     /// and promote it to the current version in the subgraphs where it was the
     /// pending version so far
@@ -1143,10 +1307,47 @@ impl SubgraphStore for MockStore {
         unimplemented!()
     }
 
+    fn rename_subgraph(&self, _: SubgraphName, _: SubgraphName, _: bool) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
+    fn alias_subgraph(&self, _: SubgraphName, _: SubgraphName) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
     fn reassign_subgraph(&self, _: &DeploymentLocator, _: &NodeId) -> Result<(), StoreError> {
         unimplemented!()
     }
 
+    fn reassign_subgraphs(&self, _: &[(DeploymentLocator, NodeId)]) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
+    fn retry_subgraph(
+        &self,
+        _: &DeploymentLocator,
+        _: Option<BlockNumber>,
+    ) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
+    fn pause_subgraph(&self, _: &DeploymentLocator) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
+    fn rewind(&self, _: DeploymentHash, _: BlockPtr) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
+    fn set_deployment_setting(
+        &self,
+        _: &DeploymentHash,
+        _: &str,
+        _: &str,
+    ) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
     fn assigned_node(&self, _: &DeploymentLocator) -> Result<Option<NodeId>, StoreError> {
         unimplemented!()
     }
@@ -1194,6 +1395,27 @@ impl SubgraphStore for MockStore {
     fn locators(&self, _: &str) -> Result<Vec<DeploymentLocator>, StoreError> {
         unimplemented!()
     }
+
+    fn export_entities(
+        &self,
+        _: &DeploymentHash,
+        _: &EntityType,
+        _: BlockNumber,
+        _: u32,
+        _: &mut dyn FnMut(Vec<Entity>) -> Result<(), StoreError>,
+    ) -> Result<usize, StoreError> {
+        unimplemented!()
+    }
+
+    fn import_graft_base(
+        &self,
+        _: &Schema,
+        _: SubgraphDeploymentEntity,
+        _: String,
+        _: Vec<(EntityType, Vec<Entity>)>,
+    ) -> Result<DeploymentLocator, StoreError> {
+        unimplemented!()
+    }
 }
 
 // The store trait must be implemented manually because mockall does not support async_trait, nor borrowing from arguments.
@@ -1223,7 +1445,7 @@ impl WritableStore for MockStore {
         unimplemented!()
     }
 
-    async fn fail_subgraph(&self, _: SubgraphError) -> Result<(), StoreError> {
+    async fn fail_subgraph(&self, _: SubgraphError, _: ErrorClass) -> Result<(), StoreError> {
         unimplemented!()
     }
 
@@ -1254,6 +1476,10 @@ impl WritableStore for MockStore {
         self.get_many_mock(ids_for_type)
     }
 
+    fn entity_ids(&self, _: &EntityType, _: u32) -> Result<Vec<String>, StoreError> {
+        unimplemented!()
+    }
+
     async fn is_deployment_synced(&self) -> Result<bool, StoreError> {
         unimplemented!()
     }
@@ -1373,6 +1599,12 @@ pub trait ChainStore: Send + Sync + 'static {
     /// Find the block with `block_hash` and return the network name and number
     fn block_number(&self, block_hash: H256) -> Result<Option<(String, BlockNumber)>, StoreError>;
 
+    /// The most recent `first` head lag samples for this chain, newest first
+    fn head_lag_samples(&self, first: usize) -> Result<Vec<HeadLagSample>, Error>;
+
+    /// The most recent `first` reorg events for this chain, newest first
+    fn reorg_events(&self, first: usize) -> Result<Vec<ReorgEvent>, Error>;
+
     /// Tries to retrieve all transactions receipts for a given block.
     async fn transaction_receipts_in_block(
         &self,
@@ -1402,10 +1634,7 @@ pub trait EthereumCallCache: Send + Sync + 'static {
 /// Store operations used when serving queries for a specific deployment
 #[async_trait]
 pub trait QueryStore: Send + Sync {
-    fn find_query_values(
-        &self,
-        query: EntityQuery,
-    ) -> Result<Vec<BTreeMap<String, r::Value>>, QueryExecutionError>;
+    fn find_query_values(&self, query: EntityQuery) -> Result<Vec<r::Object>, QueryExecutionError>;
 
     async fn is_deployment_synced(&self) -> Result<bool, Error>;
 
@@ -1439,6 +1668,40 @@ pub trait StatusStore: Send + Sync + 'static {
 
     fn status(&self, filter: status::Filter) -> Result<Vec<status::Info>, StoreError>;
 
+    /// List summaries of all deployments that match `filter`, aggregated
+    /// across all shards, sorted by `order` and paginated with `first`
+    /// and `skip`. Used by the index node's `deployments` query
+    fn deployment_summaries(
+        &self,
+        filter: status::SummaryFilter,
+        order: status::SummaryOrder,
+        first: usize,
+        skip: usize,
+    ) -> Result<Vec<status::Summary>, StoreError>;
+
+    /// Report how well `deployment` kept up with its chain over the last
+    /// `days` days, counting a sample as good when the deployment was at
+    /// most `within_blocks` blocks behind the chain head. The samples
+    /// come from the SLA sampling job that runs on the index node
+    fn sla_report(
+        &self,
+        deployment: &DeploymentHash,
+        days: i32,
+        within_blocks: i32,
+    ) -> Result<status::SlaReport, StoreError>;
+
+    /// Run the restricted read-only SQL query `sql` against the entity
+    /// tables of `deployment` on a read replica and return the rows as
+    /// JSON objects. The query must be a single `select` statement that
+    /// only references the deployment's own tables and runs with a
+    /// statement timeout and a row limit. Used by the index node's
+    /// `_sql` endpoint
+    fn execute_sql(
+        &self,
+        deployment: &DeploymentHash,
+        sql: &str,
+    ) -> Result<Vec<serde_json::Value>, StoreError>;
+
     /// Support for the explorer-specific API
     fn version_info(&self, version_id: &str) -> Result<VersionInfo, StoreError>;
 
@@ -1469,6 +1732,41 @@ pub trait StatusStore: Send + Sync + 'static {
         indexer: &Option<Address>,
         block: BlockPtr,
     ) -> Result<Option<[u8; 32]>, StoreError>;
+
+    /// Look up the hash of the block with the given number on the network
+    /// that `subgraph_id` indexes. Returns `None` if the block is not in the
+    /// block cache, or if more than one hash is stored for the number because
+    /// of an unresolved reorg. Used by the index node's `poiComparison` query
+    /// to bisect the range of blocks in which two indexers diverge.
+    fn block_ptr_for_number(
+        &self,
+        subgraph_id: &DeploymentHash,
+        number: BlockNumber,
+    ) -> Result<Option<BlockPtr>, StoreError>;
+
+    /// List the dynamic data sources of the deployment `subgraph_id` in
+    /// the order in which they were created. Used by the index node's
+    /// `dynamicDataSources` query
+    async fn dynamic_data_sources(
+        &self,
+        subgraph_id: &DeploymentHash,
+    ) -> Result<Vec<StoredDynamicDataSource>, StoreError>;
+
+    /// The most recent `first` head lag samples for `network`, newest
+    /// first. Used by the index node's `chainHealth` query
+    fn chain_head_lag_samples(
+        &self,
+        network: &str,
+        first: usize,
+    ) -> Result<Vec<HeadLagSample>, StoreError>;
+
+    /// The most recent `first` reorg events for `network`, newest first.
+    /// Used by the index node's `chainHealth` query
+    fn chain_reorg_events(
+        &self,
+        network: &str,
+        first: usize,
+    ) -> Result<Vec<ReorgEvent>, StoreError>;
 }
 
 /// An entity operation that can be transacted into the store; as opposed to
@@ -1647,6 +1945,15 @@ impl EntityCache {
         self.entity_op(key, EntityOp::Remove);
     }
 
+    /// Remove all entities with the given keys in one batch. The removals
+    /// for one entity type are applied to the store as a single delete
+    /// operation when the block is transacted
+    pub fn remove_many(&mut self, keys: Vec<EntityKey>) {
+        for key in keys {
+            self.entity_op(key, EntityOp::Remove);
+        }
+    }
+
     pub fn set(&mut self, key: EntityKey, entity: Entity) {
         self.entity_op(key, EntityOp::Update(entity))
     }
@@ -1666,6 +1973,53 @@ impl EntityCache {
         }
     }
 
+    /// Return the ids of up to `first` entities of the given type, taking
+    /// both the entities in the store and the changes that have accumulated
+    /// in the cache for the current block into account. The ids are in
+    /// ascending order.
+    pub fn ids_for_type(
+        &mut self,
+        subgraph_id: &DeploymentHash,
+        entity_type: &EntityType,
+        first: u32,
+    ) -> Result<Vec<String>, StoreError> {
+        // Pending removals can knock ids out of the window fetched from
+        // the store while entities past the window are still live; fetch
+        // enough extra ids that the merged result can still reach `first`
+        // entries
+        let removals = self
+            .updates
+            .iter()
+            .chain(self.handler_updates.iter())
+            .filter(|(key, op)| {
+                &key.subgraph_id == subgraph_id
+                    && &key.entity_type == entity_type
+                    && matches!(op, EntityOp::Remove)
+            })
+            .count() as u32;
+        let mut ids: BTreeSet<String> = self
+            .store
+            .entity_ids(entity_type, first + removals)?
+            .into_iter()
+            .collect();
+        // Apply the pending changes; `handler_updates` comes after `updates`
+        // so that changes from the currently executing handler win
+        for (key, op) in self.updates.iter().chain(self.handler_updates.iter()) {
+            if &key.subgraph_id != subgraph_id || &key.entity_type != entity_type {
+                continue;
+            }
+            match op {
+                EntityOp::Remove => {
+                    ids.remove(&key.entity_id);
+                }
+                EntityOp::Update(_) | EntityOp::Overwrite(_) => {
+                    ids.insert(key.entity_id.clone());
+                }
+            }
+        }
+        Ok(ids.into_iter().take(first as usize).collect())
+    }
+
     /// Add a dynamic data source
     pub fn add_data_source<C: Blockchain>(&mut self, data_source: &impl DataSource<C>) {
         self.data_sources
@@ -1687,6 +2041,24 @@ impl EntityCache {
         }
     }
 
+    /// A copy of the changes that have accumulated in this cache, reading
+    /// through to the same store. With a write-back cache policy, a
+    /// snapshot taken before a block is processed captures the changes of
+    /// the already processed blocks so that they can still be written out
+    /// on their own if that block fails
+    pub fn snapshot(&self) -> EntityCache {
+        assert!(!self.in_handler);
+
+        EntityCache {
+            current: LfuCache::new(),
+            updates: self.updates.clone(),
+            handler_updates: HashMap::new(),
+            in_handler: false,
+            data_sources: vec![],
+            store: self.store.clone(),
+        }
+    }
+
     pub(crate) fn extend(&mut self, other: EntityCache) {
         assert!(!other.in_handler);
 
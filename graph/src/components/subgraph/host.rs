@@ -1,4 +1,5 @@
 use std::cmp::PartialEq;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Instant;
 
@@ -9,7 +10,10 @@ use futures::sync::mpsc;
 use crate::blockchain::TriggerWithHandler;
 use crate::prelude::*;
 use crate::{blockchain::Blockchain, components::subgraph::SharedProofOfIndexing};
-use crate::{components::metrics::HistogramVec, runtime::DeterministicHostError};
+use crate::{
+    components::metrics::{CounterVec, Gauge, HistogramVec},
+    runtime::DeterministicHostError,
+};
 
 #[derive(Debug)]
 pub enum MappingError {
@@ -67,6 +71,13 @@ pub trait RuntimeHost<C: Blockchain>: Send + Sync + 'static {
 pub struct HostMetrics {
     handler_execution_time: Box<HistogramVec>,
     host_fn_execution_time: Box<HistogramVec>,
+    handler_trap_count: Box<CounterVec>,
+    wasm_memory_bytes: Box<Gauge>,
+    /// Cumulative number of `ethereum.call` invocations, including ones
+    /// served from the call cache. The instance manager snapshots this
+    /// around a block to report the per-block count in the processing
+    /// summary
+    eth_call_count: AtomicU64,
     pub stopwatch: StopwatchMetrics,
 }
 
@@ -94,19 +105,64 @@ impl HostMetrics {
                 vec![0.025, 0.05, 0.2, 2.0, 8.0, 20.0],
             )
             .expect("failed to create `deployment_host_fn_execution_time` histogram");
+        let handler_trap_count = registry
+            .new_deployment_counter_vec(
+                "deployment_handler_trap_count",
+                "Counts handler executions that ended in a trap, by kind",
+                subgraph,
+                vec![String::from("handler"), String::from("kind")],
+            )
+            .expect("failed to create `deployment_handler_trap_count` counter");
+        let wasm_memory_bytes = registry
+            .new_deployment_gauge(
+                "deployment_wasm_memory_bytes",
+                "High-water mark of WASM linear memory, in bytes",
+                subgraph,
+            )
+            .expect("failed to create `deployment_wasm_memory_bytes` gauge");
         Self {
             handler_execution_time,
             host_fn_execution_time,
+            handler_trap_count,
+            wasm_memory_bytes,
+            eth_call_count: AtomicU64::new(0),
             stopwatch,
         }
     }
 
+    /// Count one `ethereum.call` invocation
+    pub fn observe_eth_call(&self) {
+        self.eth_call_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The cumulative number of `ethereum.call` invocations since the
+    /// deployment was started
+    pub fn eth_call_count(&self) -> u64 {
+        self.eth_call_count.load(Ordering::Relaxed)
+    }
+
     pub fn observe_handler_execution_time(&self, duration: f64, handler: &str) {
         self.handler_execution_time
             .with_label_values(&[handler][..])
             .observe(duration);
     }
 
+    /// Count a handler execution that ended in a trap. The `kind` is one of
+    /// `timeout`, `deterministic` or `nondeterministic`
+    pub fn observe_handler_trap(&self, handler: &str, kind: &str) {
+        self.handler_trap_count
+            .with_label_values(&[handler, kind][..])
+            .inc();
+    }
+
+    /// Track the size of the WASM linear memory; the gauge keeps the largest
+    /// size seen over the lifetime of the deployment's instances
+    pub fn track_wasm_memory(&self, size_bytes: f64) {
+        if size_bytes > self.wasm_memory_bytes.get() {
+            self.wasm_memory_bytes.set(size_bytes);
+        }
+    }
+
     pub fn observe_host_fn_execution_time(&self, duration: f64, fn_name: &str) {
         self.host_fn_execution_time
             .with_label_values(&[fn_name][..])
@@ -38,6 +38,19 @@ impl<C: Blockchain> BlockState<C> {
         }
     }
 
+    /// Like `new`, but continue with `entity_cache`, which still holds
+    /// the changes from previously processed blocks that have not been
+    /// written to the store yet under a write-back cache policy
+    pub fn with_cache(entity_cache: EntityCache) -> Self {
+        BlockState {
+            entity_cache,
+            deterministic_errors: Vec::new(),
+            created_data_sources: Vec::new(),
+            handler_created_data_sources: Vec::new(),
+            in_handler: false,
+        }
+    }
+
     pub fn extend(&mut self, other: BlockState<C>) {
         assert!(!other.in_handler);
 
@@ -33,6 +33,20 @@ pub trait SubgraphRegistrar: Send + Sync + 'static {
         assignment_node_id: NodeId,
     ) -> Result<(), SubgraphRegistrarError>;
 
+    /// Deploy the manifest `hash` under `name` as a clone of the
+    /// deployment `base`: the entity data of `base` up to `block` is
+    /// copied into the new deployment, which then indexes forward with
+    /// its own mappings. This lets developers compare the output of
+    /// changed mappings against the original from the same base state
+    async fn clone_subgraph_version(
+        &self,
+        name: SubgraphName,
+        hash: DeploymentHash,
+        assignment_node_id: NodeId,
+        base: DeploymentHash,
+        block: BlockNumber,
+    ) -> Result<(), SubgraphRegistrarError>;
+
     async fn remove_subgraph(&self, name: SubgraphName) -> Result<(), SubgraphRegistrarError>;
 
     async fn reassign_subgraph(
@@ -40,4 +54,35 @@ pub trait SubgraphRegistrar: Send + Sync + 'static {
         hash: &DeploymentHash,
         node_id: &NodeId,
     ) -> Result<(), SubgraphRegistrarError>;
+
+    /// Reassign several deployments at once. The assignments are applied in
+    /// one metadata transaction: either all of them take effect or none do
+    async fn reassign_subgraphs(
+        &self,
+        assignments: &[(DeploymentHash, NodeId)],
+    ) -> Result<(), SubgraphRegistrarError>;
+
+    /// Clear a non-deterministic failure of the deployment and resume
+    /// indexing from the failed block. Fails if the fatal error was
+    /// deterministic; use `retry_subgraph_from` for those
+    async fn retry_subgraph(&self, hash: &DeploymentHash) -> Result<(), SubgraphRegistrarError>;
+
+    /// Clear the failure of the deployment, deterministic or not, and
+    /// resume indexing from `block`, which must be the failed block
+    async fn retry_subgraph_from(
+        &self,
+        hash: &DeploymentHash,
+        block: BlockNumber,
+    ) -> Result<(), SubgraphRegistrarError>;
+
+    /// Attach the setting `name` with `value` to the deployment,
+    /// overriding a global tunable for just this deployment; an empty
+    /// `value` removes the setting again. The recognized names are
+    /// listed in `crate::settings`
+    async fn set_deployment_setting(
+        &self,
+        hash: &DeploymentHash,
+        name: &str,
+        value: &str,
+    ) -> Result<(), SubgraphRegistrarError>;
 }
@@ -0,0 +1,93 @@
+//! Indexing speed per deployment, measured over a sliding window. The
+//! instance manager records a sample for every block it processes; the
+//! index node exposes the speed and an estimated time to sync through the
+//! indexing status API so operators do not have to scrape logs for them.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
+
+use crate::components::store::BlockNumber;
+
+/// How long a sample counts towards the sliding window
+const WINDOW: Duration = Duration::from_secs(600);
+
+/// An upper bound on the samples kept per deployment, for deployments
+/// that process many blocks per second
+const MAX_SAMPLES: usize = 10_000;
+
+struct Entry {
+    /// When a block was processed and where the deployment head was
+    /// afterwards, oldest first
+    samples: VecDeque<(Instant, BlockNumber)>,
+}
+
+/// Tracks how fast each deployment that this node indexes moves its
+/// deployment head
+pub struct SyncProgressRegistry {
+    entries: Mutex<BTreeMap<String, Entry>>,
+}
+
+impl SyncProgressRegistry {
+    fn new() -> Self {
+        Self {
+            entries: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Record that `deployment` moved its head to `block`. After a
+    /// revert, the window starts over since mixing samples from before
+    /// and after the revert would make the speed meaningless
+    pub fn record(&self, deployment: &str, block: BlockNumber) {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries
+            .entry(deployment.to_owned())
+            .or_insert_with(|| Entry {
+                samples: VecDeque::new(),
+            });
+        if let Some((_, last)) = entry.samples.back() {
+            if *last > block {
+                entry.samples.clear();
+            }
+        }
+        entry.samples.push_back((Instant::now(), block));
+        let cutoff = Instant::now() - WINDOW;
+        while entry.samples.len() > MAX_SAMPLES
+            || entry
+                .samples
+                .front()
+                .map_or(false, |(time, _)| *time < cutoff)
+        {
+            entry.samples.pop_front();
+        }
+    }
+
+    /// The speed at which `deployment` moved its head over the sliding
+    /// window, in blocks per second. `None` if we have not seen enough
+    /// blocks to measure, or if the deployment stopped processing blocks
+    /// longer than the window ago
+    pub fn blocks_per_second(&self, deployment: &str) -> Option<f64> {
+        let cutoff = Instant::now() - WINDOW;
+        let entries = self.entries.lock().unwrap();
+        let samples = &entries.get(deployment)?.samples;
+        let (first_time, first_block) = samples.iter().find(|(time, _)| *time >= cutoff)?;
+        let (last_time, last_block) = samples.back()?;
+        let elapsed = last_time.duration_since(*first_time).as_secs_f64();
+        if elapsed < 1.0 || last_block <= first_block {
+            return None;
+        }
+        Some((last_block - first_block) as f64 / elapsed)
+    }
+
+    /// Forget about `deployment`, e.g. because it was unassigned from
+    /// this node
+    pub fn remove(&self, deployment: &str) {
+        self.entries.lock().unwrap().remove(deployment);
+    }
+}
+
+lazy_static! {
+    pub static ref SYNC_PROGRESS: SyncProgressRegistry = SyncProgressRegistry::new();
+}
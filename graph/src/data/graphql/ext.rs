@@ -59,6 +59,8 @@ pub trait DocumentExt {
 
     fn get_root_subscription_type(&self) -> Option<&ObjectType>;
 
+    fn get_root_mutation_type(&self) -> Option<&ObjectType>;
+
     fn object_or_interface(&self, name: &str) -> Option<ObjectOrInterface<'_>>;
 
     fn get_named_type(&self, name: &str) -> Option<&TypeDefinition>;
@@ -172,6 +174,19 @@ impl DocumentExt for Document {
             .next()
     }
 
+    fn get_root_mutation_type(&self) -> Option<&ObjectType> {
+        self.definitions
+            .iter()
+            .filter_map(|d| match d {
+                Definition::TypeDefinition(TypeDefinition::Object(t)) if t.name == "Mutation" => {
+                    Some(t)
+                }
+                _ => None,
+            })
+            .peekable()
+            .next()
+    }
+
     fn object_or_interface(&self, name: &str) -> Option<ObjectOrInterface<'_>> {
         match self.get_named_type(name) {
             Some(TypeDefinition::Object(t)) => Some(t.into()),
@@ -1,15 +1,12 @@
 use crate::prelude::q;
 use crate::prelude::r;
-use std::collections::BTreeMap;
 use std::iter::FromIterator;
 
 /// Creates a `graphql_parser::query::Value::Object` from key/value pairs.
 /// If you don't need to determine which keys are included dynamically at runtime
 /// consider using the `object! {}` macro instead.
 pub fn object_value(data: Vec<(&str, r::Value)>) -> r::Value {
-    r::Value::Object(BTreeMap::from_iter(
-        data.into_iter().map(|(k, v)| (k.to_string(), v)),
-    ))
+    r::Value::Object(r::Object::from_iter(data))
 }
 
 pub trait IntoValue {
@@ -88,10 +85,10 @@ impl_into_values![(String, String), (f64, Float), (bool, Boolean)];
 macro_rules! object {
     ($($name:ident: $value:expr,)*) => {
         {
-            let mut result = ::std::collections::BTreeMap::new();
+            let mut result = $crate::prelude::r::Object::new();
             $(
                 let value = $crate::data::graphql::object_macro::IntoValue::into_value($value);
-                result.insert(stringify!($name).to_string(), value);
+                result.insert(stringify!($name), value);
             )*
             $crate::prelude::r::Value::Object(result)
         }
@@ -166,6 +166,27 @@ impl ValueMap for r::Value {
     }
 }
 
+impl ValueMap for &r::Object {
+    fn get_required<T>(&self, key: &str) -> Result<T, Error>
+    where
+        T: TryFromValue,
+    {
+        self.get(key)
+            .ok_or_else(|| anyhow!("Required field `{}` not set", key))
+            .and_then(|value| T::try_from_value(value).map_err(|e| e.into()))
+    }
+
+    fn get_optional<T>(&self, key: &str) -> Result<Option<T>, Error>
+    where
+        T: TryFromValue,
+    {
+        self.get(key).map_or(Ok(None), |value| match value {
+            r::Value::Null => Ok(None),
+            _ => T::try_from_value(value).map(Some).map_err(Into::into),
+        })
+    }
+}
+
 impl ValueMap for &BTreeMap<String, r::Value> {
     fn get_required<T>(&self, key: &str) -> Result<T, Error>
     where
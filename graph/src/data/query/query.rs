@@ -7,7 +7,7 @@ use std::sync::Arc;
 
 use crate::{
     data::graphql::shape_hash::shape_hash,
-    prelude::{q, r, DeploymentHash, SubgraphName},
+    prelude::{q, r, CancelHandle, DeploymentHash, SubgraphName},
 };
 
 fn deserialize_number<'de, D>(deserializer: D) -> Result<q::Number, D::Error>
@@ -136,11 +136,18 @@ pub struct Query {
     pub shape_hash: u64,
     pub query_text: Arc<String>,
     pub variables_text: Arc<String>,
+    /// Collect the SQL statements the query causes and return them in the
+    /// `extensions` of the response; see `Trace`
+    pub trace: bool,
+    /// When set, the handle becomes canceled if the client that submitted
+    /// the query goes away before the query finishes, and the store stops
+    /// any SQL work it is still doing for the query
+    pub cancel: Option<CancelHandle>,
     _force_use_of_new: (),
 }
 
 impl Query {
-    pub fn new(document: q::Document, variables: Option<QueryVariables>) -> Self {
+    pub fn new(document: q::Document, variables: Option<QueryVariables>, trace: bool) -> Self {
         let shape_hash = shape_hash(&document);
 
         let (query_text, variables_text) = if *crate::log::LOG_GQL_TIMING {
@@ -160,6 +167,8 @@ impl Query {
             shape_hash,
             query_text: Arc::new(query_text),
             variables_text: Arc::new(variables_text),
+            trace,
+            cancel: None,
             _force_use_of_new: (),
         }
     }
@@ -1,5 +1,6 @@
 use super::error::{QueryError, QueryExecutionError};
-use crate::prelude::{r, CacheWeight, DeploymentHash};
+use super::trace::Trace;
+use crate::prelude::{r, BlockPtr, CacheWeight, DeploymentHash};
 use http::header::{
     ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
     CONTENT_TYPE,
@@ -17,8 +18,8 @@ where
     let mut ser = serializer.serialize_map(None)?;
 
     // Unwrap: data is only serialized if it is `Some`.
-    for (k, v) in data.as_ref().unwrap() {
-        ser.serialize_entry(k, v)?;
+    for (k, v) in data.as_ref().unwrap().iter() {
+        ser.serialize_entry(k.as_ref(), v)?;
     }
     ser.end()
 }
@@ -32,31 +33,51 @@ where
 {
     let mut ser = serializer.serialize_map(None)?;
     for map in data {
-        for (k, v) in map {
-            ser.serialize_entry(k, v)?;
+        for (k, v) in map.iter() {
+            ser.serialize_entry(k.as_ref(), v)?;
         }
     }
     ser.end()
 }
 
-pub type Data = BTreeMap<String, r::Value>;
+pub type Data = r::Object;
 
 #[derive(Debug)]
 /// A collection of query results that is serialized as a single result.
 pub struct QueryResults {
     results: Vec<Arc<QueryResult>>,
+    /// When tracing is enabled for the query, the SQL statements that were
+    /// run on its behalf. The trace is serialized into the `extensions` of
+    /// the response and deliberately lives outside the individual results
+    /// so that cached results are never polluted with trace data
+    trace: Option<Arc<Trace>>,
 }
 
 impl QueryResults {
     pub fn empty() -> Self {
         QueryResults {
             results: Vec::new(),
+            trace: None,
         }
     }
 
+    pub fn set_trace(&mut self, trace: Option<Arc<Trace>>) {
+        self.trace = trace;
+    }
+
     pub fn first(&self) -> Option<&Arc<QueryResult>> {
         self.results.first()
     }
+
+    /// The oldest block pointer any of the results was derived at. Since a
+    /// query can address several block constraints, this is the most
+    /// conservative pointer to base caching decisions on
+    pub fn block_ptr(&self) -> Option<&BlockPtr> {
+        self.results
+            .iter()
+            .filter_map(|res| res.block_ptr.as_ref())
+            .min_by_key(|ptr| ptr.number)
+    }
 }
 
 impl Serialize for QueryResults {
@@ -70,6 +91,9 @@ impl Serialize for QueryResults {
         if has_errors {
             len += 1;
         }
+        if self.trace.is_some() {
+            len += 1;
+        }
 
         let mut state = serializer.serialize_struct("QueryResults", len)?;
 
@@ -106,6 +130,11 @@ impl Serialize for QueryResults {
             state.serialize_field("errors", &SerError(self))?;
         }
 
+        // Serialize the trace as extensions.
+        if let Some(trace) = &self.trace {
+            state.serialize_field("extensions", &trace.to_value())?;
+        }
+
         state.end()
     }
 }
@@ -114,21 +143,32 @@ impl From<Data> for QueryResults {
     fn from(x: Data) -> Self {
         QueryResults {
             results: vec![Arc::new(x.into())],
+            trace: None,
         }
     }
 }
 
+impl From<BTreeMap<String, r::Value>> for QueryResults {
+    fn from(x: BTreeMap<String, r::Value>) -> Self {
+        QueryResults::from(Data::from(x))
+    }
+}
+
 impl From<QueryResult> for QueryResults {
     fn from(x: QueryResult) -> Self {
         QueryResults {
             results: vec![Arc::new(x)],
+            trace: None,
         }
     }
 }
 
 impl From<Arc<QueryResult>> for QueryResults {
     fn from(x: Arc<QueryResult>) -> Self {
-        QueryResults { results: vec![x] }
+        QueryResults {
+            results: vec![x],
+            trace: None,
+        }
     }
 }
 
@@ -136,6 +176,7 @@ impl From<QueryExecutionError> for QueryResults {
     fn from(x: QueryExecutionError) -> Self {
         QueryResults {
             results: vec![Arc::new(x.into())],
+            trace: None,
         }
     }
 }
@@ -144,6 +185,7 @@ impl From<Vec<QueryExecutionError>> for QueryResults {
     fn from(x: Vec<QueryExecutionError>) -> Self {
         QueryResults {
             results: vec![Arc::new(x.into())],
+            trace: None,
         }
     }
 }
@@ -153,6 +195,16 @@ impl QueryResults {
         self.results.push(other);
     }
 
+    pub fn is_attestable(&self) -> bool {
+        self.results.iter().all(|r| r.is_attestable())
+    }
+
+    /// True if any of the results produced data. A response with errors
+    /// but no data means the request itself failed before execution
+    pub fn has_data(&self) -> bool {
+        self.results.iter().any(|r| r.has_data())
+    }
+
     pub fn as_http_response<T: From<String>>(&self) -> http::Response<T> {
         let status_code = http::StatusCode::OK;
         let json =
@@ -163,10 +215,7 @@ impl QueryResults {
             .header(ACCESS_CONTROL_ALLOW_HEADERS, "Content-Type, User-Agent")
             .header(ACCESS_CONTROL_ALLOW_METHODS, "GET, OPTIONS, POST")
             .header(CONTENT_TYPE, "application/json")
-            .header(
-                "Graph-Attestable",
-                self.results.iter().all(|r| r.is_attestable()).to_string(),
-            )
+            .header("Graph-Attestable", self.is_attestable().to_string())
             .body(T::from(json))
             .unwrap()
     }
@@ -184,6 +233,9 @@ pub struct QueryResult {
     errors: Vec<QueryError>,
     #[serde(skip_serializing)]
     pub deployment: Option<DeploymentHash>,
+    /// The block at which the query was executed; used for cache headers
+    #[serde(skip_serializing)]
+    pub block_ptr: Option<BlockPtr>,
 }
 
 impl QueryResult {
@@ -192,6 +244,7 @@ impl QueryResult {
             data: Some(data),
             errors: Vec::new(),
             deployment: None,
+            block_ptr: None,
         }
     }
 
@@ -204,6 +257,7 @@ impl QueryResult {
             data: self.data.clone(),
             errors: self.errors.clone(),
             deployment: self.deployment.clone(),
+            block_ptr: self.block_ptr.clone(),
         }
     }
 
@@ -235,6 +289,10 @@ impl QueryResult {
         self.data = data
     }
 
+    pub fn errors(&self) -> &[QueryError] {
+        &self.errors
+    }
+
     pub fn errors_mut(&mut self) -> &mut Vec<QueryError> {
         &mut self.errors
     }
@@ -246,6 +304,7 @@ impl From<QueryExecutionError> for QueryResult {
             data: None,
             errors: vec![e.into()],
             deployment: None,
+            block_ptr: None,
         }
     }
 }
@@ -256,6 +315,7 @@ impl From<QueryError> for QueryResult {
             data: None,
             errors: vec![e],
             deployment: None,
+            block_ptr: None,
         }
     }
 }
@@ -266,6 +326,7 @@ impl From<Vec<QueryExecutionError>> for QueryResult {
             data: None,
             errors: e.into_iter().map(QueryError::from).collect(),
             deployment: None,
+            block_ptr: None,
         }
     }
 }
@@ -276,6 +337,12 @@ impl From<Data> for QueryResult {
     }
 }
 
+impl From<BTreeMap<String, r::Value>> for QueryResult {
+    fn from(val: BTreeMap<String, r::Value>) -> Self {
+        QueryResult::new(Data::from(val))
+    }
+}
+
 impl TryFrom<r::Value> for QueryResult {
     type Error = &'static str;
 
@@ -0,0 +1,58 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::prelude::r;
+
+/// One SQL statement that was run on behalf of a GraphQL query, together
+/// with how long it took and how many entities it produced
+#[derive(Debug)]
+pub struct SqlQueryTrace {
+    pub query: String,
+    pub elapsed: Duration,
+    pub entity_count: usize,
+}
+
+/// A collector for the SQL statements that a GraphQL query causes. The
+/// GraphQL executor shares it with the store through the `EntityQuery`, and
+/// turns it into the `extensions` of the response when execution finishes.
+/// Collecting is only enabled when the client asks for it with the
+/// `X-GraphTrace: sql` header, and the statements appear in the order in
+/// which they were executed
+#[derive(Debug, Default)]
+pub struct Trace {
+    queries: Mutex<Vec<SqlQueryTrace>>,
+}
+
+impl Trace {
+    pub fn push(&self, query: String, elapsed: Duration, entity_count: usize) {
+        self.queries.lock().unwrap().push(SqlQueryTrace {
+            query,
+            elapsed,
+            entity_count,
+        });
+    }
+
+    /// Render the trace in the form in which it is sent to clients as part
+    /// of the `extensions` of the response
+    pub fn to_value(&self) -> r::Value {
+        let queries = self
+            .queries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|query| {
+                let mut obj = r::Object::new();
+                obj.insert("query", r::Value::String(query.query.clone()));
+                obj.insert(
+                    "elapsed_ms",
+                    r::Value::Int(query.elapsed.as_millis() as i64),
+                );
+                obj.insert("entity_count", r::Value::Int(query.entity_count as i64));
+                r::Value::Object(obj)
+            })
+            .collect();
+        let mut obj = r::Object::new();
+        obj.insert("trace", r::Value::List(queries));
+        r::Value::Object(obj)
+    }
+}
@@ -1,5 +1,7 @@
 use crate::components::store::{EntityType, SubgraphStore};
-use crate::data::graphql::ext::{DirectiveExt, DirectiveFinder, DocumentExt, TypeExt, ValueExt};
+use crate::data::graphql::ext::{
+    DirectiveExt, DirectiveFinder, DocumentExt, ObjectTypeExt, TypeExt, ValueExt,
+};
 use crate::data::store::ValueType;
 use crate::data::subgraph::{DeploymentHash, SubgraphName};
 use crate::prelude::{
@@ -53,6 +55,8 @@ pub enum SchemaValidationError {
     InterfaceFieldsMissing(String, String, Strings), // (type, interface, missing_fields)
     #[error("Field `{1}` in type `{0}` has invalid @derivedFrom: {2}")]
     InvalidDerivedFrom(String, String, String), // (type, field, reason)
+    #[error("Type `{0}` has invalid @index: {1}")]
+    InvalidIndexDirective(String, String), // (type, reason)
     #[error("The following type names are reserved: `{0}`")]
     UsageOfReservedTypes(Strings),
     #[error("_Schema_ type is only for @imports and must not have any fields")]
@@ -352,6 +356,7 @@ pub struct ApiSchema {
     // Root types for the api schema.
     pub query_type: Arc<ObjectType>,
     pub subscription_type: Option<Arc<ObjectType>>,
+    pub mutation_type: Option<Arc<ObjectType>>,
 }
 
 impl ApiSchema {
@@ -367,11 +372,17 @@ impl ApiSchema {
             .get_root_subscription_type()
             .cloned()
             .map(Arc::new);
+        let mutation_type = api_schema
+            .document
+            .get_root_mutation_type()
+            .cloned()
+            .map(Arc::new);
 
         Ok(Self {
             schema: api_schema,
             query_type: Arc::new(query_type),
             subscription_type,
+            mutation_type,
         })
     }
 
@@ -660,6 +671,7 @@ impl Schema {
         errors.append(&mut self.validate_fields());
         errors.append(&mut self.validate_import_directives());
         errors.append(&mut self.validate_fulltext_directives());
+        errors.append(&mut self.validate_index_directives());
         errors.append(&mut self.validate_imported_types(schemas));
 
         if errors.is_empty() {
@@ -1146,6 +1158,84 @@ impl Schema {
         }
     }
 
+    /// Validate the `@index(fields: [...], unique: Boolean)` directives
+    /// through which entity types request explicit composite or unique
+    /// indexes. The indexed fields must exist on the type and must be
+    /// stored in a table column, i.e., they must not be derived
+    fn validate_index_directives(&self) -> Vec<SchemaValidationError> {
+        fn invalid(object_type: &ObjectType, reason: &str) -> SchemaValidationError {
+            SchemaValidationError::InvalidIndexDirective(
+                object_type.name.to_owned(),
+                reason.to_owned(),
+            )
+        }
+
+        fn validate_index(
+            object_type: &ObjectType,
+            index: &Directive,
+        ) -> Option<SchemaValidationError> {
+            match index.argument("unique") {
+                None | Some(Value::Boolean(_)) => {}
+                Some(_) => {
+                    return Some(invalid(
+                        object_type,
+                        "the `unique` argument must be a Boolean",
+                    ))
+                }
+            }
+
+            let fields = match index.argument("fields") {
+                Some(Value::List(fields)) if !fields.is_empty() => fields,
+                _ => {
+                    return Some(invalid(
+                        object_type,
+                        "the `fields` argument must be a non-empty list",
+                    ))
+                }
+            };
+
+            for field_name in fields {
+                let field_name = match field_name {
+                    Value::String(name) => name,
+                    _ => {
+                        return Some(invalid(
+                            object_type,
+                            "the `fields` argument must be a list of field names",
+                        ))
+                    }
+                };
+                let field = match object_type.field(field_name) {
+                    Some(field) => field,
+                    None => {
+                        let msg = format!("field `{}` does not exist", field_name);
+                        return Some(invalid(object_type, &msg));
+                    }
+                };
+                if field.is_derived() {
+                    let msg = format!(
+                        "field `{}` is derived and is not stored in a table column",
+                        field_name
+                    );
+                    return Some(invalid(object_type, &msg));
+                }
+            }
+            None
+        }
+
+        self.document
+            .get_object_type_definitions()
+            .into_iter()
+            .filter(|object_type| !object_type.name.eq(SCHEMA_TYPE_NAME))
+            .flat_map(|object_type| {
+                object_type
+                    .directives
+                    .iter()
+                    .filter(|directive| directive.name.eq("index"))
+                    .filter_map(move |index| validate_index(object_type, index))
+            })
+            .collect()
+    }
+
     fn validate_derived_from(&self) -> Result<(), SchemaValidationError> {
         // Helper to construct a DerivedFromInvalid
         fn invalid(
@@ -1517,6 +1607,65 @@ type A @entity {
     assert_eq!(schema.validate_fields().len(), 0);
 }
 
+#[test]
+fn test_index_directive_validation() {
+    fn validate(schema: &str) -> Vec<SchemaValidationError> {
+        let document = graphql_parser::parse_schema(schema).expect("Failed to parse schema");
+        let schema = Schema::new(DeploymentHash::new("id").unwrap(), document);
+        schema.validate_index_directives()
+    }
+
+    const VALID: &str = r#"
+type Token @entity @index(fields: ["owner", "symbol"]) @index(fields: ["symbol"], unique: true) {
+  id: ID!
+  owner: String!
+  symbol: String!
+  holders: [Holder!]! @derivedFrom(field: "token")
+}
+
+type Holder @entity {
+  id: ID!
+  token: Token!
+}"#;
+    assert_eq!(validate(VALID).len(), 0);
+
+    let invalid = |reason: &str| {
+        vec![SchemaValidationError::InvalidIndexDirective(
+            "Token".to_owned(),
+            reason.to_owned(),
+        )]
+    };
+
+    const NO_FIELDS: &str = r#"
+type Token @entity @index(unique: true) { id: ID! }"#;
+    assert_eq!(
+        validate(NO_FIELDS),
+        invalid("the `fields` argument must be a non-empty list")
+    );
+
+    const UNKNOWN_FIELD: &str = r#"
+type Token @entity @index(fields: ["symbol"]) { id: ID! }"#;
+    assert_eq!(
+        validate(UNKNOWN_FIELD),
+        invalid("field `symbol` does not exist")
+    );
+
+    const DERIVED_FIELD: &str = r#"
+type Token @entity @index(fields: ["holders"]) {
+  id: ID!
+  holders: [Holder!]! @derivedFrom(field: "token")
+}
+
+type Holder @entity {
+  id: ID!
+  token: Token!
+}"#;
+    assert_eq!(
+        validate(DERIVED_FIELD),
+        invalid("field `holders` is derived and is not stored in a table column")
+    );
+}
+
 #[test]
 fn test_recursively_imported_type_validates() {
     const ROOT_SCHEMA: &str = r#"
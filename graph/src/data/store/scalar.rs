@@ -14,7 +14,7 @@ use stable_hash::{
 use std::convert::{TryFrom, TryInto};
 use std::fmt::{self, Display, Formatter};
 use std::io::Write;
-use std::ops::{Add, BitAnd, BitOr, Deref, Div, Mul, Rem, Shl, Shr, Sub};
+use std::ops::{Add, BitAnd, BitOr, BitXor, Deref, Div, Mul, Not, Rem, Shl, Shr, Sub};
 use std::str::FromStr;
 
 pub use num_bigint::Sign as BigIntSign;
@@ -448,6 +448,10 @@ impl Rem for BigInt {
     }
 }
 
+// For the bitwise operations, negative numbers behave as if they had an
+// infinite two's complement representation, e.g., `-1` acts as a number
+// with all bits set. That matches what the EVM does for values that fit
+// into 256 bits
 impl BitOr for BigInt {
     type Output = Self;
 
@@ -464,6 +468,23 @@ impl BitAnd for BigInt {
     }
 }
 
+impl BitXor for BigInt {
+    type Output = Self;
+
+    fn bitxor(self, other: Self) -> Self {
+        Self::from(self.0.bitxor(other.0))
+    }
+}
+
+impl Not for BigInt {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        // In two's complement, `!x == -x - 1`
+        Self::from(-(self.0 + 1))
+    }
+}
+
 impl Shl<u8> for BigInt {
     type Output = Self;
 
@@ -476,6 +497,7 @@ impl Shr<u8> for BigInt {
     type Output = Self;
 
     fn shr(self, bits: u8) -> Self {
+        // Arithmetic shift: negative numbers round towards negative infinity
         Self::from(self.0.shr(bits.into()))
     }
 }
@@ -584,6 +606,25 @@ mod test {
         assert_eq!(left, right);
     }
 
+    #[test]
+    fn bigint_twos_complement_bit_ops() {
+        let big = |n: i64| BigInt::from(n);
+
+        // Negative operands behave as if they had an infinite two's
+        // complement representation
+        assert_eq!(big(-1) & big(0xff), big(0xff));
+        assert_eq!(big(-2) | big(1), big(-1));
+        assert_eq!(big(-1) ^ big(0xff), big(-256));
+        assert_eq!(!big(0), big(-1));
+        assert_eq!(!big(-1), big(0));
+        assert_eq!(!big(5), big(-6));
+
+        // Right shifts of negative numbers are arithmetic, i.e., they
+        // round towards negative infinity
+        assert_eq!(big(-5) >> 1, big(-3));
+        assert_eq!(big(5) << 2, big(20));
+    }
+
     #[test]
     fn big_int_stable_hash_same_as_int() {
         same_stable_hash(0, BigInt::from(0u64));
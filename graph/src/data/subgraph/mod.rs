@@ -50,6 +50,16 @@ pub const SPEC_VERSION_0_0_3: Version = Version::new(0, 0, 3);
 /// This version supports subgraph feature management.
 pub const SPEC_VERSION_0_0_4: Version = Version::new(0, 0, 4);
 
+/// This version supports setting a context on data sources and templates.
+pub const SPEC_VERSION_0_0_5: Version = Version::new(0, 0, 5);
+
+/// This version supports `predicates` on event handlers. Older versions
+/// of graph-node ignore the key and process every event, so manifests
+/// that use predicates must declare a spec version that old nodes reject
+/// outright; otherwise the same manifest would produce diverging PoIs
+/// across indexer versions.
+pub const SPEC_VERSION_0_0_6: Version = Version::new(0, 0, 6);
+
 pub const MIN_SPEC_VERSION: Version = Version::new(0, 0, 2);
 
 lazy_static! {
@@ -57,14 +67,38 @@ lazy_static! {
         .ok()
         .map(|s| s.eq_ignore_ascii_case("true"))
         .unwrap_or(false);
+    /// Host functions that mappings may not call, as a comma-separated
+    /// list of import names like `ipfs.cat` or `ethereum.call`. Deploying
+    /// a subgraph whose mappings call any of them fails validation, and
+    /// already deployed subgraphs fail deterministically when they call
+    /// one
+    pub static ref DISABLED_HOST_FNS: Vec<String> = std::env::var("GRAPH_DISABLED_HOST_FNS")
+        .ok()
+        .map(|s| {
+            s.split(',')
+                .map(|name| name.trim().to_owned())
+                .filter(|name| !name.is_empty())
+                .collect()
+        })
+        .unwrap_or_else(Vec::new);
     pub static ref MAX_SPEC_VERSION: Version = std::env::var("GRAPH_MAX_SPEC_VERSION")
         .ok()
         .and_then(|api_version_str| Version::parse(&api_version_str).ok())
-        .unwrap_or(SPEC_VERSION_0_0_4);
+        .unwrap_or(SPEC_VERSION_0_0_6);
+    /// All manifest spec versions that this version of graph-node knows
+    /// about, in ascending order. Which of these a node actually accepts
+    /// is additionally bounded by `MAX_SPEC_VERSION`
+    pub static ref KNOWN_SPEC_VERSIONS: Vec<Version> = vec![
+        MIN_SPEC_VERSION,
+        SPEC_VERSION_0_0_3,
+        SPEC_VERSION_0_0_4,
+        SPEC_VERSION_0_0_5,
+        SPEC_VERSION_0_0_6,
+    ];
     static ref MAX_API_VERSION: semver::Version = std::env::var("GRAPH_MAX_API_VERSION")
         .ok()
         .and_then(|api_version_str| semver::Version::parse(&api_version_str).ok())
-        .unwrap_or(semver::Version::new(0, 0, 6));
+        .unwrap_or(semver::Version::new(0, 0, 7));
 }
 
 /// Rust representation of the GraphQL schema for a `SubgraphManifest`.
@@ -182,29 +216,43 @@ impl TryFromValue for DeploymentHash {
     }
 }
 
+lazy_static! {
+    /// Overrides the default character rules for subgraph names. The regex
+    /// is matched against each `/`-separated component of the name; the
+    /// length limit and the reserved `graphql` component still apply so
+    /// that names stay usable in URLs and the database
+    static ref SUBGRAPH_NAME_PATTERN: Option<regex::Regex> =
+        std::env::var("GRAPH_SUBGRAPH_NAME_PATTERN").ok().map(|s| {
+            regex::Regex::new(&format!("\\A(?:{})\\z", s))
+                .expect("GRAPH_SUBGRAPH_NAME_PATTERN must be a valid regular expression")
+        });
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct SubgraphName(String);
 
 impl SubgraphName {
     pub fn new(s: impl Into<String>) -> Result<Self, ()> {
-        let s = s.into();
+        Self::parse(s, SUBGRAPH_NAME_PATTERN.as_ref())
+    }
 
-        // Note: these validation rules must be kept consistent with the validation rules
-        // implemented in any other components that rely on subgraph names.
+    /// Validate `s` as a subgraph name, using `pattern` in place of the
+    /// default character rules when it is set
+    ///
+    /// Note: these validation rules must be kept consistent with the
+    /// validation rules implemented in any other components that rely on
+    /// subgraph names. The registrar, HTTP routing and store lookups all go
+    /// through this function, so a name that was accepted once will be
+    /// understood everywhere
+    fn parse(s: impl Into<String>, pattern: Option<&regex::Regex>) -> Result<Self, ()> {
+        let s = s.into();
 
-        // Enforce length limits
+        // Enforce length limits; the limit is in bytes to match the
+        // database column, which matters for multibyte names
         if s.is_empty() || s.len() > 255 {
             return Err(());
         }
 
-        // Check that the name contains only allowed characters.
-        if !s
-            .chars()
-            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '/')
-        {
-            return Err(());
-        }
-
         // Parse into components and validate each
         for part in s.split('/') {
             // Each part must be non-empty
@@ -217,14 +265,31 @@ impl SubgraphName {
                 return Err(());
             }
 
-            // Part should not start or end with a special character.
-            let first_char = part.chars().next().unwrap();
-            let last_char = part.chars().last().unwrap();
-            if !first_char.is_ascii_alphanumeric()
-                || !last_char.is_ascii_alphanumeric()
-                || !part.chars().any(|c| c.is_ascii_alphabetic())
-            {
-                return Err(());
+            match pattern {
+                Some(pattern) => {
+                    if !pattern.is_match(part) {
+                        return Err(());
+                    }
+                }
+                None => {
+                    // Check that the part contains only allowed characters.
+                    if !part
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+                    {
+                        return Err(());
+                    }
+
+                    // Part should not start or end with a special character.
+                    let first_char = part.chars().next().unwrap();
+                    let last_char = part.chars().last().unwrap();
+                    if !first_char.is_ascii_alphanumeric()
+                        || !last_char.is_ascii_alphanumeric()
+                        || !part.chars().any(|c| c.is_ascii_alphabetic())
+                    {
+                        return Err(());
+                    }
+                }
             }
         }
 
@@ -263,7 +328,7 @@ impl<'de> de::Deserialize<'de> for SubgraphName {
 }
 
 /// Result of a creating a subgraph in the registar.
-#[derive(Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CreateSubgraphResult {
     /// The ID of the subgraph that was created.
     pub id: String,
@@ -291,6 +356,8 @@ pub enum SubgraphRegistrarError {
     ManifestValidationError(Vec<SubgraphManifestValidationError>),
     #[error("subgraph deployment error: {0}")]
     SubgraphDeploymentError(StoreError),
+    #[error("invalid deployment setting: {0}")]
+    InvalidSetting(Error),
     #[error("subgraph registrar error: {0}")]
     Unknown(anyhow::Error),
 }
@@ -377,6 +444,8 @@ pub enum SubgraphManifestValidationError {
     FeatureValidationError(#[from] SubgraphFeatureValidationError),
     #[error("data source {0} is invalid: {1}")]
     DataSourceValidation(String, Error),
+    #[error("the mappings call the host function `{0}`, which is disabled on this node")]
+    HostFnDisabled(String),
 }
 
 #[derive(Error, Debug)]
@@ -625,7 +694,7 @@ impl<C: Blockchain> UnvalidatedSubgraphManifest<C> {
         }
 
         for ds in &self.0.data_sources {
-            errors.extend(ds.validate().into_iter().map(|e| {
+            errors.extend(ds.validate(&self.0.spec_version).into_iter().map(|e| {
                 SubgraphManifestValidationError::DataSourceValidation(ds.name().to_owned(), e)
             }));
         }
@@ -666,6 +735,12 @@ impl<C: Blockchain> UnvalidatedSubgraphManifest<C> {
                     "Grafting of subgraphs is currently disabled".to_owned(),
                 ));
             }
+            if self.0.spec_version < SPEC_VERSION_0_0_4 {
+                errors.push(SubgraphManifestValidationError::GraftBaseInvalid(format!(
+                    "Grafting requires a specVersion of at least {}, but the manifest declares {}",
+                    SPEC_VERSION_0_0_4, self.0.spec_version
+                )));
+            }
             if validate_graft_base {
                 errors.extend(graft.validate(store));
             }
@@ -678,6 +753,23 @@ impl<C: Blockchain> UnvalidatedSubgraphManifest<C> {
             }
         }
 
+        // Reject manifests whose mappings call a host function that the
+        // operator has disabled through `GRAPH_DISABLED_HOST_FNS`, unless
+        // the deployment was marked as trusted with the `trusted` setting
+        if !crate::settings::for_deployment(&self.0.id).trusted() {
+            for host_fn in DISABLED_HOST_FNS.iter() {
+                if self
+                    .0
+                    .runtimes()
+                    .any(|runtime| calls_host_fn(runtime, host_fn).unwrap_or(false))
+                {
+                    errors.push(SubgraphManifestValidationError::HostFnDisabled(
+                        host_fn.clone(),
+                    ));
+                }
+            }
+        }
+
         match errors.is_empty() {
             true => Ok(self.0),
             false => Err(errors),
@@ -891,6 +983,16 @@ fn test_subgraph_name_validation() {
     assert!(SubgraphName::new("a/graphql").is_err());
     assert!(SubgraphName::new("graphql/a").is_err());
     assert!(SubgraphName::new("this-component-is-very-long-but-we-dont-care").is_ok());
+
+    // A custom pattern replaces the character rules but not the structural
+    // ones
+    let pattern = regex::Regex::new("\\A(?:[\\w.]+)\\z").unwrap();
+    assert!(SubgraphName::parse("org/name.v2", Some(&pattern)).is_ok());
+    assert!(SubgraphName::parse("höhle/name", Some(&pattern)).is_ok());
+    assert!(SubgraphName::parse("org/na me", Some(&pattern)).is_err());
+    assert!(SubgraphName::parse("org//name", Some(&pattern)).is_err());
+    assert!(SubgraphName::parse("org/graphql", Some(&pattern)).is_err());
+    assert!(SubgraphName::parse("", Some(&pattern)).is_err());
 }
 
 #[test]
@@ -99,6 +99,55 @@ impl TryFromValue for SubgraphHealth {
     }
 }
 
+/// A coarse classification of what caused a fatal `SubgraphError`. It is
+/// recorded with the error and tells operators whether retrying the
+/// deployment without further intervention has a chance of succeeding
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ErrorClass {
+    /// The mappings failed deterministically; retrying will fail again
+    Deterministic,
+
+    /// A chain provider request failed, e.g. an `eth_call` timed out
+    Provider,
+
+    /// Writing to the store failed, e.g. the database was unavailable
+    Store,
+
+    /// We could not tell what caused the error. If in doubt, this is used
+    Unknown,
+}
+
+impl ErrorClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorClass::Deterministic => "deterministic",
+            ErrorClass::Provider => "provider",
+            ErrorClass::Store => "store",
+            ErrorClass::Unknown => "unknown",
+        }
+    }
+}
+
+impl Display for ErrorClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for ErrorClass {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<ErrorClass, Error> {
+        match s {
+            "deterministic" => Ok(ErrorClass::Deterministic),
+            "provider" => Ok(ErrorClass::Provider),
+            "store" => Ok(ErrorClass::Store),
+            "unknown" => Ok(ErrorClass::Unknown),
+            _ => Err(anyhow!("failed to parse `{}` as ErrorClass", s)),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct SubgraphDeploymentEntity {
     pub manifest: SubgraphManifestEntity,
@@ -1,7 +1,12 @@
 //! Support for the indexing status API
 
+use std::str::FromStr;
+
+use anyhow::{anyhow, Error};
+
 use super::schema::{SubgraphError, SubgraphHealth};
 use crate::components::store::DeploymentId;
+use crate::components::sync_progress::SYNC_PROGRESS;
 use crate::data::graphql::{object, IntoValue};
 use crate::prelude::{r, web3::types::H256, BlockPtr, Value};
 
@@ -17,6 +22,166 @@ pub enum Filter {
     DeploymentIds(Vec<DeploymentId>),
 }
 
+/// Filter for the index node's `deployments` query. The individual
+/// criteria are combined with 'and'; a criterion that is `None` matches
+/// everything
+#[derive(Default)]
+pub struct SummaryFilter {
+    pub network: Option<String>,
+    pub shard: Option<String>,
+    pub synced: Option<bool>,
+    pub health: Option<SubgraphHealth>,
+}
+
+/// Sort order for the index node's `deployments` query. Sizes, entity
+/// counts and block numbers sort from biggest to smallest, deployment
+/// hashes alphabetically
+#[derive(Copy, Clone, Debug)]
+pub enum SummaryOrder {
+    SizeBytes,
+    EntityCount,
+    LatestBlock,
+    Deployment,
+}
+
+impl FromStr for SummaryOrder {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<SummaryOrder, Error> {
+        match s {
+            "sizeBytes" => Ok(SummaryOrder::SizeBytes),
+            "entityCount" => Ok(SummaryOrder::EntityCount),
+            "latestBlock" => Ok(SummaryOrder::LatestBlock),
+            "deployment" => Ok(SummaryOrder::Deployment),
+            _ => Err(anyhow!("failed to parse `{}` as SummaryOrder", s)),
+        }
+    }
+}
+
+/// One row of the index node's `deployments` query; a slimmed-down
+/// version of `Info` that also knows how much space the deployment
+/// takes up on disk
+#[derive(Debug)]
+pub struct Summary {
+    /// The deployment hash
+    pub deployment: String,
+    pub network: String,
+    /// The name of the database shard that stores the deployment
+    pub shard: String,
+    pub synced: bool,
+    pub health: SubgraphHealth,
+    /// The latest block that the deployment has processed
+    pub latest_block: Option<i32>,
+    pub entity_count: u64,
+    /// The total size of the deployment's tables and indexes on disk
+    pub size_bytes: u64,
+}
+
+impl IntoValue for Summary {
+    fn into_value(self) -> r::Value {
+        let Summary {
+            deployment,
+            network,
+            shard,
+            synced,
+            health,
+            latest_block,
+            entity_count,
+            size_bytes,
+        } = self;
+
+        object! {
+            __typename: "DeploymentSummary",
+            deployment: deployment,
+            network: network,
+            shard: shard,
+            synced: synced,
+            health: r::Value::from(health),
+            latestBlock: latest_block,
+            entityCount: format!("{}", entity_count),
+            sizeBytes: format!("{}", size_bytes),
+        }
+    }
+}
+
+/// One day of an [`SlaReport`]
+#[derive(Debug)]
+pub struct SlaDay {
+    /// The day the entry covers, as `YYYY-MM-DD` in UTC
+    pub day: String,
+    pub samples: u64,
+    /// The share of samples within the threshold, from 0 to 100
+    pub within_percent: Option<f64>,
+    /// The largest number of blocks the deployment was behind the chain
+    /// head on this day
+    pub max_behind: i32,
+}
+
+impl IntoValue for SlaDay {
+    fn into_value(self) -> r::Value {
+        let SlaDay {
+            day,
+            samples,
+            within_percent,
+            max_behind,
+        } = self;
+
+        object! {
+            __typename: "SlaDay",
+            day: day,
+            samples: samples,
+            withinPercent: within_percent,
+            maxBehind: max_behind,
+        }
+    }
+}
+
+/// How well a deployment kept up with its chain, aggregated per day from
+/// periodic head-lag samples. The percentages are the share of samples
+/// where the deployment was at most `within_blocks` blocks behind the
+/// chain head
+#[derive(Debug)]
+pub struct SlaReport {
+    /// The deployment hash
+    pub deployment: String,
+    /// The number of days the report covers, ending today
+    pub days: i32,
+    pub within_blocks: i32,
+    pub samples: u64,
+    pub within_percent: Option<f64>,
+    /// The mean number of blocks behind the chain head
+    pub avg_behind: Option<f64>,
+    pub max_behind: Option<i32>,
+    pub daily: Vec<SlaDay>,
+}
+
+impl IntoValue for SlaReport {
+    fn into_value(self) -> r::Value {
+        let SlaReport {
+            deployment,
+            days,
+            within_blocks,
+            samples,
+            within_percent,
+            avg_behind,
+            max_behind,
+            daily,
+        } = self;
+
+        object! {
+            __typename: "SlaReport",
+            deployment: deployment,
+            days: days,
+            withinBlocks: within_blocks,
+            samples: samples,
+            withinPercent: within_percent,
+            avgBehind: avg_behind,
+            maxBehind: max_behind,
+            daily: daily,
+        }
+    }
+}
+
 /// Light wrapper around `EthereumBlockPointer` that is compatible with GraphQL values.
 #[derive(Debug)]
 pub struct EthereumBlock(BlockPtr);
@@ -86,6 +251,64 @@ impl IntoValue for ChainInfo {
     }
 }
 
+/// Progress of copying one entity table during a graft or copy operation
+#[derive(Debug)]
+pub struct CopyTableInfo {
+    pub entity_type: String,
+    /// The vid of the next entity version to copy; versions up to this one
+    /// have already been copied
+    pub next_vid: i64,
+    /// The vid of the last entity version that needs to be copied
+    pub target_vid: i64,
+    pub batch_size: i64,
+    /// Time spent copying this table so far, not counting wait time
+    pub duration_ms: i64,
+}
+
+impl IntoValue for CopyTableInfo {
+    fn into_value(self) -> r::Value {
+        let CopyTableInfo {
+            entity_type,
+            next_vid,
+            target_vid,
+            batch_size,
+            duration_ms,
+        } = self;
+        object! {
+            __typename: "CopyTableProgress",
+            entityType: entity_type,
+            nextVid: format!("{}", next_vid),
+            targetVid: format!("{}", target_vid),
+            batchSize: format!("{}", batch_size),
+            durationMs: format!("{}", duration_ms),
+        }
+    }
+}
+
+/// Progress of an in-flight graft or copy into a deployment. The copy is
+/// checkpointed per table and batch and resumes where it left off after an
+/// interruption
+#[derive(Debug)]
+pub struct CopyInfo {
+    /// The block up to which data is copied
+    pub target_block_number: i32,
+    pub tables: Vec<CopyTableInfo>,
+}
+
+impl IntoValue for CopyInfo {
+    fn into_value(self) -> r::Value {
+        let CopyInfo {
+            target_block_number,
+            tables,
+        } = self;
+        object! {
+            __typename: "CopyProgress",
+            targetBlockNumber: target_block_number,
+            tables: tables.into_iter().map(IntoValue::into_value).collect::<Vec<_>>(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Info {
     pub id: DeploymentId,
@@ -104,6 +327,10 @@ pub struct Info {
 
     pub entity_count: u64,
 
+    /// Progress of copying data from another deployment, e.g., for a
+    /// graft; `None` when no copy is in flight
+    pub copy_progress: Option<CopyInfo>,
+
     /// ID of the Graph Node that the subgraph is indexed by.
     pub node: Option<String>,
 }
@@ -115,6 +342,7 @@ impl IntoValue for Info {
             subgraph,
             chains,
             entity_count,
+            copy_progress,
             fatal_error,
             health,
             node,
@@ -151,6 +379,39 @@ impl IntoValue for Info {
             .collect();
         let fatal_error_val = fatal_error.map_or(r::Value::Null, subgraph_error_to_value);
 
+        // How fast the deployment head moves and, at that speed, how long
+        // it will take to catch up with the chain head. Only known for
+        // deployments that this node itself indexes
+        let blocks_per_second = SYNC_PROGRESS.blocks_per_second(&subgraph);
+        let blocks_behind = chains
+            .iter()
+            .filter_map(
+                |chain| match (&chain.chain_head_block, &chain.latest_block) {
+                    (Some(head), Some(latest)) => Some((head.number() - latest.number()).max(0)),
+                    _ => None,
+                },
+            )
+            .max();
+        let eta_seconds = match (blocks_per_second, blocks_behind) {
+            (Some(bps), Some(behind)) if bps > 0.0 => Some((behind as f64 / bps) as i32),
+            _ => None,
+        };
+
+        // Time-travel queries can only look back to the deployment's
+        // earliest block; before that, there is no history because of a
+        // `startBlock` in the manifest or because the deployment was
+        // grafted
+        let earliest_block_number = chains
+            .iter()
+            .filter_map(|chain| chain.earliest_block.as_ref().map(|block| block.number()))
+            .min()
+            .unwrap_or(0);
+        let history_blocks = chains
+            .iter()
+            .filter_map(|chain| chain.latest_block.as_ref().map(|block| block.number()))
+            .max()
+            .map(|latest| (latest - earliest_block_number + 1).max(0));
+
         object! {
             __typename: "SubgraphIndexingStatus",
             subgraph: subgraph,
@@ -160,6 +421,15 @@ impl IntoValue for Info {
             nonFatalErrors: non_fatal_errors,
             chains: chains.into_iter().map(|chain| chain.into_value()).collect::<Vec<_>>(),
             entityCount: format!("{}", entity_count),
+            blocksPerSecond: blocks_per_second,
+            etaSeconds: eta_seconds,
+            earliestBlockNumber: earliest_block_number,
+            historyBlocks: history_blocks,
+            // This node retains all history from the earliest block on
+            // and never prunes it; the field is here so that clients can
+            // detect pruned history uniformly across nodes
+            pruneInProgress: false,
+            copyProgress: copy_progress.map_or(r::Value::Null, IntoValue::into_value),
             node: node,
         }
     }
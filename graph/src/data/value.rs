@@ -1,8 +1,115 @@
 use crate::prelude::{q, s, CacheWeight};
+use lazy_static::lazy_static;
 use serde::ser::{SerializeMap, SerializeSeq, Serializer};
 use serde::Serialize;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
 use std::convert::TryFrom;
+use std::iter::FromIterator;
+use std::sync::{Arc, RwLock};
+
+/// An interned, immutable string. Attribute names in query results are
+/// repeated for every entity in the result; interning them makes all those
+/// repetitions share one allocation
+pub type Word = Arc<str>;
+
+lazy_static! {
+    /// All the words we have interned so far. Since words are attribute
+    /// names, they come from deployed schemas and the pool stays small
+    static ref POOL: RwLock<HashSet<Word>> = RwLock::new(HashSet::new());
+}
+
+/// Intern `s`, so that all objects using it as a key share one allocation
+fn intern(s: &str) -> Word {
+    if let Some(word) = POOL.read().unwrap().get(s) {
+        return word.clone();
+    }
+    let mut pool = POOL.write().unwrap();
+    match pool.get(s) {
+        Some(word) => word.clone(),
+        None => {
+            let word = Word::from(s);
+            pool.insert(word.clone());
+            word
+        }
+    }
+}
+
+/// A map with interned keys, used for `Value::Object`. All reads go
+/// through the underlying `BTreeMap`; mutations intern the key first
+#[derive(Clone, Default, PartialEq)]
+pub struct Object(BTreeMap<Word, Value>);
+
+impl std::fmt::Debug for Object {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Print like the underlying map so that `Value::Object` renders the
+        // same way it did when it was a plain `BTreeMap`
+        f.debug_map().entries(self.0.iter()).finish()
+    }
+}
+
+impl Object {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, key: impl AsRef<str>, value: Value) -> Option<Value> {
+        self.0.insert(intern(key.as_ref()), value)
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        self.0.remove(key)
+    }
+}
+
+impl std::ops::Deref for Object {
+    type Target = BTreeMap<Word, Value>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<K: AsRef<str>> FromIterator<(K, Value)> for Object {
+    fn from_iter<T: IntoIterator<Item = (K, Value)>>(iter: T) -> Self {
+        Object(
+            iter.into_iter()
+                .map(|(key, value)| (intern(key.as_ref()), value))
+                .collect(),
+        )
+    }
+}
+
+impl<K: AsRef<str>> Extend<(K, Value)> for Object {
+    fn extend<T: IntoIterator<Item = (K, Value)>>(&mut self, iter: T) {
+        self.0.extend(
+            iter.into_iter()
+                .map(|(key, value)| (intern(key.as_ref()), value)),
+        )
+    }
+}
+
+impl From<BTreeMap<String, Value>> for Object {
+    fn from(map: BTreeMap<String, Value>) -> Self {
+        map.into_iter().collect()
+    }
+}
+
+impl IntoIterator for Object {
+    type Item = (Word, Value);
+    type IntoIter = std::collections::btree_map::IntoIter<Word, Value>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl CacheWeight for Object {
+    fn indirect_weight(&self) -> usize {
+        // Keys are interned and shared between all objects using them and
+        // therefore do not count towards the weight of any one object
+        self.0.values().map(CacheWeight::weight).sum()
+    }
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
@@ -13,7 +120,7 @@ pub enum Value {
     Null,
     Enum(String),
     List(Vec<Value>),
-    Object(BTreeMap<String, Value>),
+    Object(Object),
 }
 
 impl Value {
@@ -127,8 +234,8 @@ impl Serialize for Value {
             Value::String(s) => serializer.serialize_str(s),
             Value::Object(o) => {
                 let mut map = serializer.serialize_map(Some(o.len()))?;
-                for (k, v) in o {
-                    map.serialize_entry(k, v)?;
+                for (k, v) in o.iter() {
+                    map.serialize_entry(k.as_ref(), v)?;
                 }
                 map.end()
             }
@@ -159,7 +266,7 @@ impl TryFrom<q::Value> for Value {
                 Ok(Value::List(vals))
             }
             q::Value::Object(map) => {
-                let mut rmap = BTreeMap::new();
+                let mut rmap = Object::new();
                 for (key, value) in map.into_iter() {
                     let value = Value::try_from(value)?;
                     rmap.insert(key, value);
@@ -187,7 +294,7 @@ impl From<Value> for q::Value {
                 let mut rmap = BTreeMap::new();
                 for (key, value) in map.into_iter() {
                     let value = q::Value::from(value);
-                    rmap.insert(key, value);
+                    rmap.insert(key.to_string(), value);
                 }
                 q::Value::Object(rmap)
             }
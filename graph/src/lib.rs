@@ -27,6 +27,12 @@ pub mod firehose;
 /// Helpers for parsing environment variables.
 pub mod env;
 
+/// Per-deployment overrides for a few global tunables.
+pub mod settings;
+
+/// Typed clients for the admin JSON-RPC API and the index node server.
+pub mod client;
+
 /// Module with mocks for different parts of the system.
 pub mod mock {
     pub use crate::components::store::MockStore;
@@ -35,7 +41,9 @@ pub mod mock {
 /// Wrapper for spawning tasks that abort on panic, which is our default.
 mod task_spawn;
 pub use task_spawn::{
-    block_on, spawn, spawn_allow_panic, spawn_blocking, spawn_blocking_allow_panic, spawn_thread,
+    block_on, register_pool_metrics, spawn, spawn_allow_panic, spawn_blocking,
+    spawn_blocking_allow_panic, spawn_index, spawn_query, spawn_query_blocking_allow_panic,
+    spawn_thread, wasm_runtime_handle,
 };
 
 pub use anyhow;
@@ -122,9 +130,10 @@ pub mod prelude {
         AttributeNames, BlockNumber, ChainStore, ChildMultiplicity, EntityCache, EntityChange,
         EntityChangeOperation, EntityCollection, EntityFilter, EntityKey, EntityLink,
         EntityModification, EntityOperation, EntityOrder, EntityQuery, EntityRange, EntityWindow,
-        EthereumCallCache, ParentLink, PoolWaitStats, QueryStore, QueryStoreManager, StoreError,
-        StoreEvent, StoreEventStream, StoreEventStreamBox, SubgraphStore, WindowAttribute,
-        BLOCK_NUMBER_MAX, SUBSCRIPTION_THROTTLE_INTERVAL,
+        EthereumCallCache, HeadLagSample, OrderDirection, ParentLink, PoolWaitStats, QueryStore,
+        QueryStoreManager, ReorgEvent, StoreError, StoreEvent, StoreEventStream,
+        StoreEventStreamBox, SubgraphStore, WindowAttribute, BLOCK_NUMBER_MAX,
+        SUBSCRIPTION_THROTTLE_INTERVAL,
     };
     pub use crate::components::subgraph::{
         BlockState, DataSourceTemplateInfo, HostMetrics, RuntimeHost, RuntimeHostBuilder,
@@ -138,7 +147,7 @@ pub mod prelude {
         shape_hash::shape_hash, SerializableValue, TryFromValue, ValueMap,
     };
     pub use crate::data::query::{
-        Query, QueryError, QueryExecutionError, QueryResult, QueryVariables,
+        Query, QueryError, QueryExecutionError, QueryResult, QueryVariables, Trace,
     };
     pub use crate::data::schema::{ApiSchema, Schema};
     pub use crate::data::store::ethereum::*;
@@ -199,6 +208,6 @@ pub mod prelude {
     });
 
     pub mod r {
-        pub use crate::data::value::Value;
+        pub use crate::data::value::{Object, Value, Word};
     }
 }
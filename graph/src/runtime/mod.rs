@@ -238,6 +238,15 @@ pub enum IndexForAscTypeId {
     NearChunkHeader = 84,
     NearBlock = 85,
     NearReceiptWithOutcome = 86,
+
+    // Ethereum typed transaction (EIP-2930 access list) support. These were
+    // added after the Near type IDs and therefore do not sit next to the other
+    // Ethereum IDs; the values must never change.
+    AccessTuple = 87,
+    ArrayAccessTuple = 88,
+    ArrayH256 = 89,
+    ArrayEthereumTransaction = 90,
+    FullEthereumBlock = 91,
 }
 
 impl ToAscObj<u32> for IndexForAscTypeId {
@@ -0,0 +1,181 @@
+//! Per-deployment overrides for a small set of global tunables.
+//!
+//! Operators attach key/value settings to a deployment through the
+//! `subgraph_setting` JSON-RPC method. The settings are persisted in the
+//! primary database and loaded when a node starts; the node that handles
+//! the RPC call applies a change immediately while other nodes pick it up
+//! on their next restart. Components look the current value up every time
+//! they need it, so changing a setting does not require restarting the
+//! node that received the change.
+//!
+//! The recognized settings, and the global tunable each one shadows:
+//!
+//! * `query_timeout`: maximum execution time for a GraphQL query in
+//!   seconds (`GRAPH_GRAPHQL_QUERY_TIMEOUT`)
+//! * `max_first`: maximum value for the `first` argument
+//!   (`GRAPH_GRAPHQL_MAX_FIRST`)
+//! * `entity_cache_size`: size of the entity cache in kilobytes
+//!   (`GRAPH_ENTITY_CACHE_SIZE`)
+//! * `block_range_size`: number of blocks to scan in one batch of
+//!   provider requests while indexing
+//!   (`GRAPH_ETHEREUM_MAX_BLOCK_RANGE_SIZE`)
+//! * `trusted`: when set to a nonzero value, the deployment is exempt
+//!   from the host function policy in `GRAPH_DISABLED_HOST_FNS`
+//! * `final_blocks_only`: when set to a nonzero value, the deployment
+//!   only indexes blocks that are at least the reorg threshold behind
+//!   the chain head and are therefore considered final. Its data never
+//!   gets rolled back by a chain reorganization, at the cost of lagging
+//!   the head by the reorg threshold
+//! * `write_back_blocks`: accumulate entity changes across this many
+//!   blocks and write them to the store in one batch instead of after
+//!   every block. This speeds up backfills, but a crash loses the
+//!   buffered blocks and historical queries can not see the state at
+//!   blocks inside a batch. Buffering only happens while the deployment
+//!   is more than `GRAPH_WRITE_BACK_SAFETY_MARGIN` blocks behind the
+//!   chain head; closer to the head, every block is written out
+//!   individually so that it can be reverted individually
+//! * `priority`: how important it is that the deployment keeps indexing
+//!   during a maintenance window from `GRAPH_MAINTENANCE_WINDOWS` (see
+//!   `graph::util::maintenance`). Deployments with a nonzero priority
+//!   keep indexing through a window; all others pause until the window
+//!   closes
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+
+use crate::components::store::BlockNumber;
+use crate::prelude::{anyhow::bail, DeploymentHash, Error};
+
+pub const QUERY_TIMEOUT: &str = "query_timeout";
+pub const MAX_FIRST: &str = "max_first";
+pub const ENTITY_CACHE_SIZE: &str = "entity_cache_size";
+pub const BLOCK_RANGE_SIZE: &str = "block_range_size";
+pub const TRUSTED: &str = "trusted";
+pub const FINAL_BLOCKS_ONLY: &str = "final_blocks_only";
+pub const WRITE_BACK_BLOCKS: &str = "write_back_blocks";
+pub const PRIORITY: &str = "priority";
+
+const VALID_NAMES: [&str; 8] = [
+    QUERY_TIMEOUT,
+    MAX_FIRST,
+    ENTITY_CACHE_SIZE,
+    BLOCK_RANGE_SIZE,
+    TRUSTED,
+    FINAL_BLOCKS_ONLY,
+    WRITE_BACK_BLOCKS,
+    PRIORITY,
+];
+
+lazy_static! {
+    static ref SETTINGS: RwLock<HashMap<DeploymentHash, Arc<Settings>>> =
+        RwLock::new(HashMap::new());
+    static ref EMPTY: Arc<Settings> = Arc::new(Settings::default());
+}
+
+/// The setting overrides for one deployment. An instance reflects the
+/// settings at the time it was looked up; callers should not hold on to
+/// it but get a fresh one through `for_deployment` whenever they need a
+/// value
+#[derive(Clone, Debug, Default)]
+pub struct Settings {
+    values: BTreeMap<String, String>,
+}
+
+impl Settings {
+    fn number(&self, name: &str) -> Option<u64> {
+        self.values.get(name).and_then(|value| value.parse().ok())
+    }
+
+    pub fn query_timeout(&self) -> Option<Duration> {
+        self.number(QUERY_TIMEOUT).map(Duration::from_secs)
+    }
+
+    pub fn max_first(&self) -> Option<u32> {
+        self.number(MAX_FIRST).map(|n| n as u32)
+    }
+
+    /// The cache size in bytes, converted from the kilobytes the setting
+    /// is expressed in, like `GRAPH_ENTITY_CACHE_SIZE`
+    pub fn entity_cache_size(&self) -> Option<usize> {
+        self.number(ENTITY_CACHE_SIZE).map(|kb| 1000 * kb as usize)
+    }
+
+    pub fn block_range_size(&self) -> Option<BlockNumber> {
+        self.number(BLOCK_RANGE_SIZE).map(|n| n as BlockNumber)
+    }
+
+    /// Whether the deployment is exempt from the host function policy in
+    /// `GRAPH_DISABLED_HOST_FNS`
+    pub fn trusted(&self) -> bool {
+        self.number(TRUSTED).map(|n| n != 0).unwrap_or(false)
+    }
+
+    /// Whether the deployment only indexes blocks that are at least the
+    /// reorg threshold behind the chain head
+    pub fn final_blocks_only(&self) -> bool {
+        self.number(FINAL_BLOCKS_ONLY)
+            .map(|n| n != 0)
+            .unwrap_or(false)
+    }
+
+    /// The number of blocks to accumulate entity changes for before
+    /// writing them to the store in one batch. Values up to `1` mean the
+    /// default behavior of writing after every block
+    pub fn write_back_blocks(&self) -> Option<u32> {
+        self.number(WRITE_BACK_BLOCKS).map(|n| n as u32)
+    }
+
+    /// The priority of the deployment during a maintenance window.
+    /// Deployments with a nonzero priority keep indexing through a
+    /// window; deployments with the default priority of `0` pause
+    pub fn priority(&self) -> u64 {
+        self.number(PRIORITY).unwrap_or(0)
+    }
+}
+
+/// Check that `name` is a recognized setting and that `value` is valid
+/// for it. An empty `value` is always valid since it clears the setting
+pub fn validate(name: &str, value: &str) -> Result<(), Error> {
+    if !VALID_NAMES.contains(&name) {
+        bail!(
+            "unknown setting `{}`; the valid settings are {}",
+            name,
+            VALID_NAMES.join(", ")
+        );
+    }
+    if !value.is_empty() && value.parse::<u64>().is_err() {
+        bail!("the value `{}` for `{}` is not a number", value, name);
+    }
+    Ok(())
+}
+
+/// The current settings for `deployment`; a deployment without any
+/// overrides gets an empty `Settings` so that all accessors return
+/// `None`
+pub fn for_deployment(deployment: &DeploymentHash) -> Arc<Settings> {
+    SETTINGS
+        .read()
+        .unwrap()
+        .get(deployment)
+        .cloned()
+        .unwrap_or_else(|| EMPTY.clone())
+}
+
+/// Apply the override `name` for `deployment`; an empty `value` clears
+/// the override. This only changes the in-memory settings; persisting
+/// them is the store's business
+pub fn set(deployment: DeploymentHash, name: &str, value: &str) -> Result<(), Error> {
+    validate(name, value)?;
+
+    let mut settings = SETTINGS.write().unwrap();
+    let entry = Arc::make_mut(settings.entry(deployment).or_default());
+    if value.is_empty() {
+        entry.values.remove(name);
+    } else {
+        entry.values.insert(name.to_string(), value.to_string());
+    }
+    Ok(())
+}
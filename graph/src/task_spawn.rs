@@ -11,10 +11,153 @@
 //! functions will panic in that case. We should generally avoid mixing executors whenever possible.
 
 use futures03::future::{FutureExt, TryFutureExt};
+use lazy_static::lazy_static;
+use prometheus::{HistogramOpts, HistogramVec};
+use std::env;
 use std::future::Future as Future03;
 use std::panic::AssertUnwindSafe;
+use std::str::FromStr;
+use std::time::Instant;
+use tokio::runtime::{Builder, Runtime};
 use tokio::task::JoinHandle;
 
+use crate::components::metrics::MetricsRegistry;
+
+lazy_static! {
+    /// A runtime dedicated to query execution, sized through
+    /// `GRAPH_QUERY_WORKER_THREADS`. When the variable is not set, queries
+    /// run on the shared runtime and heavy indexing can starve them
+    static ref QUERY_POOL: Option<Runtime> = pool("GRAPH_QUERY_WORKER_THREADS", "graph-query");
+
+    /// A runtime dedicated to indexing, sized through
+    /// `GRAPH_INDEX_WORKER_THREADS`
+    static ref INDEX_POOL: Option<Runtime> = pool("GRAPH_INDEX_WORKER_THREADS", "graph-index");
+
+    /// A runtime whose blocking pool is used by the WASM mapping threads,
+    /// sized through `GRAPH_WASM_BLOCKING_THREADS`
+    static ref WASM_POOL: Option<Runtime> =
+        blocking_pool("GRAPH_WASM_BLOCKING_THREADS", "graph-wasm");
+
+    /// How long tasks wait between being spawned into one of the pools and
+    /// a thread picking them up. Large values mean the pool is undersized
+    static ref QUEUE_DELAY: HistogramVec = HistogramVec::new(
+        HistogramOpts::new(
+            "task_pool_queue_delay",
+            "Time in seconds that tasks spend waiting for a pool thread",
+        )
+        .buckets(vec![0.0001, 0.001, 0.01, 0.1, 1.0, 10.0]),
+        &["pool"],
+    )
+    .unwrap();
+}
+
+fn pool_size(var: &str) -> Option<usize> {
+    env::var(var)
+        .ok()
+        .map(|s| {
+            usize::from_str(&s)
+                .unwrap_or_else(|_| panic!("`{}` must be a number, but is `{}`", var, s))
+        })
+        .filter(|size| *size > 0)
+}
+
+fn pool(var: &str, name: &str) -> Option<Runtime> {
+    pool_size(var).map(|size| {
+        Builder::new_multi_thread()
+            .enable_all()
+            .worker_threads(size)
+            .thread_name(name)
+            .build()
+            .unwrap_or_else(|e| panic!("failed to build the `{}` pool: {}", name, e))
+    })
+}
+
+fn blocking_pool(var: &str, name: &str) -> Option<Runtime> {
+    pool_size(var).map(|size| {
+        Builder::new_multi_thread()
+            .enable_all()
+            // The pool exists for its blocking threads; one worker thread
+            // is enough to drive them
+            .worker_threads(1)
+            .max_blocking_threads(size)
+            .thread_name(name)
+            .build()
+            .unwrap_or_else(|e| panic!("failed to build the `{}` pool: {}", name, e))
+    })
+}
+
+/// Register the queue delay histogram for the task pools with `registry`.
+/// Must be called once at startup for the metrics to be exported
+pub fn register_pool_metrics(registry: &impl MetricsRegistry) {
+    registry.register("task_pool_queue_delay", Box::new(QUEUE_DELAY.clone()));
+}
+
+fn observe_queue_delay<T: Send + 'static>(
+    pool: &'static str,
+    f: impl Future03<Output = T> + Send + 'static,
+) -> impl Future03<Output = T> + Send + 'static {
+    let spawned = Instant::now();
+    async move {
+        // The first poll happens once a pool thread picks the task up
+        QUEUE_DELAY
+            .with_label_values(&[pool])
+            .observe(spawned.elapsed().as_secs_f64());
+        f.await
+    }
+}
+
+/// Aborts on panic. Runs `f` on the dedicated query pool if
+/// `GRAPH_QUERY_WORKER_THREADS` is set, and on the shared runtime otherwise.
+pub fn spawn_query<T: Send + 'static>(
+    f: impl Future03<Output = T> + Send + 'static,
+) -> JoinHandle<T> {
+    let f = abort_on_panic(observe_queue_delay("query", f));
+    match &*QUERY_POOL {
+        Some(pool) => pool.spawn(f),
+        None => tokio::spawn(f),
+    }
+}
+
+/// Aborts on panic. Runs `f` on the dedicated indexing pool if
+/// `GRAPH_INDEX_WORKER_THREADS` is set, and on the shared runtime otherwise.
+pub fn spawn_index<T: Send + 'static>(
+    f: impl Future03<Output = T> + Send + 'static,
+) -> JoinHandle<T> {
+    let f = abort_on_panic(observe_queue_delay("index", f));
+    match &*INDEX_POOL {
+        Some(pool) => pool.spawn(f),
+        None => tokio::spawn(f),
+    }
+}
+
+/// Like `spawn_blocking_allow_panic`, but uses the blocking threads of the
+/// query pool when `GRAPH_QUERY_WORKER_THREADS` is set.
+pub fn spawn_query_blocking_allow_panic<R: 'static + Send>(
+    f: impl 'static + FnOnce() -> R + Send,
+) -> JoinHandle<R> {
+    let spawned = Instant::now();
+    let f = move || {
+        QUEUE_DELAY
+            .with_label_values(&["query"])
+            .observe(spawned.elapsed().as_secs_f64());
+        f()
+    };
+    match &*QUERY_POOL {
+        Some(pool) => pool.spawn_blocking(f),
+        None => tokio::task::spawn_blocking(f),
+    }
+}
+
+/// The handle that WASM mapping threads should enter so that any async
+/// work they block on runs on the WASM pool when
+/// `GRAPH_WASM_BLOCKING_THREADS` is set.
+pub fn wasm_runtime_handle() -> tokio::runtime::Handle {
+    match &*WASM_POOL {
+        Some(pool) => pool.handle().clone(),
+        None => tokio::runtime::Handle::current(),
+    }
+}
+
 fn abort_on_panic<T: Send + 'static>(
     f: impl Future03<Output = T> + Send + 'static,
 ) -> impl Future03<Output = T> {
@@ -0,0 +1,65 @@
+//! Scheduled maintenance windows.
+//!
+//! Operators can set `GRAPH_MAINTENANCE_WINDOWS` to a comma-separated
+//! list of daily time windows in UTC, like `02:00-04:00,14:30-15:00`.
+//! While a window is open, deployments that have not been given a
+//! priority through the `priority` setting (see `graph::settings`) pause
+//! indexing so that maintenance work like vacuuming, pruning, or backups
+//! gets the I/O that indexing would otherwise use; they resume
+//! automatically when the window closes. A window may wrap around
+//! midnight, as in `23:00-01:00`.
+
+use chrono::{NaiveTime, Utc};
+use lazy_static::lazy_static;
+
+lazy_static! {
+    static ref WINDOWS: Vec<(NaiveTime, NaiveTime)> = {
+        let windows = std::env::var("GRAPH_MAINTENANCE_WINDOWS").unwrap_or_default();
+        windows
+            .split(',')
+            .filter(|window| !window.is_empty())
+            .map(|window| {
+                let mut times = window.splitn(2, '-').map(|time| {
+                    NaiveTime::parse_from_str(time, "%H:%M").unwrap_or_else(|_| {
+                        panic!(
+                            "invalid GRAPH_MAINTENANCE_WINDOWS: `{}` is not a time \
+                             in the form HH:MM",
+                            time
+                        )
+                    })
+                });
+                let start = times.next().unwrap();
+                let end = times.next().unwrap_or_else(|| {
+                    panic!(
+                        "invalid GRAPH_MAINTENANCE_WINDOWS: `{}` is not a window \
+                         in the form HH:MM-HH:MM",
+                        window
+                    )
+                });
+                (start, end)
+            })
+            .collect()
+    };
+}
+
+/// Whether any of the windows from `GRAPH_MAINTENANCE_WINDOWS` contains
+/// `time`. A window whose end lies before its start wraps around
+/// midnight
+fn contains(time: NaiveTime) -> bool {
+    WINDOWS.iter().any(|&(start, end)| {
+        if start <= end {
+            time >= start && time < end
+        } else {
+            time >= start || time < end
+        }
+    })
+}
+
+/// Whether a maintenance window is currently open. Always `false` when
+/// `GRAPH_MAINTENANCE_WINDOWS` is not set
+pub fn in_window() -> bool {
+    if WINDOWS.is_empty() {
+        return false;
+    }
+    contains(Utc::now().time())
+}
@@ -18,5 +18,8 @@ pub mod timed_rw_lock;
 
 pub mod jobs;
 
+/// Scheduled maintenance windows from `GRAPH_MAINTENANCE_WINDOWS`
+pub mod maintenance;
+
 /// Increasingly longer sleeps to back off some repeated operation
 pub mod backoff;
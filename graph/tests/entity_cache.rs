@@ -148,6 +148,46 @@ fn overwrite_modifications() {
     );
 }
 
+#[test]
+fn snapshot_preserves_earlier_modifications() {
+    let mut store = MockStore::new();
+
+    // Return no entities from the store, forcing the cache to treat any `set`
+    // operation as an insert.
+    store
+        .expect_get_many_mock()
+        .returning(|_| Ok(BTreeMap::new()));
+
+    let store = Arc::new(store);
+    let mut cache = EntityCache::new(store.clone());
+
+    // Changes from an already processed block
+    let (mogwai_key, mogwai_data) = make_band(
+        "mogwai",
+        vec![("id", "mogwai".into()), ("name", "Mogwai".into())],
+    );
+    cache.set(mogwai_key.clone(), mogwai_data.clone());
+
+    // A snapshot taken between blocks only sees the changes up to here,
+    // even if the next block makes more changes before it fails
+    let snapshot = cache.snapshot();
+
+    let (sigurros_key, sigurros_data) = make_band(
+        "sigurros",
+        vec![("id", "sigurros".into()), ("name", "Sigur Ros".into())],
+    );
+    cache.set(sigurros_key.clone(), sigurros_data.clone());
+
+    let result = snapshot.as_modifications();
+    assert_eq!(
+        sort_by_entity_key(result.unwrap().modifications),
+        sort_by_entity_key(vec![EntityModification::Insert {
+            key: mogwai_key,
+            data: mogwai_data,
+        }])
+    );
+}
+
 #[test]
 fn consecutive_modifications() {
     let mut store = MockStore::new();
@@ -11,8 +11,9 @@ use stable_hash::crypto::SetHasher;
 use stable_hash::prelude::*;
 use stable_hash::utils::stable_hash;
 use std::borrow::ToOwned;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{HashMap, HashSet};
 use std::iter;
+use std::sync::Mutex;
 use std::time::Instant;
 
 use graph::data::graphql::*;
@@ -43,6 +44,17 @@ lazy_static! {
 
     static ref CACHE_ALL: bool = CACHED_SUBGRAPH_IDS.contains(&"*".to_string());
 
+    /// Whether to handle field errors the way the GraphQL spec requires:
+    /// add an error entry to the response and set the nearest nullable
+    /// ancestor of the field to null, instead of failing the entire
+    /// query. Off by default since some clients rely on the lenient
+    /// behavior.
+    static ref SPEC_NULL_PROPAGATION: bool = {
+        std::env::var("GRAPH_GRAPHQL_SPEC_NULL_PROPAGATION")
+        .map(|s| s == "true")
+        .unwrap_or(false)
+    };
+
     // How many blocks per network should be kept in the query cache. When the limit is reached,
     // older blocks are evicted. This should be kept small since a lookup to the cache is O(n) on
     // this value, and the cache memory usage also increases with larger number. Set to 0 to disable
@@ -128,7 +140,7 @@ impl CacheWeight for WeightedResult {
 impl Default for WeightedResult {
     fn default() -> Self {
         WeightedResult {
-            result: Arc::new(QueryResult::new(BTreeMap::default())),
+            result: Arc::new(QueryResult::new(r::Object::default())),
             weight: 0,
         }
     }
@@ -230,6 +242,13 @@ where
 
     /// Records whether this was a cache hit, used for logging.
     pub(crate) cache_status: AtomicCell<CacheStatus>,
+
+    /// Errors from fields that were set to null according to the error
+    /// handling rules of the GraphQL spec, collected when
+    /// `GRAPH_GRAPHQL_SPEC_NULL_PROPAGATION` is in effect. They become
+    /// part of the errors of the response even though execution still
+    /// produces data.
+    pub(crate) nulled_errors: Mutex<Vec<QueryExecutionError>>,
 }
 
 // Helpers to look for types and fields on both the introspection and regular schemas.
@@ -282,6 +301,7 @@ where
 
             // `cache_status` is a dead value for the introspection context.
             cache_status: AtomicCell::new(CacheStatus::Miss),
+            nulled_errors: Default::default(),
         }
     }
 }
@@ -290,7 +310,7 @@ pub fn execute_root_selection_set_uncached(
     ctx: &ExecutionContext<impl Resolver>,
     selection_set: &q::SelectionSet,
     root_type: &s::ObjectType,
-) -> Result<BTreeMap<String, r::Value>, Vec<QueryExecutionError>> {
+) -> Result<r::Object, Vec<QueryExecutionError>> {
     // Split the top-level fields into introspection fields and
     // regular data fields
     let mut data_set = q::SelectionSet {
@@ -320,7 +340,7 @@ pub fn execute_root_selection_set_uncached(
 
     // If we are getting regular data, prefetch it from the database
     let mut values = if data_set.items.is_empty() && meta_items.is_empty() {
-        BTreeMap::default()
+        r::Object::default()
     } else {
         let initial_data = ctx.resolver.prefetch(&ctx, &data_set)?;
         data_set.items.extend(meta_items);
@@ -353,7 +373,12 @@ pub async fn execute_root_selection_set<R: Resolver>(
     // and once for insert.
     let mut key: Option<QueryHash> = None;
 
-    if R::CACHEABLE && (*CACHE_ALL || CACHED_SUBGRAPH_IDS.contains(ctx.query.schema.id())) {
+    // Bypass the cache for traced queries so that the trace reflects the
+    // SQL statements the query actually causes
+    if R::CACHEABLE
+        && !ctx.query.trace
+        && (*CACHE_ALL || CACHED_SUBGRAPH_IDS.contains(ctx.query.schema.id()))
+    {
         if let (Some(block_ptr), Some(network)) = (block_ptr.as_ref(), &ctx.query.network) {
             // JSONB and metadata queries use `BLOCK_NUMBER_MAX`. Ignore this case for two reasons:
             // - Metadata queries are not cacheable.
@@ -388,22 +413,31 @@ pub async fn execute_root_selection_set<R: Resolver>(
     let execute_ctx = ctx.cheap_clone();
     let execute_selection_set = selection_set.cheap_clone();
     let execute_root_type = root_type.cheap_clone();
+    let result_block_ptr = block_ptr.clone();
     let run_query = async move {
         let _permit = execute_ctx.resolver.query_permit().await;
 
         let logger = execute_ctx.logger.clone();
         let query_text = execute_ctx.query.query_text.cheap_clone();
         let variables_text = execute_ctx.query.variables_text.cheap_clone();
-        match graph::spawn_blocking_allow_panic(move || {
+        match graph::spawn_query_blocking_allow_panic(move || {
             let mut query_res = QueryResult::from(execute_root_selection_set_uncached(
                 &execute_ctx,
                 &execute_selection_set,
                 &execute_root_type,
             ));
 
+            // Errors from fields that were nulled according to the spec
+            // still belong in the errors of the response
+            let nulled = std::mem::take(&mut *execute_ctx.nulled_errors.lock().unwrap());
+            query_res
+                .errors_mut()
+                .extend(nulled.into_iter().map(QueryError::from));
+
             // Unwrap: In practice should never fail, but if it does we will catch the panic.
             execute_ctx.resolver.post_process(&mut query_res).unwrap();
             query_res.deployment = Some(execute_ctx.query.schema.id().clone());
+            query_res.block_ptr = result_block_ptr;
             Arc::new(query_res)
         })
         .await
@@ -506,14 +540,14 @@ fn execute_selection_set_to_map<'a>(
     selection_sets: impl Iterator<Item = &'a q::SelectionSet>,
     object_type: &s::ObjectType,
     prefetched_value: Option<r::Value>,
-) -> Result<BTreeMap<String, r::Value>, Vec<QueryExecutionError>> {
+) -> Result<r::Object, Vec<QueryExecutionError>> {
     let mut prefetched_object = match prefetched_value {
         Some(r::Value::Object(object)) => Some(object),
         Some(_) => unreachable!(),
         None => None,
     };
     let mut errors: Vec<QueryExecutionError> = Vec::new();
-    let mut result_map: BTreeMap<String, r::Value> = BTreeMap::new();
+    let mut result_map = r::Object::new();
 
     // Group fields with the same response key, so we can execute them together
     let grouped_field_set = collect_fields(ctx, object_type, selection_sets);
@@ -556,7 +590,7 @@ fn execute_selection_set_to_map<'a>(
                 // If the field has more than one response key, we have to clone.
                 match multiple_response_keys.contains(fields[0].name.as_str()) {
                     false => o.remove(&fields[0].name),
-                    true => o.get(&fields[0].name).cloned(),
+                    true => o.get(fields[0].name.as_str()).cloned(),
                 }
             })
             .flatten();
@@ -565,7 +599,17 @@ fn execute_selection_set_to_map<'a>(
                 result_map.insert(response_key.to_owned(), v);
             }
             Err(mut e) => {
-                errors.append(&mut e);
+                // With spec-compliant null propagation, an error in a
+                // nullable field adds an error entry to the response and
+                // nulls just that field. Errors in non-null fields keep
+                // propagating through `Err` until an ancestor field can
+                // be nulled
+                if *SPEC_NULL_PROPAGATION && !sast::is_non_null_type(&field.field_type) {
+                    ctx.nulled_errors.lock().unwrap().append(&mut e);
+                    result_map.insert(response_key.to_owned(), r::Value::Null);
+                } else {
+                    errors.append(&mut e);
+                }
             }
         }
     }
@@ -935,6 +979,16 @@ fn complete_value(
                             Ok(value) => {
                                 *value_place = value;
                             }
+                            // With spec-compliant null propagation, a
+                            // failed entry of a list with nullable
+                            // elements stays at the `Null` placeholder
+                            // instead of failing the whole list
+                            Err(errs)
+                                if *SPEC_NULL_PROPAGATION
+                                    && !sast::is_non_null_type(inner_type) =>
+                            {
+                                ctx.nulled_errors.lock().unwrap().extend(errs);
+                            }
                             Err(errs) => errors.extend(errs),
                         }
                     }
@@ -1063,7 +1117,7 @@ pub fn coerce_argument_values<'a>(
                 if argument_def.name == "text".to_string() {
                     coerced_values.insert(
                         argument_def.name.as_str(),
-                        r::Value::Object(BTreeMap::from_iter(vec![(field.name.clone(), value)])),
+                        r::Value::Object(r::Object::from_iter(vec![(field.name.clone(), value)])),
                     );
                 } else {
                     coerced_values.insert(&argument_def.name, value);
@@ -35,6 +35,7 @@ pub enum ComplexityError {
 enum Kind {
     Query,
     Subscription,
+    Mutation,
 }
 
 /// Helper to log the fields in a `SelectionSet` without cloning. Writes
@@ -100,6 +101,10 @@ pub struct Query {
     pub variables_text: Arc<String>,
     pub query_id: String,
     pub(crate) complexity: u64,
+
+    /// Whether to collect the SQL statements the query causes and return
+    /// them in the `extensions` of the response
+    pub trace: bool,
 }
 
 impl Query {
@@ -140,10 +145,15 @@ impl Query {
             q::OperationDefinition::Subscription(q::Subscription { selection_set, .. }) => {
                 (Kind::Subscription, selection_set)
             }
-            q::OperationDefinition::Mutation(_) => {
-                return Err(vec![QueryExecutionError::NotSupported(
-                    "Mutations are not supported".to_owned(),
-                )])
+            // Mutations are only supported for schemas that declare a root
+            // `Mutation` type, like the index node API; subgraph APIs do not
+            q::OperationDefinition::Mutation(q::Mutation { selection_set, .. }) => {
+                if schema.mutation_type.is_none() {
+                    return Err(vec![QueryExecutionError::NotSupported(
+                        "Mutations are not supported".to_owned(),
+                    )]);
+                }
+                (Kind::Mutation, selection_set)
             }
         };
 
@@ -173,6 +183,7 @@ impl Query {
             variables_text: query.variables_text.cheap_clone(),
             query_id,
             complexity: 0,
+            trace: query.trace,
         };
 
         // It's important to check complexity first, so `validate_fields` doesn't risk a stack
@@ -271,6 +282,7 @@ impl Query {
             variables_text: self.variables_text.clone(),
             query_id: self.query_id.clone(),
             complexity: self.complexity,
+            trace: self.trace,
         })
     }
 
@@ -285,7 +297,7 @@ impl Query {
     pub fn is_query(&self) -> bool {
         match self.kind {
             Kind::Query => true,
-            Kind::Subscription => false,
+            Kind::Subscription | Kind::Mutation => false,
         }
     }
 
@@ -293,7 +305,15 @@ impl Query {
     pub fn is_subscription(&self) -> bool {
         match self.kind {
             Kind::Subscription => true,
-            Kind::Query => false,
+            Kind::Query | Kind::Mutation => false,
+        }
+    }
+
+    /// Return `true` if this is a mutation, not a query or a subscription
+    pub fn is_mutation(&self) -> bool {
+        match self.kind {
+            Kind::Mutation => true,
+            Kind::Query | Kind::Subscription => false,
         }
     }
 
@@ -355,7 +375,12 @@ impl Query {
     /// If the query is invalid, returns `Ok(0)` so that execution proceeds and
     /// gives a proper error.
     fn complexity(&self, max_depth: u8) -> Result<u64, QueryExecutionError> {
-        let root_type = sast::get_root_query_type_def(self.schema.document()).unwrap();
+        let root_type = match self.kind {
+            Kind::Mutation => sast::get_root_mutation_type_def(self.schema.document()).unwrap(),
+            Kind::Query | Kind::Subscription => {
+                sast::get_root_query_type_def(self.schema.document()).unwrap()
+            }
+        };
 
         match self.complexity_inner(
             root_type,
@@ -377,10 +402,18 @@ impl Query {
     }
 
     fn validate_fields(&self) -> Result<(), Vec<QueryExecutionError>> {
-        let root_type = self.schema.document().get_root_query_type().unwrap();
+        let (root_name, root_type) = match self.kind {
+            Kind::Mutation => (
+                "Mutation",
+                self.schema.document().get_root_mutation_type().unwrap(),
+            ),
+            Kind::Query | Kind::Subscription => (
+                "Query",
+                self.schema.document().get_root_query_type().unwrap(),
+            ),
+        };
 
-        let errors =
-            self.validate_fields_inner(&"Query".to_owned(), root_type.into(), &self.selection_set);
+        let errors = self.validate_fields_inner(root_name, root_type.into(), &self.selection_set);
         if errors.len() == 0 {
             Ok(())
         } else {
@@ -49,19 +49,34 @@ where
         max_first: options.max_first,
         max_skip: options.max_skip,
         cache_status: Default::default(),
+        nulled_errors: Default::default(),
     });
 
-    if !query.is_query() {
+    if query.is_subscription() {
         return Arc::new(
-            QueryExecutionError::NotSupported("Only queries are supported".to_string()).into(),
+            QueryExecutionError::NotSupported(
+                "Only queries and mutations are supported".to_string(),
+            )
+            .into(),
         );
     }
     let selection_set = selection_set
         .map(Arc::new)
         .unwrap_or_else(|| query.selection_set.cheap_clone());
 
-    // Execute top-level `query { ... }` and `{ ... }` expressions.
-    let query_type = ctx.query.schema.query_type.cheap_clone();
+    // Execute top-level `query { ... }` and `{ ... }` expressions, and
+    // `mutation { ... }` for the schemas that support mutations; `Query::new`
+    // rejects mutations against schemas without a root `Mutation` type
+    let query_type = if query.is_mutation() {
+        ctx.query
+            .schema
+            .mutation_type
+            .as_ref()
+            .expect("a mutation is only accepted when the schema has a Mutation type")
+            .cheap_clone()
+    } else {
+        ctx.query.schema.query_type.cheap_clone()
+    };
     let start = Instant::now();
     let result = execute_root_selection_set(
         ctx.cheap_clone(),
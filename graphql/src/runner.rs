@@ -4,7 +4,9 @@ use std::str::FromStr;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use crate::prelude::{QueryExecutionOptions, StoreResolver, SubscriptionExecutionOptions};
+use crate::prelude::{
+    BlockConstraint, QueryExecutionOptions, StoreResolver, SubscriptionExecutionOptions,
+};
 use crate::query::execute_query;
 use crate::subscription::execute_prepared_subscription;
 use graph::prelude::MetricsRegistry;
@@ -12,8 +14,9 @@ use graph::prometheus::{Gauge, Histogram};
 use graph::{
     components::store::SubscriptionManager,
     prelude::{
-        async_trait, o, CheapClone, DeploymentState, GraphQlRunner as GraphQlRunnerTrait, Logger,
-        Query, QueryExecutionError, Subscription, SubscriptionError, SubscriptionResult,
+        async_trait, o, BlockNumber, CheapClone, DeploymentState,
+        GraphQlRunner as GraphQlRunnerTrait, Logger, Query, QueryExecutionError, Subscription,
+        SubscriptionError, SubscriptionResult, Trace,
     },
 };
 use graph::{data::graphql::effort::LoadManager, prelude::QueryStoreManager};
@@ -182,6 +185,7 @@ where
         max_depth: Option<u8>,
         max_first: Option<u32>,
         max_skip: Option<u32>,
+        block: Option<BlockNumber>,
         result_size: Arc<ResultSizeMetrics>,
     ) -> Result<QueryResults, QueryResults> {
         // We need to use the same `QueryStore` for the entire query to ensure
@@ -207,6 +211,15 @@ where
             .unwrap_or(state);
 
         let max_depth = max_depth.unwrap_or(*GRAPHQL_MAX_DEPTH);
+        // Hold on to the cancel handle, if the server attached one, so
+        // the resolvers can stop doing SQL work for this query when the
+        // client goes away. When identical queries share one execution
+        // through the herd cache, the handle of the client that started
+        // the execution governs it; if that client goes away, the other
+        // clients get a canceled error and need to retry. Since errors
+        // are never cached, that only affects the queries in flight at
+        // that moment
+        let cancel = query.cancel.clone();
         let query = crate::execution::Query::new(
             &self.logger,
             schema,
@@ -222,12 +235,30 @@ where
                 query.query_text.as_ref(),
             )
             .to_result()?;
+        // Per-deployment overrides for the query tunables, if the
+        // operator set any
+        let settings = graph::settings::for_deployment(query.schema.id());
         let by_block_constraint = query.block_constraint()?;
         let mut max_block = 0;
         let mut result: QueryResults = QueryResults::empty();
 
+        // When the client asked for a trace, all resolvers feed into the
+        // same collector so that the response lists every SQL statement
+        // the query caused
+        let trace = if query.trace {
+            Some(Arc::new(Trace::default()))
+        } else {
+            None
+        };
+
         // Note: This will always iterate at least once.
         for (bc, (selection_set, error_policy)) in by_block_constraint {
+            // When the caller pins the query to a block, it overrides any
+            // block constraints in the query itself
+            let bc = match block {
+                Some(number) => BlockConstraint::Number(number),
+                None => bc,
+            };
             let resolver = StoreResolver::at_block(
                 &self.logger,
                 store.cheap_clone(),
@@ -236,6 +267,8 @@ where
                 error_policy,
                 query.schema.id().clone(),
                 result_size.cheap_clone(),
+                trace.cheap_clone(),
+                cancel.clone(),
             )
             .await?;
             max_block = max_block.max(resolver.block_number());
@@ -245,8 +278,14 @@ where
                 resolver.block_ptr.clone(),
                 QueryExecutionOptions {
                     resolver,
-                    deadline: GRAPHQL_QUERY_TIMEOUT.map(|t| Instant::now() + t),
-                    max_first: max_first.unwrap_or(*GRAPHQL_MAX_FIRST),
+                    deadline: settings
+                        .query_timeout()
+                        .or(*GRAPHQL_QUERY_TIMEOUT)
+                        .map(|t| Instant::now() + t),
+                    max_first: settings
+                        .max_first()
+                        .or(max_first)
+                        .unwrap_or(*GRAPHQL_MAX_FIRST),
                     max_skip: max_skip.unwrap_or(*GRAPHQL_MAX_SKIP),
                     load_manager: self.load_manager.clone(),
                 },
@@ -254,6 +293,7 @@ where
             .await;
             result.append(query_res);
         }
+        result.set_trace(trace);
 
         query.log_execution(max_block);
         self.deployment_changed(store.as_ref(), state, max_block as u64)
@@ -297,6 +337,27 @@ where
             max_depth,
             max_first,
             max_skip,
+            None,
+            self.result_size.cheap_clone(),
+        )
+        .await
+        .unwrap_or_else(|e| e)
+    }
+
+    async fn run_query_at_block(
+        self: Arc<Self>,
+        query: Query,
+        target: QueryTarget,
+        block: BlockNumber,
+    ) -> QueryResults {
+        self.execute(
+            query,
+            target,
+            *GRAPHQL_MAX_COMPLEXITY,
+            Some(*GRAPHQL_MAX_DEPTH),
+            Some(*GRAPHQL_MAX_FIRST),
+            Some(*GRAPHQL_MAX_SKIP),
+            Some(block),
             self.result_size.cheap_clone(),
         )
         .await
@@ -333,16 +394,18 @@ where
             return Err(SubscriptionError::GraphQLError(vec![err]));
         }
 
+        let settings = graph::settings::for_deployment(query.schema.id());
+
         execute_prepared_subscription(
             query,
             SubscriptionExecutionOptions {
                 logger: self.logger.clone(),
                 store,
                 subscription_manager: self.subscription_manager.cheap_clone(),
-                timeout: *GRAPHQL_QUERY_TIMEOUT,
+                timeout: settings.query_timeout().or(*GRAPHQL_QUERY_TIMEOUT),
                 max_complexity: *GRAPHQL_MAX_COMPLEXITY,
                 max_depth: *GRAPHQL_MAX_DEPTH,
-                max_first: *GRAPHQL_MAX_FIRST,
+                max_first: settings.max_first().unwrap_or(*GRAPHQL_MAX_FIRST),
                 max_skip: *GRAPHQL_MAX_SKIP,
                 result_size: self.result_size.clone(),
             },
@@ -761,15 +761,18 @@ fn collection_arguments_for_named_type(type_name: &str) -> Vec<InputValue> {
     let args = vec![
         skip,
         first,
+        // `orderBy` and `orderDirection` are lists so that clients can ask
+        // for a stable multi-column sort (e.g. `orderBy: [volume, id]`);
+        // single enum values keep working through list input coercion.
         input_value(
             &"orderBy".to_string(),
             "",
-            Type::NamedType(format!("{}_orderBy", type_name)),
+            Type::ListType(Box::new(Type::NamedType(format!("{}_orderBy", type_name)))),
         ),
         input_value(
             &"orderDirection".to_string(),
             "",
-            Type::NamedType("OrderDirection".to_string()),
+            Type::ListType(Box::new(Type::NamedType("OrderDirection".to_string()))),
         ),
         input_value(
             &"where".to_string(),
@@ -61,6 +61,17 @@ pub fn get_root_query_type_def(schema: &Document) -> Option<&TypeDefinition> {
     })
 }
 
+/// Returns the root mutation type (if there is one).
+pub fn get_root_mutation_type_def(schema: &Document) -> Option<&TypeDefinition> {
+    schema.definitions.iter().find_map(|d| match d {
+        Definition::TypeDefinition(def @ TypeDefinition::Object(_)) => match def {
+            TypeDefinition::Object(t) if t.name == "Mutation" => Some(def),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
 /// Returns all type definitions in the schema.
 pub fn get_type_definitions(schema: &Document) -> Vec<&TypeDefinition> {
     schema
@@ -4,22 +4,23 @@
 use anyhow::{anyhow, Error};
 use graph::constraint_violation;
 use graph::prelude::{r, CacheWeight};
-use graph::slog::warn;
+use graph::slog::{debug, warn};
 use graph::util::cache_weight;
 use indexmap::IndexMap;
 use lazy_static::lazy_static;
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::iter::once;
 use std::rc::Rc;
+use std::sync::Arc;
 use std::time::Instant;
 
 use graph::{components::store::EntityType, data::graphql::*};
 use graph::{
     data::graphql::ext::DirectiveFinder,
     prelude::{
-        q, s, ApiSchema, AttributeNames, BlockNumber, ChildMultiplicity, EntityCollection,
-        EntityFilter, EntityLink, EntityOrder, EntityWindow, Logger, ParentLink,
-        QueryExecutionError, QueryStore, StoreError, Value as StoreValue, WindowAttribute,
+        q, s, ApiSchema, AttributeNames, BlockNumber, CancelHandle, CheapClone, ChildMultiplicity,
+        EntityCollection, EntityFilter, EntityLink, EntityOrder, EntityWindow, Logger, ParentLink,
+        QueryExecutionError, QueryStore, StoreError, Trace, Value as StoreValue, WindowAttribute,
     },
 };
 
@@ -52,7 +53,7 @@ type GroupedFieldSet<'a> = IndexMap<&'a str, CollectedResponseKey<'a>>;
 
 /// Used for associating objects or interfaces and the field names used in `orderBy` query field
 /// attributes.
-type ComplementaryFields<'a> = BTreeMap<ObjectOrInterface<'a>, String>;
+type ComplementaryFields<'a> = BTreeMap<ObjectOrInterface<'a>, Vec<String>>;
 
 /// An `ObjectType` with `Hash` and `Eq` derived from the name.
 #[derive(Clone, Debug)]
@@ -100,7 +101,7 @@ struct Node {
     /// the keys and values of the `children` map, but not of the map itself
     children_weight: usize,
 
-    entity: BTreeMap<String, r::Value>,
+    entity: r::Object,
     /// We are using an `Rc` here for two reasons: it allows us to defer
     /// copying objects until the end, when converting to `q::Value` forces
     /// us to copy any child that is referenced by multiple parents. It also
@@ -144,8 +145,8 @@ struct Node {
     children: BTreeMap<String, Vec<Rc<Node>>>,
 }
 
-impl From<BTreeMap<String, r::Value>> for Node {
-    fn from(entity: BTreeMap<String, r::Value>) -> Self {
+impl From<r::Object> for Node {
+    fn from(entity: r::Object) -> Self {
         Node {
             children_weight: entity.weight(),
             entity,
@@ -190,7 +191,7 @@ fn is_root_node<'a>(mut nodes: impl Iterator<Item = &'a Node>) -> bool {
 }
 
 fn make_root_node() -> Vec<Node> {
-    let entity = BTreeMap::new();
+    let entity = r::Object::new();
     vec![Node {
         children_weight: entity.weight(),
         entity,
@@ -541,7 +542,7 @@ pub fn run(
 ) -> Result<r::Value, Vec<QueryExecutionError>> {
     execute_root_selection_set(resolver, ctx, selection_set).map(|nodes| {
         result_size.observe(nodes.weight());
-        let map = BTreeMap::default();
+        let map = r::Object::default();
         r::Value::Object(nodes.into_iter().fold(map, |mut map, node| {
             // For root nodes, we only care about the children
             for (key, nodes) in node.children.into_iter() {
@@ -928,10 +929,20 @@ fn collect_fields_inner<'a>(
                             ));
                         match arguments {
                             graphql_parser::schema::Value::Enum(complementary_field_name) => {
-                                complementary_fields.insert(
-                                    object_or_interface_for_field,
-                                    complementary_field_name.clone(),
-                                );
+                                complementary_fields
+                                    .entry(object_or_interface_for_field)
+                                    .or_default()
+                                    .push(complementary_field_name.clone());
+                            }
+                            graphql_parser::schema::Value::List(values) => {
+                                let names = complementary_fields
+                                    .entry(object_or_interface_for_field)
+                                    .or_default();
+                                for value in values {
+                                    if let graphql_parser::schema::Value::Enum(name) = value {
+                                        names.push(name.clone());
+                                    }
+                                }
                             }
                             _ => unimplemented!("unsure on what to do about other variants"),
                         }
@@ -1003,6 +1014,8 @@ fn execute_field(
         ctx.max_skip,
         ctx.query.query_id.clone(),
         collected_column_names,
+        resolver.trace.cheap_clone(),
+        resolver.cancel.clone(),
     )
     .map_err(|e| vec![e])
 }
@@ -1023,6 +1036,8 @@ fn fetch(
     max_skip: u32,
     query_id: String,
     collected_column_names: AttributeNamesByObjectType<'_>,
+    trace: Option<Arc<Trace>>,
+    cancel: Option<CancelHandle>,
 ) -> Result<Vec<Node>, QueryExecutionError> {
     let mut query = build_query(
         join.child_type,
@@ -1041,7 +1056,9 @@ fn fetch(
         query.order = EntityOrder::Unordered;
     }
 
-    query.logger = Some(logger);
+    query.logger = Some(logger.clone());
+    query.trace = trace;
+    query.cancel = cancel;
     if let Some(r::Value::String(id)) = arguments.get(ARG_ID.as_str()) {
         query.filter = Some(
             EntityFilter::Equal(ARG_ID.to_owned(), StoreValue::from(id.to_owned()))
@@ -1058,9 +1075,35 @@ fn fetch(
         }
         query.collection = EntityCollection::Window(windows);
     }
-    store
-        .find_query_values(query)
-        .map(|entities| entities.into_iter().map(|entity| entity.into()).collect())
+    let first = query.range.first;
+    store.find_query_values(query).map(|entities| {
+        if let (ChildMultiplicity::Many, Some(first)) = (multiplicity, first) {
+            report_capped_children(&logger, &entities, first as usize, join.child_type.name());
+        }
+        entities.into_iter().map(|entity| entity.into()).collect()
+    })
+}
+
+/// Log when a query returned as many children for one parent as the `first`
+/// argument allows. The collection was then most likely capped, and clients
+/// that want to see all children need to page through them with `first` and
+/// `skip`. Without this, collections are cut off silently, since the default
+/// for `first` applies even when the query does not mention it
+fn report_capped_children(logger: &Logger, children: &[r::Object], first: usize, child_type: &str) {
+    let mut counts: HashMap<Option<&str>, usize> = HashMap::new();
+    for child in children {
+        let parent = child.get("g$parent_id").and_then(|value| value.as_str());
+        *counts.entry(parent).or_insert(0) += 1;
+    }
+    if counts.values().any(|count| *count >= first) {
+        debug!(
+            logger,
+            "a derived collection hit the `first` limit and may be incomplete; \
+             clients can page through it with `first` and `skip`";
+            "child_type" => child_type,
+            "first" => first,
+        );
+    }
 }
 
 /// Represents a finished column collection operation, mapping each object type to the final set of
@@ -1085,10 +1128,12 @@ impl<'a> CollectedAttributeNames<'a> {
         complementary_fields: &mut ComplementaryFields<'a>,
     ) {
         for (object_or_interface, selected_attributes) in self.0.iter_mut() {
-            if let Some(complementary_field_name) =
+            if let Some(complementary_field_names) =
                 complementary_fields.remove(&object_or_interface)
             {
-                selected_attributes.add_str(&complementary_field_name)
+                for complementary_field_name in complementary_field_names {
+                    selected_attributes.add_str(&complementary_field_name)
+                }
             }
         }
     }
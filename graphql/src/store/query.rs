@@ -7,12 +7,6 @@ use graph::{components::store::EntityType, data::graphql::ObjectOrInterface};
 use crate::schema::ast as sast;
 use crate::store::prefetch::ObjectCondition;
 
-#[derive(Debug)]
-enum OrderDirection {
-    Ascending,
-    Descending,
-}
-
 /// Builds a EntityQuery from GraphQL arguments.
 ///
 /// Panics if `entity` is not present in `schema`.
@@ -49,17 +43,32 @@ pub fn build_query<'a>(
     if let Some(filter) = build_filter(entity, arguments)? {
         query = query.filter(filter);
     }
-    let order = match (
-        build_order_by(entity, arguments)?,
-        build_order_direction(arguments)?,
-    ) {
-        (Some((attr, value_type)), OrderDirection::Ascending) => {
-            EntityOrder::Ascending(attr, value_type)
-        }
-        (Some((attr, value_type)), OrderDirection::Descending) => {
-            EntityOrder::Descending(attr, value_type)
+    let keys = build_order_by(entity, arguments)?;
+    let mut directions = build_order_direction(arguments)?;
+    // When fewer directions than keys are given, the last direction applies
+    // to the remaining keys; with no directions at all, keys sort ascending
+    if directions.is_empty() {
+        directions.push(OrderDirection::Ascending);
+    }
+    let last_direction = *directions.last().unwrap();
+    let order = match keys.len() {
+        0 => EntityOrder::Default,
+        1 => {
+            let (attr, value_type) = keys.into_iter().next().unwrap();
+            match directions[0] {
+                OrderDirection::Ascending => EntityOrder::Ascending(attr, value_type),
+                OrderDirection::Descending => EntityOrder::Descending(attr, value_type),
+            }
         }
-        (None, _) => EntityOrder::Default,
+        _ => EntityOrder::Multiple(
+            keys.into_iter()
+                .enumerate()
+                .map(|(i, (attr, value_type))| {
+                    let direction = directions.get(i).copied().unwrap_or(last_direction);
+                    (attr, value_type, direction)
+                })
+                .collect(),
+        ),
     };
     query = query.order(order);
     Ok(query)
@@ -125,14 +134,14 @@ fn build_filter(
 }
 
 fn build_fulltext_filter_from_object(
-    object: &BTreeMap<String, r::Value>,
+    object: &r::Object,
 ) -> Result<Option<EntityFilter>, QueryExecutionError> {
     object.iter().next().map_or(
         Err(QueryExecutionError::FulltextQueryRequiresFilter),
         |(key, value)| {
             if let r::Value::String(s) = value {
                 Ok(Some(EntityFilter::Equal(
-                    key.clone(),
+                    key.to_string(),
                     Value::String(s.clone()),
                 )))
             } else {
@@ -145,7 +154,7 @@ fn build_fulltext_filter_from_object(
 /// Parses a GraphQL input object into an EntityFilter, if present.
 fn build_filter_from_object(
     entity: ObjectOrInterface,
-    object: &BTreeMap<String, r::Value>,
+    object: &r::Object,
 ) -> Result<Option<EntityFilter>, QueryExecutionError> {
     Ok(Some(EntityFilter::And({
         object
@@ -214,41 +223,57 @@ fn list_values(value: Value, filter_type: &str) -> Result<Vec<Value>, QueryExecu
     }
 }
 
-/// Parses GraphQL arguments into an field name to order by, if present.
+/// Parses GraphQL arguments into the field names to order by, if present.
+/// `orderBy` accepts either a single enum value or a list of them; a list
+/// turns into a multi-column sort with the listed priority.
 fn build_order_by(
     entity: ObjectOrInterface,
     arguments: &HashMap<&str, r::Value>,
-) -> Result<Option<(String, ValueType)>, QueryExecutionError> {
+) -> Result<Vec<(String, ValueType)>, QueryExecutionError> {
+    fn lookup(
+        entity: ObjectOrInterface,
+        name: &str,
+    ) -> Result<(String, ValueType), QueryExecutionError> {
+        let field = sast::get_field(entity, name).ok_or_else(|| {
+            QueryExecutionError::EntityFieldError(entity.name().to_owned(), name.to_owned())
+        })?;
+        sast::get_field_value_type(&field.field_type)
+            .map(|value_type| (name.to_owned(), value_type))
+            .map_err(|_| {
+                QueryExecutionError::OrderByNotSupportedError(
+                    entity.name().to_owned(),
+                    name.to_owned(),
+                )
+            })
+    }
+
     match arguments.get("orderBy") {
-        Some(r::Value::Enum(name)) => {
-            let field = sast::get_field(entity, name).ok_or_else(|| {
-                QueryExecutionError::EntityFieldError(entity.name().to_owned(), name.clone())
-            })?;
-            sast::get_field_value_type(&field.field_type)
-                .map(|value_type| Some((name.to_owned(), value_type)))
-                .map_err(|_| {
-                    QueryExecutionError::OrderByNotSupportedError(
-                        entity.name().to_owned(),
-                        name.clone(),
-                    )
-                })
-        }
+        Some(r::Value::Enum(name)) => Ok(vec![lookup(entity, name)?]),
+        Some(r::Value::List(values)) => values
+            .iter()
+            .map(|value| match value {
+                r::Value::Enum(name) => lookup(entity, name),
+                _ => Err(QueryExecutionError::InvalidFilterError),
+            })
+            .collect(),
         _ => match arguments.get("text") {
-            Some(r::Value::Object(filter)) => build_fulltext_order_by_from_object(filter),
-            None => Ok(None),
+            Some(r::Value::Object(filter)) => Ok(build_fulltext_order_by_from_object(filter)?
+                .into_iter()
+                .collect()),
+            None => Ok(vec![]),
             _ => Err(QueryExecutionError::InvalidFilterError),
         },
     }
 }
 
 fn build_fulltext_order_by_from_object(
-    object: &BTreeMap<String, r::Value>,
+    object: &r::Object,
 ) -> Result<Option<(String, ValueType)>, QueryExecutionError> {
     object.iter().next().map_or(
         Err(QueryExecutionError::FulltextQueryRequiresFilter),
         |(key, value)| {
             if let r::Value::String(_) = value {
-                Ok(Some((key.clone(), ValueType::String)))
+                Ok(Some((key.to_string(), ValueType::String)))
             } else {
                 Err(QueryExecutionError::FulltextQueryRequiresFilter)
             }
@@ -256,18 +281,24 @@ fn build_fulltext_order_by_from_object(
     )
 }
 
-/// Parses GraphQL arguments into a EntityOrder, if present.
+/// Parses GraphQL arguments into the order directions, if present. As with
+/// `orderBy`, both a single enum value and a list are accepted; directions
+/// are matched up with order keys by position.
 fn build_order_direction(
     arguments: &HashMap<&str, r::Value>,
-) -> Result<OrderDirection, QueryExecutionError> {
-    Ok(arguments
-        .get("orderDirection")
-        .map(|value| match value {
-            r::Value::Enum(name) if name == "asc" => OrderDirection::Ascending,
+) -> Result<Vec<OrderDirection>, QueryExecutionError> {
+    fn direction(value: &r::Value) -> OrderDirection {
+        match value {
             r::Value::Enum(name) if name == "desc" => OrderDirection::Descending,
             _ => OrderDirection::Ascending,
-        })
-        .unwrap_or(OrderDirection::Ascending))
+        }
+    }
+
+    Ok(match arguments.get("orderDirection") {
+        Some(r::Value::List(values)) => values.iter().map(direction).collect(),
+        Some(value) => vec![direction(value)],
+        None => vec![],
+    })
 }
 
 /// Parses the subgraph ID from the ObjectType directives.
@@ -724,7 +755,7 @@ mod tests {
         let mut args = default_arguments();
         args.insert(
             &whre,
-            r::Value::Object(BTreeMap::from_iter(vec![(
+            r::Value::Object(r::Object::from_iter(vec![(
                 "name_ends_with".to_string(),
                 r::Value::String("ello".to_string()),
             )])),
@@ -1,4 +1,4 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::HashMap;
 use std::result;
 use std::sync::Arc;
 
@@ -28,6 +28,11 @@ pub struct StoreResolver {
     has_non_fatal_errors: bool,
     error_policy: ErrorPolicy,
     result_size: Arc<ResultSizeMetrics>,
+    /// When set, the SQL statements run for this query are collected here
+    pub(crate) trace: Option<Arc<Trace>>,
+    /// When set, the handle becomes canceled if the client that submitted
+    /// the query goes away, and the store stops running SQL for it
+    pub(crate) cancel: Option<CancelHandle>,
 }
 
 impl CheapClone for StoreResolver {}
@@ -55,6 +60,10 @@ impl StoreResolver {
             has_non_fatal_errors: false,
             error_policy: ErrorPolicy::Deny,
             result_size,
+
+            // Tracing and cancellation are not supported for subscriptions
+            trace: None,
+            cancel: None,
         }
     }
 
@@ -71,10 +80,12 @@ impl StoreResolver {
         error_policy: ErrorPolicy,
         deployment: DeploymentHash,
         result_size: Arc<ResultSizeMetrics>,
+        trace: Option<Arc<Trace>>,
+        cancel: Option<CancelHandle>,
     ) -> Result<Self, QueryExecutionError> {
         let store_clone = store.cheap_clone();
         let deployment2 = deployment.clone();
-        let block_ptr = graph::spawn_blocking_allow_panic(move || {
+        let block_ptr = graph::spawn_query_blocking_allow_panic(move || {
             Self::locate_block(store_clone.as_ref(), bc, deployment2.clone())
         })
         .await
@@ -94,6 +105,8 @@ impl StoreResolver {
             has_non_fatal_errors,
             error_policy,
             result_size,
+            trace,
+            cancel,
         };
         Ok(resolver)
     }
@@ -201,7 +214,7 @@ impl StoreResolver {
                 .as_ref()
                 .map(|ptr| r::Value::Int((ptr.number as i32).into()))
                 .unwrap_or(r::Value::Null);
-            let mut map = BTreeMap::new();
+            let mut map = r::Object::new();
             let block = object! {
                 hash: hash,
                 number: number,
@@ -216,6 +229,12 @@ impl StoreResolver {
                 "hasIndexingErrors".to_string(),
                 r::Value::Boolean(self.has_non_fatal_errors),
             );
+            map.insert(
+                "finalBlocksOnly".to_string(),
+                r::Value::Boolean(
+                    graph::settings::for_deployment(&self.deployment).final_blocks_only(),
+                ),
+            );
             map.insert(
                 "__typename".to_string(),
                 r::Value::String(META_FIELD_TYPE.to_string()),
@@ -328,8 +347,8 @@ impl Resolver for StoreResolver {
             // or a different field queried under the response key `_meta`.
             ErrorPolicy::Deny => {
                 let data = result.take_data();
-                let meta = data.and_then(|mut d| d.remove_entry("_meta"));
-                result.set_data(meta.map(|m| BTreeMap::from_iter(Some(m))));
+                let meta = data.and_then(|mut d| d.remove("_meta"));
+                result.set_data(meta.map(|m| r::Object::from_iter(Some(("_meta", m)))));
             }
             ErrorPolicy::Allow => (),
         }
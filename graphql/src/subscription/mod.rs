@@ -97,6 +97,7 @@ fn create_source_event_stream(
         max_first: options.max_first,
         max_skip: options.max_skip,
         cache_status: Default::default(),
+        nulled_errors: Default::default(),
     };
 
     let subscription_type = ctx
@@ -198,6 +199,8 @@ async fn execute_subscription_event(
         ErrorPolicy::Deny,
         query.schema.id().clone(),
         result_size,
+        None,
+        None,
     )
     .await
     {
@@ -216,6 +219,7 @@ async fn execute_subscription_event(
         max_first,
         max_skip,
         cache_status: Default::default(),
+        nulled_errors: Default::default(),
     });
 
     let subscription_type = match ctx.query.schema.subscription_type.as_ref() {
@@ -1,7 +1,7 @@
 use crate::schema;
 use graph::prelude::s::{EnumType, InputValue, ScalarType, Type, TypeDefinition};
 use graph::prelude::{q, r, QueryExecutionError};
-use std::collections::{BTreeMap, HashMap};
+use std::collections::HashMap;
 
 /// A GraphQL value that can be coerced according to a type.
 pub trait MaybeCoercible<T> {
@@ -74,7 +74,7 @@ fn coerce_to_definition<'a>(
         TypeDefinition::InputObject(t) => match value {
             q::Value::Object(object) => {
                 let object_for_error = q::Value::Object(object.clone());
-                let mut coerced_object = BTreeMap::new();
+                let mut coerced_object = r::Object::new();
                 for (name, value) in object {
                     let def = t
                         .fields
@@ -188,8 +188,21 @@ pub(crate) fn coerce_value<'a>(
             Ok(r::Value::List(coerced_values))
         }
 
-        // Otherwise the list type is not coercible.
-        (Type::ListType(_), value) => Err(value),
+        // Per the GraphQL spec, a non-list value is coerced into a list of
+        // size one. This also keeps queries passing a single `orderBy` value
+        // working now that the argument is a list.
+        (Type::ListType(_), value) => {
+            let t = match ty {
+                Type::ListType(ty) => ty,
+                _ => unreachable!(),
+            };
+            Ok(r::Value::List(vec![coerce_value(
+                value,
+                t,
+                resolver,
+                variable_values,
+            )?]))
+        }
     }
 }
 
@@ -561,6 +561,7 @@ async fn introspection_query(schema: Schema, query: &str) -> QueryResult {
     let query = Query::new(
         graphql_parser::parse_query(query).unwrap().into_static(),
         None,
+        false,
     );
 
     // Execute it
@@ -7,7 +7,7 @@ use std::iter::FromIterator;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use std::{
-    collections::{BTreeMap, BTreeSet, HashMap},
+    collections::{BTreeSet, HashMap},
     marker::PhantomData,
 };
 
@@ -263,7 +263,7 @@ async fn execute_query_document_with_variables(
         METRICS_REGISTRY.clone(),
     ));
     let target = QueryTarget::Deployment(id.clone());
-    let query = Query::new(query, variables);
+    let query = Query::new(query, variables, false);
 
     runner
         .run_query_with_complexity(query, target, None, None, None, None)
@@ -392,6 +392,132 @@ fn can_query_one_to_one_relationship() {
     })
 }
 
+#[test]
+fn can_paginate_derived_collections() {
+    run_test_sequentially(|store| async move {
+        let deployment = setup(store.as_ref());
+        let result = execute_query_document(
+            &deployment.hash,
+            graphql_parser::parse_query(
+                "
+        query {
+            musicians(first: 100, orderBy: id) {
+                name
+                writtenSongs(first: 1, skip: 1, orderBy: id) {
+                    title
+                }
+            }
+        }
+        ",
+            )
+            .expect("Invalid test query")
+            .into_static(),
+        )
+        .await;
+
+        // The window applies per parent: John has two songs and we see the
+        // second one; everybody else has at most one song and gets nothing
+        assert_eq!(
+            extract_data!(result),
+            Some(object_value(vec![(
+                "musicians",
+                r::Value::List(vec![
+                    object_value(vec![
+                        ("name", r::Value::String(String::from("John"))),
+                        (
+                            "writtenSongs",
+                            r::Value::List(vec![object_value(vec![(
+                                "title",
+                                r::Value::String(String::from("Pop Tune"))
+                            )])]),
+                        ),
+                    ]),
+                    object_value(vec![
+                        ("name", r::Value::String(String::from("Lisa"))),
+                        ("writtenSongs", r::Value::List(vec![])),
+                    ]),
+                    object_value(vec![
+                        ("name", r::Value::String(String::from("Tom"))),
+                        ("writtenSongs", r::Value::List(vec![])),
+                    ]),
+                    object_value(vec![
+                        ("name", r::Value::String(String::from("Valerie"))),
+                        ("writtenSongs", r::Value::List(vec![])),
+                    ]),
+                ])
+            )]))
+        )
+    })
+}
+
+#[test]
+fn can_filter_derived_collections() {
+    run_test_sequentially(|store| async move {
+        let deployment = setup(store.as_ref());
+        let result = execute_query_document(
+            &deployment.hash,
+            graphql_parser::parse_query(
+                "
+        query {
+            musicians(first: 100, orderBy: id) {
+                name
+                writtenSongs(first: 100, orderBy: id, where: { title_not: \"Pop Tune\" }) {
+                    title
+                }
+            }
+        }
+        ",
+            )
+            .expect("Invalid test query")
+            .into_static(),
+        )
+        .await;
+
+        assert_eq!(
+            extract_data!(result),
+            Some(object_value(vec![(
+                "musicians",
+                r::Value::List(vec![
+                    object_value(vec![
+                        ("name", r::Value::String(String::from("John"))),
+                        (
+                            "writtenSongs",
+                            r::Value::List(vec![object_value(vec![(
+                                "title",
+                                r::Value::String(String::from("Cheesy Tune"))
+                            )])]),
+                        ),
+                    ]),
+                    object_value(vec![
+                        ("name", r::Value::String(String::from("Lisa"))),
+                        (
+                            "writtenSongs",
+                            r::Value::List(vec![object_value(vec![(
+                                "title",
+                                r::Value::String(String::from("Rock Tune"))
+                            )])]),
+                        ),
+                    ]),
+                    object_value(vec![
+                        ("name", r::Value::String(String::from("Tom"))),
+                        (
+                            "writtenSongs",
+                            r::Value::List(vec![object_value(vec![(
+                                "title",
+                                r::Value::String(String::from("Folk Tune"))
+                            )])]),
+                        ),
+                    ]),
+                    object_value(vec![
+                        ("name", r::Value::String(String::from("Valerie"))),
+                        ("writtenSongs", r::Value::List(vec![])),
+                    ]),
+                ])
+            )]))
+        )
+    })
+}
+
 #[test]
 fn can_query_one_to_many_relationships_in_both_directions() {
     run_test_sequentially(|store| async move {
@@ -794,6 +920,7 @@ fn query_complexity() {
             .unwrap()
             .into_static(),
             None,
+            false,
         );
         let max_complexity = Some(1_010_100);
 
@@ -827,6 +954,7 @@ fn query_complexity() {
             .unwrap()
             .into_static(),
             None,
+            false,
         );
 
         // The extra introspection causes the complexity to go over.
@@ -870,6 +998,7 @@ fn query_complexity_subscriptions() {
             .unwrap()
             .into_static(),
             None,
+            false,
         );
         let max_complexity = Some(1_010_100);
         let options = SubscriptionExecutionOptions {
@@ -912,6 +1041,7 @@ fn query_complexity_subscriptions() {
             .unwrap()
             .into_static(),
             None,
+            false,
         );
 
         let store = STORE
@@ -953,6 +1083,7 @@ fn instant_timeout() {
                 .unwrap()
                 .into_static(),
             None,
+            false,
         );
 
         match first_result(
@@ -1298,6 +1429,7 @@ fn subscription_gets_result_even_without_events() {
             .unwrap()
             .into_static(),
             None,
+            false,
         );
 
         let options = SubscriptionExecutionOptions {
@@ -1537,8 +1669,8 @@ fn query_at_block_with_vars() {
             check_musicians_at(&deployment.hash, query, var, expected.clone(), qid).await;
 
             let query = "query by_nr($block: Block_height!) { musicians(block: $block) { id } }";
-            let mut map = BTreeMap::new();
-            map.insert("number".to_owned(), number);
+            let mut map = r::Object::new();
+            map.insert("number", number);
             let block = r::Value::Object(map);
             let var = Some(("block", block));
 
@@ -0,0 +1,179 @@
+//! Scripting of chain reorgs against the in-memory chain store, plus
+//! assertion helpers for checking that indexing through a reorg (revert and
+//! reapply) leaves the same entity state behind as a straight-line run.
+
+use std::collections::BTreeMap;
+
+use graph::blockchain::Block;
+use graph::prelude::web3::types::H256;
+use graph::prelude::*;
+
+use crate::store::{InMemoryStore, MockChainStore};
+
+/// A block on the mock chain. The hash is derived from the block number and
+/// the fork the block is on, so scripted chains are fully deterministic
+#[derive(Clone, Debug)]
+pub struct MockBlock {
+    ptr: BlockPtr,
+    parent: Option<BlockPtr>,
+}
+
+impl Block for MockBlock {
+    fn ptr(&self) -> BlockPtr {
+        self.ptr.clone()
+    }
+
+    fn parent_ptr(&self) -> Option<BlockPtr> {
+        self.parent.clone()
+    }
+}
+
+/// A scriptable mock chain: advance the canonical chain block by block,
+/// or fork it at an earlier block and grow a new canonical chain from
+/// there. All blocks are recorded in the underlying `MockChainStore` and
+/// the chain head is moved along
+pub struct MockChain {
+    store: Arc<MockChainStore>,
+    canonical: Vec<MockBlock>,
+    /// Bumped on every reorg so that blocks on the new chain get hashes
+    /// different from the blocks they replace
+    fork: u32,
+}
+
+impl MockChain {
+    pub fn new(network: String) -> Self {
+        let genesis = Self::make_block(0, 0, None);
+        let store = Arc::new(MockChainStore::new(network, genesis.ptr()));
+        Self {
+            store,
+            canonical: vec![genesis],
+            fork: 0,
+        }
+    }
+
+    pub fn chain_store(&self) -> Arc<MockChainStore> {
+        self.store.clone()
+    }
+
+    /// The current head of the canonical chain
+    pub fn head(&self) -> BlockPtr {
+        // Unwrap: the chain always contains at least the genesis block
+        self.canonical.last().unwrap().ptr()
+    }
+
+    /// The pointer to the canonical block with the given number. Panics if
+    /// the chain has not grown that far
+    pub fn ptr(&self, number: BlockNumber) -> BlockPtr {
+        self.canonical[number as usize].ptr()
+    }
+
+    /// Grow the canonical chain by `count` blocks and return the pointers
+    /// to the new blocks, in ascending order
+    pub async fn advance(&mut self, count: BlockNumber) -> Vec<BlockPtr> {
+        let mut added = Vec::new();
+        for _ in 0..count {
+            let parent = self.canonical.last().unwrap().ptr();
+            let block = Self::make_block(parent.number + 1, self.fork, Some(parent));
+            added.push(block.ptr());
+            self.canonical.push(block);
+        }
+        self.update_store(&added).await;
+        added
+    }
+
+    /// Fork the chain at block `fork_at`: all canonical blocks past it are
+    /// replaced, and the new canonical chain grows to `new_height`. Returns
+    /// the pointers to the blocks of the new fork, in ascending order
+    pub async fn reorg(&mut self, fork_at: BlockNumber, new_height: BlockNumber) -> Vec<BlockPtr> {
+        assert!(
+            fork_at < self.head().number,
+            "a reorg must fork below the chain head"
+        );
+        assert!(
+            new_height > fork_at,
+            "the new chain must grow past the fork block"
+        );
+        self.fork += 1;
+        self.canonical.truncate(fork_at as usize + 1);
+        self.advance(new_height - fork_at).await
+    }
+
+    fn make_block(number: BlockNumber, fork: u32, parent: Option<BlockPtr>) -> MockBlock {
+        let hash = H256::from_low_u64_be(((fork as u64) << 32) | number as u64);
+        MockBlock {
+            ptr: BlockPtr::from((hash, number as u64)),
+            parent,
+        }
+    }
+
+    async fn update_store(&self, added: &[BlockPtr]) {
+        use graph::prelude::ChainStore as _;
+
+        for ptr in added {
+            let block = self.canonical[ptr.number as usize].clone();
+            // Unwrap: upserts into the in-memory store do not fail
+            self.store.upsert_block(Arc::new(block)).await.unwrap();
+        }
+        let head = self.canonical.last().unwrap().clone();
+        self.store
+            .clone()
+            .set_chain_head(Arc::new(head), "".to_string())
+            .await
+            .unwrap();
+    }
+}
+
+/// Panic unless the two stores hold exactly the same latest entity
+/// versions. The deployment hashes of the two stores may differ; entities
+/// are matched by type and id. Use this to check that entity state after
+/// revert and reapply matches a straight-line indexing run
+pub fn assert_entities_eq(left: &InMemoryStore, right: &InMemoryStore) {
+    fn by_type_and_id(store: &InMemoryStore) -> BTreeMap<(String, String), Entity> {
+        store
+            .entity_snapshot()
+            .into_iter()
+            .map(|(key, entity)| ((key.entity_type.into_string(), key.entity_id), entity))
+            .collect()
+    }
+
+    let left = by_type_and_id(left);
+    let right = by_type_and_id(right);
+
+    for key in left.keys() {
+        assert!(
+            right.contains_key(key),
+            "entity {:?} exists in the left store but not in the right one",
+            key
+        );
+    }
+    for key in right.keys() {
+        assert!(
+            left.contains_key(key),
+            "entity {:?} exists in the right store but not in the left one",
+            key
+        );
+    }
+    for (key, left_entity) in &left {
+        let right_entity = &right[key];
+        assert!(
+            left_entity == right_entity,
+            "entity {:?} differs between the stores: {:?} != {:?}",
+            key,
+            left_entity,
+            right_entity
+        );
+    }
+}
+
+/// Panic unless the store's block pointer is at `ptr`
+pub fn assert_block_ptr(store: &InMemoryStore, ptr: &BlockPtr) {
+    use graph::components::store::WritableStore as _;
+
+    let actual = store.block_ptr().unwrap();
+    assert!(
+        actual.as_ref() == Some(ptr),
+        "expected the store to be at block {}, but it is at {:?}",
+        ptr,
+        actual
+    );
+}
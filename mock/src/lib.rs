@@ -1,3 +1,11 @@
+#[cfg(feature = "store")]
+pub mod chain;
 mod metrics_registry;
+#[cfg(feature = "store")]
+pub mod store;
 
+#[cfg(feature = "store")]
+pub use self::chain::{assert_block_ptr, assert_entities_eq, MockBlock, MockChain};
 pub use self::metrics_registry::MockMetricsRegistry;
+#[cfg(feature = "store")]
+pub use self::store::{InMemoryStore, MockBlockStore, MockChainStore, MockSubscriptionManager};
@@ -0,0 +1,552 @@
+//! Simple in-memory implementations of the store traits so that components
+//! like the GraphQL runner or the subgraph instance manager can be exercised
+//! without a Postgres instance. Entities are kept with their full version
+//! history so that block reverts behave like they do in the real store.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use graph::blockchain::Block;
+use graph::components::store::{
+    BlockStore, EntityType, StoredDynamicDataSource, SubscriptionManager, UnitStream, WritableStore,
+};
+use graph::data::subgraph::schema::{ErrorClass, SubgraphError, SubgraphHealth};
+use graph::prelude::tokio::sync::mpsc;
+use graph::prelude::web3::types::H256;
+use graph::prelude::*;
+use graph::tokio_stream::wrappers::UnboundedReceiverStream;
+
+/// A `SubscriptionManager` that distributes store events that are handed to
+/// it through `send_store_event`, usually by an `InMemoryStore`
+pub struct MockSubscriptionManager {
+    next_id: AtomicUsize,
+    subscriptions: Mutex<
+        HashMap<
+            usize,
+            (
+                BTreeSet<SubscriptionFilter>,
+                mpsc::UnboundedSender<Arc<StoreEvent>>,
+            ),
+        >,
+    >,
+    subscriptions_no_payload:
+        Mutex<HashMap<usize, (BTreeSet<SubscriptionFilter>, mpsc::UnboundedSender<()>)>>,
+}
+
+impl MockSubscriptionManager {
+    pub fn new() -> Self {
+        Self {
+            next_id: AtomicUsize::new(0),
+            subscriptions: Mutex::new(HashMap::new()),
+            subscriptions_no_payload: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Deliver `event` to all matching subscribers. Subscriptions whose
+    /// receiving end has been dropped are removed
+    pub fn send_store_event(&self, event: StoreEvent) {
+        let event = Arc::new(event);
+        self.subscriptions.lock().unwrap().retain(|_, sub| {
+            if event.matches(&sub.0) {
+                sub.1.send(event.cheap_clone()).is_ok()
+            } else {
+                !sub.1.is_closed()
+            }
+        });
+        self.subscriptions_no_payload
+            .lock()
+            .unwrap()
+            .retain(|_, sub| {
+                if event.matches(&sub.0) {
+                    sub.1.send(()).is_ok()
+                } else {
+                    !sub.1.is_closed()
+                }
+            });
+    }
+}
+
+impl SubscriptionManager for MockSubscriptionManager {
+    fn subscribe(&self, entities: BTreeSet<SubscriptionFilter>) -> StoreEventStreamBox {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .insert(id, (entities.clone(), sender));
+        StoreEventStream::new(Box::new(
+            UnboundedReceiverStream::new(receiver).map(Ok).compat(),
+        ))
+        .filter_by_entities(entities)
+    }
+
+    fn subscribe_no_payload(&self, entities: BTreeSet<SubscriptionFilter>) -> UnitStream {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.subscriptions_no_payload
+            .lock()
+            .unwrap()
+            .insert(id, (entities, sender));
+        Box::new(UnboundedReceiverStream::new(receiver))
+    }
+}
+
+/// The value of one entity at one block; `None` means the entity was
+/// deleted at that block
+type EntityVersion = (BlockNumber, Option<Entity>);
+
+#[derive(Default)]
+struct State {
+    block_ptr: Option<BlockPtr>,
+    cursor: Option<String>,
+    /// For each entity, its versions in ascending block order
+    entities: BTreeMap<EntityKey, Vec<EntityVersion>>,
+    data_sources: Vec<(BlockNumber, StoredDynamicDataSource)>,
+    errors: Vec<SubgraphError>,
+    health: Option<SubgraphHealth>,
+    synced: bool,
+}
+
+/// An in-memory `WritableStore` for a single deployment. Entity versions
+/// are kept per block so that `revert_block_operations` can restore the
+/// state as of an earlier block. Store events for transacted and reverted
+/// blocks are sent through the associated `MockSubscriptionManager`
+pub struct InMemoryStore {
+    id: DeploymentHash,
+    subscriptions: Arc<MockSubscriptionManager>,
+    state: Mutex<State>,
+}
+
+impl InMemoryStore {
+    pub fn new(id: DeploymentHash, subscriptions: Arc<MockSubscriptionManager>) -> Self {
+        Self {
+            id,
+            subscriptions,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    pub fn subscription_manager(&self) -> Arc<MockSubscriptionManager> {
+        self.subscriptions.clone()
+    }
+
+    /// The latest version of every entity in the store. Deleted entities
+    /// are not part of the snapshot
+    pub fn entity_snapshot(&self) -> BTreeMap<EntityKey, Entity> {
+        let state = self.state.lock().unwrap();
+        state
+            .entities
+            .iter()
+            .filter_map(|(key, versions)| {
+                Self::entity_at(versions).map(|entity| (key.clone(), entity))
+            })
+            .collect()
+    }
+
+    fn entity_at(versions: &[EntityVersion]) -> Option<Entity> {
+        versions.last().and_then(|(_, data)| data.clone())
+    }
+}
+
+#[async_trait]
+impl WritableStore for InMemoryStore {
+    fn block_ptr(&self) -> Result<Option<BlockPtr>, StoreError> {
+        Ok(self.state.lock().unwrap().block_ptr.clone())
+    }
+
+    fn block_cursor(&self) -> Result<Option<String>, StoreError> {
+        Ok(self.state.lock().unwrap().cursor.clone())
+    }
+
+    fn start_subgraph_deployment(&self, _: &Logger) -> Result<(), StoreError> {
+        let mut state = self.state.lock().unwrap();
+        state.health = Some(SubgraphHealth::Healthy);
+        Ok(())
+    }
+
+    fn revert_block_operations(&self, block_ptr_to: BlockPtr) -> Result<(), StoreError> {
+        let mut state = self.state.lock().unwrap();
+        let number = block_ptr_to.number;
+        let mut changes = Vec::new();
+        for (key, versions) in state.entities.iter_mut() {
+            let len = versions.len();
+            versions.retain(|(block, _)| *block <= number);
+            if versions.len() < len {
+                changes.push(EntityChange::for_data(key.clone()));
+            }
+        }
+        state.entities.retain(|_, versions| !versions.is_empty());
+        state.data_sources.retain(|(block, _)| *block <= number);
+        state.block_ptr = Some(block_ptr_to);
+        drop(state);
+        self.subscriptions
+            .send_store_event(StoreEvent::new(changes));
+        Ok(())
+    }
+
+    fn unfail_deterministic_error(
+        &self,
+        _current_ptr: &BlockPtr,
+        _parent_ptr: &BlockPtr,
+    ) -> Result<(), StoreError> {
+        let mut state = self.state.lock().unwrap();
+        state.errors.clear();
+        state.health = Some(SubgraphHealth::Healthy);
+        Ok(())
+    }
+
+    fn unfail_non_deterministic_error(&self, _current_ptr: &BlockPtr) -> Result<(), StoreError> {
+        let mut state = self.state.lock().unwrap();
+        state.errors.clear();
+        state.health = Some(SubgraphHealth::Healthy);
+        Ok(())
+    }
+
+    async fn fail_subgraph(
+        &self,
+        error: SubgraphError,
+        _class: ErrorClass,
+    ) -> Result<(), StoreError> {
+        let mut state = self.state.lock().unwrap();
+        state.errors.push(error);
+        state.health = Some(SubgraphHealth::Failed);
+        Ok(())
+    }
+
+    async fn supports_proof_of_indexing(&self) -> Result<bool, StoreError> {
+        Ok(false)
+    }
+
+    fn get(&self, key: &EntityKey) -> Result<Option<Entity>, StoreError> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .entities
+            .get(key)
+            .and_then(|versions| Self::entity_at(versions)))
+    }
+
+    fn transact_block_operations(
+        &self,
+        block_ptr_to: BlockPtr,
+        firehose_cursor: Option<String>,
+        mods: Vec<EntityModification>,
+        _stopwatch: StopwatchMetrics,
+        data_sources: Vec<StoredDynamicDataSource>,
+        deterministic_errors: Vec<SubgraphError>,
+    ) -> Result<(), StoreError> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(current) = &state.block_ptr {
+            if block_ptr_to.number <= current.number {
+                return Err(StoreError::DuplicateBlockProcessing(
+                    self.id.clone(),
+                    block_ptr_to.number,
+                ));
+            }
+        }
+        let number = block_ptr_to.number;
+        let mut changes = Vec::new();
+        for modification in mods {
+            use EntityModification::*;
+            let (key, data) = match modification {
+                Insert { key, data } | Overwrite { key, data } => (key, Some(data)),
+                Remove { key } => (key, None),
+            };
+            changes.push(EntityChange::for_data(key.clone()));
+            state.entities.entry(key).or_default().push((number, data));
+        }
+        state
+            .data_sources
+            .extend(data_sources.into_iter().map(|ds| (number, ds)));
+        if !deterministic_errors.is_empty() {
+            state.errors.extend(deterministic_errors);
+            state.health = Some(SubgraphHealth::Unhealthy);
+        }
+        state.block_ptr = Some(block_ptr_to);
+        state.cursor = firehose_cursor;
+        drop(state);
+        self.subscriptions
+            .send_store_event(StoreEvent::new(changes));
+        Ok(())
+    }
+
+    fn get_many(
+        &self,
+        ids_for_type: BTreeMap<&EntityType, Vec<&str>>,
+    ) -> Result<BTreeMap<EntityType, Vec<Entity>>, StoreError> {
+        let state = self.state.lock().unwrap();
+        let mut result: BTreeMap<EntityType, Vec<Entity>> = BTreeMap::new();
+        for (entity_type, ids) in ids_for_type {
+            for id in ids {
+                let key = EntityKey {
+                    subgraph_id: self.id.clone(),
+                    entity_type: entity_type.clone(),
+                    entity_id: id.to_string(),
+                };
+                if let Some(entity) = state
+                    .entities
+                    .get(&key)
+                    .and_then(|versions| Self::entity_at(versions))
+                {
+                    result.entry(entity_type.clone()).or_default().push(entity);
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    fn entity_ids(&self, entity_type: &EntityType, first: u32) -> Result<Vec<String>, StoreError> {
+        let state = self.state.lock().unwrap();
+        let mut ids: Vec<String> = state
+            .entities
+            .iter()
+            .filter(|(key, versions)| {
+                &key.entity_type == entity_type && Self::entity_at(versions).is_some()
+            })
+            .map(|(key, _)| key.entity_id.clone())
+            .collect();
+        ids.sort();
+        ids.truncate(first as usize);
+        Ok(ids)
+    }
+
+    async fn is_deployment_synced(&self) -> Result<bool, StoreError> {
+        Ok(self.state.lock().unwrap().synced)
+    }
+
+    fn unassign_subgraph(&self) -> Result<(), StoreError> {
+        Ok(())
+    }
+
+    async fn load_dynamic_data_sources(&self) -> Result<Vec<StoredDynamicDataSource>, StoreError> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .data_sources
+            .iter()
+            .map(|(_, ds)| ds.clone())
+            .collect())
+    }
+
+    fn deployment_synced(&self) -> Result<(), StoreError> {
+        self.state.lock().unwrap().synced = true;
+        Ok(())
+    }
+
+    fn shard(&self) -> &str {
+        "in_memory"
+    }
+
+    async fn health(&self, _: &DeploymentHash) -> Result<SubgraphHealth, StoreError> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .health
+            .unwrap_or(SubgraphHealth::Healthy))
+    }
+}
+
+struct StoredBlock {
+    ptr: BlockPtr,
+    parent: Option<H256>,
+    data: serde_json::Value,
+}
+
+#[derive(Default)]
+struct ChainState {
+    head: Option<BlockPtr>,
+    cursor: Option<String>,
+    blocks: HashMap<H256, StoredBlock>,
+}
+
+/// An in-memory `ChainStore` that keeps blocks in a `HashMap`
+pub struct MockChainStore {
+    network: String,
+    genesis: BlockPtr,
+    state: Mutex<ChainState>,
+}
+
+impl MockChainStore {
+    pub fn new(network: String, genesis: BlockPtr) -> Self {
+        Self {
+            network,
+            genesis,
+            state: Mutex::new(ChainState::default()),
+        }
+    }
+
+    fn insert(state: &mut ChainState, block: &dyn Block) -> Result<(), Error> {
+        let ptr = block.ptr();
+        let stored = StoredBlock {
+            ptr: ptr.clone(),
+            parent: block.parent_ptr().map(|ptr| ptr.hash_as_h256()),
+            data: block.data()?,
+        };
+        state.blocks.insert(ptr.hash_as_h256(), stored);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ChainStore for MockChainStore {
+    fn genesis_block_ptr(&self) -> Result<BlockPtr, Error> {
+        Ok(self.genesis.clone())
+    }
+
+    async fn upsert_block(&self, block: Arc<dyn Block>) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        Self::insert(&mut state, block.as_ref())
+    }
+
+    fn upsert_light_blocks(&self, blocks: &[&dyn Block]) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        for block in blocks {
+            Self::insert(&mut state, *block)?;
+        }
+        Ok(())
+    }
+
+    async fn attempt_chain_head_update(
+        self: Arc<Self>,
+        _ancestor_count: BlockNumber,
+    ) -> Result<Option<H256>, Error> {
+        let mut state = self.state.lock().unwrap();
+        let candidate = state
+            .blocks
+            .values()
+            .max_by_key(|block| block.ptr.number)
+            .map(|block| block.ptr.clone());
+        match candidate {
+            Some(ptr) if Some(&ptr) != state.head.as_ref() => {
+                // Require that the parent is known, like the real store does,
+                // and report it as missing otherwise
+                let parent = state.blocks.get(&ptr.hash_as_h256()).and_then(|b| b.parent);
+                if let Some(parent) = parent {
+                    if ptr.number > 0 && !state.blocks.contains_key(&parent) {
+                        return Ok(Some(parent));
+                    }
+                }
+                state.head = Some(ptr);
+                Ok(None)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn chain_head_ptr(&self) -> Result<Option<BlockPtr>, Error> {
+        Ok(self.state.lock().unwrap().head.clone())
+    }
+
+    fn chain_head_cursor(&self) -> Result<Option<String>, Error> {
+        Ok(self.state.lock().unwrap().cursor.clone())
+    }
+
+    async fn set_chain_head(
+        self: Arc<Self>,
+        block: Arc<dyn Block>,
+        cursor: String,
+    ) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        Self::insert(&mut state, block.as_ref())?;
+        state.head = Some(block.ptr());
+        state.cursor = Some(cursor);
+        Ok(())
+    }
+
+    fn blocks(&self, hashes: &[H256]) -> Result<Vec<serde_json::Value>, Error> {
+        let state = self.state.lock().unwrap();
+        Ok(hashes
+            .iter()
+            .filter_map(|hash| state.blocks.get(hash).map(|block| block.data.clone()))
+            .collect())
+    }
+
+    fn ancestor_block(
+        &self,
+        block_ptr: BlockPtr,
+        offset: BlockNumber,
+    ) -> Result<Option<serde_json::Value>, Error> {
+        if block_ptr.number < offset {
+            return Err(anyhow!("block offset points to before genesis block"));
+        }
+        let state = self.state.lock().unwrap();
+        let mut hash = block_ptr.hash_as_h256();
+        for _ in 0..offset {
+            match state.blocks.get(&hash).and_then(|block| block.parent) {
+                Some(parent) => hash = parent,
+                None => return Ok(None),
+            }
+        }
+        Ok(state.blocks.get(&hash).map(|block| block.data.clone()))
+    }
+
+    fn cleanup_cached_blocks(
+        &self,
+        _ancestor_count: BlockNumber,
+    ) -> Result<Option<(BlockNumber, usize)>, Error> {
+        Ok(None)
+    }
+
+    fn block_hashes_by_block_number(&self, number: BlockNumber) -> Result<Vec<H256>, Error> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .blocks
+            .values()
+            .filter(|block| block.ptr.number == number)
+            .map(|block| block.ptr.hash_as_h256())
+            .collect())
+    }
+
+    fn confirm_block_hash(&self, number: BlockNumber, hash: &H256) -> Result<usize, Error> {
+        let mut state = self.state.lock().unwrap();
+        let len = state.blocks.len();
+        state
+            .blocks
+            .retain(|block_hash, block| block.ptr.number != number || block_hash == hash);
+        Ok(len - state.blocks.len())
+    }
+
+    fn block_number(&self, block_hash: H256) -> Result<Option<(String, BlockNumber)>, StoreError> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .blocks
+            .get(&block_hash)
+            .map(|block| (self.network.clone(), block.ptr.number)))
+    }
+
+    fn head_lag_samples(&self, _first: usize) -> Result<Vec<HeadLagSample>, Error> {
+        Ok(Vec::new())
+    }
+
+    fn reorg_events(&self, _first: usize) -> Result<Vec<ReorgEvent>, Error> {
+        Ok(Vec::new())
+    }
+
+    async fn transaction_receipts_in_block(
+        &self,
+        _block_ptr: &H256,
+    ) -> Result<Vec<transaction_receipt::LightTransactionReceipt>, StoreError> {
+        Ok(Vec::new())
+    }
+}
+
+/// A `BlockStore` over a fixed set of `MockChainStore`s
+pub struct MockBlockStore {
+    chains: HashMap<String, Arc<MockChainStore>>,
+}
+
+impl MockBlockStore {
+    pub fn new(chains: HashMap<String, Arc<MockChainStore>>) -> Self {
+        Self { chains }
+    }
+}
+
+impl BlockStore for MockBlockStore {
+    type ChainStore = MockChainStore;
+
+    fn chain_store(&self, network: &str) -> Option<Arc<Self::ChainStore>> {
+        self.chains.get(network).cloned()
+    }
+}
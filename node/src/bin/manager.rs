@@ -144,6 +144,31 @@ pub enum Command {
         /// The deployments to rewind
         names: Vec<String>,
     },
+    /// Replay a single block against a deployment's mappings
+    ///
+    /// The block's triggers are fetched from the chain's providers and run
+    /// through the deployment's current mappings in a sandbox. Mapping logs
+    /// and host function calls are printed, followed by the entity changes
+    /// the block would make; nothing is written to the store unless
+    /// `--commit` is given. Committing requires that the deployment head
+    /// is the parent of the replayed block; use `graphman rewind` to get
+    /// there
+    Replay {
+        /// The deployment, an id, schema name or subgraph name
+        deployment: String,
+        /// The number of the block to replay
+        block_number: i32,
+        /// Replay only the trigger with this index within the block
+        #[structopt(long, short)]
+        trigger: Option<usize>,
+        /// Write the entity changes to the store and advance the
+        /// deployment head to the replayed block
+        #[structopt(long)]
+        commit: bool,
+        /// HTTP addresses of IPFS nodes for fetching the subgraph files
+        #[structopt(long, env = "IPFS", default_value = "https://api.thegraph.com/ipfs/")]
+        ipfs: Vec<String>,
+    },
     /// Check and interrogate the configuration
     ///
     /// Print information about a configuration file without
@@ -164,6 +189,26 @@ pub enum Command {
         /// The variables in the form `key=value`
         vars: Vec<String>,
     },
+    /// Export one entity type of a deployment to CSV or Parquet
+    ///
+    /// The export streams from the store in batches and can therefore
+    /// handle entity types that do not fit in memory. The output can be
+    /// a local file or an `s3://bucket/path/to/file` URL; for the
+    /// latter, credentials come from the usual `AWS_*` environment
+    /// variables and S3-compatible stores can be addressed by setting
+    /// `GRAPH_S3_ENDPOINT`
+    Export {
+        /// The id of the deployment to export from
+        deployment: String,
+        /// The entity type to export
+        entity_type: String,
+        /// The output file, ending in `.csv` or `.parquet`, or an
+        /// `s3://` URL for such a file
+        output: String,
+        /// Export the state as of this block instead of the latest block
+        #[structopt(long, short)]
+        block: Option<i32>,
+    },
     /// Get information about chains and manipulate them
     Chain(ChainCommand),
     /// Manipulate internal subgraph statistics
@@ -324,6 +369,29 @@ pub enum StatsCommand {
         clear: bool,
         table: String,
     },
+    /// Compress the history of a table
+    ///
+    /// Lower the size at which Postgres moves rows of a table out of line
+    /// and compresses them (`toast_tuple_target`). Since old entity
+    /// versions are rarely read but usually make up most of a table, this
+    /// can shrink a table considerably at a small cost for time-travel
+    /// queries. Changing the target only affects versions written from
+    /// then on; pass `--rewrite` to also compress existing versions, which
+    /// rewrites the table with `vacuum full` and therefore locks it for
+    /// the duration. Newly created tables can be compressed from the start
+    /// by setting GRAPH_STORE_HISTORY_TOAST_TUPLE_TARGET
+    Compress {
+        /// The fully qualified table name, e.g. `sgd1.thing`
+        table: String,
+        #[structopt(
+            long,
+            default_value = "256",
+            help = "the row size in bytes above which rows get compressed"
+        )]
+        target: usize,
+        #[structopt(long, help = "compress existing entity versions, too\n")]
+        rewrite: bool,
+    },
     /// Show statistics for the tables of a deployment
     ///
     /// Show how many distinct entities and how many versions the tables of
@@ -582,6 +650,31 @@ async fn main() {
                 sleep,
             )
         }
+        Replay {
+            deployment,
+            block_number,
+            trigger,
+            commit,
+            ipfs,
+        } => {
+            let config = ctx.config.clone();
+            let registry = ctx.registry.clone();
+            let node_id = ctx.node_id.clone();
+            let (store, primary) = ctx.store_and_primary();
+            commands::replay::run(
+                store,
+                primary,
+                &config,
+                registry,
+                node_id,
+                ipfs,
+                deployment,
+                block_number,
+                trigger,
+                commit,
+            )
+            .await
+        }
         Listen(cmd) => {
             use ListenCommand::*;
             match cmd {
@@ -617,6 +710,12 @@ async fn main() {
             query,
             vars,
         } => commands::query::run(ctx.graphql_runner(), target, query, vars).await,
+        Export {
+            deployment,
+            entity_type,
+            output,
+            block,
+        } => commands::export::run(ctx.subgraph_store(), deployment, entity_type, output, block),
         Chain(cmd) => {
             use ChainCommand::*;
             match cmd {
@@ -644,6 +743,11 @@ async fn main() {
                 AccountLike { clear, table } => {
                     commands::stats::account_like(ctx.pools(), clear, table)
                 }
+                Compress {
+                    table,
+                    target,
+                    rewrite,
+                } => commands::stats::compress(ctx.pools(), table, target, rewrite),
                 Show { nsp, table } => commands::stats::show(ctx.pools(), nsp, table),
             }
         }
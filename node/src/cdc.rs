@@ -0,0 +1,222 @@
+//! Optional change-data-capture integration that forwards the store's
+//! entity change events for selected deployments to a Kafka topic so
+//! that downstream pipelines can react to indexing progress in realtime.
+//!
+//! The integration is configured entirely through environment variables:
+//! setting `GRAPH_CDC_KAFKA_BROKERS` to a Kafka bootstrap server list
+//! turns it on, `GRAPH_CDC_DEPLOYMENTS` lists the deployment ids to
+//! publish as a comma-separated list, `GRAPH_CDC_KAFKA_TOPIC` overrides
+//! the default topic `graph-entity-changes`, and `GRAPH_CDC_FORMAT`
+//! selects between `json` (the default) and `avro` serialization.
+//!
+//! Events carry the deployment and the set of entity types that changed
+//! in one store transaction, which is the granularity of the store's
+//! event stream; consumers that need the changed entities themselves
+//! query them through GraphQL when an event arrives.
+
+use std::collections::BTreeSet;
+use std::env;
+use std::sync::Arc;
+
+use avro_rs::types::{Record, Value as AvroValue};
+use avro_rs::Schema as AvroSchema;
+use futures::compat::Stream01CompatExt;
+use futures::StreamExt;
+use graph::components::store::{EntityChange, EntityType, SubscriptionManager as _};
+use graph::prelude::{
+    anyhow::{anyhow, bail, Error},
+    error, info, o, serde_json, DeploymentHash, Logger, SubgraphStore as _, SubscriptionFilter,
+};
+use graph_store_postgres::{SubgraphStore, SubscriptionManager};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::util::Timeout;
+use rdkafka::ClientConfig;
+
+/// The Avro schema for one entity change event
+const AVRO_SCHEMA: &str = r#"
+{
+    "type": "record",
+    "name": "EntityChangeEvent",
+    "fields": [
+        { "name": "deployment", "type": "string" },
+        { "name": "tag", "type": "long" },
+        { "name": "entity_types", "type": { "type": "array", "items": "string" } }
+    ]
+}
+"#;
+
+#[derive(Clone, Copy)]
+enum Format {
+    Json,
+    Avro,
+}
+
+pub struct CdcConfig {
+    brokers: String,
+    topic: String,
+    deployments: Vec<DeploymentHash>,
+    format: Format,
+}
+
+impl CdcConfig {
+    /// Read the configuration from the environment; returns `None` when
+    /// `GRAPH_CDC_KAFKA_BROKERS` is not set and the integration should
+    /// stay off
+    pub fn from_env() -> Result<Option<Self>, Error> {
+        let brokers = match env::var("GRAPH_CDC_KAFKA_BROKERS") {
+            Ok(brokers) => brokers,
+            Err(_) => return Ok(None),
+        };
+        let topic = env::var("GRAPH_CDC_KAFKA_TOPIC")
+            .unwrap_or_else(|_| "graph-entity-changes".to_string());
+        let deployments = env::var("GRAPH_CDC_DEPLOYMENTS")
+            .map_err(|_| {
+                anyhow!(
+                    "GRAPH_CDC_DEPLOYMENTS must list the deployments to publish \
+                     when GRAPH_CDC_KAFKA_BROKERS is set"
+                )
+            })?
+            .split(',')
+            .map(|id| {
+                DeploymentHash::new(id.trim()).map_err(|id| {
+                    anyhow!("invalid deployment id `{}` in GRAPH_CDC_DEPLOYMENTS", id)
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let format = match env::var("GRAPH_CDC_FORMAT").as_deref() {
+            Err(_) | Ok("json") => Format::Json,
+            Ok("avro") => Format::Avro,
+            Ok(other) => bail!(
+                "GRAPH_CDC_FORMAT must be either `json` or `avro`, not `{}`",
+                other
+            ),
+        };
+        Ok(Some(CdcConfig {
+            brokers,
+            topic,
+            deployments,
+            format,
+        }))
+    }
+}
+
+fn payload(
+    format: Format,
+    avro_schema: &AvroSchema,
+    deployment: &DeploymentHash,
+    tag: usize,
+    entity_types: &BTreeSet<&str>,
+) -> Result<Vec<u8>, Error> {
+    match format {
+        Format::Json => Ok(serde_json::to_vec(&serde_json::json!({
+            "deployment": deployment.to_string(),
+            "tag": tag,
+            "entity_types": entity_types,
+        }))?),
+        Format::Avro => {
+            let mut record = Record::new(avro_schema).expect("the Avro schema is a record");
+            record.put("deployment", deployment.to_string());
+            record.put("tag", tag as i64);
+            record.put(
+                "entity_types",
+                AvroValue::Array(
+                    entity_types
+                        .iter()
+                        .map(|entity_type| AvroValue::String(entity_type.to_string()))
+                        .collect(),
+                ),
+            );
+            Ok(avro_rs::to_avro_datum(avro_schema, record.into())?)
+        }
+    }
+}
+
+/// Subscribe to the store's event stream and publish one message per
+/// configured deployment for every store transaction that changed any of
+/// its entities. The publisher runs until the event stream ends
+pub fn spawn(
+    logger: &Logger,
+    store: Arc<SubgraphStore>,
+    mgr: Arc<SubscriptionManager>,
+    config: CdcConfig,
+) -> Result<(), Error> {
+    use graph::data::graphql::DocumentExt;
+
+    let logger = logger.new(o!("component" => "EntityChangePublisher"));
+
+    // The store's subscriptions are per entity type; subscribe to every
+    // type that the configured deployments define
+    let mut filters = BTreeSet::new();
+    for deployment in &config.deployments {
+        let schema = store.input_schema(deployment)?;
+        for object_type in schema.document.get_object_type_definitions() {
+            filters.insert(SubscriptionFilter::Entities(
+                deployment.clone(),
+                EntityType::from(object_type),
+            ));
+        }
+    }
+
+    let producer: FutureProducer = ClientConfig::new()
+        .set("bootstrap.servers", &config.brokers)
+        .create()?;
+    let avro_schema = AvroSchema::parse_str(AVRO_SCHEMA)?;
+
+    info!(logger, "Publishing entity change events to Kafka";
+          "brokers" => &config.brokers,
+          "topic" => &config.topic,
+          "deployments" => config.deployments.len());
+
+    let mut events = mgr.subscribe(filters).compat();
+    graph::spawn(async move {
+        while let Some(event) = events.next().await {
+            let event = match event {
+                Ok(event) => event,
+                Err(()) => {
+                    error!(logger, "The store event stream failed");
+                    return;
+                }
+            };
+            for deployment in &config.deployments {
+                let entity_types: BTreeSet<&str> = event
+                    .changes
+                    .iter()
+                    .filter_map(|change| match change {
+                        EntityChange::Data {
+                            subgraph_id,
+                            entity_type,
+                        } if subgraph_id == deployment => Some(entity_type.as_str()),
+                        _ => None,
+                    })
+                    .collect();
+                if entity_types.is_empty() {
+                    continue;
+                }
+                let payload = match payload(
+                    config.format,
+                    &avro_schema,
+                    deployment,
+                    event.tag,
+                    &entity_types,
+                ) {
+                    Ok(payload) => payload,
+                    Err(e) => {
+                        error!(logger, "Failed to serialize entity change event";
+                               "deployment" => deployment.to_string(),
+                               "error" => e.to_string());
+                        continue;
+                    }
+                };
+                let record = FutureRecord::to(&config.topic)
+                    .key(deployment.as_str())
+                    .payload(payload.as_slice());
+                if let Err((e, _)) = producer.send(record, Timeout::Never).await {
+                    error!(logger, "Failed to publish entity change event";
+                           "deployment" => deployment.to_string(),
+                           "error" => e.to_string());
+                }
+            }
+        }
+    });
+    Ok(())
+}
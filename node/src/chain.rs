@@ -3,19 +3,27 @@ use ethereum::{EthereumNetworks, ProviderEthRpcMetrics};
 use futures::future::join_all;
 use futures::TryFutureExt;
 use graph::anyhow::Error;
-use graph::blockchain::{Block as BlockchainBlock, BlockchainKind, ChainIdentifier};
+use graph::blockchain::block_ingestor::BlockIngestor;
+use graph::blockchain::firehose_block_ingestor::FirehoseBlockIngestor;
+use graph::blockchain::{
+    Block as BlockchainBlock, Blockchain, BlockchainKind, BlockchainMap, ChainIdentifier,
+};
 use graph::cheap_clone::CheapClone;
-use graph::firehose::endpoints::{FirehoseEndpoint, FirehoseNetworks};
+use graph::components::store::BlockStore;
+use graph::firehose::endpoints::{FirehoseEndpoint, FirehoseNetworkEndpoints, FirehoseNetworks};
 use graph::ipfs_client::IpfsClient;
-use graph::prelude::{anyhow, tokio, BlockNumber};
+use graph::prelude::{anyhow, tokio, BlockNumber, LoggerFactory, NodeId};
 use graph::prelude::{prost, MetricsRegistry as MetricsRegistryTrait};
 use graph::slog::{debug, error, info, o, Logger};
 use graph::util::security::SafeDisplay;
 use graph_chain_ethereum::{self as ethereum, EthereumAdapterTrait, Transport};
+use graph_chain_near as near;
 use graph_core::MetricsRegistry;
+use graph_store_postgres::{ChainHeadUpdateListener, Store};
 use lazy_static::lazy_static;
 use std::collections::{BTreeMap, HashMap};
 use std::env;
+use std::iter::FromIterator;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
@@ -152,7 +160,11 @@ pub async fn create_ethereum_networks(
                     Rpc => Transport::new_rpc(&web3.url, web3.headers.clone()),
                     Ipc => Transport::new_ipc(&web3.url).await,
                     Ws => Transport::new_ws(&web3.url).await,
-                };
+                }
+                // Record responses, or replay them from an earlier
+                // recording, if GRAPH_ETHEREUM_RPC_RECORD_DIR or
+                // GRAPH_ETHEREUM_RPC_REPLAY_DIR is set
+                .recorded(name);
 
                 let supports_eip_1898 = !web3.features.contains("no_eip1898");
 
@@ -162,6 +174,7 @@ pub async fn create_ethereum_networks(
                     Arc::new(
                         graph_chain_ethereum::EthereumAdapter::new(
                             logger,
+                            name.to_string(),
                             provider.label.clone(),
                             &web3.url,
                             transport,
@@ -478,3 +491,219 @@ mod test {
         assert_eq!(mainnet_capability, traces);
     }
 }
+
+/// Return the hashmap of ethereum chains and also add them to `blockchain_map`.
+pub fn ethereum_networks_as_chains(
+    blockchain_map: &mut BlockchainMap,
+    logger: &Logger,
+    node_id: NodeId,
+    registry: Arc<MetricsRegistry>,
+    firehose_networks: Option<&FirehoseNetworks>,
+    eth_networks: &EthereumNetworks,
+    store: &Store,
+    chain_head_update_listener: Arc<ChainHeadUpdateListener>,
+    logger_factory: &LoggerFactory,
+) -> HashMap<String, Arc<ethereum::Chain>> {
+    let chains: Vec<_> = eth_networks
+        .networks
+        .iter()
+        .filter_map(|(network_name, eth_adapters)| {
+            store
+                .block_store()
+                .chain_store(network_name)
+                .map(|chain_store| {
+                    let is_ingestible = chain_store.is_ingestible();
+                    (network_name, eth_adapters, chain_store, is_ingestible)
+                })
+                .or_else(|| {
+                    error!(
+                        logger,
+                        "No store configured for Ethereum chain {}; ignoring this chain",
+                        network_name
+                    );
+                    None
+                })
+        })
+        .map(|(network_name, eth_adapters, chain_store, is_ingestible)| {
+            let firehose_endpoints = firehose_networks.and_then(|v| v.networks.get(network_name));
+
+            let chain = ethereum::Chain::new(
+                logger_factory.clone(),
+                network_name.clone(),
+                node_id.clone(),
+                registry.clone(),
+                chain_store.cheap_clone(),
+                chain_store,
+                store.subgraph_store(),
+                firehose_endpoints.map_or_else(|| FirehoseNetworkEndpoints::new(), |v| v.clone()),
+                eth_adapters.clone(),
+                chain_head_update_listener.clone(),
+                *ANCESTOR_COUNT,
+                *REORG_THRESHOLD,
+                is_ingestible,
+            );
+            (network_name.clone(), Arc::new(chain))
+        })
+        .collect();
+
+    for (network_name, chain) in chains.iter().cloned() {
+        blockchain_map.insert::<graph_chain_ethereum::Chain>(network_name, chain)
+    }
+
+    HashMap::from_iter(chains)
+}
+
+/// Return the hashmap of NEAR chains and also add them to `blockchain_map`.
+pub fn near_networks_as_chains(
+    blockchain_map: &mut BlockchainMap,
+    logger: &Logger,
+    firehose_networks: &FirehoseNetworks,
+    store: &Store,
+    logger_factory: &LoggerFactory,
+) -> HashMap<String, FirehoseChain<near::Chain>> {
+    let chains: Vec<_> = firehose_networks
+        .networks
+        .iter()
+        .filter_map(|(network_name, firehose_endpoints)| {
+            store
+                .block_store()
+                .chain_store(network_name)
+                .map(|chain_store| (network_name, chain_store, firehose_endpoints))
+                .or_else(|| {
+                    error!(
+                        logger,
+                        "No store configured for NEAR chain {}; ignoring this chain", network_name
+                    );
+                    None
+                })
+        })
+        .map(|(network_name, chain_store, firehose_endpoints)| {
+            (
+                network_name.clone(),
+                FirehoseChain {
+                    chain: Arc::new(near::Chain::new(
+                        logger_factory.clone(),
+                        network_name.clone(),
+                        chain_store,
+                        firehose_endpoints.clone(),
+                    )),
+                    firehose_endpoints: firehose_endpoints.clone(),
+                },
+            )
+        })
+        .collect();
+
+    for (network_name, firehose_chain) in chains.iter() {
+        blockchain_map
+            .insert::<graph_chain_near::Chain>(network_name.clone(), firehose_chain.chain.clone())
+    }
+
+    HashMap::from_iter(chains)
+}
+
+pub fn start_block_ingestor(
+    logger: &Logger,
+    block_polling_interval: Duration,
+    chains: HashMap<String, Arc<ethereum::Chain>>,
+) {
+    // BlockIngestor must be configured to keep at least REORG_THRESHOLD ancestors,
+    // otherwise BlockStream will not work properly.
+    // BlockStream expects the blocks after the reorg threshold to be present in the
+    // database.
+    assert!(*ANCESTOR_COUNT >= *REORG_THRESHOLD);
+
+    info!(
+        logger,
+        "Starting block ingestors with {} chains [{}]",
+        chains.len(),
+        chains
+            .keys()
+            .map(|v| v.clone())
+            .collect::<Vec<String>>()
+            .join(", ")
+    );
+
+    // Create Ethereum block ingestors and spawn a thread to run each
+    chains
+        .iter()
+        .filter(|(network_name, chain)| {
+            if !chain.is_ingestible {
+                error!(logger, "Not starting block ingestor (chain is defective)"; "network_name" => &network_name);
+            }
+            chain.is_ingestible
+        })
+        .for_each(|(network_name, chain)| {
+            info!(
+                logger,
+                "Starting block ingestor for network";
+                "network_name" => &network_name
+            );
+
+            let block_ingestor = BlockIngestor::<ethereum::Chain>::new(
+                chain.ingestor_adapter(),
+                block_polling_interval,
+            )
+            .expect("failed to create Ethereum block ingestor");
+
+            // Run the Ethereum block ingestor in the background
+            graph::spawn(block_ingestor.into_polling_stream());
+        });
+}
+
+#[derive(Clone)]
+pub struct FirehoseChain<C: Blockchain> {
+    pub chain: Arc<C>,
+    pub firehose_endpoints: FirehoseNetworkEndpoints,
+}
+
+pub fn start_firehose_block_ingestor<C, M>(
+    logger: &Logger,
+    store: &Store,
+    chains: HashMap<String, FirehoseChain<C>>,
+) where
+    C: Blockchain,
+    M: prost::Message + BlockchainBlock + Default + 'static,
+{
+    info!(
+        logger,
+        "Starting firehose block ingestors with {} chains [{}]",
+        chains.len(),
+        chains
+            .keys()
+            .map(|v| v.clone())
+            .collect::<Vec<String>>()
+            .join(", ")
+    );
+
+    // Create Firehose block ingestors and spawn a thread to run each
+    chains
+        .iter()
+        .for_each(|(network_name, chain)| {
+            info!(
+                logger,
+                "Starting firehose block ingestor for network";
+                "network_name" => &network_name
+            );
+
+            let endpoint = chain
+                .firehose_endpoints
+                .random()
+                .expect("One Firehose endpoint should exist at that execution point");
+
+            match store.block_store().chain_store(network_name.as_ref()) {
+                Some(s) => {
+                    let block_ingestor = FirehoseBlockIngestor::<M>::new(
+                        s,
+                        endpoint.clone(),
+                        logger.new(o!("component" => "FirehoseBlockIngestor", "provider" => endpoint.provider.clone())),
+                    );
+
+                    // Run the Firehose block ingestor in the background
+                    graph::spawn(block_ingestor.run());
+                },
+                None => {
+                    error!(logger, "Not starting firehose block ingestor (no chain store available)"; "network_name" => &network_name);
+                }
+            }
+        });
+}
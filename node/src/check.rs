@@ -0,0 +1,181 @@
+//! The `graph-node check` subcommand. It validates the full
+//! configuration the way a deployment pipeline needs it: every Postgres
+//! shard and replica must be reachable and report its migration state,
+//! every chain provider must answer with its network identifier, and
+//! IPFS must respond. The readiness report goes to stdout and the
+//! process exits nonzero when any check fails.
+
+use std::sync::Arc;
+
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::Text;
+use graph::ipfs_client::IpfsClient;
+use graph::prelude::Logger;
+use graph::prometheus::Registry;
+use graph_core::MetricsRegistry;
+
+use crate::chain::{connect_ethereum_networks, create_ethereum_networks};
+use crate::config::{Config, ProviderDetails};
+use crate::opt::Opt;
+
+/// Collects the outcome of the individual checks and renders them as
+/// they happen
+struct Report {
+    failures: usize,
+}
+
+impl Report {
+    fn ok(&mut self, msg: String) {
+        println!("ok: {}", msg);
+    }
+
+    fn fail(&mut self, msg: String) {
+        self.failures += 1;
+        println!("FAIL: {}", msg);
+    }
+
+    fn skip(&mut self, msg: String) {
+        println!("skip: {}", msg);
+    }
+}
+
+fn check_database(report: &mut Report, what: String, connection: &str) {
+    #[derive(QueryableByName)]
+    struct Migration {
+        #[sql_type = "Text"]
+        version: String,
+    }
+
+    let conn = match PgConnection::establish(connection) {
+        Ok(conn) => conn,
+        Err(e) => {
+            return report.fail(format!("{}: connection failed: {}", what, e));
+        }
+    };
+    let migration = sql_query(
+        "select version from __diesel_schema_migrations \
+          order by version desc limit 1",
+    )
+    .get_result::<Migration>(&conn)
+    .optional();
+    match migration {
+        Ok(Some(migration)) => report.ok(format!(
+            "{}: connected, latest migration {}",
+            what, migration.version
+        )),
+        Ok(None) => report.fail(format!("{}: connected, but no migrations applied", what)),
+        Err(_) => report.fail(format!(
+            "{}: connected, but the database has not been initialized for graph-node",
+            what
+        )),
+    }
+}
+
+pub async fn run(logger: &Logger, opt: &Opt, config: &Config) -> i32 {
+    let mut report = Report { failures: 0 };
+
+    report.ok(format!(
+        "configuration: valid ({} shard(s), {} chain(s))",
+        config.stores.len(),
+        config.chains.chains.len()
+    ));
+
+    for (name, shard) in &config.stores {
+        check_database(&mut report, format!("shard {}", name), &shard.connection);
+        for (replica_name, replica) in &shard.replicas {
+            check_database(
+                &mut report,
+                format!("shard {} replica {}", name, replica_name),
+                &replica.connection,
+            );
+        }
+    }
+
+    // Probe the web3 providers the same way startup does; providers that
+    // do not answer are removed from the returned networks
+    let registry = Arc::new(MetricsRegistry::new(
+        logger.clone(),
+        Arc::new(Registry::new()),
+    ));
+    match create_ethereum_networks(logger.clone(), registry, config).await {
+        Ok(eth_networks) => {
+            let (_, idents) = connect_ethereum_networks(logger, eth_networks).await;
+            for (chain_name, chain) in &config.chains.chains {
+                let web3_providers = chain
+                    .providers
+                    .iter()
+                    .filter(|provider| matches!(provider.details, ProviderDetails::Web3(_)))
+                    .count();
+                let skipped = chain.providers.len() - web3_providers;
+                if skipped > 0 {
+                    report.skip(format!(
+                        "chain {}: {} firehose provider(s) not probed",
+                        chain_name, skipped
+                    ));
+                }
+                if web3_providers == 0 {
+                    continue;
+                }
+                let idents = idents
+                    .iter()
+                    .find(|(network, _)| network == chain_name)
+                    .map(|(_, idents)| idents.as_slice())
+                    .unwrap_or(&[]);
+                if idents.len() < web3_providers {
+                    report.fail(format!(
+                        "chain {}: only {} of {} provider(s) answered",
+                        chain_name,
+                        idents.len(),
+                        web3_providers
+                    ));
+                    continue;
+                }
+                let genesis: Vec<_> = idents
+                    .iter()
+                    .map(|ident| ident.genesis_block_hash.to_string())
+                    .collect();
+                if genesis.windows(2).any(|pair| pair[0] != pair[1]) {
+                    report.fail(format!(
+                        "chain {}: providers disagree on the genesis block: {}",
+                        chain_name,
+                        genesis.join(", ")
+                    ));
+                    continue;
+                }
+                report.ok(format!(
+                    "chain {}: {} provider(s), net_version {}, genesis {}",
+                    chain_name,
+                    idents.len(),
+                    idents[0].net_version,
+                    genesis[0]
+                ));
+            }
+        }
+        Err(e) => report.fail(format!("chains: invalid provider configuration: {}", e)),
+    }
+
+    for address in &opt.ipfs {
+        let address = if address.starts_with("http://") || address.starts_with("https://") {
+            address.clone()
+        } else {
+            format!("http://{}", address)
+        };
+        match IpfsClient::new(&address) {
+            Ok(client) => match client.test().await {
+                Ok(()) => report.ok(format!("ipfs {}: reachable", address)),
+                Err(e) => report.fail(format!("ipfs {}: {}", address, e)),
+            },
+            Err(e) => report.fail(format!("ipfs {}: invalid address: {}", address, e)),
+        }
+    }
+
+    if report.failures == 0 {
+        println!("readiness: ok");
+        0
+    } else {
+        println!("readiness: {} check(s) failed", report.failures);
+        1
+    }
+}
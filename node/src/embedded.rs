@@ -0,0 +1,373 @@
+//! Run `graph-node` embedded inside another Rust process.
+//!
+//! [`NodeBuilder`] assembles the same components that `main` wires
+//! together — stores, chains, the subgraph provider and registrar, and
+//! optionally the servers — but driven from code instead of command line
+//! arguments, so that integration tests and applications that embed an
+//! indexer do not have to shell out to the `graph-node` binary. The
+//! resulting [`Node`] deploys subgraphs programmatically and can wait for
+//! them to sync.
+//!
+//! The embedded mode is deliberately minimal: it only connects Ethereum
+//! chains, does not run the subscription or metrics servers, and does not
+//! register background store jobs. Anything beyond that should run the
+//! full binary.
+//!
+//! ```ignore
+//! let node = NodeBuilder::new("postgresql://...", "localhost:5001")
+//!     .ethereum_rpc("mainnet:http://localhost:8545")
+//!     .serve_queries(8000, 8001)
+//!     .start()
+//!     .await?;
+//! node.deploy("test/subgraph", "QmSubgraph...").await?;
+//! node.await_synced("QmSubgraph...", Duration::from_secs(300)).await?;
+//! ```
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use graph::blockchain::BlockchainMap;
+use graph::components::store::StatusStore;
+use graph::data::graphql::effort::LoadManager;
+use graph::data::subgraph::status;
+use graph::prelude::{IndexNodeServer as _, JsonRpcServer as _, *};
+use graph::prometheus::Registry;
+use graph_core::{
+    LinkResolver, MetricsRegistry, SubgraphAssignmentProvider as IpfsSubgraphAssignmentProvider,
+    SubgraphInstanceManager, SubgraphRegistrar as IpfsSubgraphRegistrar,
+};
+use graph_graphql::prelude::GraphQlRunner;
+use graph_server_http::GraphQLServer as GraphQLQueryServer;
+use graph_server_index_node::IndexNodeServer;
+use graph_server_json_rpc::JsonRpcServer;
+use graph_store_postgres::Store;
+
+use crate::chain::{
+    connect_ethereum_networks, create_ethereum_networks, create_ipfs_clients,
+    ethereum_networks_as_chains, start_block_ingestor,
+};
+use crate::config::{self, Config};
+use crate::store_builder::StoreBuilder;
+
+/// How often the embedded block ingestor polls for new blocks
+const BLOCK_POLLING_INTERVAL: Duration = Duration::from_millis(1000);
+
+/// How often [`Node::await_synced`] checks the indexing status
+const SYNC_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Configuration for an embedded `graph-node`. All settings that are not
+/// part of the builder are taken from the same environment variables as
+/// the binary
+pub struct NodeBuilder {
+    postgres_url: String,
+    ipfs: String,
+    ethereum_rpcs: Vec<String>,
+    node_id: String,
+    logger: Option<Logger>,
+    query_ports: Option<(u16, u16)>,
+    index_node_port: Option<u16>,
+    admin_port: Option<u16>,
+    block_ingestor: bool,
+}
+
+impl NodeBuilder {
+    /// Create a builder for a node that stores its data in the database
+    /// at `postgres_url` and resolves subgraph files through the IPFS
+    /// node at `ipfs`
+    pub fn new(postgres_url: impl Into<String>, ipfs: impl Into<String>) -> Self {
+        NodeBuilder {
+            postgres_url: postgres_url.into(),
+            ipfs: ipfs.into(),
+            ethereum_rpcs: Vec::new(),
+            node_id: "embedded".to_string(),
+            logger: None,
+            query_ports: None,
+            index_node_port: None,
+            admin_port: None,
+            block_ingestor: true,
+        }
+    }
+
+    /// Add an Ethereum chain in the same form the `--ethereum-rpc`
+    /// option takes: `name:[capability,capability:]url`
+    pub fn ethereum_rpc(mut self, rpc: impl Into<String>) -> Self {
+        self.ethereum_rpcs.push(rpc.into());
+        self
+    }
+
+    /// The node id under which deployments are assigned; `embedded` when
+    /// it is not set
+    pub fn node_id(mut self, node_id: impl Into<String>) -> Self {
+        self.node_id = node_id.into();
+        self
+    }
+
+    /// Log through `logger` instead of a fresh terminal logger
+    pub fn logger(mut self, logger: Logger) -> Self {
+        self.logger = Some(logger);
+        self
+    }
+
+    /// Serve GraphQL queries over HTTP on `http_port` and subscriptions
+    /// over WebSockets on `ws_port`. Without this, deployments can only
+    /// be queried through the store
+    pub fn serve_queries(mut self, http_port: u16, ws_port: u16) -> Self {
+        self.query_ports = Some((http_port, ws_port));
+        self
+    }
+
+    /// Serve the index node API on `port`
+    pub fn serve_index_node(mut self, port: u16) -> Self {
+        self.index_node_port = Some(port);
+        self
+    }
+
+    /// Serve the admin JSON-RPC API on `port`. Embedders usually do not
+    /// need this since [`Node::deploy`] talks to the registrar directly
+    pub fn serve_admin(mut self, port: u16) -> Self {
+        self.admin_port = Some(port);
+        self
+    }
+
+    /// Do not ingest blocks into the chain store. Only useful when
+    /// another node that shares the database does the ingesting
+    pub fn disable_block_ingestor(mut self) -> Self {
+        self.block_ingestor = false;
+        self
+    }
+
+    /// Start stores, chains and the configured servers and return a
+    /// handle for deploying subgraphs
+    pub async fn start(self) -> Result<Node, Error> {
+        let logger = self
+            .logger
+            .clone()
+            .unwrap_or_else(|| graph::log::logger(false));
+        let node_id = NodeId::new(self.node_id.clone())
+            .map_err(|()| anyhow!("invalid node id `{}`", self.node_id))?;
+
+        let config = Config::load(
+            &logger,
+            &config::Opt {
+                postgres_url: Some(self.postgres_url),
+                node_id: self.node_id.clone(),
+                ethereum_rpc: self.ethereum_rpcs,
+                disable_block_ingestor: !self.block_ingestor,
+                ..config::Opt::default()
+            },
+        )?;
+
+        let logger_factory = LoggerFactory::new(logger.clone(), None);
+
+        let ipfs_clients = create_ipfs_clients(&logger, &vec![self.ipfs]);
+        let link_resolver = Arc::new(LinkResolver::from(ipfs_clients));
+
+        let prometheus_registry = Arc::new(Registry::new());
+        let metrics_registry = Arc::new(MetricsRegistry::new(
+            logger.clone(),
+            prometheus_registry.clone(),
+        ));
+
+        let eth_networks =
+            create_ethereum_networks(logger.clone(), metrics_registry.clone(), &config).await?;
+
+        let store_builder =
+            StoreBuilder::new(&logger, &node_id, &config, metrics_registry.cheap_clone()).await;
+        let subscription_manager = store_builder.subscription_manager();
+        let chain_head_update_listener = store_builder.chain_head_update_listener();
+
+        let (eth_networks, ethereum_idents) =
+            connect_ethereum_networks(&logger, eth_networks).await;
+        let network_store = store_builder.network_store(ethereum_idents.into_iter().collect());
+
+        network_store.subgraph_store().load_deployment_settings()?;
+
+        let mut blockchain_map = BlockchainMap::new();
+        let ethereum_chains = ethereum_networks_as_chains(
+            &mut blockchain_map,
+            &logger,
+            node_id.clone(),
+            metrics_registry.clone(),
+            None,
+            &eth_networks,
+            network_store.as_ref(),
+            chain_head_update_listener,
+            &logger_factory,
+        );
+        let blockchain_map = Arc::new(blockchain_map);
+
+        if self.block_ingestor && !ethereum_chains.is_empty() {
+            start_block_ingestor(&logger, BLOCK_POLLING_INTERVAL, ethereum_chains);
+        }
+
+        let load_manager = Arc::new(LoadManager::new(
+            &logger,
+            Vec::new(),
+            metrics_registry.clone(),
+        ));
+        let graphql_runner = Arc::new(GraphQlRunner::new(
+            &logger,
+            network_store.clone(),
+            subscription_manager.clone(),
+            load_manager,
+            metrics_registry.clone(),
+        ));
+
+        let subgraph_instance_manager = SubgraphInstanceManager::new(
+            &logger_factory,
+            network_store.subgraph_store(),
+            blockchain_map.cheap_clone(),
+            metrics_registry.clone(),
+            link_resolver.cheap_clone(),
+        );
+        let subgraph_provider = IpfsSubgraphAssignmentProvider::new(
+            &logger_factory,
+            link_resolver.cheap_clone(),
+            subgraph_instance_manager,
+        );
+        let subgraph_registrar = Arc::new(IpfsSubgraphRegistrar::new(
+            &logger_factory,
+            link_resolver.cheap_clone(),
+            Arc::new(subgraph_provider),
+            network_store.subgraph_store(),
+            subscription_manager,
+            blockchain_map,
+            node_id.clone(),
+            SubgraphVersionSwitchingMode::Instant,
+        ));
+
+        // Unlike the binary, wait for the registrar to have started all
+        // deployments assigned to this node so that startup is
+        // deterministic for tests
+        subgraph_registrar.start().await?;
+
+        if let Some((http_port, ws_port)) = self.query_ports {
+            let mut graphql_server = GraphQLQueryServer::new(
+                &logger_factory,
+                metrics_registry.clone(),
+                graphql_runner.clone(),
+                node_id.clone(),
+            );
+            graph::spawn(
+                graphql_server
+                    .serve(http_port, ws_port)
+                    .map_err(|e| anyhow!("failed to start GraphQL query server: {}", e))?
+                    .compat(),
+            );
+        }
+
+        if let Some(port) = self.index_node_port {
+            let mut index_node_server = IndexNodeServer::new(
+                &logger_factory,
+                graphql_runner.clone(),
+                network_store.clone(),
+                link_resolver.clone(),
+                network_store.subgraph_store().clone(),
+            );
+            graph::spawn(
+                index_node_server
+                    .serve(port)
+                    .map_err(|e| anyhow!("failed to start index node server: {}", e))?
+                    .compat(),
+            );
+        }
+
+        if let Some(port) = self.admin_port {
+            let (http_port, ws_port) = self.query_ports.unwrap_or((0, 0));
+            let json_rpc_server = JsonRpcServer::serve(
+                port,
+                http_port,
+                ws_port,
+                subgraph_registrar.clone(),
+                node_id.clone(),
+                logger.clone(),
+            )
+            .map_err(|e| anyhow!("failed to start JSON-RPC admin server: {}", e))?;
+            // The server runs as long as the process does
+            std::mem::forget(json_rpc_server);
+        }
+
+        Ok(Node {
+            logger,
+            node_id,
+            store: network_store,
+            registrar: subgraph_registrar,
+        })
+    }
+}
+
+/// A running embedded `graph-node`, created through [`NodeBuilder`].
+/// Dropping it does not stop the spawned components; embedders that need
+/// to shut down should end the process or the tokio runtime
+pub struct Node {
+    logger: Logger,
+    node_id: NodeId,
+    store: Arc<Store>,
+    registrar: Arc<dyn SubgraphRegistrar>,
+}
+
+impl Node {
+    /// The store of the node, for status queries and direct access to
+    /// deployments
+    pub fn store(&self) -> Arc<Store> {
+        self.store.cheap_clone()
+    }
+
+    /// The registrar of the node, for operations that [`Node::deploy`]
+    /// does not cover, like removing or cloning deployments
+    pub fn registrar(&self) -> Arc<dyn SubgraphRegistrar> {
+        self.registrar.cheap_clone()
+    }
+
+    pub fn node_id(&self) -> &NodeId {
+        &self.node_id
+    }
+
+    /// Deploy the manifest `hash` under `name`, creating the name if
+    /// needed, and start indexing on this node
+    pub async fn deploy(&self, name: &str, hash: &str) -> Result<(), Error> {
+        let name =
+            SubgraphName::new(name).map_err(|()| anyhow!("invalid subgraph name `{}`", name))?;
+        let hash =
+            DeploymentHash::new(hash).map_err(|id| anyhow!("invalid deployment hash `{}`", id))?;
+
+        self.registrar.create_subgraph(name.clone()).await?;
+        self.registrar
+            .create_subgraph_version(name.clone(), hash.clone(), self.node_id.clone())
+            .await?;
+
+        info!(self.logger, "Deployed subgraph";
+              "subgraph_name" => name.to_string(),
+              "subgraph_hash" => hash.to_string());
+        Ok(())
+    }
+
+    /// Wait until the deployment `hash` has caught up with the head of
+    /// its chain. Fails when the deployment fails or when it is not
+    /// synced after `timeout`
+    pub async fn await_synced(&self, hash: &str, timeout: Duration) -> Result<(), Error> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let infos = self
+                .store
+                .status(status::Filter::Deployments(vec![hash.to_string()]))?;
+            let info = infos
+                .first()
+                .ok_or_else(|| anyhow!("deployment `{}` not found", hash))?;
+            if let Some(error) = &info.fatal_error {
+                bail!("deployment `{}` failed: {}", hash, error.message);
+            }
+            if info.synced {
+                return Ok(());
+            }
+            if Instant::now() > deadline {
+                bail!(
+                    "deployment `{}` did not sync within {}s",
+                    hash,
+                    timeout.as_secs()
+                );
+            }
+            tokio::time::sleep(SYNC_POLL_INTERVAL).await;
+        }
+    }
+}
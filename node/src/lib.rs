@@ -1,8 +1,12 @@
 #[macro_use]
 extern crate diesel;
 
+#[cfg(feature = "kafka")]
+pub mod cdc;
 pub mod chain;
+pub mod check;
 pub mod config;
+pub mod embedded;
 pub mod opt;
 pub mod store_builder;
 
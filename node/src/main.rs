@@ -1,16 +1,13 @@
 use ethereum::EthereumNetworks;
 use git_testament::{git_testament, render_testament};
-use graph::blockchain::block_ingestor::BlockIngestor;
-use graph::blockchain::firehose_block_ingestor::FirehoseBlockIngestor;
-use graph::blockchain::{Block as BlockchainBlock, Blockchain, BlockchainKind, BlockchainMap};
-use graph::components::store::BlockStore;
+use graph::blockchain::{BlockchainKind, BlockchainMap};
 use graph::data::graphql::effort::LoadManager;
-use graph::firehose::endpoints::{FirehoseNetworkEndpoints, FirehoseNetworks};
+use graph::firehose::endpoints::FirehoseNetworks;
 use graph::log::logger;
 use graph::prelude::{IndexNodeServer as _, JsonRpcServer as _, *};
 use graph::prometheus::Registry;
 use graph_chain_ethereum as ethereum;
-use graph_chain_near::{self as near, HeaderOnlyBlock as NearFirehoseHeaderOnlyBlock};
+use graph_chain_near::HeaderOnlyBlock as NearFirehoseHeaderOnlyBlock;
 use graph_core::{
     LinkResolver, MetricsRegistry, SubgraphAssignmentProvider as IpfsSubgraphAssignmentProvider,
     SubgraphInstanceManager, SubgraphRegistrar as IpfsSubgraphRegistrar,
@@ -18,7 +15,8 @@ use graph_core::{
 use graph_graphql::prelude::GraphQlRunner;
 use graph_node::chain::{
     connect_ethereum_networks, connect_firehose_networks, create_ethereum_networks,
-    create_firehose_networks, create_ipfs_clients, ANCESTOR_COUNT, REORG_THRESHOLD,
+    create_firehose_networks, create_ipfs_clients, ethereum_networks_as_chains,
+    near_networks_as_chains, start_block_ingestor, start_firehose_block_ingestor,
 };
 use graph_node::config::Config;
 use graph_node::opt;
@@ -28,13 +26,13 @@ use graph_server_index_node::IndexNodeServer;
 use graph_server_json_rpc::JsonRpcServer;
 use graph_server_metrics::PrometheusMetricsServer;
 use graph_server_websocket::SubscriptionServer as GraphQLSubscriptionServer;
-use graph_store_postgres::{register_jobs as register_store_jobs, ChainHeadUpdateListener, Store};
+use graph_store_postgres::register_jobs as register_store_jobs;
 use std::collections::BTreeMap;
+use std::env;
 use std::io::{BufRead, BufReader};
 use std::path::Path;
 use std::sync::atomic;
 use std::time::Duration;
-use std::{collections::HashMap, env};
 use structopt::StructOpt;
 use tokio::sync::mpsc;
 
@@ -109,6 +107,17 @@ async fn main() {
         eprintln!("Successfully validated configuration");
         std::process::exit(0);
     }
+    if let Some(graph_node::opt::Command::Check) = opt.command {
+        let code = graph_node::check::run(&logger, &opt, &config).await;
+        std::process::exit(code);
+    }
+
+    // Register the effective configuration for the `nodeConfiguration`
+    // query of the index node server
+    match config.redacted_json() {
+        Ok(json) => graph_server_index_node::set_node_configuration(json),
+        Err(e) => warn!(logger, "Failed to serialize configuration"; "error" => e.to_string()),
+    }
 
     let node_id =
         NodeId::new(opt.node_id.clone()).expect("Node ID must contain only a-z, A-Z, 0-9, and '_'");
@@ -158,6 +167,7 @@ async fn main() {
         logger.clone(),
         prometheus_registry.clone(),
     ));
+    graph::register_pool_metrics(metrics_registry.as_ref());
     let mut metrics_server =
         PrometheusMetricsServer::new(&logger_factory, prometheus_registry.clone());
 
@@ -212,6 +222,33 @@ async fn main() {
         let network_identifiers = ethereum_idents.into_iter().chain(near_idents).collect();
         let network_store = store_builder.network_store(network_identifiers);
 
+        network_store
+            .subgraph_store()
+            .load_deployment_settings()
+            .expect("Failed to load per-deployment settings");
+
+        // Optionally forward entity change events to Kafka; see the
+        // `cdc` module for the environment variables that control this
+        #[cfg(feature = "kafka")]
+        match graph_node::cdc::CdcConfig::from_env() {
+            Ok(Some(cdc_config)) => graph_node::cdc::spawn(
+                &logger,
+                network_store.subgraph_store(),
+                subscription_manager.clone(),
+                cdc_config,
+            )
+            .expect("Failed to start the entity change publisher"),
+            Ok(None) => (),
+            Err(e) => panic!("Invalid CDC configuration: {}", e),
+        }
+        #[cfg(not(feature = "kafka"))]
+        if std::env::var_os("GRAPH_CDC_KAFKA_BROKERS").is_some() {
+            panic!(
+                "GRAPH_CDC_KAFKA_BROKERS is set, but this graph-node was \
+                 built without the `kafka` feature"
+            );
+        }
+
         let ethereum_chains = ethereum_networks_as_chains(
             &mut blockchain_map,
             &logger,
@@ -321,12 +358,13 @@ async fn main() {
             node_id.clone(),
             version_switching_mode,
         ));
-        graph::spawn(
-            subgraph_registrar
+        let registrar = subgraph_registrar.cheap_clone();
+        graph::spawn(async move {
+            registrar
                 .start()
-                .map_err(|e| panic!("failed to initialize subgraph provider {}", e))
-                .compat(),
-        );
+                .await
+                .unwrap_or_else(|e| panic!("failed to initialize subgraph provider {}", e))
+        });
 
         // Start admin JSON-RPC server.
         let json_rpc_server = JsonRpcServer::serve(
@@ -430,221 +468,26 @@ async fn main() {
         }
     });
 
-    futures::future::pending::<()>().await;
-}
-
-/// Return the hashmap of ethereum chains and also add them to `blockchain_map`.
-fn ethereum_networks_as_chains(
-    blockchain_map: &mut BlockchainMap,
-    logger: &Logger,
-    node_id: NodeId,
-    registry: Arc<MetricsRegistry>,
-    firehose_networks: Option<&FirehoseNetworks>,
-    eth_networks: &EthereumNetworks,
-    store: &Store,
-    chain_head_update_listener: Arc<ChainHeadUpdateListener>,
-    logger_factory: &LoggerFactory,
-) -> HashMap<String, Arc<ethereum::Chain>> {
-    let chains: Vec<_> = eth_networks
-        .networks
-        .iter()
-        .filter_map(|(network_name, eth_adapters)| {
-            store
-                .block_store()
-                .chain_store(network_name)
-                .map(|chain_store| {
-                    let is_ingestible = chain_store.is_ingestible();
-                    (network_name, eth_adapters, chain_store, is_ingestible)
-                })
-                .or_else(|| {
-                    error!(
-                        logger,
-                        "No store configured for Ethereum chain {}; ignoring this chain",
-                        network_name
-                    );
-                    None
-                })
-        })
-        .map(|(network_name, eth_adapters, chain_store, is_ingestible)| {
-            let firehose_endpoints = firehose_networks.and_then(|v| v.networks.get(network_name));
-
-            let chain = ethereum::Chain::new(
-                logger_factory.clone(),
-                network_name.clone(),
-                node_id.clone(),
-                registry.clone(),
-                chain_store.cheap_clone(),
-                chain_store,
-                store.subgraph_store(),
-                firehose_endpoints.map_or_else(|| FirehoseNetworkEndpoints::new(), |v| v.clone()),
-                eth_adapters.clone(),
-                chain_head_update_listener.clone(),
-                *ANCESTOR_COUNT,
-                *REORG_THRESHOLD,
-                is_ingestible,
-            );
-            (network_name.clone(), Arc::new(chain))
-        })
-        .collect();
-
-    for (network_name, chain) in chains.iter().cloned() {
-        blockchain_map.insert::<graph_chain_ethereum::Chain>(network_name, chain)
+    // Wait for a termination signal. When the node runs as PID 1 in a
+    // container built from scratch, nothing installs signal handlers for
+    // us, and the kernel ignores signals whose disposition is the default
+    // in PID 1; without listening explicitly, `docker stop` would hang
+    // until the daemon gives up and sends SIGKILL
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut sigint = signal(SignalKind::interrupt()).expect("Failed to listen for SIGINT");
+        let mut sigterm = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+        tokio::select! {
+            _ = sigint.recv() => info!(logger, "Received SIGINT; shutting down"),
+            _ = sigterm.recv() => info!(logger, "Received SIGTERM; shutting down"),
+        };
     }
-
-    HashMap::from_iter(chains)
-}
-
-/// Return the hashmap of NEAR chains and also add them to `blockchain_map`.
-fn near_networks_as_chains(
-    blockchain_map: &mut BlockchainMap,
-    logger: &Logger,
-    firehose_networks: &FirehoseNetworks,
-    store: &Store,
-    logger_factory: &LoggerFactory,
-) -> HashMap<String, FirehoseChain<near::Chain>> {
-    let chains: Vec<_> = firehose_networks
-        .networks
-        .iter()
-        .filter_map(|(network_name, firehose_endpoints)| {
-            store
-                .block_store()
-                .chain_store(network_name)
-                .map(|chain_store| (network_name, chain_store, firehose_endpoints))
-                .or_else(|| {
-                    error!(
-                        logger,
-                        "No store configured for NEAR chain {}; ignoring this chain", network_name
-                    );
-                    None
-                })
-        })
-        .map(|(network_name, chain_store, firehose_endpoints)| {
-            (
-                network_name.clone(),
-                FirehoseChain {
-                    chain: Arc::new(near::Chain::new(
-                        logger_factory.clone(),
-                        network_name.clone(),
-                        chain_store,
-                        firehose_endpoints.clone(),
-                    )),
-                    firehose_endpoints: firehose_endpoints.clone(),
-                },
-            )
-        })
-        .collect();
-
-    for (network_name, firehose_chain) in chains.iter() {
-        blockchain_map
-            .insert::<graph_chain_near::Chain>(network_name.clone(), firehose_chain.chain.clone())
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to listen for Ctrl-C");
+        info!(logger, "Received Ctrl-C; shutting down");
     }
-
-    HashMap::from_iter(chains)
-}
-
-fn start_block_ingestor(
-    logger: &Logger,
-    block_polling_interval: Duration,
-    chains: HashMap<String, Arc<ethereum::Chain>>,
-) {
-    // BlockIngestor must be configured to keep at least REORG_THRESHOLD ancestors,
-    // otherwise BlockStream will not work properly.
-    // BlockStream expects the blocks after the reorg threshold to be present in the
-    // database.
-    assert!(*ANCESTOR_COUNT >= *REORG_THRESHOLD);
-
-    info!(
-        logger,
-        "Starting block ingestors with {} chains [{}]",
-        chains.len(),
-        chains
-            .keys()
-            .map(|v| v.clone())
-            .collect::<Vec<String>>()
-            .join(", ")
-    );
-
-    // Create Ethereum block ingestors and spawn a thread to run each
-    chains
-        .iter()
-        .filter(|(network_name, chain)| {
-            if !chain.is_ingestible {
-                error!(logger, "Not starting block ingestor (chain is defective)"; "network_name" => &network_name);
-            }
-            chain.is_ingestible
-        })
-        .for_each(|(network_name, chain)| {
-            info!(
-                logger,
-                "Starting block ingestor for network";
-                "network_name" => &network_name
-            );
-
-            let block_ingestor = BlockIngestor::<ethereum::Chain>::new(
-                chain.ingestor_adapter(),
-                block_polling_interval,
-            )
-            .expect("failed to create Ethereum block ingestor");
-
-            // Run the Ethereum block ingestor in the background
-            graph::spawn(block_ingestor.into_polling_stream());
-        });
-}
-
-#[derive(Clone)]
-struct FirehoseChain<C: Blockchain> {
-    chain: Arc<C>,
-    firehose_endpoints: FirehoseNetworkEndpoints,
-}
-
-fn start_firehose_block_ingestor<C, M>(
-    logger: &Logger,
-    store: &Store,
-    chains: HashMap<String, FirehoseChain<C>>,
-) where
-    C: Blockchain,
-    M: prost::Message + BlockchainBlock + Default + 'static,
-{
-    info!(
-        logger,
-        "Starting firehose block ingestors with {} chains [{}]",
-        chains.len(),
-        chains
-            .keys()
-            .map(|v| v.clone())
-            .collect::<Vec<String>>()
-            .join(", ")
-    );
-
-    // Create Firehose block ingestors and spawn a thread to run each
-    chains
-        .iter()
-        .for_each(|(network_name, chain)| {
-            info!(
-                logger,
-                "Starting firehose block ingestor for network";
-                "network_name" => &network_name
-            );
-
-            let endpoint = chain
-                .firehose_endpoints
-                .random()
-                .expect("One Firehose endpoint should exist at that execution point");
-
-            match store.block_store().chain_store(network_name.as_ref()) {
-                Some(s) => {
-                    let block_ingestor = FirehoseBlockIngestor::<M>::new(
-                        s,
-                        endpoint.clone(),
-                        logger.new(o!("component" => "FirehoseBlockIngestor", "provider" => endpoint.provider.clone())),
-                    );
-
-                    // Run the Firehose block ingestor in the background
-                    graph::spawn(block_ingestor.run());
-                },
-                None => {
-                    error!(logger, "Not starting firehose block ingestor (no chain store available)"; "network_name" => &network_name);
-                }
-            }
-        });
 }
@@ -0,0 +1,370 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use graph::components::store::EntityType;
+use graph::data::graphql::ext::DirectiveFinder;
+use graph::data::graphql::{DocumentExt, TypeExt};
+use graph::prelude::{
+    anyhow::{self, anyhow, bail},
+    s, serde_json, DeploymentHash, Entity, StoreError, SubgraphStore as _, Value, BLOCK_NUMBER_MAX,
+};
+use graph_store_postgres::SubgraphStore;
+#[cfg(feature = "parquet")]
+use parquet::{
+    basic::{ConvertedType, Repetition, Type as PhysicalType},
+    column::writer::ColumnWriter,
+    data_type::ByteArray,
+    file::properties::WriterProperties,
+    file::writer::{FileWriter, SerializedFileWriter},
+    schema::types::Type as ParquetType,
+};
+use s3::{bucket::Bucket, creds::Credentials, region::Region};
+
+/// How many entities to read from the store in one query. Each batch
+/// becomes one row group in Parquet output
+const BATCH_SIZE: u32 = 10_000;
+
+/// How an exported column is represented in the output. Everything that
+/// does not have an exact equivalent in both formats is written as text
+#[derive(Clone, Copy, PartialEq)]
+enum ColumnKind {
+    Bool,
+    Int,
+    Text,
+}
+
+/// A non-derived field of the entity type we are exporting, in the order
+/// in which it appears in the subgraph schema
+struct Column {
+    name: String,
+    kind: ColumnKind,
+}
+
+impl Column {
+    fn new(field: &s::Field) -> Self {
+        let kind = if is_list(&field.field_type) {
+            ColumnKind::Text
+        } else {
+            match field.field_type.get_base_type() {
+                "Boolean" => ColumnKind::Bool,
+                "Int" => ColumnKind::Int,
+                _ => ColumnKind::Text,
+            }
+        };
+        Column {
+            name: field.name.clone(),
+            kind,
+        }
+    }
+}
+
+fn is_list(field_type: &s::Type) -> bool {
+    match field_type {
+        s::Type::NamedType(_) => false,
+        s::Type::NonNullType(inner) => is_list(inner),
+        s::Type::ListType(_) => true,
+    }
+}
+
+/// Render `value` the way it should appear in an exported text field:
+/// scalars use their canonical string form, lists become JSON arrays of
+/// strings, and nulls become empty fields
+fn field_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::List(values) => {
+            serde_json::Value::from(values.iter().map(field_text).collect::<Vec<_>>()).to_string()
+        }
+        value => value.to_string(),
+    }
+}
+
+enum Format {
+    Csv,
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Result<Self, anyhow::Error> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("csv") => Ok(Format::Csv),
+            #[cfg(feature = "parquet")]
+            Some("parquet") => Ok(Format::Parquet),
+            #[cfg(not(feature = "parquet"))]
+            Some("parquet") => bail!(
+                "this graphman was built without the `parquet` feature and \
+                 can only export to `.csv`"
+            ),
+            _ => bail!(
+                "can not tell the output format from `{}`; the output must \
+                 end in `.csv` or `.parquet`",
+                path.display()
+            ),
+        }
+    }
+}
+
+enum Writer {
+    Csv(csv::Writer<File>),
+    #[cfg(feature = "parquet")]
+    Parquet(Box<SerializedFileWriter<File>>),
+}
+
+impl Writer {
+    fn new(format: Format, path: &Path, columns: &[Column]) -> Result<Self, anyhow::Error> {
+        match format {
+            Format::Csv => {
+                let mut writer = csv::Writer::from_path(path)?;
+                writer.write_record(columns.iter().map(|column| column.name.as_str()))?;
+                Ok(Writer::Csv(writer))
+            }
+            #[cfg(feature = "parquet")]
+            Format::Parquet => {
+                let mut fields = columns
+                    .iter()
+                    .map(|column| {
+                        let builder = match column.kind {
+                            ColumnKind::Bool => ParquetType::primitive_type_builder(
+                                &column.name,
+                                PhysicalType::BOOLEAN,
+                            ),
+                            ColumnKind::Int => ParquetType::primitive_type_builder(
+                                &column.name,
+                                PhysicalType::INT32,
+                            ),
+                            ColumnKind::Text => ParquetType::primitive_type_builder(
+                                &column.name,
+                                PhysicalType::BYTE_ARRAY,
+                            )
+                            .with_converted_type(ConvertedType::UTF8),
+                        };
+                        builder
+                            .with_repetition(Repetition::OPTIONAL)
+                            .build()
+                            .map(Arc::new)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let schema = Arc::new(
+                    ParquetType::group_type_builder("schema")
+                        .with_fields(&mut fields)
+                        .build()?,
+                );
+                let props = Arc::new(WriterProperties::builder().build());
+                let writer = SerializedFileWriter::new(File::create(path)?, schema, props)?;
+                Ok(Writer::Parquet(Box::new(writer)))
+            }
+        }
+    }
+
+    /// Write one batch of entities; for Parquet, the batch becomes one
+    /// row group
+    fn write(&mut self, columns: &[Column], entities: &[Entity]) -> Result<(), anyhow::Error> {
+        match self {
+            Writer::Csv(writer) => {
+                for entity in entities {
+                    writer.write_record(columns.iter().map(|column| {
+                        entity.get(&column.name).map(field_text).unwrap_or_default()
+                    }))?;
+                }
+                Ok(())
+            }
+            #[cfg(feature = "parquet")]
+            Writer::Parquet(writer) => {
+                let mut row_group = writer.next_row_group()?;
+                for column in columns {
+                    let mut col_writer = row_group
+                        .next_column()?
+                        .ok_or_else(|| anyhow!("the Parquet schema is missing a column"))?;
+                    let values: Vec<Option<&Value>> = entities
+                        .iter()
+                        .map(|entity| {
+                            entity
+                                .get(&column.name)
+                                .filter(|value| !matches!(value, Value::Null))
+                        })
+                        .collect();
+                    let def_levels: Vec<i16> = values
+                        .iter()
+                        .map(|value| if value.is_some() { 1 } else { 0 })
+                        .collect();
+                    match (&mut col_writer, column.kind) {
+                        (ColumnWriter::BoolColumnWriter(writer), ColumnKind::Bool) => {
+                            let values = values
+                                .iter()
+                                .filter_map(|value| *value)
+                                .map(|value| match value {
+                                    Value::Bool(b) => Ok(*b),
+                                    _ => Err(anyhow!(
+                                        "field `{}` contains a value that is not a Boolean",
+                                        column.name
+                                    )),
+                                })
+                                .collect::<Result<Vec<_>, _>>()?;
+                            writer.write_batch(&values, Some(&def_levels), None)?;
+                        }
+                        (ColumnWriter::Int32ColumnWriter(writer), ColumnKind::Int) => {
+                            let values = values
+                                .iter()
+                                .filter_map(|value| *value)
+                                .map(|value| match value {
+                                    Value::Int(i) => Ok(*i),
+                                    _ => Err(anyhow!(
+                                        "field `{}` contains a value that is not an Int",
+                                        column.name
+                                    )),
+                                })
+                                .collect::<Result<Vec<_>, _>>()?;
+                            writer.write_batch(&values, Some(&def_levels), None)?;
+                        }
+                        (ColumnWriter::ByteArrayColumnWriter(writer), ColumnKind::Text) => {
+                            let values = values
+                                .iter()
+                                .filter_map(|value| *value)
+                                .map(|value| ByteArray::from(field_text(value).into_bytes()))
+                                .collect::<Vec<_>>();
+                            writer.write_batch(&values, Some(&def_levels), None)?;
+                        }
+                        _ => bail!("the Parquet schema does not match the entity type"),
+                    }
+                    row_group.close_column(col_writer)?;
+                }
+                writer.close_row_group(row_group)?;
+                Ok(())
+            }
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), anyhow::Error> {
+        match self {
+            Writer::Csv(writer) => writer.flush().map_err(Into::into),
+            #[cfg(feature = "parquet")]
+            Writer::Parquet(writer) => writer.close().map(|_| ()).map_err(Into::into),
+        }
+    }
+}
+
+/// Where the export should end up. For S3, we write to a temporary local
+/// file first and upload it once it is complete, since neither output
+/// format can be finalized without seeking
+enum Destination {
+    Local(PathBuf),
+    S3 { bucket: String, key: String },
+}
+
+impl Destination {
+    fn new(output: &str) -> Result<Self, anyhow::Error> {
+        match output.strip_prefix("s3://") {
+            Some(rest) => {
+                let (bucket, key) = rest
+                    .split_once('/')
+                    .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+                    .ok_or_else(|| {
+                        anyhow!("`{}` must have the form `s3://bucket/path/to/file`", output)
+                    })?;
+                Ok(Destination::S3 {
+                    bucket: bucket.to_string(),
+                    key: key.to_string(),
+                })
+            }
+            None => Ok(Destination::Local(PathBuf::from(output))),
+        }
+    }
+
+    /// The local file the export is written to; for S3, a temporary file
+    fn local_path(&self) -> PathBuf {
+        match self {
+            Destination::Local(path) => path.clone(),
+            Destination::S3 { key, .. } => {
+                let name = key.rsplit('/').next().unwrap_or(key);
+                std::env::temp_dir().join(format!(
+                    "graphman-export-{}-{}",
+                    std::process::id(),
+                    name
+                ))
+            }
+        }
+    }
+
+    fn upload(&self, path: &Path) -> Result<(), anyhow::Error> {
+        let (bucket, key) = match self {
+            Destination::Local(_) => return Ok(()),
+            Destination::S3 { bucket, key } => (bucket, key),
+        };
+        let region = std::env::var("AWS_REGION").unwrap_or_else(|_| "us-east-1".to_string());
+        let region = match std::env::var("GRAPH_S3_ENDPOINT") {
+            Ok(endpoint) => Region::Custom { region, endpoint },
+            Err(_) => region.parse()?,
+        };
+        let s3_bucket = Bucket::new(bucket, region, Credentials::default()?)?;
+        let mut file = File::open(path)?;
+        let status = s3_bucket.put_object_stream_blocking(&mut file, key)?;
+        std::fs::remove_file(path)?;
+        if status != 200 {
+            bail!(
+                "uploading to `s3://{}/{}` failed with HTTP status {}",
+                bucket,
+                key,
+                status
+            );
+        }
+        Ok(())
+    }
+}
+
+pub fn run(
+    store: Arc<SubgraphStore>,
+    deployment: String,
+    entity_type: String,
+    output: String,
+    block: Option<i32>,
+) -> Result<(), anyhow::Error> {
+    let deployment =
+        DeploymentHash::new(deployment).map_err(|id| anyhow!("illegal deployment id `{}`", id))?;
+    let schema = store.input_schema(&deployment)?;
+    let object_type = schema
+        .document
+        .get_object_type_definitions()
+        .into_iter()
+        .find(|object_type| object_type.name == entity_type)
+        .ok_or_else(|| {
+            anyhow!(
+                "deployment `{}` does not have an entity type `{}`",
+                deployment,
+                entity_type
+            )
+        })?;
+    let columns: Vec<_> = object_type
+        .fields
+        .iter()
+        .filter(|field| !field.is_derived())
+        .map(Column::new)
+        .collect();
+
+    let destination = Destination::new(&output)?;
+    let path = destination.local_path();
+    let format = Format::from_path(&path)?;
+    let mut writer = Writer::new(format, &path, &columns)?;
+
+    let block = block.unwrap_or(BLOCK_NUMBER_MAX);
+    let count = store.export_entities(
+        &deployment,
+        &EntityType::new(entity_type.clone()),
+        block,
+        BATCH_SIZE,
+        &mut |entities| {
+            writer
+                .write(&columns, &entities)
+                .map_err(StoreError::Unknown)
+        },
+    )?;
+    writer.finish()?;
+    destination.upload(&path)?;
+
+    println!(
+        "exported {} `{}` entities to {}",
+        count, entity_type, output
+    );
+    Ok(())
+}
@@ -3,10 +3,12 @@ pub mod chain;
 pub mod config;
 pub mod copy;
 pub mod create;
+pub mod export;
 pub mod info;
 pub mod listen;
 pub mod query;
 pub mod remove;
+pub mod replay;
 pub mod rewind;
 pub mod stats;
 pub mod txn_speed;
@@ -52,6 +52,7 @@ pub async fn run(
     let query = Query::new(
         document,
         Some(QueryVariables::new(HashMap::from_iter(vars))),
+        false,
     );
 
     let res = runner.run_query(query, target).await;
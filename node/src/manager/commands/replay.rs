@@ -0,0 +1,177 @@
+use std::sync::Arc;
+
+use graph::anyhow::bail;
+use graph::blockchain::BlockchainKind;
+use graph::components::store::{BlockStore as _, EntityModification};
+use graph::firehose::endpoints::FirehoseNetworkEndpoints;
+use graph::prelude::{
+    anyhow, serde_yaml, BlockNumber, CheapClone, LinkResolver as _, LoggerFactory, NodeId,
+};
+use graph_chain_ethereum as ethereum;
+use graph_core::{replay_block, LinkResolver, MetricsRegistry};
+use graph_store_postgres::{connection_pool::ConnectionPool, ChainHeadUpdateListener, Store};
+
+use crate::chain::{
+    create_ethereum_networks, create_ipfs_clients, ANCESTOR_COUNT, REORG_THRESHOLD,
+};
+use crate::config::Config;
+use crate::manager::deployment::Deployment;
+
+pub async fn run(
+    store: Arc<Store>,
+    primary: ConnectionPool,
+    config: &Config,
+    registry: Arc<MetricsRegistry>,
+    node_id: NodeId,
+    ipfs: Vec<String>,
+    name: String,
+    block_number: BlockNumber,
+    trigger: Option<usize>,
+    commit: bool,
+) -> Result<(), anyhow::Error> {
+    // The point of a replay is seeing what the mappings do: log to the
+    // terminal regardless of GRAPH_LOG and make the runtime log every
+    // host function call
+    std::env::set_var("GRAPH_TRACE_HOST_FNS", "1");
+    let logger = graph::log::logger(false);
+
+    let deployments = Deployment::lookup(&primary, name.clone())?;
+    let deployment = match deployments.len() {
+        0 => bail!("no deployment matches `{}`", name),
+        1 => deployments.into_iter().next().unwrap(),
+        n => bail!("`{}` is ambiguous and matches {} deployments", name, n),
+    };
+    let locator = deployment.locator();
+
+    let subgraph_store = store.subgraph_store();
+    let chain_store = store
+        .block_store()
+        .chain_store(&deployment.chain)
+        .ok_or_else(|| anyhow!("can not find chain store for {}", deployment.chain))?;
+
+    let eth_networks = create_ethereum_networks(logger.clone(), registry.clone(), config).await?;
+    let eth_adapters = eth_networks
+        .networks
+        .get(&deployment.chain)
+        .cloned()
+        .ok_or_else(|| {
+            anyhow!(
+                "the configuration has no provider for chain {}",
+                deployment.chain
+            )
+        })?;
+
+    let link_resolver = Arc::new(LinkResolver::from(create_ipfs_clients(&logger, &ipfs)));
+
+    // The listener is only used by block streams, which a replay never
+    // creates, but the chain insists on having one
+    let chain_head_update_listener = Arc::new(ChainHeadUpdateListener::new(
+        &logger,
+        registry.cheap_clone(),
+        config.primary_store().connection.to_owned(),
+    ));
+
+    let logger_factory = LoggerFactory::new(logger.clone(), None);
+    let is_ingestible = chain_store.is_ingestible();
+    let chain = Arc::new(ethereum::Chain::new(
+        logger_factory,
+        deployment.chain.clone(),
+        node_id,
+        registry.cheap_clone(),
+        chain_store.cheap_clone(),
+        chain_store,
+        subgraph_store.clone(),
+        FirehoseNetworkEndpoints::new(),
+        eth_adapters,
+        chain_head_update_listener,
+        *ANCESTOR_COUNT,
+        *REORG_THRESHOLD,
+        is_ingestible,
+    ));
+
+    // Fetch the manifest the same way the assignment provider does and
+    // make sure this is a deployment we know how to replay
+    let file_bytes = link_resolver
+        .cat(&logger, &locator.hash.to_ipfs_link())
+        .await?;
+    let raw_manifest: serde_yaml::Mapping = serde_yaml::from_slice(&file_bytes)?;
+    match BlockchainKind::from_manifest(&raw_manifest)? {
+        BlockchainKind::Ethereum => (),
+        kind => bail!("replay is not supported for {} deployments", kind),
+    }
+
+    let outcome = replay_block::<ethereum::Chain, _, _, _>(
+        &logger,
+        subgraph_store,
+        chain,
+        link_resolver,
+        registry,
+        locator.clone(),
+        raw_manifest,
+        block_number,
+        trigger,
+        commit,
+    )
+    .await?;
+
+    println!(
+        "\nReplayed block #{} ({}) of {}",
+        outcome.block_ptr.number, outcome.block_ptr.hash, locator
+    );
+    println!("{} trigger(s) processed", outcome.trigger_count);
+
+    for error in &outcome.deterministic_errors {
+        println!("deterministic error: {}", error);
+    }
+    if outcome.created_data_sources > 0 {
+        println!(
+            "{} dynamic data source(s) would be created; the replay does not instantiate them",
+            outcome.created_data_sources
+        );
+    }
+
+    if outcome.diffs.is_empty() {
+        println!("no entity changes");
+    } else {
+        println!("\nEntity changes:");
+    }
+    for diff in &outcome.diffs {
+        match &diff.modification {
+            EntityModification::Insert { key, data } => {
+                println!("+ {}[{}]", key.entity_type, key.entity_id);
+                for (attr, value) in data.clone().sorted() {
+                    println!("    {}: {}", attr, value);
+                }
+            }
+            EntityModification::Overwrite { key, data } => {
+                println!("~ {}[{}]", key.entity_type, key.entity_id);
+                let previous = diff.previous.clone().unwrap_or_default();
+                for (attr, value) in data.clone().sorted() {
+                    match previous.get(&attr) {
+                        Some(old) if old == &value => (),
+                        Some(old) => println!("    {}: {} -> {}", attr, old, value),
+                        None => println!("    {}: (unset) -> {}", attr, value),
+                    }
+                }
+                for (attr, old) in previous.clone().sorted() {
+                    if data.get(&attr).is_none() {
+                        println!("    {}: {} -> (unset)", attr, old);
+                    }
+                }
+            }
+            EntityModification::Remove { key } => {
+                println!("- {}[{}]", key.entity_type, key.entity_id);
+            }
+        }
+    }
+
+    if outcome.committed {
+        println!(
+            "\nCommitted; the deployment head is now block {}",
+            outcome.block_ptr.number
+        );
+    } else {
+        println!("\nNothing was written to the store");
+    }
+    Ok(())
+}
@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use diesel::r2d2::ConnectionManager;
 use diesel::r2d2::PooledConnection;
 use diesel::sql_query;
-use diesel::sql_types::{Integer, Text};
+use diesel::sql_types::{BigInt, Integer, Text};
 use diesel::PgConnection;
 use diesel::RunQueryDsl;
 use graph::prelude::anyhow;
@@ -61,6 +61,66 @@ pub fn account_like(
     Ok(())
 }
 
+pub fn compress(
+    pools: HashMap<Shard, ConnectionPool>,
+    table: String,
+    target: usize,
+    rewrite: bool,
+) -> Result<(), anyhow::Error> {
+    // The limits that Postgres enforces for `toast_tuple_target`
+    if target < 128 || target > 8160 {
+        bail!(
+            "the target must be between 128 and 8160 bytes, but is {}",
+            target
+        );
+    }
+
+    let (nsp, table_name) = parse_table_name(&table)?;
+    let (site, conn) = site_and_conn(pools, nsp)?;
+    let qname = format!("{}.\"{}\"", site.namespace, table_name.as_str());
+
+    #[derive(Queryable, QueryableByName)]
+    struct TableSize {
+        #[sql_type = "BigInt"]
+        size: i64,
+    }
+
+    let size = |conn: &PooledConnection<ConnectionManager<PgConnection>>| {
+        sql_query("select pg_total_relation_size($1::regclass) as size")
+            .bind::<Text, _>(&qname)
+            .get_result::<TableSize>(conn)
+            .map(|row| row.size)
+    };
+
+    sql_query(format!(
+        "alter table {} set (toast_tuple_target = {})",
+        qname, target
+    ))
+    .execute(&conn)?;
+    println!("{}: toast_tuple_target set to {}", table, target);
+
+    if rewrite {
+        let before = size(&conn)?;
+        println!("rewriting {} (this locks the table) ...", table);
+        sql_query(format!("vacuum full {}", qname)).execute(&conn)?;
+        let after = size(&conn)?;
+        println!(
+            "{}: size went from {} to {} bytes ({:.0}% of the original size)",
+            table,
+            before,
+            after,
+            after as f64 * 100.0 / before as f64
+        );
+    } else {
+        println!(
+            "only versions written from now on will be compressed; \
+             rerun with --rewrite to also compress existing versions"
+        );
+    }
+
+    Ok(())
+}
+
 pub fn show(
     pools: HashMap<Shard, ConnectionPool>,
     nsp: String,
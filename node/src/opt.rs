@@ -195,6 +195,17 @@ pub struct Opt {
         help = "Allows setting configurations that may result in incorrect Proofs of Indexing."
     )]
     pub unsafe_config: bool,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub enum Command {
+    /// Validate the full configuration: connect to every Postgres shard
+    /// and check its migrations, probe every chain provider and IPFS
+    /// node, print a readiness report and exit nonzero on failure
+    Check,
 }
 
 impl From<Opt> for config::Opt {
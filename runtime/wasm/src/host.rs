@@ -74,7 +74,9 @@ impl<C: Blockchain> RuntimeHostBuilderTrait<C> for RuntimeHostBuilder<C> {
             logger,
             subgraph_id,
             metrics,
-            tokio::runtime::Handle::current(),
+            // Any async work the mapping blocks on runs on the WASM pool
+            // if one is configured
+            graph::wasm_runtime_handle(),
             *TIMEOUT,
             experimental_features,
         )
@@ -24,6 +24,11 @@ use wasmtime::Trap;
 
 use crate::module::{WasmInstance, WasmInstanceContext};
 
+/// The maximum number of entities that `store.remove_all` will remove. The
+/// limit affects whether a handler fails deterministically and must
+/// therefore be the same across all indexers; do not make it configurable
+const STORE_REMOVE_ALL_LIMIT: u32 = 1000;
+
 fn write_poi_event(
     proof_of_indexing: &SharedProofOfIndexing,
     poi_event: &ProofOfIndexingEvent,
@@ -214,6 +219,55 @@ impl<C: Blockchain> HostExports<C> {
         Ok(())
     }
 
+    pub(crate) fn store_remove_all(
+        &self,
+        logger: &Logger,
+        state: &mut BlockState<C>,
+        proof_of_indexing: &SharedProofOfIndexing,
+        entity_type: String,
+    ) -> Result<(), HostExportError> {
+        let entity_type = EntityType::new(entity_type);
+
+        // Fetch one id more than the limit so that we can tell a working set
+        // that is exactly at the limit from one that exceeds it
+        let ids = state
+            .entity_cache
+            .ids_for_type(&self.subgraph_id, &entity_type, STORE_REMOVE_ALL_LIMIT + 1)
+            .map_err(|e| HostExportError::Unknown(e.into()))?;
+        if ids.len() > STORE_REMOVE_ALL_LIMIT as usize {
+            return Err(HostExportError::Deterministic(anyhow!(
+                "store.remove_all(\"{}\") would remove more than {} entities; \
+                 it is only meant for small working sets",
+                entity_type,
+                STORE_REMOVE_ALL_LIMIT
+            )));
+        }
+
+        // The ids are in ascending order, which keeps the PoI deterministic
+        for id in &ids {
+            write_poi_event(
+                proof_of_indexing,
+                &ProofOfIndexingEvent::RemoveEntity {
+                    entity_type: entity_type.as_str(),
+                    id: id.as_str(),
+                },
+                &self.causality_region,
+                logger,
+            );
+        }
+        let keys = ids
+            .into_iter()
+            .map(|id| EntityKey {
+                subgraph_id: self.subgraph_id.clone(),
+                entity_type: entity_type.clone(),
+                entity_id: id,
+            })
+            .collect();
+        state.entity_cache.remove_many(keys);
+
+        Ok(())
+    }
+
     pub(crate) fn store_get(
         &self,
         state: &mut BlockState<C>,
@@ -435,6 +489,20 @@ impl<C: Blockchain> HostExports<C> {
         Ok(x & y)
     }
 
+    pub(crate) fn big_int_bit_xor(
+        &self,
+        x: BigInt,
+        y: BigInt,
+    ) -> Result<BigInt, DeterministicHostError> {
+        Ok(x ^ y)
+    }
+
+    /// Bitwise negation in two's complement, i.e., `!x == -x - 1`. This
+    /// matches the EVM's `not` opcode for values that fit into 256 bits
+    pub(crate) fn big_int_bit_not(&self, x: BigInt) -> Result<BigInt, DeterministicHostError> {
+        Ok(!x)
+    }
+
     pub(crate) fn big_int_left_shift(
         &self,
         x: BigInt,
@@ -451,6 +519,39 @@ impl<C: Blockchain> HostExports<C> {
         Ok(x >> bits)
     }
 
+    /// Like `big_int_left_shift`, but takes the full `u32` the mapping
+    /// passes and fails deterministically when `bits` is out of range
+    /// instead of letting AssemblyScript silently truncate it to `u8`
+    pub(crate) fn big_int_checked_left_shift(
+        &self,
+        x: BigInt,
+        bits: u32,
+    ) -> Result<BigInt, DeterministicHostError> {
+        let bits = u8::try_from(bits).map_err(|_| {
+            DeterministicHostError(anyhow!(
+                "left shift by {} bits is out of range, max is 255",
+                bits
+            ))
+        })?;
+        Ok(x << bits)
+    }
+
+    /// See `big_int_checked_left_shift`. The shift is arithmetic, i.e.,
+    /// negative numbers round towards negative infinity
+    pub(crate) fn big_int_checked_right_shift(
+        &self,
+        x: BigInt,
+        bits: u32,
+    ) -> Result<BigInt, DeterministicHostError> {
+        let bits = u8::try_from(bits).map_err(|_| {
+            DeterministicHostError(anyhow!(
+                "right shift by {} bits is out of range, max is 255",
+                bits
+            ))
+        })?;
+        Ok(x >> bits)
+    }
+
     /// Useful for IPFS hashes stored as bytes
     pub(crate) fn bytes_to_base58(&self, bytes: Vec<u8>) -> Result<String, DeterministicHostError> {
         Ok(::bs58::encode(&bytes).into_string())
@@ -602,6 +703,13 @@ impl<C: Blockchain> HostExports<C> {
         self.data_source_network.clone()
     }
 
+    /// The IPFS hash of the deployment, e.g. `Qm...`. Together with
+    /// `data_source_network`, this lets mappings that are compiled once and
+    /// deployed to several networks branch on where they are running
+    pub(crate) fn deployment_id(&self) -> String {
+        self.subgraph_id.to_string()
+    }
+
     pub(crate) fn data_source_context(&self) -> Entity {
         self.data_source_context
             .as_ref()
@@ -35,6 +35,13 @@ pub use stopwatch::TimeoutStopwatch;
 
 pub const TRAP_TIMEOUT: &str = "trap: interrupt";
 
+lazy_static! {
+    /// Log every host function call through the mapping logger. Mostly
+    /// useful with `graphman replay`, which sets this to show what the
+    /// mappings do while a block is replayed
+    static ref TRACE_HOST_FNS: bool = std::env::var("GRAPH_TRACE_HOST_FNS").is_ok();
+}
+
 pub trait IntoTrap {
     fn determinism_level(&self) -> DeterminismLevel;
     fn into_trap(self) -> Trap;
@@ -146,8 +153,19 @@ impl<C: Blockchain> WasmInstance<C> {
         // Caution: Make sure all exit paths from this function call `exit_handler`.
         self.instance_ctx_mut().ctx.state.enter_handler();
 
+        let result = func.typed()?.call(arg.wasm_ptr());
+
+        {
+            // Track the high-water mark of linear memory; AssemblyScript
+            // never shrinks the memory, so looking at it after the call sees
+            // the largest size this invocation reached
+            let ctx = self.instance_ctx();
+            ctx.host_metrics
+                .track_wasm_memory(ctx.memory.data_size() as f64);
+        }
+
         // This `match` will return early if there was a non-deterministic trap.
-        let deterministic_error: Option<Error> = match func.typed()?.call(arg.wasm_ptr()) {
+        let deterministic_error: Option<Error> = match result {
             Ok(()) => None,
             Err(trap) if self.instance_ctx().possible_reorg => {
                 self.instance_ctx_mut().ctx.state.exit_handler();
@@ -155,6 +173,9 @@ impl<C: Blockchain> WasmInstance<C> {
             }
             Err(trap) if trap.to_string().contains(TRAP_TIMEOUT) => {
                 self.instance_ctx_mut().ctx.state.exit_handler();
+                self.instance_ctx()
+                    .host_metrics
+                    .observe_handler_trap(handler, "timeout");
                 return Err(MappingError::Unknown(Error::from(trap).context(format!(
                     "Handler '{}' hit the timeout of '{}' seconds",
                     handler,
@@ -178,6 +199,9 @@ impl<C: Blockchain> WasmInstance<C> {
                     _ if self.instance_ctx().deterministic_host_trap => Some(e),
                     _ => {
                         self.instance_ctx_mut().ctx.state.exit_handler();
+                        self.instance_ctx()
+                            .host_metrics
+                            .observe_handler_trap(handler, "nondeterministic");
                         return Err(MappingError::Unknown(e));
                     }
                 }
@@ -185,6 +209,9 @@ impl<C: Blockchain> WasmInstance<C> {
         };
 
         if let Some(deterministic_error) = deterministic_error {
+            self.instance_ctx()
+                .host_metrics
+                .observe_handler_trap(handler, "deterministic");
             let message = format!("{:#}", deterministic_error).replace("\n", "\t");
 
             // Log the error and restore the updates snapshot, effectively reverting the handler.
@@ -266,6 +293,10 @@ impl<C: Blockchain> WasmInstance<C> {
         let host_fns = ctx.host_fns.cheap_clone();
         let api_version = ctx.host_exports.api_version.clone();
 
+        // Deployments that the operator marked as trusted with the
+        // `trusted` setting are exempt from GRAPH_DISABLED_HOST_FNS
+        let trusted = graph::settings::for_deployment(&ctx.host_exports.subgraph_id).trusted();
+
         // Used by exports to access the instance context. There are two ways this can be set:
         // - After instantiation, if no host export is called in the start function.
         // - During the start function, if it calls a host export.
@@ -303,6 +334,11 @@ impl<C: Blockchain> WasmInstance<C> {
             };
 
             ($wasm_name:expr, $rust_name:ident, $section:expr, $($param:ident),*) => {
+                let disabled = !trusted
+                    && graph::data::subgraph::DISABLED_HOST_FNS
+                        .iter()
+                        .any(|name| name == $wasm_name);
+
                 let modules = valid_module
                     .import_name_to_modules
                     .get($wasm_name)
@@ -339,6 +375,22 @@ impl<C: Blockchain> WasmInstance<C> {
                             let instance = instance.as_mut().unwrap();
                             let _section = instance.host_metrics.stopwatch.start_section($section);
 
+                            // The operator disabled this host function through
+                            // GRAPH_DISABLED_HOST_FNS; fail deterministically
+                            if disabled {
+                                instance.deterministic_host_trap = true;
+                                return Err(anyhow!(
+                                    "host function `{}` is disabled by the operator",
+                                    $wasm_name
+                                )
+                                .into());
+                            }
+
+                            if *TRACE_HOST_FNS {
+                                info!(instance.ctx.logger, "Host function call";
+                                    "host_fn" => $wasm_name);
+                            }
+
                             let result = instance.$rust_name(
                                 $($param.into()),*
                             );
@@ -375,6 +427,10 @@ impl<C: Blockchain> WasmInstance<C> {
             for module in modules {
                 let func_shared_ctx = Rc::downgrade(&shared_ctx);
                 let host_fn = host_fn.cheap_clone();
+                let disabled = !trusted
+                    && graph::data::subgraph::DISABLED_HOST_FNS
+                        .iter()
+                        .any(|name| name == host_fn.name);
                 linker.func(module, host_fn.name, move |call_ptr: u32| {
                     let start = Instant::now();
                     let instance = func_shared_ctx.upgrade().unwrap();
@@ -393,6 +449,22 @@ impl<C: Blockchain> WasmInstance<C> {
                         }
                     };
 
+                    // The operator disabled this host function through
+                    // GRAPH_DISABLED_HOST_FNS; fail deterministically
+                    if disabled {
+                        instance.deterministic_host_trap = true;
+                        return Err(anyhow!(
+                            "host function `{}` is disabled by the operator",
+                            host_fn.name
+                        )
+                        .into());
+                    }
+
+                    if *TRACE_HOST_FNS {
+                        info!(instance.ctx.logger, "Host function call";
+                            "host_fn" => host_fn.name);
+                    }
+
                     let name_for_metrics = host_fn.name.replace('.', "_");
                     let stopwatch = &instance.host_metrics.stopwatch;
                     let _section =
@@ -418,6 +490,9 @@ impl<C: Blockchain> WasmInstance<C> {
                         start.elapsed().as_secs_f64(),
                         &name_for_metrics,
                     );
+                    if host_fn.name == "ethereum.call" {
+                        instance.host_metrics.observe_eth_call();
+                    }
                     Ok(ret)
                 })?;
             }
@@ -455,6 +530,7 @@ impl<C: Blockchain> WasmInstance<C> {
         );
 
         link!("store.remove", store_remove, entity_ptr, id_ptr);
+        link!("store.remove_all", store_remove_all, entity_ptr);
 
         link!("typeConversion.bytesToString", bytes_to_string, ptr);
         link!("typeConversion.bytesToHex", bytes_to_hex, ptr);
@@ -482,8 +558,22 @@ impl<C: Blockchain> WasmInstance<C> {
         link!("bigInt.fromString", big_int_from_string, ptr);
         link!("bigInt.bitOr", big_int_bit_or, x_ptr, y_ptr);
         link!("bigInt.bitAnd", big_int_bit_and, x_ptr, y_ptr);
+        link!("bigInt.bitXor", big_int_bit_xor, x_ptr, y_ptr);
+        link!("bigInt.bitNot", big_int_bit_not, x_ptr);
         link!("bigInt.leftShift", big_int_left_shift, x_ptr, bits);
         link!("bigInt.rightShift", big_int_right_shift, x_ptr, bits);
+        link!(
+            "bigInt.checkedLeftShift",
+            big_int_checked_left_shift,
+            x,
+            bits
+        );
+        link!(
+            "bigInt.checkedRightShift",
+            big_int_checked_right_shift,
+            x,
+            bits
+        );
 
         link!("bigDecimal.toString", big_decimal_to_string, ptr);
         link!("bigDecimal.fromString", big_decimal_from_string, ptr);
@@ -505,6 +595,8 @@ impl<C: Blockchain> WasmInstance<C> {
         link!("dataSource.network", data_source_network,);
         link!("dataSource.context", data_source_context,);
 
+        link!("deployment.id", deployment_id,);
+
         link!("ens.nameByHash", ens_name_by_hash, ptr);
 
         link!("log.log", log_log, level, msg_ptr);
@@ -815,6 +907,20 @@ impl<C: Blockchain> WasmInstanceContext<C> {
         )
     }
 
+    /// function store.remove_all(entity: string): void
+    pub fn store_remove_all(
+        &mut self,
+        entity_ptr: AscPtr<AscString>,
+    ) -> Result<(), HostExportError> {
+        let entity = asc_get(self, entity_ptr)?;
+        self.ctx.host_exports.store_remove_all(
+            &self.ctx.logger,
+            &mut self.ctx.state,
+            &self.ctx.proof_of_indexing,
+            entity,
+        )
+    }
+
     /// function store.get(entity: string, id: string): Entity | null
     pub fn store_get(
         &mut self,
@@ -1199,6 +1305,31 @@ impl<C: Blockchain> WasmInstanceContext<C> {
         asc_new(self, &result)
     }
 
+    /// function bigInt.bitXor(x: BigInt, y: BigInt): BigInt
+    pub fn big_int_bit_xor(
+        &mut self,
+        x_ptr: AscPtr<AscBigInt>,
+        y_ptr: AscPtr<AscBigInt>,
+    ) -> Result<AscPtr<AscBigInt>, DeterministicHostError> {
+        let result = self
+            .ctx
+            .host_exports
+            .big_int_bit_xor(asc_get(self, x_ptr)?, asc_get(self, y_ptr)?)?;
+        asc_new(self, &result)
+    }
+
+    /// function bigInt.bitNot(x: BigInt): BigInt
+    pub fn big_int_bit_not(
+        &mut self,
+        x_ptr: AscPtr<AscBigInt>,
+    ) -> Result<AscPtr<AscBigInt>, DeterministicHostError> {
+        let result = self
+            .ctx
+            .host_exports
+            .big_int_bit_not(asc_get(self, x_ptr)?)?;
+        asc_new(self, &result)
+    }
+
     /// function bigInt.leftShift(x: BigInt, bits: u8): BigInt
     pub fn big_int_left_shift(
         &mut self,
@@ -1227,6 +1358,32 @@ impl<C: Blockchain> WasmInstanceContext<C> {
         asc_new(self, &result)
     }
 
+    /// function bigInt.checkedLeftShift(x: BigInt, bits: u32): BigInt
+    pub fn big_int_checked_left_shift(
+        &mut self,
+        x_ptr: AscPtr<AscBigInt>,
+        bits: u32,
+    ) -> Result<AscPtr<AscBigInt>, DeterministicHostError> {
+        let result = self
+            .ctx
+            .host_exports
+            .big_int_checked_left_shift(asc_get(self, x_ptr)?, bits)?;
+        asc_new(self, &result)
+    }
+
+    /// function bigInt.checkedRightShift(x: BigInt, bits: u32): BigInt
+    pub fn big_int_checked_right_shift(
+        &mut self,
+        x_ptr: AscPtr<AscBigInt>,
+        bits: u32,
+    ) -> Result<AscPtr<AscBigInt>, DeterministicHostError> {
+        let result = self
+            .ctx
+            .host_exports
+            .big_int_checked_right_shift(asc_get(self, x_ptr)?, bits)?;
+        asc_new(self, &result)
+    }
+
     /// function typeConversion.bytesToBase58(bytes: Bytes): string
     pub fn bytes_to_base58(
         &mut self,
@@ -1379,6 +1536,11 @@ impl<C: Blockchain> WasmInstanceContext<C> {
         asc_new(self, &self.ctx.host_exports.data_source_context().sorted())
     }
 
+    /// function deployment.id(): String
+    pub fn deployment_id(&mut self) -> Result<AscPtr<AscString>, DeterministicHostError> {
+        asc_new(self, &self.ctx.host_exports.deployment_id())
+    }
+
     pub fn ens_name_by_hash(
         &mut self,
         hash_ptr: AscPtr<AscString>,
@@ -9,6 +9,7 @@ extern crate hyper;
 extern crate serde;
 
 mod request;
+mod response;
 mod server;
 mod service;
 
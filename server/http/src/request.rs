@@ -8,12 +8,13 @@ use graph::prelude::*;
 /// Future for a query parsed from an HTTP request.
 pub struct GraphQLRequest {
     body: Bytes,
+    trace: bool,
 }
 
 impl GraphQLRequest {
     /// Creates a new GraphQLRequest future based on an HTTP request and a result sender.
-    pub fn new(body: Bytes) -> Self {
-        GraphQLRequest { body }
+    pub fn new(body: Bytes, trace: bool) -> Self {
+        GraphQLRequest { body, trace }
     }
 }
 
@@ -26,50 +27,86 @@ impl Future for GraphQLRequest {
         let json: serde_json::Value = serde_json::from_slice(&self.body)
             .map_err(|e| GraphQLServerError::ClientError(format!("{}", e)))?;
 
-        // Ensure the JSON data is an object
-        let obj = json.as_object().ok_or_else(|| {
-            GraphQLServerError::ClientError(String::from("Request data is not an object"))
-        })?;
-
-        // Ensure the JSON data has a "query" field
-        let query_value = obj.get("query").ok_or_else(|| {
-            GraphQLServerError::ClientError(String::from(
-                "The \"query\" field is missing in request data",
-            ))
-        })?;
-
-        // Ensure the "query" field is a string
-        let query_string = query_value.as_str().ok_or_else(|| {
-            GraphQLServerError::ClientError(String::from("The \"query\" field is not a string"))
-        })?;
-
-        // Parse the "query" field of the JSON body
-        let document = graphql_parser::parse_query(query_string)
-            .map_err(|e| GraphQLServerError::from(QueryError::ParseError(Arc::new(e.into()))))?
-            .into_static();
+        parse_request(&json, self.trace).map(Async::Ready)
+    }
+}
 
-        // Parse the "variables" field of the JSON body, if present
-        let variables = match obj.get("variables") {
-            None | Some(serde_json::Value::Null) => Ok(None),
-            Some(variables @ serde_json::Value::Object(_)) => {
-                serde_json::from_value(variables.clone())
-                    .map_err(|e| GraphQLServerError::ClientError(e.to_string()))
-                    .map(Some)
-            }
-            _ => Err(GraphQLServerError::ClientError(
-                "Invalid query variables provided".to_string(),
-            )),
-        }?;
+/// Parses a GraphQL query from the query string of a GET request. The
+/// `query` parameter holds the query text; the optional `variables`
+/// parameter holds the variables as URL-encoded JSON
+pub fn parse_get_request(query_string: &str, trace: bool) -> Result<Query, GraphQLServerError> {
+    use graph::url::form_urlencoded;
 
-        Ok(Async::Ready(Query::new(document, variables)))
+    let mut obj = serde_json::Map::new();
+    for (name, value) in form_urlencoded::parse(query_string.as_bytes()) {
+        match name.as_ref() {
+            "query" => {
+                obj.insert(
+                    String::from("query"),
+                    serde_json::Value::String(value.into_owned()),
+                );
+            }
+            "variables" => {
+                let variables = serde_json::from_str(&value).map_err(|e| {
+                    GraphQLServerError::ClientError(format!(
+                        "Invalid query variables provided: {}",
+                        e
+                    ))
+                })?;
+                obj.insert(String::from("variables"), variables);
+            }
+            _ => (),
+        }
     }
+
+    parse_request(&serde_json::Value::Object(obj), trace)
+}
+
+pub(crate) fn parse_request(
+    json: &serde_json::Value,
+    trace: bool,
+) -> Result<Query, GraphQLServerError> {
+    // Ensure the JSON data is an object
+    let obj = json.as_object().ok_or_else(|| {
+        GraphQLServerError::ClientError(String::from("Request data is not an object"))
+    })?;
+
+    // Ensure the JSON data has a "query" field
+    let query_value = obj.get("query").ok_or_else(|| {
+        GraphQLServerError::ClientError(String::from(
+            "The \"query\" field is missing in request data",
+        ))
+    })?;
+
+    // Ensure the "query" field is a string
+    let query_string = query_value.as_str().ok_or_else(|| {
+        GraphQLServerError::ClientError(String::from("The \"query\" field is not a string"))
+    })?;
+
+    // Parse the "query" field
+    let document = graphql_parser::parse_query(query_string)
+        .map_err(|e| GraphQLServerError::from(QueryError::ParseError(Arc::new(e.into()))))?
+        .into_static();
+
+    // Parse the "variables" field, if present
+    let variables = match obj.get("variables") {
+        None | Some(serde_json::Value::Null) => Ok(None),
+        Some(variables @ serde_json::Value::Object(_)) => serde_json::from_value(variables.clone())
+            .map_err(|e| GraphQLServerError::ClientError(e.to_string()))
+            .map(Some),
+        _ => Err(GraphQLServerError::ClientError(
+            "Invalid query variables provided".to_string(),
+        )),
+    }?;
+
+    Ok(Query::new(document, variables, trace))
 }
 
 #[cfg(test)]
 mod tests {
     use graphql_parser;
     use hyper;
-    use std::collections::{BTreeMap, HashMap};
+    use std::collections::HashMap;
 
     use graph::{data::query::QueryTarget, prelude::*};
 
@@ -82,13 +119,13 @@ mod tests {
 
     #[test]
     fn rejects_invalid_json() {
-        let request = GraphQLRequest::new(hyper::body::Bytes::from("!@#)%"));
+        let request = GraphQLRequest::new(hyper::body::Bytes::from("!@#)%"), false);
         request.wait().expect_err("Should reject invalid JSON");
     }
 
     #[test]
     fn rejects_json_without_query_field() {
-        let request = GraphQLRequest::new(hyper::body::Bytes::from("{}"));
+        let request = GraphQLRequest::new(hyper::body::Bytes::from("{}"), false);
         request
             .wait()
             .expect_err("Should reject JSON without query field");
@@ -96,7 +133,7 @@ mod tests {
 
     #[test]
     fn rejects_json_with_non_string_query_field() {
-        let request = GraphQLRequest::new(hyper::body::Bytes::from("{\"query\": 5}"));
+        let request = GraphQLRequest::new(hyper::body::Bytes::from("{\"query\": 5}"), false);
         request
             .wait()
             .expect_err("Should reject JSON with a non-string query field");
@@ -104,15 +141,16 @@ mod tests {
 
     #[test]
     fn rejects_broken_queries() {
-        let request = GraphQLRequest::new(hyper::body::Bytes::from("{\"query\": \"foo\"}"));
+        let request = GraphQLRequest::new(hyper::body::Bytes::from("{\"query\": \"foo\"}"), false);
         request.wait().expect_err("Should reject broken queries");
     }
 
     #[test]
     fn accepts_valid_queries() {
-        let request = GraphQLRequest::new(hyper::body::Bytes::from(
-            "{\"query\": \"{ user { name } }\"}",
-        ));
+        let request = GraphQLRequest::new(
+            hyper::body::Bytes::from("{\"query\": \"{ user { name } }\"}"),
+            false,
+        );
         let query = request.wait().expect("Should accept valid queries");
         assert_eq!(
             query.document,
@@ -124,13 +162,16 @@ mod tests {
 
     #[test]
     fn accepts_null_variables() {
-        let request = GraphQLRequest::new(hyper::body::Bytes::from(
-            "\
+        let request = GraphQLRequest::new(
+            hyper::body::Bytes::from(
+                "\
                  {\
                  \"query\": \"{ user { name } }\", \
                  \"variables\": null \
                  }",
-        ));
+            ),
+            false,
+        );
         let query = request.wait().expect("Should accept null variables");
 
         let expected_query = graphql_parser::parse_query("{ user { name } }")
@@ -142,27 +183,58 @@ mod tests {
 
     #[test]
     fn rejects_non_map_variables() {
-        let request = GraphQLRequest::new(hyper::body::Bytes::from(
-            "\
+        let request = GraphQLRequest::new(
+            hyper::body::Bytes::from(
+                "\
                  {\
                  \"query\": \"{ user { name } }\", \
                  \"variables\": 5 \
                  }",
-        ));
+            ),
+            false,
+        );
         request.wait().expect_err("Should reject non-map variables");
     }
 
+    #[test]
+    fn parses_get_request_query_strings() {
+        let query = super::parse_get_request(
+            "query=%7B%20user%20%7B%20name%20%7D%20%7D&variables=%7B%22int%22%3A5%7D",
+            false,
+        )
+        .expect("Should accept valid query strings");
+
+        let expected_query = graphql_parser::parse_query("{ user { name } }")
+            .unwrap()
+            .into_static();
+        let expected_variables = QueryVariables::new(HashMap::from_iter(
+            vec![(String::from("int"), r::Value::Int(5))].into_iter(),
+        ));
+
+        assert_eq!(query.document, expected_query);
+        assert_eq!(query.variables, Some(expected_variables));
+    }
+
+    #[test]
+    fn rejects_get_requests_without_query() {
+        super::parse_get_request("variables=%7B%7D", false)
+            .expect_err("Should reject query strings without a query parameter");
+    }
+
     #[test]
     fn parses_variables() {
-        let request = GraphQLRequest::new(hyper::body::Bytes::from(
-            "\
+        let request = GraphQLRequest::new(
+            hyper::body::Bytes::from(
+                "\
                  {\
                  \"query\": \"{ user { name } }\", \
                  \"variables\": { \
                  \"string\": \"s\", \"map\": {\"k\": \"v\"}, \"int\": 5 \
                  } \
                  }",
-        ));
+            ),
+            false,
+        );
         let query = request.wait().expect("Should accept valid queries");
 
         let expected_query = graphql_parser::parse_query("{ user { name } }")
@@ -173,7 +245,7 @@ mod tests {
                 (String::from("string"), r::Value::String(String::from("s"))),
                 (
                     String::from("map"),
-                    r::Value::Object(BTreeMap::from_iter(
+                    r::Value::Object(r::Object::from_iter(
                         vec![(String::from("k"), r::Value::String(String::from("v")))].into_iter(),
                     )),
                 ),
@@ -0,0 +1,115 @@
+use std::io::{self, Write};
+
+use graph::data::query::QueryResults;
+use graph::prelude::*;
+use http::header::{
+    ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
+    CONTENT_TYPE,
+};
+use hyper::body::{Bytes, Sender};
+use hyper::{Body, Response, StatusCode};
+
+/// How many bytes to buffer before handing a chunk to the response body
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// An `io::Write` that forwards everything written to it to the body of
+/// an HTTP response in chunks of `CHUNK_SIZE` bytes
+struct ChunkWriter {
+    sender: Sender,
+    buf: Vec<u8>,
+}
+
+impl ChunkWriter {
+    fn new(sender: Sender) -> Self {
+        ChunkWriter {
+            sender,
+            buf: Vec::with_capacity(CHUNK_SIZE),
+        }
+    }
+
+    fn send_buf(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let chunk = Bytes::from(std::mem::replace(
+            &mut self.buf,
+            Vec::with_capacity(CHUNK_SIZE),
+        ));
+        // Fails only when the client has gone away
+        graph::block_on(self.sender.send_data(chunk))
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))
+    }
+
+    fn abort(self) {
+        self.sender.abort()
+    }
+}
+
+impl Write for ChunkWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        if self.buf.len() >= CHUNK_SIZE {
+            self.send_buf()?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_buf()
+    }
+}
+
+/// The media type of the graphql-over-http spec. Clients that list it in
+/// their `Accept` header get the spec's status code semantics: 4xx when
+/// the request itself fails, 2xx with an `errors` array for field errors
+pub(crate) const GRAPHQL_RESPONSE_MEDIA_TYPE: &str = "application/graphql-response+json";
+
+/// Turn `results` into an HTTP response whose body is serialized directly
+/// into the network stream. Unlike `QueryResults::as_http_response`, this
+/// never builds the entire JSON response in memory, which matters for
+/// results that run to many megabytes
+///
+/// With `graphql_response`, the response follows the graphql-over-http
+/// spec: the body is marked as `application/graphql-response+json` and a
+/// result that has only errors and no data, i.e., a request that failed
+/// before execution, becomes a `400 Bad Request`
+pub(crate) fn stream_response(results: QueryResults, graphql_response: bool) -> Response<Body> {
+    let attestable = results.is_attestable();
+    let request_error =
+        !results.has_data() && results.first().map_or(false, |result| result.has_errors());
+    let (status, content_type) = if graphql_response {
+        let status = if request_error {
+            StatusCode::BAD_REQUEST
+        } else {
+            StatusCode::OK
+        };
+        (status, GRAPHQL_RESPONSE_MEDIA_TYPE)
+    } else {
+        (StatusCode::OK, "application/json")
+    };
+    let (sender, body) = Body::channel();
+
+    // Serialization is CPU-bound and the chunk writer blocks when the
+    // client does not keep up, so it must not run on the main executor
+    graph::spawn_blocking_allow_panic(move || {
+        let mut writer = ChunkWriter::new(sender);
+        let written = serde_json::to_writer(&mut writer, &results)
+            .map_err(io::Error::from)
+            .and_then(|()| writer.flush());
+        if written.is_err() {
+            // The client is gone or a value failed to serialize; all we
+            // can do at this point is signal a truncated response
+            writer.abort();
+        }
+    });
+
+    Response::builder()
+        .status(status)
+        .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+        .header(ACCESS_CONTROL_ALLOW_HEADERS, "Content-Type, User-Agent")
+        .header(ACCESS_CONTROL_ALLOW_METHODS, "GET, OPTIONS, POST")
+        .header(CONTENT_TYPE, content_type)
+        .header("Graph-Attestable", attestable.to_string())
+        .body(body)
+        .unwrap()
+}
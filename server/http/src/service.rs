@@ -6,7 +6,10 @@ use std::task::Poll;
 use std::time::Instant;
 
 use graph::prelude::*;
-use graph::{components::server::query::GraphQLServerError, data::query::QueryTarget};
+use graph::{
+    components::server::query::GraphQLServerError,
+    data::query::{QueryResults, QueryTarget},
+};
 use http::header;
 use http::header::{
     ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
@@ -175,8 +178,25 @@ where
             GraphQLServerError::ClientError(format!("Invalid subgraph name {:?}", subgraph_name))
         })?;
 
-        self.handle_graphql_query(subgraph_name.into(), request.into_body())
-            .await
+        // An `X-Graph-Deployment` header pins the query to a specific
+        // deployment version of the subgraph, which may have been superseded
+        // by a newer deploy, for as long as that version exists in the store
+        let target = match deployment_header(&request)? {
+            Some(deployment) => deployment.into(),
+            None => subgraph_name.into(),
+        };
+
+        let if_none_match = if_none_match(&request);
+        let trace = trace_requested(&request);
+        let graphql_response = accepts_graphql_response(&request);
+        self.handle_graphql_query(
+            target,
+            request.into_body(),
+            if_none_match,
+            trace,
+            graphql_response,
+        )
+        .await
     }
 
     fn handle_graphql_query_by_id(
@@ -188,9 +208,19 @@ where
             .map_err(|id| GraphQLServerError::ClientError(format!("Invalid subgraph id `{}`", id)));
         match res {
             Err(_) => self.handle_not_found(),
-            Ok(id) => self
-                .handle_graphql_query(id.into(), request.into_body())
-                .boxed(),
+            Ok(id) => {
+                let if_none_match = if_none_match(&request);
+                let trace = trace_requested(&request);
+                let graphql_response = accepts_graphql_response(&request);
+                self.handle_graphql_query(
+                    id.into(),
+                    request.into_body(),
+                    if_none_match,
+                    trace,
+                    graphql_response,
+                )
+                .boxed()
+            }
         }
     }
 
@@ -198,6 +228,9 @@ where
         self,
         target: QueryTarget,
         request_body: Body,
+        if_none_match: Option<String>,
+        trace: bool,
+        graphql_response: bool,
     ) -> GraphQLServiceResult {
         let service = self.clone();
         let service_metrics = self.metrics.clone();
@@ -206,10 +239,159 @@ where
         let body = hyper::body::to_bytes(request_body)
             .map_err(|_| GraphQLServerError::InternalError("Failed to read request body".into()))
             .await?;
-        let query = GraphQLRequest::new(body).compat().await;
+        let query = GraphQLRequest::new(body.clone(), trace).compat().await;
+
+        let result = match query {
+            Ok(mut query) => {
+                // When the client disconnects, hyper drops this future,
+                // and with it the guard. That cancels the handle, and the
+                // store stops any SQL statements the query is still
+                // running
+                let guard = CancelGuard::new();
+                query.cancel = Some(guard.handle());
+                let result = service.graphql_runner.run_query(query, target).await;
+                guard.cancel();
+                result
+            }
+            Err(GraphQLServerError::QueryError(e)) => QueryResult::from(e).into(),
+            Err(e) => return Err(e),
+        };
+
+        if let Some(id) = result.first().and_then(|res| res.deployment.clone()) {
+            service_metrics
+                .observe_query_execution_time(start.elapsed().as_secs_f64(), id.to_string());
+        }
+
+        let etag = etag(&body, &result);
+        if etag_matches(etag.as_deref(), if_none_match.as_deref()) {
+            // Unwrap: `etag_matches` only returns true when there is an etag
+            return Ok(not_modified(&etag.unwrap()));
+        }
+        let mut response = crate::response::stream_response(result, graphql_response);
+        if let Some(etag) = etag {
+            // Unwrap: etags only contain ASCII
+            response
+                .headers_mut()
+                .insert(header::ETAG, header::HeaderValue::from_str(&etag).unwrap());
+        }
+        Ok(response)
+    }
+
+    /// Handles a batch of queries against several deployments, all pinned
+    /// to the same block number, so that dashboards combining data from
+    /// several subgraphs on the same chain see one consistent snapshot.
+    /// The request body has the form `{ "block": <number>, "queries":
+    /// [ { "deployment": "Qm...", "query": "...", "variables": {...} } ] }`
+    /// and the response lists, besides one result per query, the
+    /// deployments that have not indexed the requested block yet
+    async fn handle_multi_graphql_query(self, request_body: Body) -> GraphQLServiceResult {
+        let body = hyper::body::to_bytes(request_body)
+            .map_err(|_| GraphQLServerError::InternalError("Failed to read request body".into()))
+            .await?;
+        let json: serde_json::Value = serde_json::from_slice(&body)
+            .map_err(|e| GraphQLServerError::ClientError(format!("{}", e)))?;
+        let obj = json.as_object().ok_or_else(|| {
+            GraphQLServerError::ClientError(String::from("Request data is not an object"))
+        })?;
+        let block: BlockNumber = obj
+            .get("block")
+            .and_then(|block| block.as_i64())
+            .and_then(|block| BlockNumber::try_from(block).ok())
+            .ok_or_else(|| {
+                GraphQLServerError::ClientError(String::from(
+                    "The \"block\" field is missing or not a valid block number",
+                ))
+            })?;
+        let queries = obj
+            .get("queries")
+            .and_then(|qs| qs.as_array())
+            .ok_or_else(|| {
+                GraphQLServerError::ClientError(String::from(
+                    "The \"queries\" field is missing or not an array",
+                ))
+            })?;
+
+        let mut results = Vec::new();
+        let mut unservable = Vec::new();
+        for entry in queries {
+            let deployment = entry
+                .get("deployment")
+                .and_then(|deployment| deployment.as_str())
+                .ok_or_else(|| {
+                    GraphQLServerError::ClientError(String::from(
+                        "Each query needs a \"deployment\" field with a deployment id",
+                    ))
+                })
+                .and_then(|deployment| {
+                    DeploymentHash::new(deployment).map_err(|id| {
+                        GraphQLServerError::ClientError(format!("Invalid deployment id `{}`", id))
+                    })
+                })?;
+            let mut query = crate::request::parse_request(entry, false)?;
+            // See `handle_graphql_query` for how this guard stops the
+            // query when the client disconnects
+            let guard = CancelGuard::new();
+            query.cancel = Some(guard.handle());
+            let result = self
+                .graphql_runner
+                .cheap_clone()
+                .run_query_at_block(query, deployment.clone().into(), block)
+                .await;
+            guard.cancel();
+            if could_not_serve_block(&result) {
+                unservable.push(deployment.to_string());
+                results.push(serde_json::Value::Null);
+            } else {
+                results.push(serde_json::to_value(&result).map_err(|e| {
+                    GraphQLServerError::InternalError(format!(
+                        "Failed to serialize query result: {}",
+                        e
+                    ))
+                })?);
+            }
+        }
+
+        let response = serde_json::json!({
+            "block": block,
+            "results": results,
+            "unservable": unservable,
+        });
+        Ok(Response::builder()
+            .status(200)
+            .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(response.to_string()))
+            .unwrap())
+    }
+
+    /// Handles GraphQL queries sent as `query` and `variables` parameters
+    /// in the query string of a GET request, and makes the response
+    /// cacheable by CDNs
+    async fn handle_graphql_get(
+        self,
+        target: Result<QueryTarget, GraphQLServerError>,
+        query_string: String,
+        if_none_match: Option<String>,
+        trace: bool,
+        graphql_response: bool,
+    ) -> GraphQLServiceResult {
+        let target = target?;
+        let service = self.clone();
+        let service_metrics = self.metrics.clone();
+
+        let start = Instant::now();
+        let query = crate::request::parse_get_request(&query_string, trace);
 
         let result = match query {
-            Ok(query) => service.graphql_runner.run_query(query, target).await,
+            Ok(mut query) => {
+                // See `handle_graphql_query` for how this guard stops the
+                // query when the client disconnects
+                let guard = CancelGuard::new();
+                query.cancel = Some(guard.handle());
+                let result = service.graphql_runner.run_query(query, target).await;
+                guard.cancel();
+                result
+            }
             Err(GraphQLServerError::QueryError(e)) => QueryResult::from(e).into(),
             Err(e) => return Err(e),
         };
@@ -219,7 +401,42 @@ where
                 .observe_query_execution_time(start.elapsed().as_secs_f64(), id.to_string());
         }
 
-        Ok(result.as_http_response())
+        let etag = etag(query_string.as_bytes(), &result);
+        if etag_matches(etag.as_deref(), if_none_match.as_deref()) {
+            // Unwrap: `etag_matches` only returns true when there is an etag
+            return Ok(not_modified(&etag.unwrap()));
+        }
+        let mut response = crate::response::stream_response(result, graphql_response);
+        add_cache_headers(&mut response, etag.as_deref());
+        Ok(response)
+    }
+
+    /// The query target for the given request path; mirrors the POST routes
+    fn target_from_path(path: &[&str]) -> Result<QueryTarget, GraphQLServerError> {
+        let name = |name: String| {
+            SubgraphName::new(name.as_str())
+                .map(QueryTarget::from)
+                .map_err(|()| {
+                    GraphQLServerError::ClientError(format!("Invalid subgraph name {:?}", name))
+                })
+        };
+
+        match path {
+            ["subgraphs", "id", id] => {
+                DeploymentHash::new(*id)
+                    .map(QueryTarget::from)
+                    .map_err(|id| {
+                        GraphQLServerError::ClientError(format!("Invalid subgraph id `{}`", id))
+                    })
+            }
+            ["subgraphs", "name", subgraph_name] => name(subgraph_name.to_string()),
+            ["subgraphs", "name", part1, part2] => name(format!("{}/{}", part1, part2)),
+            ["subgraphs", "network", part1, part2] => name(format!("network/{}/{}", part1, part2)),
+            _ => Err(GraphQLServerError::ClientError(format!(
+                "Invalid query path `/{}`",
+                path.join("/")
+            ))),
+        }
     }
 
     // Handles OPTIONS requests
@@ -228,7 +445,10 @@ where
             Ok(Response::builder()
                 .status(200)
                 .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
-                .header(ACCESS_CONTROL_ALLOW_HEADERS, "Content-Type, User-Agent")
+                .header(
+                    ACCESS_CONTROL_ALLOW_HEADERS,
+                    "Content-Type, User-Agent, X-Graph-Deployment",
+                )
                 .header(ACCESS_CONTROL_ALLOW_METHODS, "GET, OPTIONS, POST")
                 .header(CONTENT_TYPE, "text/html")
                 .body(Body::from(""))
@@ -298,12 +518,48 @@ where
             (Method::GET, path @ ["subgraphs", "id", _])
             | (Method::GET, path @ ["subgraphs", "name", _])
             | (Method::GET, path @ ["subgraphs", "name", _, _])
-            | (Method::GET, path @ ["subgraphs", "network", _, _])
-            | (Method::GET, path @ ["subgraphs"]) => {
+            | (Method::GET, path @ ["subgraphs", "network", _, _]) => {
+                // A GET with a `query` parameter is a GraphQL query;
+                // anything else gets redirected to the GraphiQL UI
+                match req.uri().query() {
+                    Some(query_string) if query_string.contains("query=") => {
+                        let target = Self::target_from_path(path).and_then(|target| {
+                            // See `handle_graphql_query_by_name` for the
+                            // meaning of the `X-Graph-Deployment` header
+                            match (deployment_header(&req)?, target) {
+                                (Some(deployment), QueryTarget::Name(_)) => Ok(deployment.into()),
+                                (_, target) => Ok(target),
+                            }
+                        });
+                        let query_string = query_string.to_owned();
+                        let if_none_match = if_none_match(&req);
+                        let trace = trace_requested(&req);
+                        let graphql_response = accepts_graphql_response(&req);
+                        self.handle_graphql_get(
+                            target,
+                            query_string,
+                            if_none_match,
+                            trace,
+                            graphql_response,
+                        )
+                        .boxed()
+                    }
+                    _ => {
+                        let dest = format!("/{}/graphql", path.join("/"));
+                        self.handle_temp_redirect(dest).boxed()
+                    }
+                }
+            }
+            (Method::GET, path @ ["subgraphs"]) => {
                 let dest = format!("/{}/graphql", path.join("/"));
                 self.handle_temp_redirect(dest).boxed()
             }
 
+            (Method::POST, ["subgraphs", "snapshot"]) => {
+                self.handle_multi_graphql_query(req.into_body()).boxed()
+            }
+            (Method::OPTIONS, ["subgraphs", "snapshot"]) => self.handle_graphql_options(req),
+
             (Method::POST, &["subgraphs", "id", subgraph_id]) => {
                 self.handle_graphql_query_by_id(subgraph_id.to_owned(), req)
             }
@@ -332,6 +588,167 @@ where
     }
 }
 
+/// True if `result` failed because the deployment has not indexed the
+/// requested block yet; see `StoreResolver::locate_block` for where that
+/// error originates
+fn could_not_serve_block(result: &QueryResults) -> bool {
+    result.first().map_or(false, |result| {
+        result.errors().iter().any(|err| {
+            matches!(err,
+                QueryError::ExecutionError(QueryExecutionError::ValueParseError(field, _))
+                    if field == "block.number")
+        })
+    })
+}
+
+/// The deployment hash in the `X-Graph-Deployment` header of `request`, if
+/// present. On name-based endpoints, the header pins the query to that
+/// deployment version instead of the subgraph's current version
+fn deployment_header(
+    request: &Request<Body>,
+) -> Result<Option<DeploymentHash>, GraphQLServerError> {
+    match request.headers().get("x-graph-deployment") {
+        None => Ok(None),
+        Some(value) => value
+            .to_str()
+            .ok()
+            .and_then(|value| DeploymentHash::new(value).ok())
+            .map(Some)
+            .ok_or_else(|| {
+                GraphQLServerError::ClientError(
+                    "invalid deployment id in `X-Graph-Deployment` header".into(),
+                )
+            }),
+    }
+}
+
+lazy_static! {
+    /// Only honor the `X-GraphTrace` header when this is set to `true`.
+    /// Since the trace exposes the generated SQL, it must only be enabled
+    /// on nodes that are not reachable by untrusted clients
+    static ref ALLOW_GRAPH_TRACE: bool = std::env::var("GRAPH_GRAPHQL_ALLOW_TRACE")
+        .ok()
+        .map(|s| s == "true")
+        .unwrap_or(false);
+}
+
+/// True if the client asked for a trace of the SQL statements the query
+/// causes with an `X-GraphTrace: sql` header, and the node allows tracing
+fn trace_requested(request: &Request<Body>) -> bool {
+    *ALLOW_GRAPH_TRACE
+        && request
+            .headers()
+            .get("x-graphtrace")
+            .and_then(|value| value.to_str().ok())
+            .map_or(false, |value| value == "sql")
+}
+
+/// True if the client listed `application/graphql-response+json`, the
+/// media type from the graphql-over-http spec, in its `Accept` header.
+/// Such clients get responses with that media type and the spec's status
+/// code semantics instead of the legacy `application/json` behavior of
+/// always responding with a 200
+fn accepts_graphql_response(request: &Request<Body>) -> bool {
+    request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .map_or(false, |accept| {
+            accept.split(',').any(|media_type| {
+                // Strip any parameters like `;charset=utf-8`
+                media_type.split(';').next().map_or(false, |media_type| {
+                    media_type
+                        .trim()
+                        .eq_ignore_ascii_case(crate::response::GRAPHQL_RESPONSE_MEDIA_TYPE)
+                })
+            })
+        })
+}
+
+/// The value of the `If-None-Match` header of `request`, if present
+fn if_none_match(request: &Request<Body>) -> Option<String> {
+    request
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .map(ToOwned::to_owned)
+}
+
+/// A weak ETag for `result`, derived from the deployment, the raw query
+/// and the block pointer the result was served at. The tag therefore stays
+/// stable while the deployment does not advance, and changes as soon as a
+/// query could produce different data. Results without a block pointer,
+/// e.g., errors, do not get a tag
+fn etag(query: &[u8], result: &QueryResults) -> Option<String> {
+    let first = result.first()?;
+    if first.has_errors() {
+        return None;
+    }
+    let deployment = first.deployment.as_ref()?;
+    let block_ptr = result.block_ptr()?;
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(deployment.as_bytes());
+    bytes.extend_from_slice(query);
+    bytes.extend_from_slice(block_ptr.hash_slice());
+    let hash = tiny_keccak::keccak256(&bytes);
+    Some(format!("W/\"{}\"", hex::encode(&hash[..16])))
+}
+
+/// True if one of the tags the client presented in `If-None-Match` matches
+/// `etag`
+fn etag_matches(etag: Option<&str>, if_none_match: Option<&str>) -> bool {
+    match (etag, if_none_match) {
+        (Some(etag), Some(if_none_match)) => if_none_match.split(',').any(|tag| tag.trim() == etag),
+        _ => false,
+    }
+}
+
+/// The headers the response depends on besides the URL. The response
+/// varies by `Accept` through content negotiation and by
+/// `X-Graph-Deployment` through deployment pinning; a shared cache that
+/// does not key on them would serve one deployment's (or media type's)
+/// body to requests for another
+const VARY_HEADERS: &str = "Accept-Encoding, Accept, X-Graph-Deployment";
+
+/// An empty 304 response that lets the client keep using its cached copy
+/// of the result
+fn not_modified(etag: &str) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::NOT_MODIFIED)
+        .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+        .header(header::VARY, VARY_HEADERS)
+        // Unwrap: etags only contain ASCII
+        .header(header::ETAG, header::HeaderValue::from_str(etag).unwrap())
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// Adds CDN-friendly caching headers to a GET response so that a CDN can
+/// serve repeated polls of the same URL from its cache and only needs to
+/// refetch once the deployment advances. Results without an etag are
+/// marked uncacheable
+fn add_cache_headers(response: &mut Response<Body>, etag: Option<&str>) {
+    use http::header::{CACHE_CONTROL, ETAG, VARY};
+
+    let headers = response.headers_mut();
+    headers.insert(VARY, header::HeaderValue::from_static(VARY_HEADERS));
+
+    match etag {
+        Some(etag) => {
+            // Unwrap: etags only contain ASCII
+            headers.insert(ETAG, header::HeaderValue::from_str(etag).unwrap());
+            headers.insert(
+                CACHE_CONTROL,
+                header::HeaderValue::from_static("public, max-age=0, must-revalidate"),
+            );
+        }
+        None => {
+            headers.insert(CACHE_CONTROL, header::HeaderValue::from_static("no-store"));
+        }
+    }
+}
+
 impl<Q> Service<Request<Body>> for GraphQLService<Q>
 where
     Q: GraphQlRunner,
@@ -350,24 +767,39 @@ where
 
         // Returning Err here will prevent the client from receiving any response.
         // Instead, we generate a Response with an error code and return Ok
+        let graphql_response = accepts_graphql_response(&req);
         Box::pin(async move {
+            // Errors become a JSON response with an `errors` array for
+            // clients that negotiated the graphql-over-http media type,
+            // and plain text for everybody else
+            let error_body = |err: &GraphQLServerError, legacy: String| match graphql_response {
+                true => Body::from(
+                    serde_json::json!({ "errors": [{ "message": err.to_string() }] }).to_string(),
+                ),
+                false => Body::from(legacy),
+            };
+            let error_content_type = match graphql_response {
+                true => crate::response::GRAPHQL_RESPONSE_MEDIA_TYPE,
+                false => "text/plain",
+            };
+
             let result = service.handle_call(req).await;
             match result {
                 Ok(response) => Ok(response),
                 Err(err @ GraphQLServerError::ClientError(_)) => Ok(Response::builder()
                     .status(400)
-                    .header(CONTENT_TYPE, "text/plain")
+                    .header(CONTENT_TYPE, error_content_type)
                     .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
-                    .body(Body::from(err.to_string()))
+                    .body(error_body(&err, err.to_string()))
                     .unwrap()),
                 Err(err @ GraphQLServerError::QueryError(_)) => {
                     error!(logger, "GraphQLService call failed: {}", err);
 
                     Ok(Response::builder()
                         .status(400)
-                        .header(CONTENT_TYPE, "text/plain")
+                        .header(CONTENT_TYPE, error_content_type)
                         .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
-                        .body(Body::from(format!("Query error: {}", err)))
+                        .body(error_body(&err, format!("Query error: {}", err)))
                         .unwrap())
                 }
                 Err(err @ GraphQLServerError::InternalError(_)) => {
@@ -375,9 +807,9 @@ where
 
                     Ok(Response::builder()
                         .status(500)
-                        .header(CONTENT_TYPE, "text/plain")
+                        .header(CONTENT_TYPE, error_content_type)
                         .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
-                        .body(Body::from(format!("Internal server error: {}", err)))
+                        .body(error_body(&err, format!("Internal server error: {}", err)))
                         .unwrap())
                 }
             }
@@ -435,6 +867,15 @@ mod tests {
             ))
         }
 
+        async fn run_query_at_block(
+            self: Arc<Self>,
+            _query: Query,
+            _target: QueryTarget,
+            _block: BlockNumber,
+        ) -> QueryResults {
+            unimplemented!();
+        }
+
         async fn run_subscription(
             self: Arc<Self>,
             _subscription: Subscription,
@@ -480,6 +921,42 @@ mod tests {
         );
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn getting_valid_queries_yields_result_response() {
+        let logger = Logger::root(slog::Discard, o!());
+        let metrics_registry = Arc::new(MockMetricsRegistry::new());
+        let metrics = Arc::new(GraphQLServiceMetrics::new(metrics_registry));
+        let subgraph_id = USERS.clone();
+        let graphql_runner = Arc::new(TestGraphQlRunner);
+
+        let node_id = NodeId::new("test").unwrap();
+        let mut service = GraphQLService::new(logger, metrics, graphql_runner, 8001, node_id);
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri(format!(
+                "http://localhost:8000/subgraphs/id/{}?query=%7B%20name%20%7D",
+                subgraph_id
+            ))
+            .body(Body::from(""))
+            .unwrap();
+
+        // The response must be a 200
+        let response = tokio::spawn(service.call(request))
+            .await
+            .unwrap()
+            .expect("Should return a response");
+        let data = test_utils::assert_successful_response(response);
+
+        // The body should match the simulated query result
+        let name = data
+            .get("name")
+            .expect("Query result data has no \"name\" field")
+            .as_str()
+            .expect("Query result field \"name\" is not a string");
+        assert_eq!(name, "Jordi".to_string());
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn posting_valid_queries_yields_result_response() {
         let logger = Logger::root(slog::Discard, o!());
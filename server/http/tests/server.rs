@@ -61,6 +61,15 @@ impl GraphQlRunner for TestGraphQlRunner {
         .into()
     }
 
+    async fn run_query_at_block(
+        self: Arc<Self>,
+        _query: Query,
+        _target: QueryTarget,
+        _block: BlockNumber,
+    ) -> QueryResults {
+        unimplemented!();
+    }
+
     async fn run_subscription(
         self: Arc<Self>,
         _subscription: Subscription,
@@ -1,10 +1,12 @@
 mod explorer;
+mod node_configuration;
 mod request;
 mod resolver;
 mod schema;
 mod server;
 mod service;
 
+pub use self::node_configuration::set_node_configuration;
 pub use self::request::IndexNodeRequest;
 pub use self::server::IndexNodeServer;
 pub use self::service::{IndexNodeService, IndexNodeServiceResponse};
@@ -0,0 +1,23 @@
+//! The configuration snapshot that the node registers at startup and that
+//! the index node exposes through the `nodeConfiguration` query. Keeping
+//! the snapshot here avoids a dependency from this crate on the node's
+//! configuration handling.
+
+use std::sync::Mutex;
+
+use graph::prelude::{lazy_static, serde_json};
+
+lazy_static! {
+    static ref CONFIG: Mutex<Option<serde_json::Value>> = Mutex::new(None);
+}
+
+/// Register the configuration that this node loaded at startup. The caller
+/// must remove any credentials before registering; the value is served
+/// verbatim to anybody who can reach the index node server
+pub fn set_node_configuration(config: serde_json::Value) {
+    *CONFIG.lock().unwrap() = Some(config);
+}
+
+pub(crate) fn node_configuration() -> Option<serde_json::Value> {
+    CONFIG.lock().unwrap().clone()
+}
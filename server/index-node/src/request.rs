@@ -62,7 +62,7 @@ impl Future for IndexNodeRequest {
             )),
         }?;
 
-        Ok(Async::Ready(Query::new(document, variables)))
+        Ok(Async::Ready(Query::new(document, variables, false)))
     }
 }
 
@@ -70,7 +70,7 @@ impl Future for IndexNodeRequest {
 mod tests {
     use graphql_parser;
     use hyper;
-    use std::collections::{BTreeMap, HashMap};
+    use std::collections::HashMap;
 
     use graph::prelude::*;
 
@@ -169,7 +169,7 @@ mod tests {
                 (String::from("string"), r::Value::String(String::from("s"))),
                 (
                     String::from("map"),
-                    r::Value::Object(BTreeMap::from_iter(
+                    r::Value::Object(r::Object::from_iter(
                         vec![(String::from("k"), r::Value::String(String::from("v")))].into_iter(),
                     )),
                 ),
@@ -1,18 +1,58 @@
 use either::Either;
 use graph::blockchain::{Blockchain, BlockchainKind};
-use std::collections::{BTreeMap, HashMap};
+use std::collections::HashMap;
 
 use graph::data::subgraph::features::detect_features;
-use graph::data::subgraph::{status, MAX_SPEC_VERSION};
+use graph::data::subgraph::schema::SubgraphHealth;
+use graph::data::subgraph::{status, KNOWN_SPEC_VERSIONS, MAX_SPEC_VERSION, MIN_SPEC_VERSION};
 use graph::prelude::*;
 use graph::{
-    components::store::StatusStore,
+    components::provider::PROVIDER_REGISTRY,
+    components::store::{DeploymentLocator, StatusStore},
     data::graphql::{IntoValue, ObjectOrInterface, ValueMap},
 };
 use graph_graphql::prelude::{ExecutionContext, Resolver};
 use std::convert::TryInto;
+use std::str::FromStr;
 use web3::types::{Address, H256};
 
+/// The environment variables that the `nodeConfiguration` query reports.
+/// Only variables from this list are reported, since arbitrary environment
+/// variables can contain credentials. The list should track the settings
+/// from `docs/environment-variables.md` that operators most often need to
+/// verify
+const REPORTED_ENV_VARS: &[&str] = &[
+    "GRAPH_ALLOW_NON_DETERMINISTIC_FULLTEXT_SEARCH",
+    "GRAPH_ALLOW_NON_DETERMINISTIC_IPFS",
+    "GRAPH_DISABLED_HOST_FNS",
+    "GRAPH_ENTITY_CACHE_SIZE",
+    "GRAPH_ETHEREUM_ANCESTOR_COUNT",
+    "GRAPH_ETHEREUM_BLOCK_BATCH_SIZE",
+    "GRAPH_ETHEREUM_CLEANUP_BLOCKS",
+    "GRAPH_ETHEREUM_MAX_BLOCK_RANGE_SIZE",
+    "GRAPH_ETHEREUM_MAX_EVENT_ONLY_RANGE",
+    "GRAPH_ETHEREUM_REORG_THRESHOLD",
+    "GRAPH_ETHEREUM_RPC_RECORD_DIR",
+    "GRAPH_ETHEREUM_RPC_REPLAY_DIR",
+    "GRAPH_ETHEREUM_TARGET_TRIGGERS_PER_BLOCK_RANGE",
+    "GRAPH_GRAPHQL_MAX_COMPLEXITY",
+    "GRAPH_GRAPHQL_MAX_DEPTH",
+    "GRAPH_GRAPHQL_MAX_FIRST",
+    "GRAPH_GRAPHQL_QUERY_TIMEOUT",
+    "GRAPH_KILL_IF_UNRESPONSIVE",
+    "GRAPH_LOAD_THRESHOLD",
+    "GRAPH_LOG",
+    "GRAPH_LOG_QUERY_TIMING",
+    "GRAPH_MAX_API_VERSION",
+    "GRAPH_MAX_SPEC_VERSION",
+    "GRAPH_NODE_ID",
+    "GRAPH_QUERY_CACHE_BLOCKS",
+    "GRAPH_QUERY_CACHE_MAX_MEM",
+    "GRAPH_STORE_CONNECTION_TIMEOUT",
+    "GRAPH_STORE_HISTORY_TOAST_TUPLE_TARGET",
+    "STORE_CONNECTION_POOL_SIZE",
+];
+
 /// Resolver for the index node GraphQL API.
 pub struct IndexNodeResolver<S, R, St> {
     logger: Logger,
@@ -137,6 +177,594 @@ where
         Ok(poi)
     }
 
+    /// Report which manifest spec versions this node can index. The upper
+    /// bound can be lowered through `GRAPH_MAX_SPEC_VERSION`.
+    fn resolve_spec_version_support(&self) -> Result<r::Value, QueryExecutionError> {
+        let supported: Vec<r::Value> = KNOWN_SPEC_VERSIONS
+            .iter()
+            .filter(|version| *version <= &*MAX_SPEC_VERSION)
+            .map(|version| r::Value::String(version.to_string()))
+            .collect();
+
+        let mut response = r::Object::new();
+        response.insert("supported".to_string(), r::Value::List(supported));
+        response.insert(
+            "minSpecVersion".to_string(),
+            r::Value::String(MIN_SPEC_VERSION.to_string()),
+        );
+        response.insert(
+            "maxSpecVersion".to_string(),
+            r::Value::String(MAX_SPEC_VERSION.to_string()),
+        );
+        Ok(r::Value::Object(response))
+    }
+
+    /// List each configured provider with its capabilities, configured
+    /// limits, last error and rolling latency stats. The stats are
+    /// collected from the chain head requests the block ingestor makes
+    fn resolve_provider_statuses(&self) -> Result<r::Value, QueryExecutionError> {
+        let statuses = PROVIDER_REGISTRY
+            .status()
+            .into_iter()
+            .map(|status| {
+                let optional_int = |value: Option<u64>| {
+                    value
+                        .map(|value| r::Value::Int(value as i64))
+                        .unwrap_or(r::Value::Null)
+                };
+
+                let mut obj = r::Object::new();
+                obj.insert("network".to_string(), r::Value::String(status.network));
+                obj.insert("provider".to_string(), r::Value::String(status.provider));
+                obj.insert(
+                    "capabilities".to_string(),
+                    r::Value::String(status.capabilities),
+                );
+                obj.insert(
+                    "getLogsRangeLimit".to_string(),
+                    r::Value::Int(status.get_logs_range_limit as i64),
+                );
+                obj.insert(
+                    "batchSizeLimit".to_string(),
+                    r::Value::Int(status.batch_size_limit as i64),
+                );
+                obj.insert(
+                    "lastError".to_string(),
+                    status
+                        .last_error
+                        .map(r::Value::String)
+                        .unwrap_or(r::Value::Null),
+                );
+                obj.insert(
+                    "latencyAvgMs".to_string(),
+                    optional_int(status.latency_avg_ms),
+                );
+                obj.insert(
+                    "latencyP90Ms".to_string(),
+                    optional_int(status.latency_p90_ms),
+                );
+                obj.insert(
+                    "latencySamples".to_string(),
+                    r::Value::Int(status.latency_samples as i64),
+                );
+                r::Value::Object(obj)
+            })
+            .collect();
+        Ok(r::Value::List(statuses))
+    }
+
+    /// Report the configuration the node loaded at startup, with
+    /// credentials removed, together with the settings of the environment
+    /// variables from `REPORTED_ENV_VARS`, so operators can compare a
+    /// running node against their config management
+    fn resolve_node_configuration(&self) -> Result<r::Value, QueryExecutionError> {
+        let config = crate::node_configuration::node_configuration()
+            .map(json_to_value)
+            .unwrap_or(r::Value::Null);
+
+        let environment = REPORTED_ENV_VARS
+            .iter()
+            .map(|name| {
+                let mut obj = r::Object::new();
+                obj.insert("name".to_string(), r::Value::String(name.to_string()));
+                obj.insert(
+                    "value".to_string(),
+                    std::env::var(name)
+                        .map(r::Value::String)
+                        .unwrap_or(r::Value::Null),
+                );
+                r::Value::Object(obj)
+            })
+            .collect();
+
+        let mut response = r::Object::new();
+        response.insert("config".to_string(), config);
+        response.insert("environment".to_string(), r::Value::List(environment));
+        Ok(r::Value::Object(response))
+    }
+
+    /// List the dynamic data sources a deployment created from its
+    /// templates, in creation order. `skip` and `first` page through the
+    /// list since factory-pattern subgraphs can create very many of them
+    async fn resolve_dynamic_data_sources(
+        &self,
+        arguments: &HashMap<&str, r::Value>,
+    ) -> Result<r::Value, QueryExecutionError> {
+        // We can safely unwrap because the argument is non-nullable and has been validated.
+        let subgraph_id = arguments.get_required::<String>("subgraph").unwrap();
+        let deployment_hash = DeploymentHash::new(subgraph_id)
+            .map_err(QueryExecutionError::SubgraphDeploymentIdError)?;
+        let skip = arguments
+            .get_optional::<u64>("skip")
+            .expect("Invalid skip")
+            .unwrap_or(0) as usize;
+        let first = arguments
+            .get_optional::<u64>("first")
+            .expect("Invalid first")
+            .map(|first| first as usize)
+            .unwrap_or(usize::MAX);
+
+        let data_sources = self.store.dynamic_data_sources(&deployment_hash).await?;
+
+        let data_sources = data_sources
+            .into_iter()
+            .skip(skip)
+            .take(first)
+            .map(|ds| {
+                let mut obj = r::Object::new();
+                obj.insert("name".to_string(), r::Value::String(ds.name));
+                obj.insert(
+                    "address".to_string(),
+                    ds.source
+                        .address
+                        .map(|address| r::Value::String(format!("0x{:x}", address)))
+                        .unwrap_or(r::Value::Null),
+                );
+                obj.insert(
+                    "creationBlock".to_string(),
+                    ds.creation_block
+                        .map(|block| r::Value::Int(block as i64))
+                        .unwrap_or(r::Value::Null),
+                );
+                obj.insert(
+                    "context".to_string(),
+                    ds.context.map(r::Value::String).unwrap_or(r::Value::Null),
+                );
+                r::Value::Object(obj)
+            })
+            .collect();
+        Ok(r::Value::List(data_sources))
+    }
+
+    /// The recent head lag samples and reorg events for a network so that
+    /// dashboards can plot chain health alongside subgraph health. `first`
+    /// limits both lists and defaults to 100 entries
+    fn resolve_chain_health(
+        &self,
+        arguments: &HashMap<&str, r::Value>,
+    ) -> Result<r::Value, QueryExecutionError> {
+        // We can safely unwrap because the argument is non-nullable and has been validated.
+        let network = arguments.get_required::<String>("network").unwrap();
+        let first = arguments
+            .get_optional::<u64>("first")
+            .expect("Invalid first")
+            .unwrap_or(100) as usize;
+
+        let samples = self
+            .store
+            .chain_head_lag_samples(&network, first)?
+            .into_iter()
+            .map(|sample| {
+                let mut obj = r::Object::new();
+                obj.insert(
+                    "headNumber".to_string(),
+                    r::Value::Int(sample.head_number as i64),
+                );
+                obj.insert(
+                    "blocksAdvanced".to_string(),
+                    r::Value::String(format!("{}", sample.blocks_advanced)),
+                );
+                obj.insert("sampledAt".to_string(), r::Value::String(sample.sampled_at));
+                r::Value::Object(obj)
+            })
+            .collect();
+
+        let events = self
+            .store
+            .chain_reorg_events(&network, first)?
+            .into_iter()
+            .map(|event| {
+                let mut obj = r::Object::new();
+                obj.insert("fromHash".to_string(), r::Value::String(event.from_hash));
+                obj.insert(
+                    "fromNumber".to_string(),
+                    r::Value::Int(event.from_number as i64),
+                );
+                obj.insert("toHash".to_string(), r::Value::String(event.to_hash));
+                obj.insert(
+                    "toNumber".to_string(),
+                    r::Value::Int(event.to_number as i64),
+                );
+                obj.insert(
+                    "detectedAt".to_string(),
+                    r::Value::String(event.detected_at),
+                );
+                r::Value::Object(obj)
+            })
+            .collect();
+
+        let mut health = r::Object::new();
+        health.insert("headLagSamples".to_string(), r::Value::List(samples));
+        health.insert("reorgEvents".to_string(), r::Value::List(events));
+        Ok(r::Value::Object(health))
+    }
+
+    /// List all deployments in the cluster together with the space they
+    /// take up on disk, across all shards, for dashboards managing large
+    /// fleets. Filters are combined with 'and'; `orderBy` defaults to
+    /// `sizeBytes`, and `first` and `skip` page through the result
+    fn resolve_deployments(
+        &self,
+        arguments: &HashMap<&str, r::Value>,
+    ) -> Result<r::Value, QueryExecutionError> {
+        let filter = status::SummaryFilter {
+            network: arguments
+                .get_optional::<String>("network")
+                .expect("Invalid network"),
+            shard: arguments
+                .get_optional::<String>("shard")
+                .expect("Invalid shard"),
+            synced: arguments
+                .get_optional::<bool>("synced")
+                .expect("Invalid synced"),
+            health: arguments
+                .get_optional::<SubgraphHealth>("health")
+                .expect("Invalid health"),
+        };
+        // We can safely expect because the enum has been validated.
+        let order = arguments
+            .get_optional::<String>("orderBy")
+            .expect("Invalid orderBy")
+            .map(|order| status::SummaryOrder::from_str(&order).expect("Invalid orderBy"))
+            .unwrap_or(status::SummaryOrder::SizeBytes);
+        let first = arguments
+            .get_optional::<u64>("first")
+            .expect("Invalid first")
+            .unwrap_or(100) as usize;
+        let skip = arguments
+            .get_optional::<u64>("skip")
+            .expect("Invalid skip")
+            .unwrap_or(0) as usize;
+
+        let summaries = self
+            .store
+            .deployment_summaries(filter, order, first, skip)?
+            .into_iter()
+            .map(IntoValue::into_value)
+            .collect();
+        Ok(r::Value::List(summaries))
+    }
+
+    /// Report how well a deployment kept up with its chain over the last
+    /// `days` days (30 when omitted), counting a sample as within the SLA
+    /// when the deployment was at most `withinBlocks` blocks behind the
+    /// chain head (2 when omitted)
+    fn resolve_sla_report(
+        &self,
+        arguments: &HashMap<&str, r::Value>,
+    ) -> Result<r::Value, QueryExecutionError> {
+        // We can safely unwrap because the argument is non-nullable and has been validated.
+        let deployment = arguments
+            .get_required::<DeploymentHash>("subgraph")
+            .unwrap();
+        let days = arguments
+            .get_optional::<u64>("days")
+            .expect("Invalid days")
+            .unwrap_or(30) as i32;
+        let within_blocks = arguments
+            .get_optional::<u64>("withinBlocks")
+            .expect("Invalid withinBlocks")
+            .unwrap_or(2) as i32;
+
+        let report = self.store.sla_report(&deployment, days, within_blocks)?;
+        Ok(report.into_value())
+    }
+
+    /// Find the unique deployment locator for `subgraph`. Operator actions
+    /// refuse to guess when the hash is deployed in more than one shard;
+    /// `graphman` can disambiguate with its `--shard` option
+    fn locate(&self, subgraph: &str) -> Result<DeploymentLocator, QueryExecutionError> {
+        let mut locators = self.subgraph_store.locators(subgraph)?;
+        match locators.len() {
+            0 => Err(StoreError::DeploymentNotFound(subgraph.to_string()).into()),
+            1 => Ok(locators.pop().unwrap()),
+            _ => Err(StoreError::Unknown(anyhow!(
+                "deployment `{}` is ambiguous: it exists in multiple shards",
+                subgraph
+            ))
+            .into()),
+        }
+    }
+
+    /// The `assign` mutation: assign the deployment to `node` and start
+    /// indexing it there
+    fn resolve_assign(
+        &self,
+        arguments: &HashMap<&str, r::Value>,
+    ) -> Result<r::Value, QueryExecutionError> {
+        // We can safely unwrap because the arguments are non-nullable and have been validated.
+        let subgraph = arguments.get_required::<String>("subgraph").unwrap();
+        let node = arguments.get_required::<String>("node").unwrap();
+
+        let node = NodeId::new(node.clone())
+            .map_err(|()| StoreError::Unknown(anyhow!("illegal node id `{}`", node)))?;
+        let locator = self.locate(&subgraph)?;
+
+        info!(
+            self.logger,
+            "Assign subgraph";
+            "subgraph" => &subgraph,
+            "node" => node.as_str(),
+        );
+
+        self.subgraph_store.reassign_subgraph(&locator, &node)?;
+        Ok(r::Value::Boolean(true))
+    }
+
+    /// The `pause` mutation: stop indexing the deployment without removing
+    /// any of its data
+    fn resolve_pause(
+        &self,
+        arguments: &HashMap<&str, r::Value>,
+    ) -> Result<r::Value, QueryExecutionError> {
+        // We can safely unwrap because the argument is non-nullable and has been validated.
+        let subgraph = arguments.get_required::<String>("subgraph").unwrap();
+        let locator = self.locate(&subgraph)?;
+
+        info!(self.logger, "Pause subgraph"; "subgraph" => &subgraph);
+
+        self.subgraph_store.pause_subgraph(&locator)?;
+        Ok(r::Value::Boolean(true))
+    }
+
+    /// The `rewind` mutation: undo all entity changes after the given
+    /// block. The caller is expected to pause the deployment first
+    fn resolve_rewind(
+        &self,
+        arguments: &HashMap<&str, r::Value>,
+    ) -> Result<r::Value, QueryExecutionError> {
+        let deployment_id = arguments
+            .get_required::<DeploymentHash>("subgraph")
+            .expect("Valid subgraph required");
+
+        let block_number: u64 = arguments
+            .get_required::<u64>("blockNumber")
+            .expect("Valid blockNumber required")
+            .try_into()
+            .unwrap();
+
+        let block_hash = arguments
+            .get_required::<H256>("blockHash")
+            .expect("Valid blockHash required")
+            .try_into()
+            .unwrap();
+
+        let block_ptr = BlockPtr::from((block_hash, block_number));
+
+        info!(
+            self.logger,
+            "Rewind subgraph";
+            "subgraph" => deployment_id.to_string(),
+            "block" => format!("{}", block_ptr),
+        );
+
+        self.subgraph_store.rewind(deployment_id, block_ptr)?;
+        Ok(r::Value::Boolean(true))
+    }
+
+    /// The `retry` mutation: clear the fatal error of the deployment and
+    /// poke the assigned node so that indexing resumes at the block where
+    /// it failed
+    fn resolve_retry(
+        &self,
+        arguments: &HashMap<&str, r::Value>,
+    ) -> Result<r::Value, QueryExecutionError> {
+        // We can safely unwrap because the argument is non-nullable and has been validated.
+        let subgraph = arguments.get_required::<String>("subgraph").unwrap();
+        let block = arguments
+            .get_optional::<u64>("blockNumber")
+            .expect("Invalid blockNumber")
+            .map(|block| block as BlockNumber);
+        let locator = self.locate(&subgraph)?;
+
+        info!(
+            self.logger,
+            "Retry subgraph";
+            "subgraph" => &subgraph,
+            "block" => block,
+        );
+
+        self.subgraph_store.retry_subgraph(&locator, block)?;
+        Ok(r::Value::Boolean(true))
+    }
+
+    /// The local proof of indexing for `block`, as a hex string. `None` if
+    /// the store has no PoI for the block or if the lookup failed.
+    async fn local_poi(
+        &self,
+        deployment_id: &DeploymentHash,
+        indexer: &Option<Address>,
+        block: BlockPtr,
+    ) -> Option<String> {
+        match self
+            .store
+            .get_proof_of_indexing(deployment_id, indexer, block.clone())
+            .await
+        {
+            Ok(poi) => poi.map(|poi| format!("0x{}", hex::encode(&poi))),
+            Err(e) => {
+                error!(
+                    self.logger,
+                    "Failed to query proof of indexing";
+                    "subgraph" => deployment_id.to_string(),
+                    "block" => format!("{}", block),
+                    "error" => format!("{:?}", e)
+                );
+                None
+            }
+        }
+    }
+
+    /// Ask the index node at `remote` for its proof of indexing for `block`
+    /// through its own `proofOfIndexing` query.
+    async fn remote_poi(
+        &self,
+        remote: &str,
+        deployment_id: &DeploymentHash,
+        indexer: &Option<Address>,
+        block: &BlockPtr,
+    ) -> Result<Option<String>, QueryExecutionError> {
+        const POI_QUERY: &str =
+            "query($subgraph:String!,$blockNumber:Int!,$blockHash:Bytes!,$indexer:Bytes){\
+             proofOfIndexing(subgraph:$subgraph,blockNumber:$blockNumber,\
+             blockHash:$blockHash,indexer:$indexer)}";
+
+        let body = serde_json::json!({
+            "query": POI_QUERY,
+            "variables": {
+                "subgraph": deployment_id.to_string(),
+                "blockNumber": block.number,
+                "blockHash": format!("0x{}", block.hash_hex()),
+                "indexer": indexer.as_ref().map(|indexer| format!("{:#x}", indexer)),
+            }
+        });
+
+        let response: serde_json::Value = reqwest::Client::new()
+            .post(remote)
+            .json(&body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| {
+                StoreError::Unknown(anyhow!(
+                    "failed to query remote index node {}: {}",
+                    remote,
+                    e
+                ))
+            })?
+            .json()
+            .await
+            .map_err(|e| {
+                StoreError::Unknown(anyhow!(
+                    "invalid response from remote index node {}: {}",
+                    remote,
+                    e
+                ))
+            })?;
+
+        Ok(response["data"]["proofOfIndexing"]
+            .as_str()
+            .map(|poi| poi.to_owned()))
+    }
+
+    /// Compare our proof of indexing for a block with the one served by the
+    /// index node at `remoteIndexNode`. If the two disagree, bisect the
+    /// block range up to the requested block to find the first block for
+    /// which the proofs diverge.
+    async fn resolve_poi_comparison(
+        &self,
+        arguments: &HashMap<&str, r::Value>,
+    ) -> Result<r::Value, QueryExecutionError> {
+        let deployment_id = arguments
+            .get_required::<DeploymentHash>("subgraph")
+            .expect("Valid subgraph required");
+
+        let block_number: u64 = arguments
+            .get_required::<u64>("blockNumber")
+            .expect("Valid blockNumber required")
+            .try_into()
+            .unwrap();
+
+        let block_hash = arguments
+            .get_required::<H256>("blockHash")
+            .expect("Valid blockHash required")
+            .try_into()
+            .unwrap();
+
+        // We can safely unwrap because the argument is non-nullable and has been validated.
+        let remote = arguments.get_required::<String>("remoteIndexNode").unwrap();
+
+        let indexer = arguments
+            .get_optional::<Address>("indexer")
+            .expect("Invalid indexer");
+
+        let block = BlockPtr::from((block_hash, block_number));
+
+        let local = self
+            .local_poi(&deployment_id, &indexer, block.clone())
+            .await;
+        let remote_poi = self
+            .remote_poi(&remote, &deployment_id, &indexer, &block)
+            .await?;
+
+        let matches = local.is_some() && local == remote_poi;
+
+        let mut divergent_block = r::Value::Null;
+        if !matches {
+            // Bisect for the first diverging block: proofs agree for all
+            // blocks below `lo` and diverge at `hi`
+            let mut lo: BlockNumber = 0;
+            let mut hi = block.number;
+            let mut first_divergent = block.clone();
+            while lo < hi {
+                let mid = lo + (hi - lo) / 2;
+                let mid_ptr = match self.store.block_ptr_for_number(&deployment_id, mid)? {
+                    Some(ptr) => ptr,
+                    // Without a canonical hash for `mid` we can not compare
+                    // proofs there; stop with the narrowest range found so far
+                    None => break,
+                };
+                let local_mid = self
+                    .local_poi(&deployment_id, &indexer, mid_ptr.clone())
+                    .await;
+                let remote_mid = self
+                    .remote_poi(&remote, &deployment_id, &indexer, &mid_ptr)
+                    .await?;
+                if local_mid.is_some() && local_mid == remote_mid {
+                    lo = mid + 1;
+                } else {
+                    first_divergent = mid_ptr;
+                    hi = mid;
+                }
+            }
+
+            let mut block_value = r::Object::new();
+            block_value.insert(
+                "hash".to_string(),
+                r::Value::String(format!("0x{}", first_divergent.hash_hex())),
+            );
+            block_value.insert(
+                "number".to_string(),
+                r::Value::String(format!("{}", first_divergent.number)),
+            );
+            divergent_block = r::Value::Object(block_value);
+        }
+
+        let mut response = r::Object::new();
+        response.insert("matches".to_string(), r::Value::Boolean(matches));
+        response.insert(
+            "localProofOfIndexing".to_string(),
+            local.map(r::Value::String).unwrap_or(r::Value::Null),
+        );
+        response.insert(
+            "remoteProofOfIndexing".to_string(),
+            remote_poi.map(r::Value::String).unwrap_or(r::Value::Null),
+        );
+        response.insert("divergentBlock".to_string(), divergent_block);
+
+        Ok(r::Value::Object(response))
+    }
+
     fn resolve_indexing_status_for_version(
         &self,
         arguments: &HashMap<&str, r::Value>,
@@ -244,13 +872,152 @@ where
 
         // We then bulid a GraphqQL `Object` value that contains the feature detection and
         // validation results and send it back as a response.
-        let mut response: BTreeMap<String, r::Value> = BTreeMap::new();
+        let mut response = r::Object::new();
         response.insert("features".to_string(), features);
         response.insert("errors".to_string(), errors);
         response.insert("network".to_string(), network);
 
         Ok(r::Value::Object(response))
     }
+
+    /// Resolve the content hashes of the files a deployment consists of, so
+    /// that third parties can verify that the deployment corresponds to
+    /// published source artifacts. All hashes are keccak-256 over the raw
+    /// file contents as served by the link resolver.
+    async fn resolve_deployment_artifacts(
+        &self,
+        arguments: &HashMap<&str, r::Value>,
+    ) -> Result<r::Value, QueryExecutionError> {
+        // We can safely unwrap because the argument is non-nullable and has been validated.
+        let subgraph_id = arguments.get_required::<String>("subgraphId").unwrap();
+        let deployment_hash = DeploymentHash::new(subgraph_id)
+            .map_err(QueryExecutionError::SubgraphDeploymentIdError)?;
+
+        let manifest_bytes = self
+            .link_resolver
+            .cat(&self.logger, &deployment_hash.to_ipfs_link())
+            .await
+            .map_err(SubgraphManifestResolveError::ResolveError)?;
+        let raw: serde_yaml::Value = serde_yaml::from_slice(&manifest_bytes)
+            .map_err(SubgraphManifestResolveError::ParseError)?;
+
+        let mut response = r::Object::new();
+        response.insert("manifest".to_string(), keccak_hex(&manifest_bytes));
+
+        // The schema file
+        let schema = match raw.get("schema").and_then(file_link) {
+            Some(link) => {
+                let bytes = self
+                    .link_resolver
+                    .cat(&self.logger, &link)
+                    .await
+                    .map_err(SubgraphManifestResolveError::ResolveError)?;
+                keccak_hex(&bytes)
+            }
+            None => r::Value::Null,
+        };
+        response.insert("schema".to_string(), schema);
+
+        // One mapping WASM per data source or template, and the ABIs each of
+        // them references. Identical files are fetched once per occurrence;
+        // the link resolver caches by content address.
+        let mut mappings = Vec::new();
+        let mut abis = Vec::new();
+        for section in &["dataSources", "templates"] {
+            let data_sources = match raw.get(section).and_then(serde_yaml::Value::as_sequence) {
+                Some(data_sources) => data_sources,
+                None => continue,
+            };
+            for data_source in data_sources {
+                let ds_name = data_source
+                    .get("name")
+                    .and_then(serde_yaml::Value::as_str)
+                    .unwrap_or("unknown");
+                let mapping = match data_source.get("mapping") {
+                    Some(mapping) => mapping,
+                    None => continue,
+                };
+                if let Some(link) = file_link(mapping) {
+                    let bytes = self
+                        .link_resolver
+                        .cat(&self.logger, &link)
+                        .await
+                        .map_err(SubgraphManifestResolveError::ResolveError)?;
+                    mappings.push(named_artifact(ds_name, keccak_hex(&bytes)));
+                }
+                for abi in mapping
+                    .get("abis")
+                    .and_then(serde_yaml::Value::as_sequence)
+                    .map(|abis| abis.iter())
+                    .into_iter()
+                    .flatten()
+                {
+                    let abi_name = abi
+                        .get("name")
+                        .and_then(serde_yaml::Value::as_str)
+                        .unwrap_or("unknown");
+                    if let Some(link) = file_link(abi) {
+                        let bytes = self
+                            .link_resolver
+                            .cat(&self.logger, &link)
+                            .await
+                            .map_err(SubgraphManifestResolveError::ResolveError)?;
+                        abis.push(named_artifact(abi_name, keccak_hex(&bytes)));
+                    }
+                }
+            }
+        }
+        response.insert("mappings".to_string(), r::Value::List(mappings));
+        response.insert("abis".to_string(), r::Value::List(abis));
+
+        Ok(r::Value::Object(response))
+    }
+}
+
+/// The keccak-256 hash of `bytes` as a `Bytes` GraphQL value.
+/// Translate the JSON rendering of the node's configuration into the
+/// GraphQL value for the `JSONObject` scalar
+fn json_to_value(json: serde_json::Value) -> r::Value {
+    use serde_json::Value::*;
+
+    match json {
+        Null => r::Value::Null,
+        Bool(b) => r::Value::Boolean(b),
+        Number(n) => match (n.as_i64(), n.as_f64()) {
+            (Some(i), _) => r::Value::Int(i),
+            (None, Some(f)) => r::Value::Float(f),
+            (None, None) => r::Value::Null,
+        },
+        String(s) => r::Value::String(s),
+        Array(values) => r::Value::List(values.into_iter().map(json_to_value).collect()),
+        Object(map) => {
+            let mut obj = r::Object::new();
+            for (key, value) in map {
+                obj.insert(key, json_to_value(value));
+            }
+            r::Value::Object(obj)
+        }
+    }
+}
+
+fn keccak_hex(bytes: &[u8]) -> r::Value {
+    r::Value::String(format!("0x{}", hex::encode(tiny_keccak::keccak256(bytes))))
+}
+
+/// Extract the IPLD link of a `file` entry, e.g. `file: { "/": "/ipfs/Qm..." }`.
+fn file_link(value: &serde_yaml::Value) -> Option<Link> {
+    value
+        .get("file")?
+        .get("/")?
+        .as_str()
+        .map(|s| Link::from(s.to_owned()))
+}
+
+fn named_artifact(name: &str, hash: r::Value) -> r::Value {
+    let mut artifact = r::Object::new();
+    artifact.insert("name".to_string(), r::Value::String(name.to_owned()));
+    artifact.insert("hash".to_string(), hash);
+    r::Value::Object(artifact)
 }
 
 struct ValidationPostProcessResult {
@@ -390,6 +1157,18 @@ where
         value: Option<r::Value>,
         argument_values: &HashMap<&str, r::Value>,
     ) -> Result<r::Value, QueryExecutionError> {
+        // The fields of the root `Mutation` type are operator actions; the
+        // service only lets authenticated mutations get this far
+        if &parent_object_type.name == "Mutation" {
+            match field.name.as_str() {
+                "assign" => return self.resolve_assign(argument_values),
+                "pause" => return self.resolve_pause(argument_values),
+                "rewind" => return self.resolve_rewind(argument_values),
+                "retry" => return self.resolve_retry(argument_values),
+                _ => (),
+            }
+        }
+
         // Check if we are resolving the proofOfIndexing bytes
         if &parent_object_type.name == "Query"
             && &field.name == "proofOfIndexing"
@@ -424,6 +1203,21 @@ where
                 self.resolve_indexing_statuses_for_subgraph_name(arguments)
             }
 
+            // The top-level `providerStatuses` field
+            (None, "ProviderStatus", "providerStatuses") => self.resolve_provider_statuses(),
+
+            // The top-level `dynamicDataSources` field
+            (None, "DynamicDataSource", "dynamicDataSources") => {
+                graph::block_on(self.resolve_dynamic_data_sources(arguments))
+            }
+
+            // The top-level `chainHealth` field
+            (None, "ChainHealth", "chainHealth") => self.resolve_chain_health(arguments),
+
+            // The top-level `deployments` field
+            (None, "DeploymentSummary", "deployments") => self.resolve_deployments(arguments),
+            (None, "SlaReport", "slaReport") => self.resolve_sla_report(arguments),
+
             // Resolve fields of `Object` values (e.g. the `chains` field of `ChainIndexingStatus`)
             (value, _, _) => Ok(value.unwrap_or(r::Value::Null)),
         }
@@ -453,6 +1247,20 @@ where
                 graph::block_on(self.resolve_subgraph_features(arguments))
             }
 
+            // The top-level `deploymentArtifacts` field
+            (None, "deploymentArtifacts") => {
+                graph::block_on(self.resolve_deployment_artifacts(arguments))
+            }
+
+            // The top-level `poiComparison` field
+            (None, "poiComparison") => graph::block_on(self.resolve_poi_comparison(arguments)),
+
+            // The top-level `specVersionSupport` field
+            (None, "specVersionSupport") => self.resolve_spec_version_support(),
+
+            // The top-level `nodeConfiguration` field
+            (None, "nodeConfiguration") => self.resolve_node_configuration(),
+
             // Resolve fields of `Object` values (e.g. the `latestBlock` field of `EthereumBlock`)
             (value, _) => Ok(value.unwrap_or(r::Value::Null)),
         }
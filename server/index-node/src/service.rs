@@ -4,13 +4,64 @@ use http::header::{
 };
 use hyper::service::Service;
 use hyper::{Body, Method, Request, Response, StatusCode};
+use std::env;
+use std::str::FromStr;
 use std::task::Context;
 use std::task::Poll;
+use std::time::Duration;
 
+use graph::data::graphql::DocumentExt;
+use graph::data::subgraph::status;
 use graph::{components::server::query::GraphQLServerError, data::query::QueryResults};
-use graph::{components::store::StatusStore, prelude::*};
+use graph::{
+    components::store::{EntityType, StatusStore},
+    prelude::*,
+};
 use graph_graphql::prelude::{execute_query, Query as PreparedQuery, QueryExecutionOptions};
 
+lazy_static! {
+    /// How often the status stream polls the store for changes
+    static ref STATUS_STREAM_POLL_INTERVAL: Duration = {
+        let secs = env::var("GRAPH_STATUS_STREAM_POLL_INTERVAL")
+            .ok()
+            .map(|s| {
+                u64::from_str(&s).unwrap_or_else(|_| {
+                    panic!(
+                        "GRAPH_STATUS_STREAM_POLL_INTERVAL must be a number, but is `{}`",
+                        s
+                    )
+                })
+            })
+            .unwrap_or(5);
+        Duration::from_secs(secs)
+    };
+    /// Send a block progress event whenever a deployment advances by this
+    /// many blocks; health and sync transitions are always sent
+    static ref STATUS_STREAM_BLOCK_INTERVAL: i32 = env::var("GRAPH_STATUS_STREAM_BLOCK_INTERVAL")
+        .ok()
+        .map(|s| {
+            i32::from_str(&s).unwrap_or_else(|_| {
+                panic!(
+                    "GRAPH_STATUS_STREAM_BLOCK_INTERVAL must be a number, but is `{}`",
+                    s
+                )
+            })
+        })
+        .unwrap_or(1_000);
+    /// The token that requests must present as a bearer token in the
+    /// `Authorization` header to run mutations. When it is not set,
+    /// mutations are rejected altogether
+    static ref ADMIN_TOKEN: Option<String> = env::var("GRAPH_INDEX_NODE_ADMIN_TOKEN").ok();
+    /// The token that requests to the `_sql` endpoint must present as a
+    /// bearer token in the `Authorization` header. When it is not set,
+    /// the endpoint is disabled altogether
+    static ref SQL_TOKEN: Option<String> = env::var("GRAPH_SQL_TOKEN").ok();
+}
+
+/// How many entities to read from the store in one query when serving an
+/// export
+const EXPORT_BATCH_SIZE: u32 = 10_000;
+
 use crate::explorer::Explorer;
 use crate::request::IndexNodeRequest;
 use crate::resolver::IndexNodeResolver;
@@ -99,9 +150,31 @@ where
         Self::serve_file(Self::graphiql_html(), "text/html")
     }
 
+    /// Return `true` if the request carries `token` as a bearer token in
+    /// the `Authorization` header. A token of `None` authorizes nobody
+    fn has_bearer_token(req: &Request<Body>, token: &Option<String>) -> bool {
+        let token = match token {
+            Some(token) => token,
+            None => return false,
+        };
+        req.headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(|bearer| bearer == token)
+            .unwrap_or(false)
+    }
+
+    /// Return `true` if the request carries the admin token from
+    /// `GRAPH_INDEX_NODE_ADMIN_TOKEN` as a bearer token
+    fn is_authorized(req: &Request<Body>) -> bool {
+        Self::has_bearer_token(req, &ADMIN_TOKEN)
+    }
+
     async fn handle_graphql_query(
         &self,
         request_body: Body,
+        authorized: bool,
     ) -> Result<Response<Body>, GraphQLServerError> {
         let store = self.store.clone();
 
@@ -118,6 +191,16 @@ where
             Err(e) => return Ok(QueryResults::from(QueryResult::from(e)).as_http_response()),
         };
 
+        // Mutations are operator actions and need the admin token; when no
+        // token is configured, nobody can run them
+        if query.is_mutation() && !authorized {
+            return Err(GraphQLServerError::ClientError(
+                "mutations require the admin token from GRAPH_INDEX_NODE_ADMIN_TOKEN \
+                 as a bearer token in the `Authorization` header"
+                    .into(),
+            ));
+        }
+
         let load_manager = self.graphql_runner.load_manager();
 
         // Run the query using the index node resolver
@@ -147,13 +230,289 @@ where
         Ok(QueryResults::from(result).as_http_response())
     }
 
+    /// Runs a restricted read-only SQL query against the entity tables
+    /// of a deployment, for analytics that GraphQL can not express. The
+    /// request body must be a JSON object with the query under `query`;
+    /// the response carries the rows as JSON objects under `rows`. The
+    /// query runs on a read replica with a statement timeout and a row
+    /// limit; see the `sql` module in the store for exactly what is
+    /// allowed
+    async fn handle_sql_query(
+        &self,
+        deployment: &str,
+        request_body: Body,
+    ) -> Result<Response<Body>, GraphQLServerError> {
+        let deployment = DeploymentHash::new(deployment).map_err(|id| {
+            GraphQLServerError::ClientError(format!("invalid deployment hash `{}`", id))
+        })?;
+
+        let body = hyper::body::to_bytes(request_body)
+            .map_err(|_| GraphQLServerError::InternalError("Failed to read request body".into()))
+            .await?;
+
+        #[derive(Deserialize)]
+        struct SqlRequest {
+            query: String,
+        }
+        let SqlRequest { query } = serde_json::from_slice(&body)
+            .map_err(|e| GraphQLServerError::ClientError(format!("invalid request body: {}", e)))?;
+
+        let store = self.store.cheap_clone();
+        let result =
+            graph::spawn_blocking_allow_panic(move || store.execute_sql(&deployment, &query))
+                .await
+                .map_err(|e| GraphQLServerError::InternalError(e.to_string()))?;
+
+        // Everything that can go wrong from here on, including statement
+        // timeouts, is caused by the query the client sent
+        let (status, body) = match result {
+            Ok(rows) => (
+                StatusCode::OK,
+                serde_json::json!({ "rowCount": rows.len(), "rows": rows }),
+            ),
+            Err(e) => (
+                StatusCode::BAD_REQUEST,
+                serde_json::json!({ "error": e.to_string() }),
+            ),
+        };
+        Ok(Response::builder()
+            .status(status)
+            .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap())
+    }
+
+    /// Streams indexing status changes as server-sent events so that
+    /// dashboards do not have to poll `indexingStatuses`. An event is sent
+    /// whenever the health or sync flag of a deployment changes, or when a
+    /// deployment advances by `GRAPH_STATUS_STREAM_BLOCK_INTERVAL` blocks.
+    /// The first poll always sends an event per deployment as the initial
+    /// snapshot. The set of deployments can be restricted with a
+    /// `deployments` query parameter listing IPFS hashes, separated by
+    /// commas.
+    fn handle_status_stream(&self, query_string: Option<&str>) -> Response<Body> {
+        let deployments: Vec<String> = query_string
+            .and_then(|query| {
+                query.split('&').find_map(|kv| {
+                    kv.strip_prefix("deployments=").map(|list| {
+                        list.split(',')
+                            .filter(|id| !id.is_empty())
+                            .map(String::from)
+                            .collect()
+                    })
+                })
+            })
+            .unwrap_or_default();
+
+        let store = self.store.clone();
+        let logger = self.logger.clone();
+        let (mut sender, body) = Body::channel();
+
+        graph::spawn(async move {
+            // The last state we sent for each deployment: health, sync flag,
+            // and latest block rounded down to the block interval
+            let mut last_sent: std::collections::HashMap<String, (String, bool, i32)> =
+                std::collections::HashMap::new();
+            loop {
+                let infos = match store.status(status::Filter::Deployments(deployments.clone())) {
+                    Ok(infos) => infos,
+                    Err(e) => {
+                        warn!(logger, "Failed to query indexing statuses for stream";
+                              "error" => e.to_string());
+                        tokio::time::sleep(*STATUS_STREAM_POLL_INTERVAL).await;
+                        continue;
+                    }
+                };
+                for info in infos {
+                    let latest = info
+                        .chains
+                        .first()
+                        .and_then(|chain| chain.latest_block.as_ref())
+                        .map(|block| block.number())
+                        .unwrap_or(0);
+                    let state = (
+                        info.health.as_str().to_owned(),
+                        info.synced,
+                        latest / *STATUS_STREAM_BLOCK_INTERVAL,
+                    );
+                    if last_sent.get(&info.subgraph) == Some(&state) {
+                        continue;
+                    }
+                    let event = serde_json::json!({
+                        "subgraph": info.subgraph,
+                        "health": info.health.as_str(),
+                        "synced": info.synced,
+                        "latestBlock": latest,
+                        "fatalError": info.fatal_error.as_ref().map(|e| e.message.clone()),
+                    });
+                    let data = format!("data: {}\n\n", event);
+                    if sender.send_data(data.into()).await.is_err() {
+                        // The client went away
+                        return;
+                    }
+                    last_sent.insert(info.subgraph, state);
+                }
+                tokio::time::sleep(*STATUS_STREAM_POLL_INTERVAL).await;
+            }
+        });
+
+        Response::builder()
+            .status(200)
+            .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+            .header(CONTENT_TYPE, "text/event-stream")
+            .header(header::CACHE_CONTROL, "no-cache")
+            .body(body)
+            .unwrap()
+    }
+
+    /// Serves all entities of a deployment that are live at the block
+    /// given by the `block` query parameter, or at the deployment's
+    /// earliest usable block when the parameter is missing, as
+    /// newline-delimited JSON. Another graph-node can fetch a graft base
+    /// that it does not have locally through this endpoint
+    fn handle_export(
+        &self,
+        deployment: &str,
+        query_string: Option<&str>,
+    ) -> Result<Response<Body>, GraphQLServerError> {
+        let deployment = DeploymentHash::new(deployment).map_err(|id| {
+            GraphQLServerError::ClientError(format!("invalid deployment hash `{}`", id))
+        })?;
+        let block = query_string
+            .and_then(|query| query.split('&').find_map(|kv| kv.strip_prefix("block=")))
+            .map(|number| {
+                number.parse::<BlockNumber>().map_err(|_| {
+                    GraphQLServerError::ClientError(format!("invalid block number `{}`", number))
+                })
+            })
+            .transpose()?;
+
+        let (mut sender, body) = Body::channel();
+        let (chunks, mut rx) = tokio::sync::mpsc::channel::<Vec<u8>>(8);
+
+        // Pass data to the client from a separate task; the export reads
+        // from the store with blocking queries and can therefore not
+        // `await` the sends itself
+        graph::spawn(async move {
+            while let Some(chunk) = rx.recv().await {
+                if sender.send_data(chunk.into()).await.is_err() {
+                    // The client went away; dropping the receiver stops
+                    // the export task
+                    return;
+                }
+            }
+        });
+
+        let store = self.store.clone();
+        let subgraph_store = self.subgraph_store.clone();
+        let logger = self.logger.clone();
+        graph::spawn_blocking_allow_panic(move || {
+            if let Err(e) = Self::export(store, subgraph_store, &deployment, block, &chunks) {
+                // Dropping `chunks` without having sent the trailer
+                // truncates the response, so the client knows that the
+                // export failed partway through
+                warn!(logger, "Failed to export deployment";
+                      "subgraph_id" => deployment.to_string(),
+                      "error" => e.to_string());
+            }
+        });
+
+        Ok(Response::builder()
+            .status(200)
+            .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+            .header(CONTENT_TYPE, "application/x-ndjson")
+            .header(header::CACHE_CONTROL, "no-cache")
+            .body(body)
+            .unwrap())
+    }
+
+    /// Write the export of `deployment` at `block` to `out`: a header
+    /// object with the deployment and block, one object per entity, and a
+    /// trailer with the entity count and the keccak-256 hash of the raw
+    /// entity lines, including their newlines but excluding the header.
+    /// The hash lets the receiver check the download without having to
+    /// reserialize the entities
+    fn export(
+        store: Arc<S>,
+        subgraph_store: Arc<St>,
+        deployment: &DeploymentHash,
+        block: Option<BlockNumber>,
+        out: &tokio::sync::mpsc::Sender<Vec<u8>>,
+    ) -> Result<(), Error> {
+        let send = |chunk: Vec<u8>| {
+            out.blocking_send(chunk)
+                .map_err(|_| anyhow!("the client went away"))
+        };
+
+        let schema = subgraph_store.input_schema(deployment)?;
+        let block = match block {
+            Some(block) => block,
+            None => {
+                subgraph_store
+                    .least_block_ptr(deployment)?
+                    .ok_or_else(|| {
+                        anyhow!("deployment `{}` has not processed any blocks", deployment)
+                    })?
+                    .number
+            }
+        };
+        let block_hash = store
+            .block_ptr_for_number(deployment, block)?
+            .map(|ptr| format!("0x{}", ptr.hash_hex()));
+
+        let header = serde_json::json!({
+            "deployment": deployment.to_string(),
+            "block": block,
+            "blockHash": block_hash,
+        });
+        send(format!("{}\n", header).into_bytes())?;
+
+        let mut keccak = tiny_keccak::Keccak::new_keccak256();
+        let mut count: u64 = 0;
+        for object_type in schema.document.get_object_type_definitions() {
+            let entity_type = EntityType::new(object_type.name.clone());
+            subgraph_store.export_entities(
+                deployment,
+                &entity_type,
+                block,
+                EXPORT_BATCH_SIZE,
+                &mut |entities| {
+                    let mut chunk = Vec::new();
+                    for entity in entities {
+                        let line = serde_json::json!({
+                            "entityType": entity_type.as_str(),
+                            "data": entity,
+                        });
+                        chunk.extend_from_slice(line.to_string().as_bytes());
+                        chunk.push(b'\n');
+                        count += 1;
+                    }
+                    keccak.update(&chunk);
+                    send(chunk).map_err(StoreError::Unknown)
+                },
+            )?;
+        }
+
+        let mut hash = [0u8; 32];
+        keccak.finalize(&mut hash);
+        let trailer = serde_json::json!({
+            "entityCount": count,
+            "keccak256": format!("0x{}", hex::encode(hash)),
+        });
+        send(format!("{}\n", trailer).into_bytes())
+    }
+
     // Handles OPTIONS requests
     fn handle_graphql_options(_request: Request<Body>) -> Response<Body> {
         Response::builder()
             .status(200)
             .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
             .header(CONTENT_TYPE, "text/plain")
-            .header(ACCESS_CONTROL_ALLOW_HEADERS, "Content-Type, User-Agent")
+            .header(
+                ACCESS_CONTROL_ALLOW_HEADERS,
+                "Content-Type, User-Agent, Authorization",
+            )
             .header(ACCESS_CONTROL_ALLOW_METHODS, "GET, OPTIONS, POST")
             .body(Body::from(""))
             .unwrap()
@@ -216,9 +575,29 @@ where
             }
             (Method::GET, ["graphql", "playground"]) => Ok(Self::handle_graphiql()),
 
-            (Method::POST, ["graphql"]) => self.handle_graphql_query(req.into_body()).await,
+            (Method::POST, ["graphql"]) => {
+                let authorized = Self::is_authorized(&req);
+                self.handle_graphql_query(req.into_body(), authorized).await
+            }
             (Method::OPTIONS, ["graphql"]) => Ok(Self::handle_graphql_options(req)),
 
+            (Method::GET, ["status", "stream"]) => Ok(self.handle_status_stream(req.uri().query())),
+
+            (Method::GET, ["export", deployment]) => {
+                self.handle_export(deployment, req.uri().query())
+            }
+
+            (Method::POST, ["subgraphs", "id", deployment, "_sql"]) => {
+                if !Self::has_bearer_token(&req, &SQL_TOKEN) {
+                    return Err(GraphQLServerError::ClientError(
+                        "SQL queries require the token from GRAPH_SQL_TOKEN as a bearer \
+                         token in the `Authorization` header"
+                            .into(),
+                    ));
+                }
+                self.handle_sql_query(deployment, req.into_body()).await
+            }
+
             (Method::GET, ["explorer", rest @ ..]) => self.explorer.handle(&self.logger, rest),
 
             _ => Ok(Self::handle_not_found()),
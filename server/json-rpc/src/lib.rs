@@ -29,6 +29,9 @@ const JSON_RPC_DEPLOY_ERROR: i64 = 0;
 const JSON_RPC_REMOVE_ERROR: i64 = 1;
 const JSON_RPC_CREATE_ERROR: i64 = 2;
 const JSON_RPC_REASSIGN_ERROR: i64 = 3;
+const JSON_RPC_RETRY_ERROR: i64 = 4;
+const JSON_RPC_SETTING_ERROR: i64 = 5;
+const JSON_RPC_CLONE_ERROR: i64 = 6;
 
 #[derive(Debug, Deserialize)]
 struct SubgraphCreateParams {
@@ -42,6 +45,15 @@ struct SubgraphDeployParams {
     node_id: Option<NodeId>,
 }
 
+#[derive(Debug, Deserialize)]
+struct SubgraphCloneParams {
+    name: SubgraphName,
+    ipfs_hash: DeploymentHash,
+    base: DeploymentHash,
+    block: BlockNumber,
+    node_id: Option<NodeId>,
+}
+
 #[derive(Debug, Deserialize)]
 struct SubgraphRemoveParams {
     name: SubgraphName,
@@ -53,6 +65,35 @@ struct SubgraphReassignParams {
     node_id: NodeId,
 }
 
+#[derive(Debug, Deserialize)]
+struct SubgraphRetryParams {
+    ipfs_hash: DeploymentHash,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubgraphRetryFromParams {
+    ipfs_hash: DeploymentHash,
+    block: BlockNumber,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubgraphSettingParams {
+    ipfs_hash: DeploymentHash,
+    name: String,
+    #[serde(default)]
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubgraphDeployBatchParams {
+    deployments: Vec<SubgraphDeployParams>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubgraphReassignBatchParams {
+    reassignments: Vec<SubgraphReassignParams>,
+}
+
 pub struct JsonRpcServer<R> {
     registrar: Arc<R>,
     http_port: u16,
@@ -108,6 +149,70 @@ impl<R: SubgraphRegistrar> JsonRpcServer<R> {
         }
     }
 
+    /// Handler for the `subgraph_deploy_batch` endpoint. Deployments are
+    /// applied in order; since each one resolves its manifest from IPFS they
+    /// cannot share a transaction, so deployments before a failing one
+    /// remain applied. The error names the deployment that failed.
+    async fn deploy_batch_handler(
+        &self,
+        params: SubgraphDeployBatchParams,
+    ) -> Result<Value, jsonrpc_core::Error> {
+        info!(&self.logger, "Received subgraph_deploy_batch request"; "params" => format!("{:?}", params));
+
+        let mut routes = Vec::with_capacity(params.deployments.len());
+        for params in &params.deployments {
+            let node_id = params.node_id.clone().unwrap_or(self.node_id.clone());
+            self.registrar
+                .create_subgraph_version(params.name.clone(), params.ipfs_hash.clone(), node_id)
+                .await
+                .map_err(|e| {
+                    json_rpc_error(
+                        &self.logger,
+                        "subgraph_deploy_batch",
+                        e,
+                        JSON_RPC_DEPLOY_ERROR,
+                        params,
+                    )
+                })?;
+            routes.push(subgraph_routes(&params.name, self.http_port, self.ws_port));
+        }
+        Ok(Value::Array(routes))
+    }
+
+    /// Handler for the `subgraph_clone` endpoint. Deploys `ipfs_hash`
+    /// under `name` with the entity data of the deployment `base` up to
+    /// `block` copied in, so that the new mappings index forward from the
+    /// same state as the old ones.
+    async fn clone_handler(
+        &self,
+        params: SubgraphCloneParams,
+    ) -> Result<Value, jsonrpc_core::Error> {
+        info!(&self.logger, "Received subgraph_clone request"; "params" => format!("{:?}", params));
+
+        let node_id = params.node_id.clone().unwrap_or(self.node_id.clone());
+        let routes = subgraph_routes(&params.name, self.http_port, self.ws_port);
+        match self
+            .registrar
+            .clone_subgraph_version(
+                params.name.clone(),
+                params.ipfs_hash.clone(),
+                node_id,
+                params.base.clone(),
+                params.block,
+            )
+            .await
+        {
+            Ok(_) => Ok(routes),
+            Err(e) => Err(json_rpc_error(
+                &self.logger,
+                "subgraph_clone",
+                e,
+                JSON_RPC_CLONE_ERROR,
+                params,
+            )),
+        }
+    }
+
     /// Handler for the `subgraph_remove` endpoint.
     async fn remove_handler(
         &self,
@@ -151,6 +256,98 @@ impl<R: SubgraphRegistrar> JsonRpcServer<R> {
             )),
         }
     }
+
+    /// Handler for the `subgraph_reassign_batch` endpoint. All
+    /// reassignments are applied in one metadata transaction: either all of
+    /// them take effect or none do.
+    async fn reassign_batch_handler(
+        &self,
+        params: SubgraphReassignBatchParams,
+    ) -> Result<Value, jsonrpc_core::Error> {
+        info!(&self.logger, "Received subgraph_reassign_batch request"; "params" => format!("{:?}", params));
+
+        let assignments: Vec<_> = params
+            .reassignments
+            .iter()
+            .map(|params| (params.ipfs_hash.clone(), params.node_id.clone()))
+            .collect();
+        match self.registrar.reassign_subgraphs(&assignments).await {
+            Ok(_) => Ok(Value::Null),
+            Err(e) => Err(json_rpc_error(
+                &self.logger,
+                "subgraph_reassign_batch",
+                e,
+                JSON_RPC_REASSIGN_ERROR,
+                params,
+            )),
+        }
+    }
+
+    /// Handler for the `subgraph_retry` endpoint.
+    async fn retry_handler(
+        &self,
+        params: SubgraphRetryParams,
+    ) -> Result<Value, jsonrpc_core::Error> {
+        info!(&self.logger, "Received subgraph_retry request"; "params" => format!("{:?}", params));
+
+        match self.registrar.retry_subgraph(&params.ipfs_hash).await {
+            Ok(_) => Ok(Value::Null),
+            Err(e) => Err(json_rpc_error(
+                &self.logger,
+                "subgraph_retry",
+                e,
+                JSON_RPC_RETRY_ERROR,
+                params,
+            )),
+        }
+    }
+
+    /// Handler for the `subgraph_retry_from` endpoint.
+    async fn retry_from_handler(
+        &self,
+        params: SubgraphRetryFromParams,
+    ) -> Result<Value, jsonrpc_core::Error> {
+        info!(&self.logger, "Received subgraph_retry_from request"; "params" => format!("{:?}", params));
+
+        match self
+            .registrar
+            .retry_subgraph_from(&params.ipfs_hash, params.block)
+            .await
+        {
+            Ok(_) => Ok(Value::Null),
+            Err(e) => Err(json_rpc_error(
+                &self.logger,
+                "subgraph_retry_from",
+                e,
+                JSON_RPC_RETRY_ERROR,
+                params,
+            )),
+        }
+    }
+
+    /// Handler for the `subgraph_setting` endpoint. An empty value
+    /// clears the setting again.
+    async fn setting_handler(
+        &self,
+        params: SubgraphSettingParams,
+    ) -> Result<Value, jsonrpc_core::Error> {
+        info!(&self.logger, "Received subgraph_setting request"; "params" => format!("{:?}", params));
+
+        match self
+            .registrar
+            .set_deployment_setting(&params.ipfs_hash, &params.name, &params.value)
+            .await
+        {
+            Ok(_) => Ok(Value::Null),
+            Err(e) => Err(json_rpc_error(
+                &self.logger,
+                "subgraph_setting",
+                e,
+                JSON_RPC_SETTING_ERROR,
+                params,
+            )),
+        }
+    }
 }
 
 impl<R> JsonRpcServerTrait<R> for JsonRpcServer<R>
@@ -246,6 +443,36 @@ where
             .compat()
         });
 
+        let me = arc_self.clone();
+        let sender = task_sender.clone();
+        handler.add_method("subgraph_deploy_batch", move |params: Params| {
+            let me = me.clone();
+            Box::pin(tokio02_spawn(
+                sender.clone(),
+                async move {
+                    let params = params.parse()?;
+                    me.deploy_batch_handler(params).await
+                }
+                .boxed(),
+            ))
+            .compat()
+        });
+
+        let me = arc_self.clone();
+        let sender = task_sender.clone();
+        handler.add_method("subgraph_clone", move |params: Params| {
+            let me = me.clone();
+            Box::pin(tokio02_spawn(
+                sender.clone(),
+                async move {
+                    let params = params.parse()?;
+                    me.clone_handler(params).await
+                }
+                .boxed(),
+            ))
+            .compat()
+        });
+
         let me = arc_self.clone();
         let sender = task_sender.clone();
         handler.add_method("subgraph_remove", move |params: Params| {
@@ -276,6 +503,66 @@ where
             .compat()
         });
 
+        let me = arc_self.clone();
+        let sender = task_sender.clone();
+        handler.add_method("subgraph_reassign_batch", move |params: Params| {
+            let me = me.clone();
+            Box::pin(tokio02_spawn(
+                sender.clone(),
+                async move {
+                    let params = params.parse()?;
+                    me.reassign_batch_handler(params).await
+                }
+                .boxed(),
+            ))
+            .compat()
+        });
+
+        let me = arc_self.clone();
+        let sender = task_sender.clone();
+        handler.add_method("subgraph_retry", move |params: Params| {
+            let me = me.clone();
+            Box::pin(tokio02_spawn(
+                sender.clone(),
+                async move {
+                    let params = params.parse()?;
+                    me.retry_handler(params).await
+                }
+                .boxed(),
+            ))
+            .compat()
+        });
+
+        let me = arc_self.clone();
+        let sender = task_sender.clone();
+        handler.add_method("subgraph_retry_from", move |params: Params| {
+            let me = me.clone();
+            Box::pin(tokio02_spawn(
+                sender.clone(),
+                async move {
+                    let params = params.parse()?;
+                    me.retry_from_handler(params).await
+                }
+                .boxed(),
+            ))
+            .compat()
+        });
+
+        let me = arc_self.clone();
+        let sender = task_sender.clone();
+        handler.add_method("subgraph_setting", move |params: Params| {
+            let me = me.clone();
+            Box::pin(tokio02_spawn(
+                sender.clone(),
+                async move {
+                    let params = params.parse()?;
+                    me.setting_handler(params).await
+                }
+                .boxed(),
+            ))
+            .compat()
+        });
+
         ServerBuilder::new(handler)
             // Enable REST API:
             // POST /<method>/<param1>/<param2>
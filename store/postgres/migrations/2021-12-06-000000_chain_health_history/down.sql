@@ -0,0 +1,3 @@
+drop table public.chain_reorg_events;
+
+drop table public.chain_head_lag_samples;
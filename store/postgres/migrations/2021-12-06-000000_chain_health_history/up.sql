@@ -0,0 +1,23 @@
+create table public.chain_head_lag_samples (
+    id              bigserial primary key,
+    network         text not null,
+    head_number     bigint not null,
+    blocks_advanced bigint not null,
+    sampled_at      timestamptz not null default now()
+);
+
+create index chain_head_lag_samples_network_sampled_at
+    on public.chain_head_lag_samples(network, sampled_at);
+
+create table public.chain_reorg_events (
+    id          bigserial primary key,
+    network     text not null,
+    from_hash   text not null,
+    from_number bigint not null,
+    to_hash     text not null,
+    to_number   bigint not null,
+    detected_at timestamptz not null default now()
+);
+
+create index chain_reorg_events_network_detected_at
+    on public.chain_reorg_events(network, detected_at);
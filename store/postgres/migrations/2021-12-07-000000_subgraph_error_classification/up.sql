@@ -0,0 +1,6 @@
+alter table subgraphs.subgraph_error
+    add column classification text not null default 'unknown';
+
+update subgraphs.subgraph_error
+    set classification = 'deterministic'
+    where deterministic;
@@ -0,0 +1 @@
+drop table public.subgraph_settings;
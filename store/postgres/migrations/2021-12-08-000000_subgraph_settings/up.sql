@@ -0,0 +1,7 @@
+create table public.subgraph_settings (
+    deployment text not null,
+    name       text not null,
+    value      text not null,
+
+    primary key (deployment, name)
+);
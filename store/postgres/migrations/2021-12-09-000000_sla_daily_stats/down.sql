@@ -0,0 +1 @@
+drop table public.sla_daily_stats;
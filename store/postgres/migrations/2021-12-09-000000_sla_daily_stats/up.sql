@@ -0,0 +1,13 @@
+create table public.sla_daily_stats (
+    deployment    text not null,
+    day           date not null,
+    samples       integer not null,
+    -- Element i (arrays are 1-based) counts samples where the deployment
+    -- was i - 1 blocks behind the chain head; the last element counts
+    -- samples that were more than 10 blocks behind
+    behind_counts integer[] not null,
+    max_behind    integer not null,
+    sum_behind    bigint not null,
+
+    primary key (deployment, day)
+);
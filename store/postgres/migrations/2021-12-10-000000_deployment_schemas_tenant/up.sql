@@ -0,0 +1,5 @@
+alter table deployment_schemas
+  add column tenant text;
+
+comment on column deployment_schemas.tenant is
+  'The tenant owning the deployment; read access to the schema is limited to the tenant''s database role';
@@ -3,7 +3,7 @@ use diesel::{connection::SimpleConnection, prelude::RunQueryDsl, select};
 use diesel::{insert_into, OptionalExtension};
 use diesel::{pg::PgConnection, sql_query};
 use diesel::{
-    sql_types::{Array, Nullable, Text},
+    sql_types::{Array, BigInt, Double, Nullable, Text},
     ExpressionMethods, QueryDsl,
 };
 use std::collections::{HashMap, HashSet};
@@ -47,17 +47,35 @@ table! {
     }
 }
 
+/// Statistics about one table in a deployment's namespace, harvested
+/// from `pg_class` and `pg_stats`
+#[derive(Debug, Clone, Default)]
+pub struct TableStats {
+    /// The planner's estimate of the number of rows in the table
+    pub rows: f64,
+    /// The number of distinct values for each column that `pg_stats` has
+    /// data for. Negative values are fractions of `rows`; see the
+    /// Postgres documentation for `pg_stats.n_distinct`
+    pub n_distinct: HashMap<String, f64>,
+}
+
 /// Information about what tables and columns we have in the database
 #[derive(Debug, Clone)]
 pub struct Catalog {
     pub site: Arc<Site>,
     text_columns: HashMap<String, HashSet<String>>,
+    stats: HashMap<String, TableStats>,
 }
 
 impl Catalog {
     pub fn new(conn: &PgConnection, site: Arc<Site>) -> Result<Self, StoreError> {
         let text_columns = get_text_columns(conn, &site.namespace)?;
-        Ok(Catalog { site, text_columns })
+        let stats = get_stats(conn, &site.namespace)?;
+        Ok(Catalog {
+            site,
+            text_columns,
+            stats,
+        })
     }
 
     /// Make a catalog as if the given `schema` did not exist in the database
@@ -67,6 +85,7 @@ impl Catalog {
         Ok(Catalog {
             site,
             text_columns: HashMap::default(),
+            stats: HashMap::default(),
         })
     }
 
@@ -78,6 +97,12 @@ impl Catalog {
             .map(|cols| cols.contains(column.as_str()))
             .unwrap_or(false)
     }
+
+    /// The statistics for `table`, if the database has any. For a newly
+    /// created deployment, there are none
+    pub fn stats(&self, table: &SqlName) -> Option<&TableStats> {
+        self.stats.get(table.as_str())
+    }
 }
 
 fn get_text_columns(
@@ -110,6 +135,84 @@ fn get_text_columns(
     Ok(map)
 }
 
+fn get_stats(
+    conn: &PgConnection,
+    namespace: &Namespace,
+) -> Result<HashMap<String, TableStats>, StoreError> {
+    const QUERY: &str = "
+        select c.relname::text as table_name,
+               c.reltuples::float8 as rows,
+               s.attname::text as column_name,
+               s.n_distinct::float8 as n_distinct
+          from pg_class c
+          join pg_namespace n on c.relnamespace = n.oid
+          left join pg_stats s
+            on s.schemaname = n.nspname
+           and s.tablename = c.relname
+         where n.nspname = $1
+           and c.relkind = 'r'";
+
+    #[derive(Debug, QueryableByName)]
+    struct Stat {
+        #[sql_type = "Text"]
+        pub table_name: String,
+        #[sql_type = "Double"]
+        pub rows: f64,
+        #[sql_type = "Nullable<Text>"]
+        pub column_name: Option<String>,
+        #[sql_type = "Nullable<Double>"]
+        pub n_distinct: Option<f64>,
+    }
+
+    let map: HashMap<String, TableStats> = diesel::sql_query(QUERY)
+        .bind::<Text, _>(namespace.as_str())
+        .load::<Stat>(conn)?
+        .into_iter()
+        .fold(HashMap::new(), |mut map, stat| {
+            let entry: &mut TableStats = map.entry(stat.table_name).or_default();
+            entry.rows = stat.rows;
+            if let (Some(column), Some(n_distinct)) = (stat.column_name, stat.n_distinct) {
+                entry.n_distinct.insert(column, n_distinct);
+            }
+            map
+        });
+    Ok(map)
+}
+
+/// Return the total on-disk size of each of the given namespaces in
+/// bytes, including indexes and toast tables. Namespaces that do not
+/// exist in the database are absent from the result
+pub fn namespace_sizes(
+    conn: &PgConnection,
+    namespaces: Vec<String>,
+) -> Result<HashMap<String, u64>, StoreError> {
+    // Restricting to ordinary tables avoids counting indexes and toast
+    // tables twice since `pg_total_relation_size` already includes them
+    const QUERY: &str = "
+        select n.nspname::text as nspname,
+               sum(pg_total_relation_size(c.oid))::int8 as size
+          from pg_class c
+          join pg_namespace n on c.relnamespace = n.oid
+         where n.nspname = any($1)
+           and c.relkind = 'r'
+         group by n.nspname";
+
+    #[derive(QueryableByName)]
+    struct NamespaceSize {
+        #[sql_type = "Text"]
+        nspname: String,
+        #[sql_type = "BigInt"]
+        size: i64,
+    }
+
+    Ok(sql_query(QUERY)
+        .bind::<Array<Text>, _>(namespaces)
+        .load::<NamespaceSize>(conn)?
+        .into_iter()
+        .map(|ns| (ns.nspname, ns.size.max(0) as u64))
+        .collect())
+}
+
 pub fn supports_proof_of_indexing(
     conn: &diesel::pg::PgConnection,
     namespace: &Namespace,
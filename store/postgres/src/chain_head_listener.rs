@@ -115,8 +115,12 @@ impl ChainHeadUpdateListener {
             )
             .unwrap();
         // Create a Postgres notification listener for chain head updates
-        let (mut listener, receiver) =
-            NotificationListener::new(&logger, postgres_url, CHANNEL_NAME.clone());
+        let (mut listener, receiver) = NotificationListener::new(
+            &logger,
+            postgres_url,
+            CHANNEL_NAME.clone(),
+            registry.clone(),
+        );
         let watchers = Arc::new(TimedRwLock::new(
             BTreeMap::new(),
             "chain_head_listener_watchers",
@@ -9,13 +9,13 @@ use graph::{
     constraint_violation,
     prelude::{
         async_trait, ethabi, CancelableError, ChainStore as ChainStoreTrait, EthereumCallCache,
-        StoreError,
+        HeadLagSample, ReorgEvent, StoreError,
     },
 };
 
 use graph::ensure;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     convert::{TryFrom, TryInto},
     iter::FromIterator,
     sync::Arc,
@@ -27,7 +27,7 @@ use graph::prelude::{
 
 use crate::{
     block_store::ChainStatus, chain_head_listener::ChainHeadUpdateSender,
-    connection_pool::ConnectionPool,
+    connection_pool::ConnectionPool, object_block_cache::ObjectBlockCache,
 };
 
 /// Tables in the 'public' database schema that store chain-specific data
@@ -43,6 +43,28 @@ mod public {
             head_block_cursor -> Nullable<Varchar>,
         }
     }
+
+    table! {
+        chain_head_lag_samples (id) {
+            id -> BigInt,
+            network -> Text,
+            head_number -> BigInt,
+            blocks_advanced -> BigInt,
+            sampled_at -> Timestamptz,
+        }
+    }
+
+    table! {
+        chain_reorg_events (id) {
+            id -> BigInt,
+            network -> Text,
+            from_hash -> Text,
+            from_number -> BigInt,
+            to_hash -> Text,
+            to_number -> BigInt,
+            detected_at -> Timestamptz,
+        }
+    }
 }
 
 pub use data::Storage;
@@ -860,6 +882,156 @@ mod data {
             Ok(data)
         }
 
+        /// The hex hash of the block `offset` blocks before `block_ptr`,
+        /// in the format that `ethereum_networks.head_block_hash` uses.
+        /// Returns `None` if the block cache does not go back that far
+        pub(super) fn ancestor_hash(
+            &self,
+            conn: &PgConnection,
+            block_ptr: &BlockPtr,
+            offset: i64,
+        ) -> Result<Option<String>, StoreError> {
+            match self {
+                Storage::Shared => {
+                    // Same as `ANCESTOR_SQL` in `ancestor_block`, but we
+                    // only need the hash, not the block data
+                    const ANCESTOR_HASH_SQL: &str = "
+        with recursive ancestors(block_hash, block_offset) as (
+            values ($1, 0)
+            union all
+            select b.parent_hash, a.block_offset+1
+              from ancestors a, ethereum_blocks b
+             where a.block_hash = b.hash
+               and a.block_offset < $2
+        )
+        select a.block_hash as hash
+          from ancestors a
+         where a.block_offset = $2;";
+
+                    let hash = sql_query(ANCESTOR_HASH_SQL)
+                        .bind::<Text, _>(block_ptr.hash_hex())
+                        .bind::<BigInt, _>(offset)
+                        .get_result::<BlockHashText>(conn)
+                        .optional()?;
+                    Ok(hash.map(|hash| hash.hash))
+                }
+                Storage::Private(Schema { blocks, .. }) => {
+                    let query = format!(
+                        "
+        with recursive ancestors(block_hash, block_offset) as (
+            values ($1, 0)
+            union all
+            select b.parent_hash, a.block_offset+1
+              from ancestors a, {} b
+             where a.block_hash = b.hash
+               and a.block_offset < $2
+        )
+        select a.block_hash as hash
+          from ancestors a
+         where a.block_offset = $2;",
+                        blocks.qname
+                    );
+
+                    let hash = sql_query(query)
+                        .bind::<Bytea, _>(block_ptr.hash_slice())
+                        .bind::<BigInt, _>(offset)
+                        .get_result::<BlockHashBytea>(conn)
+                        .optional()?;
+                    hash.map(|hash| h256_from_bytes(&hash.hash).map(|hash| format!("{:x}", hash)))
+                        .transpose()
+                }
+            }
+        }
+
+        /// Return the hex hash and data of up to `limit` blocks with
+        /// numbers before `block`, skipping the first `offset` of them,
+        /// in a stable order. Like `delete_blocks_before`, the genesis
+        /// block is never included
+        pub(super) fn blocks_before(
+            &self,
+            conn: &PgConnection,
+            chain: &str,
+            block: i64,
+            limit: i64,
+            offset: i64,
+        ) -> Result<Vec<(String, json::Value)>, Error> {
+            #[derive(QueryableByName)]
+            struct HashAndData {
+                #[sql_type = "Text"]
+                hash: String,
+                #[sql_type = "Jsonb"]
+                data: json::Value,
+            }
+
+            let rows = match self {
+                Storage::Shared => sql_query(
+                    "select hash, data from public.ethereum_blocks \
+                      where network_name = $1 and number < $2 and number > 0 \
+                      order by number, hash limit $3 offset $4",
+                )
+                .bind::<Text, _>(chain)
+                .bind::<BigInt, _>(block)
+                .bind::<BigInt, _>(limit)
+                .bind::<BigInt, _>(offset)
+                .load::<HashAndData>(conn)?,
+                Storage::Private(Schema { blocks, .. }) => {
+                    let query = format!(
+                        "select encode(hash, 'hex') as hash, data from {} \
+                          where number < $1 and number > 0 \
+                          order by number, hash limit $2 offset $3",
+                        blocks.qname
+                    );
+                    sql_query(query)
+                        .bind::<BigInt, _>(block)
+                        .bind::<BigInt, _>(limit)
+                        .bind::<BigInt, _>(offset)
+                        .load::<HashAndData>(conn)?
+                }
+            };
+            Ok(rows.into_iter().map(|row| (row.hash, row.data)).collect())
+        }
+
+        /// The raw data of the block with the given hex hash (without
+        /// the `0x` prefix)
+        pub(super) fn block_data(
+            &self,
+            conn: &PgConnection,
+            chain: &str,
+            hash: &str,
+        ) -> Result<Option<json::Value>, Error> {
+            match self {
+                Storage::Shared => {
+                    use public::ethereum_blocks as b;
+
+                    b::table
+                        .filter(b::network_name.eq(chain))
+                        .filter(b::hash.eq(hash))
+                        .select(b::data)
+                        .first::<json::Value>(conn)
+                        .optional()
+                        .map_err(Error::from)
+                }
+                Storage::Private(Schema { blocks, .. }) => {
+                    #[derive(QueryableByName)]
+                    struct Data {
+                        #[sql_type = "Jsonb"]
+                        data: json::Value,
+                    }
+
+                    let query = format!(
+                        "select data from {} where hash = decode($1, 'hex')",
+                        blocks.qname
+                    );
+                    sql_query(query)
+                        .bind::<Text, _>(hash)
+                        .get_result::<Data>(conn)
+                        .optional()
+                        .map(|row| row.map(|row| row.data))
+                        .map_err(Error::from)
+                }
+            }
+        }
+
         pub(super) fn delete_blocks_before(
             &self,
             conn: &PgConnection,
@@ -1176,6 +1348,9 @@ pub struct ChainStore {
     genesis_block_ptr: BlockPtr,
     status: ChainStatus,
     chain_head_update_sender: ChainHeadUpdateSender,
+    /// When set, blocks evicted from the database are spilled to object
+    /// storage and lookups fall back to it; see `object_block_cache`
+    object_cache: Option<Arc<ObjectBlockCache>>,
 }
 
 impl ChainStore {
@@ -1187,6 +1362,9 @@ impl ChainStore {
         chain_head_update_sender: ChainHeadUpdateSender,
         pool: ConnectionPool,
     ) -> Self {
+        let object_cache = ObjectBlockCache::for_chain(&chain)
+            .expect("the block cache configuration is valid")
+            .map(Arc::new);
         let store = ChainStore {
             pool,
             chain,
@@ -1194,6 +1372,7 @@ impl ChainStore {
             genesis_block_ptr: BlockPtr::new(net_identifier.genesis_block_hash.clone(), 0),
             status,
             chain_head_update_sender,
+            object_cache,
         };
 
         store
@@ -1207,6 +1386,73 @@ impl ChainStore {
         self.pool.get().map_err(Error::from)
     }
 
+    /// Upload all blocks before `block` to the object cache so that
+    /// `delete_blocks_before` can remove them from the database without
+    /// losing them. If an upload fails, nothing gets deleted
+    fn spill_blocks_before(
+        &self,
+        conn: &PgConnection,
+        cache: &ObjectBlockCache,
+        block: i64,
+    ) -> Result<usize, Error> {
+        const BATCH_SIZE: i64 = 1000;
+
+        let mut offset = 0;
+        let mut total = 0;
+        loop {
+            let batch = self
+                .storage
+                .blocks_before(conn, &self.chain, block, BATCH_SIZE, offset)?;
+            if batch.is_empty() {
+                return Ok(total);
+            }
+            for (hash, data) in &batch {
+                cache.put(hash, data)?;
+            }
+            total += batch.len();
+            offset += BATCH_SIZE;
+        }
+    }
+
+    /// Walk the chain backwards one block at a time, consulting the
+    /// object cache for blocks that are no longer in the database. This
+    /// is the slow path for `ancestor_block` when the recursive SQL
+    /// query fell off the end of the cached blocks
+    fn ancestor_block_spilled(
+        &self,
+        conn: &PgConnection,
+        cache: &ObjectBlockCache,
+        block_ptr: BlockPtr,
+        offset: BlockNumber,
+    ) -> Result<Option<json::Value>, Error> {
+        let block_data = |hash: &str| -> Result<Option<json::Value>, Error> {
+            match self.storage.block_data(conn, &self.chain, hash)? {
+                Some(data) => Ok(Some(data)),
+                None => cache.get(hash),
+            }
+        };
+
+        let mut hash = block_ptr.hash_hex();
+        for _ in 0..offset {
+            let data = match block_data(&hash)? {
+                Some(data) => data,
+                None => return Ok(None),
+            };
+            // The data may or may not be wrapped in a toplevel 'block'
+            // field; see 7736e440-4c6b-11ec-8c4d-b42e99f52061
+            hash = match data
+                .get("block")
+                .unwrap_or(&data)
+                .get("parentHash")
+                .and_then(|hash| hash.as_str())
+            {
+                Some(parent) => parent.trim_start_matches("0x").to_string(),
+                None => return Ok(None),
+            };
+        }
+        block_data(&hash)
+    }
+
     pub(crate) fn create(&self, ident: &ChainIdentifier) -> Result<(), Error> {
         use public::ethereum_networks::dsl::*;
 
@@ -1301,6 +1547,61 @@ impl ChainStore {
         self.storage.truncate_block_cache(&conn)?;
         Ok(())
     }
+
+    /// Record a head lag sample for the move from `old_head` to `new_head`,
+    /// and a reorg event if the new head does not descend from the old one.
+    /// Must be called from within the transaction that updates the chain
+    /// head. We only check for a reorg if the old head is at most
+    /// `ancestor_count` blocks behind the new head since we can not be sure
+    /// that the block cache goes back further than that
+    fn record_head_update(
+        &self,
+        conn: &PgConnection,
+        old_head: (Option<String>, Option<i64>),
+        new_head: &BlockPtr,
+        ancestor_count: i64,
+    ) -> Result<(), StoreError> {
+        use public::chain_head_lag_samples as s;
+        use public::chain_reorg_events as r;
+
+        let number = new_head.number as i64;
+        let blocks_advanced = match old_head.1 {
+            Some(old_number) => number - old_number,
+            // The first head update for this chain; we advanced from
+            // nothing to the new head
+            None => number + 1,
+        };
+
+        insert_into(s::table)
+            .values((
+                s::network.eq(&self.chain),
+                s::head_number.eq(number),
+                s::blocks_advanced.eq(blocks_advanced),
+            ))
+            .execute(conn)?;
+
+        if let (Some(old_hash), Some(old_number)) = (old_head.0, old_head.1) {
+            let offset = number - old_number;
+            if offset >= 0 && offset <= ancestor_count {
+                // If the ancestor is not in the block cache, we can not
+                // tell whether there was a reorg and stay quiet
+                if let Some(ancestor) = self.storage.ancestor_hash(conn, new_head, offset)? {
+                    if ancestor != old_hash {
+                        insert_into(r::table)
+                            .values((
+                                r::network.eq(&self.chain),
+                                r::from_hash.eq(&old_hash),
+                                r::from_number.eq(old_number),
+                                r::to_hash.eq(new_head.hash_hex()),
+                                r::to_number.eq(number),
+                            ))
+                            .execute(conn)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -1374,12 +1675,25 @@ impl ChainStoreTrait for ChainStore {
 
                     conn.transaction(
                         || -> Result<(Option<H256>, Option<(String, i64)>), StoreError> {
+                            let old_head = n::table
+                                .filter(n::name.eq(&chain_store.chain))
+                                .select((n::head_block_hash, n::head_block_number))
+                                .first::<(Option<String>, Option<i64>)>(conn)?;
+
                             update(n::table.filter(n::name.eq(&chain_store.chain)))
                                 .set((
                                     n::head_block_hash.eq(&hash),
                                     n::head_block_number.eq(number),
                                 ))
                                 .execute(conn)?;
+
+                            chain_store.record_head_update(
+                                conn,
+                                old_head,
+                                ptr,
+                                ancestor_count as i64,
+                            )?;
+
                             Ok((None, Some((hash, number))))
                         },
                     )
@@ -1468,7 +1782,28 @@ impl ChainStoreTrait for ChainStore {
 
     fn blocks(&self, hashes: &[H256]) -> Result<Vec<json::Value>, Error> {
         let conn = self.get_conn()?;
-        self.storage.blocks(&conn, &self.chain, hashes)
+        let mut blocks = self.storage.blocks(&conn, &self.chain, hashes)?;
+        if blocks.len() < hashes.len() {
+            if let Some(cache) = &self.object_cache {
+                let found: HashSet<String> = blocks
+                    .iter()
+                    .filter_map(|block| block.get("hash").and_then(|hash| hash.as_str()))
+                    .map(|hash| hash.trim_start_matches("0x").to_string())
+                    .collect();
+                for hash in hashes {
+                    let hash = format!("{:x}", hash);
+                    if found.contains(&hash) {
+                        continue;
+                    }
+                    if let Some(data) = cache.get(&hash)? {
+                        // Unwrap the toplevel 'block' field the same way
+                        // the database query does
+                        blocks.push(data.get("block").cloned().unwrap_or(data));
+                    }
+                }
+            }
+        }
+        Ok(blocks)
     }
 
     fn ancestor_block(
@@ -1484,7 +1819,16 @@ impl ChainStoreTrait for ChainStore {
         );
 
         let conn = self.get_conn()?;
-        self.storage.ancestor_block(&conn, block_ptr, offset)
+        match self
+            .storage
+            .ancestor_block(&conn, block_ptr.clone(), offset)?
+        {
+            Some(block) => Ok(Some(block)),
+            None => match &self.object_cache {
+                Some(cache) => self.ancestor_block_spilled(&conn, cache, block_ptr, offset),
+                None => Ok(None),
+            },
+        }
     }
 
     fn cleanup_cached_blocks(
@@ -1544,6 +1888,11 @@ impl ChainStoreTrait for ChainStore {
                 // returns -1, and we should not do anything. We also guard
                 // against removing the genesis block
                 if *block > 0 {
+                    // Move the blocks to the object cache first so that
+                    // they stay available for ancestor lookups
+                    if let Some(cache) = &self.object_cache {
+                        self.spill_blocks_before(&conn, cache, *block as i64)?;
+                    }
                     self.storage
                         .delete_blocks_before(&conn, &self.chain, *block as i64)
                         .map(|rows| Some((*block, rows)))
@@ -1575,6 +1924,64 @@ impl ChainStoreTrait for ChainStore {
             .map(|number| (self.chain.clone(), number)))
     }
 
+    fn head_lag_samples(&self, first: usize) -> Result<Vec<HeadLagSample>, Error> {
+        use diesel::dsl::sql;
+        use public::chain_head_lag_samples as s;
+
+        let conn = self.get_conn()?;
+        s::table
+            .filter(s::network.eq(&self.chain))
+            .order_by(s::sampled_at.desc())
+            .limit(first as i64)
+            .select((
+                s::head_number,
+                s::blocks_advanced,
+                sql::<Text>("sampled_at::text"),
+            ))
+            .load::<(i64, i64, String)>(&conn)?
+            .into_iter()
+            .map(|(head_number, blocks_advanced, sampled_at)| {
+                Ok(HeadLagSample {
+                    head_number: head_number.try_into()?,
+                    blocks_advanced,
+                    sampled_at,
+                })
+            })
+            .collect()
+    }
+
+    fn reorg_events(&self, first: usize) -> Result<Vec<ReorgEvent>, Error> {
+        use diesel::dsl::sql;
+        use public::chain_reorg_events as r;
+
+        let conn = self.get_conn()?;
+        r::table
+            .filter(r::network.eq(&self.chain))
+            .order_by(r::detected_at.desc())
+            .limit(first as i64)
+            .select((
+                r::from_hash,
+                r::from_number,
+                r::to_hash,
+                r::to_number,
+                sql::<Text>("detected_at::text"),
+            ))
+            .load::<(String, i64, String, i64, String)>(&conn)?
+            .into_iter()
+            .map(
+                |(from_hash, from_number, to_hash, to_number, detected_at)| {
+                    Ok(ReorgEvent {
+                        from_hash,
+                        from_number: from_number.try_into()?,
+                        to_hash,
+                        to_number: to_number.try_into()?,
+                        detected_at,
+                    })
+                },
+            )
+            .collect()
+    }
+
     async fn transaction_receipts_in_block(
         &self,
         block_hash: &H256,
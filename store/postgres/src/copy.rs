@@ -29,6 +29,7 @@ use diesel::{
 use graph::{
     components::store::EntityType,
     constraint_violation,
+    data::subgraph::status,
     prelude::{info, o, warn, BlockNumber, BlockPtr, Logger, StoreError},
 };
 
@@ -89,6 +90,56 @@ pub enum Status {
     Cancelled,
 }
 
+/// Return the progress of an in-flight copy into the deployment `dst`, or
+/// `None` if no copy is currently running. This is used by the indexing
+/// status API so that operators can watch grafts and copies progress
+pub(crate) fn status(
+    conn: &PgConnection,
+    dst: DeploymentId,
+) -> Result<Option<status::CopyInfo>, StoreError> {
+    use copy_state as cs;
+    use copy_table_state as cts;
+
+    let target_block_number = match cs::table
+        .filter(cs::dst.eq(dst))
+        .filter(cs::finished_at.is_null())
+        .select(cs::target_block_number)
+        .first::<BlockNumber>(conn)
+        .optional()?
+    {
+        Some(number) => number,
+        None => return Ok(None),
+    };
+
+    let tables = cts::table
+        .filter(cts::dst.eq(dst))
+        .select((
+            cts::entity_type,
+            cts::next_vid,
+            cts::target_vid,
+            cts::batch_size,
+            cts::duration_ms,
+        ))
+        .order_by(cts::entity_type)
+        .load::<(String, i64, i64, i64, i64)>(conn)?
+        .into_iter()
+        .map(
+            |(entity_type, next_vid, target_vid, batch_size, duration_ms)| status::CopyTableInfo {
+                entity_type,
+                next_vid,
+                target_vid,
+                batch_size,
+                duration_ms,
+            },
+        )
+        .collect();
+
+    Ok(Some(status::CopyInfo {
+        target_block_number,
+        tables,
+    }))
+}
+
 #[allow(dead_code)]
 struct CopyState {
     src: Arc<Layout>,
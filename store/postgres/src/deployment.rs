@@ -18,7 +18,10 @@ use graph::prelude::{
     anyhow, bigdecimal::ToPrimitive, hex, web3::types::H256, BigDecimal, BlockNumber, BlockPtr,
     DeploymentHash, DeploymentState, Schema, StoreError,
 };
-use graph::{data::subgraph::schema::SubgraphError, prelude::SubgraphDeploymentEntity};
+use graph::{
+    data::subgraph::schema::{ErrorClass, SubgraphError},
+    prelude::SubgraphDeploymentEntity,
+};
 use stable_hash::crypto::SetHasher;
 use std::str::FromStr;
 use std::{collections::BTreeSet, convert::TryFrom, ops::Bound};
@@ -91,6 +94,7 @@ table! {
         handler -> Nullable<Text>,
         deterministic -> Bool,
         block_range -> Range<Integer>,
+        classification -> Text,
     }
 }
 
@@ -459,7 +463,11 @@ pub fn exists_and_synced(conn: &PgConnection, id: &str) -> Result<bool, StoreErr
 }
 
 // Does nothing if the error already exists. Returns the error id.
-fn insert_subgraph_error(conn: &PgConnection, error: &SubgraphError) -> anyhow::Result<String> {
+fn insert_subgraph_error(
+    conn: &PgConnection,
+    error: &SubgraphError,
+    class: ErrorClass,
+) -> anyhow::Result<String> {
     use subgraph_error as e;
 
     let error_id = hex::encode(&stable_hash::utils::stable_hash::<SetHasher, _>(&error));
@@ -488,6 +496,7 @@ fn insert_subgraph_error(conn: &PgConnection, error: &SubgraphError) -> anyhow::
             e::deterministic.eq(deterministic),
             e::block_hash.eq(block_ptr.as_ref().map(|ptr| ptr.hash_slice())),
             e::block_range.eq((Bound::Included(block_num), Bound::Unbounded)),
+            e::classification.eq(class.as_str()),
         ))
         .on_conflict_do_nothing()
         .execute(conn)?;
@@ -499,8 +508,9 @@ pub fn fail(
     conn: &PgConnection,
     id: &DeploymentHash,
     error: &SubgraphError,
+    class: ErrorClass,
 ) -> Result<(), StoreError> {
-    let error_id = insert_subgraph_error(conn, error)?;
+    let error_id = insert_subgraph_error(conn, error, class)?;
 
     update_deployment_status(conn, id, SubgraphHealth::Failed, Some(error_id))?;
 
@@ -583,7 +593,7 @@ pub(crate) fn insert_subgraph_errors(
     block: BlockNumber,
 ) -> Result<(), StoreError> {
     for error in deterministic_errors {
-        insert_subgraph_error(conn, error)?;
+        insert_subgraph_error(conn, error, ErrorClass::Deterministic)?;
     }
 
     check_health(conn, id, block)
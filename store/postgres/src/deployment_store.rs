@@ -26,12 +26,12 @@ use std::time::Instant;
 use graph::components::store::EntityCollection;
 use graph::components::subgraph::ProofOfIndexingFinisher;
 use graph::constraint_violation;
-use graph::data::subgraph::schema::{SubgraphError, POI_OBJECT};
+use graph::data::subgraph::schema::{ErrorClass, SubgraphError, POI_OBJECT};
 use graph::prelude::{
-    anyhow, debug, info, lazy_static, o, warn, web3, ApiSchema, AttributeNames, BlockNumber,
-    BlockPtr, CheapClone, DeploymentHash, DeploymentState, Entity, EntityKey, EntityModification,
-    EntityQuery, Error, Logger, QueryExecutionError, Schema, StopwatchMetrics, StoreError,
-    StoreEvent, Value, BLOCK_NUMBER_MAX,
+    anyhow, debug, info, lazy_static, o, serde_json, warn, web3, ApiSchema, AttributeNames,
+    BlockNumber, BlockPtr, CheapClone, DeploymentHash, DeploymentState, Entity, EntityKey,
+    EntityModification, EntityQuery, EntityRange, Error, Logger, QueryExecutionError, Schema,
+    StopwatchMetrics, StoreError, StoreEvent, Value, BLOCK_NUMBER_MAX,
 };
 use graph_graphql::prelude::api_schema;
 use web3::types::Address;
@@ -217,6 +217,14 @@ impl DeploymentStore {
         })
     }
 
+    /// Give the database role for `tenant` read access to the schema of
+    /// `site`, creating the role in this shard if it does not exist yet.
+    /// See the `tenant` module for how tenant isolation works
+    pub(crate) fn set_tenant_access(&self, site: &Site, tenant: &str) -> Result<(), StoreError> {
+        let conn = self.get_conn()?;
+        crate::tenant::grant_access(&conn, &site.namespace, tenant)
+    }
+
     pub(crate) fn load_deployment(
         &self,
         site: &Site,
@@ -254,6 +262,7 @@ impl DeploymentStore {
             query.range,
             query.block,
             query.query_id,
+            query.trace,
         )
     }
 
@@ -620,6 +629,16 @@ impl DeploymentStore {
         })
     }
 
+    pub(crate) fn deployment_summaries(
+        &self,
+        sites: &Vec<Arc<Site>>,
+    ) -> Result<Vec<status::Summary>, StoreError> {
+        let conn = self.get_conn()?;
+        conn.transaction(|| -> Result<Vec<status::Summary>, StoreError> {
+            detail::deployment_summaries(&conn, sites)
+        })
+    }
+
     pub(crate) fn deployment_exists_and_synced(
         &self,
         id: &DeploymentHash,
@@ -827,6 +846,18 @@ impl DeploymentStore {
         layout.find_many(&conn, ids_for_type, BLOCK_NUMBER_MAX)
     }
 
+    pub(crate) fn entity_ids(
+        &self,
+        site: Arc<Site>,
+        entity_type: &EntityType,
+        first: u32,
+    ) -> Result<Vec<String>, StoreError> {
+        let conn = self.get_conn()?;
+        let layout = self.layout(&conn, site)?;
+
+        layout.entity_ids(&conn, entity_type, first, BLOCK_NUMBER_MAX)
+    }
+
     // Only used by tests
     #[cfg(debug_assertions)]
     pub(crate) fn find(
@@ -838,6 +869,111 @@ impl DeploymentStore {
         self.execute_query(&conn, site, query)
     }
 
+    /// Stream all entities of `entity_type` that are live at `block` to
+    /// `sink` in batches of at most `batch_size` entities, ordered by id,
+    /// and return how many entities were streamed. Reading happens in one
+    /// query per batch so that arbitrarily large entity types can be
+    /// exported without materializing them in memory all at once
+    pub(crate) fn export_entities<F>(
+        &self,
+        site: Arc<Site>,
+        entity_type: &EntityType,
+        block: BlockNumber,
+        batch_size: u32,
+        mut sink: F,
+    ) -> Result<usize, StoreError>
+    where
+        F: FnMut(Vec<Entity>) -> Result<(), StoreError>,
+    {
+        let conn = self.get_conn()?;
+        let mut skip = 0;
+        let mut total = 0;
+        loop {
+            let query = EntityQuery::new(
+                site.deployment.clone(),
+                block,
+                EntityCollection::All(vec![(entity_type.clone(), AttributeNames::All)]),
+            )
+            .range(EntityRange {
+                first: Some(batch_size),
+                skip,
+            });
+            let batch: Vec<Entity> = self.execute_query(&conn, site.cheap_clone(), query)?;
+            let found = batch.len();
+            total += found;
+            skip += batch_size;
+            if found > 0 {
+                sink(batch)?;
+            }
+            if found < batch_size as usize {
+                return Ok(total);
+            }
+        }
+    }
+
+    /// Run the restricted read-only SQL query `sql` against the entity
+    /// tables of `site` on one of the replicas and return the rows as
+    /// JSON objects. See the `sql` module for the restrictions
+    pub(crate) fn execute_sql(
+        &self,
+        site: Arc<Site>,
+        sql: &str,
+    ) -> Result<Vec<serde_json::Value>, StoreError> {
+        let layout = self.find_layout(site)?;
+        let replica = self.replica_for_query(false)?;
+        let conn = self.get_replica_conn(replica)?;
+        crate::sql::execute(&conn, &layout, sql)
+    }
+
+    /// Insert `entities` into the deployment behind `site` with a block
+    /// range starting at `block` and update the entity count. This is
+    /// only used to fill a freshly created deployment with externally
+    /// fetched data; it must never run against a deployment that is
+    /// being indexed
+    pub(crate) fn import_entities(
+        &self,
+        site: Arc<Site>,
+        block: BlockNumber,
+        entities: Vec<(EntityType, Vec<Entity>)>,
+    ) -> Result<(), StoreError> {
+        let stopwatch = StopwatchMetrics::disabled(self.logger.clone());
+        let conn = self.get_conn()?;
+        let layout = self.layout(&conn, site.clone())?;
+        conn.transaction(|| -> Result<_, StoreError> {
+            let mut count = 0;
+            for (entity_type, entities) in entities {
+                let keyed: Vec<(EntityKey, Entity)> = entities
+                    .into_iter()
+                    .map(|entity| {
+                        let id = entity.id().map_err(|e| {
+                            StoreError::Unknown(anyhow!(
+                                "imported {} entity has no id: {}",
+                                entity_type,
+                                e
+                            ))
+                        })?;
+                        let key =
+                            EntityKey::data(site.deployment.clone(), entity_type.to_string(), id);
+                        Ok((key, entity))
+                    })
+                    .collect::<Result<_, StoreError>>()?;
+                let mut rows: Vec<(&EntityKey, Cow<Entity>)> = keyed
+                    .iter()
+                    .map(|(key, entity)| (key, Cow::Borrowed(entity)))
+                    .collect();
+                count +=
+                    layout.insert(&conn, &entity_type, rows.as_mut_slice(), block, &stopwatch)?
+                        as i32;
+            }
+            deployment::update_entity_count(
+                &conn,
+                site.as_ref(),
+                layout.count_query.as_str(),
+                count,
+            )
+        })
+    }
+
     pub(crate) fn transact_block_operations(
         &self,
         site: Arc<Site>,
@@ -1023,9 +1159,10 @@ impl DeploymentStore {
         &self,
         id: DeploymentHash,
         error: SubgraphError,
+        class: ErrorClass,
     ) -> Result<(), StoreError> {
         self.with_conn(move |conn, _| {
-            conn.transaction(|| deployment::fail(&conn, &id, &error))
+            conn.transaction(|| deployment::fail(&conn, &id, &error, class))
                 .map_err(Into::into)
         })
         .await?;
@@ -1334,6 +1471,94 @@ impl DeploymentStore {
         })
     }
 
+    /// Clear the fatal error of the deployment so that indexing resumes
+    /// at the block where it failed. With a `block` of `None`, refuses to
+    /// retry a deterministic failure since running the same mappings over
+    /// the same block would just fail again; passing the number of the
+    /// failed block forces the retry, e.g. after upgrading to a node that
+    /// fixes a mapping bug
+    pub(crate) fn retry_subgraph(
+        &self,
+        site: Arc<Site>,
+        block: Option<BlockNumber>,
+    ) -> Result<(), StoreError> {
+        let conn = &self.get_conn()?;
+        let deployment_id = &site.deployment;
+
+        conn.transaction(|| {
+            let subgraph_error = match detail::fatal_error(conn, deployment_id)? {
+                Some(fatal_error) => fatal_error,
+                None => {
+                    return Err(StoreError::Unknown(anyhow!(
+                        "subgraph {} has no fatal error to retry",
+                        deployment_id
+                    )))
+                }
+            };
+
+            let error_block = match subgraph_error.block_range {
+                (Bound::Included(number), _) => number,
+                _ => {
+                    return Err(constraint_violation!(
+                        "fatal error {} has no block number",
+                        subgraph_error.id
+                    ))
+                }
+            };
+
+            match block {
+                None if subgraph_error.deterministic => {
+                    return Err(StoreError::Unknown(anyhow!(
+                        "subgraph {} failed deterministically at block {} and \
+                         retrying would fail again; pass the failed block to \
+                         force the retry, e.g. after a node upgrade fixed the \
+                         failure",
+                        deployment_id,
+                        error_block
+                    )));
+                }
+                Some(block) if block != error_block => {
+                    return Err(StoreError::Unknown(anyhow!(
+                        "subgraph {} failed at block {}, not at block {}; \
+                         rewinding to a different block requires `graphman \
+                         rewind` with a block hash",
+                        deployment_id,
+                        error_block,
+                        block
+                    )));
+                }
+                // Eligible for a retry; the deployment head never advanced
+                // past the parent of the failed block, so clearing the
+                // error is all that is needed for indexing to resume at
+                // the failed block
+                None | Some(_) => {}
+            }
+
+            let latest = Self::block_ptr_with_conn(deployment_id, conn)?;
+            let health = if deployment::has_non_fatal_errors(
+                conn,
+                deployment_id,
+                latest.map(|ptr| ptr.number),
+            )? {
+                deployment::SubgraphHealth::Unhealthy
+            } else {
+                deployment::SubgraphHealth::Healthy
+            };
+            deployment::update_deployment_status(conn, deployment_id, health, None)?;
+            deployment::delete_error(conn, &subgraph_error.id)?;
+
+            info!(
+                self.logger,
+                "Cleared fatal error for retry";
+                "subgraph_id" => deployment_id,
+                "error_id" => &subgraph_error.id,
+                "block" => error_block,
+            );
+
+            Ok(())
+        })
+    }
+
     #[cfg(debug_assertions)]
     pub fn error_count(&self, id: &DeploymentHash) -> Result<usize, StoreError> {
         let conn = self.get_conn()?;
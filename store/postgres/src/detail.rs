@@ -79,6 +79,7 @@ pub(crate) struct ErrorDetail {
     handler: Option<String>,
     pub deterministic: bool,
     pub block_range: (Bound<i32>, Bound<i32>),
+    classification: String,
 }
 
 fn error(conn: &PgConnection, error_id: &str) -> Result<Option<ErrorDetail>, StoreError> {
@@ -243,6 +244,9 @@ impl<'a> TryFrom<DetailAndError<'a>> for status::Info {
             non_fatal_errors: vec![],
             chains: vec![chain],
             entity_count,
+            // This is filled in from the `copy_state` table by
+            // `deployment_statuses`
+            copy_progress: None,
             node: None,
         })
     }
@@ -274,13 +278,10 @@ pub(crate) fn deployment_statuses(
     use subgraph_error as e;
 
     // Empty deployments means 'all of them'
-    if sites.is_empty() {
+    let details = if sites.is_empty() {
         d::table
             .left_outer_join(e::table.on(d::fatal_error.eq(e::id.nullable())))
             .load::<(DeploymentDetail, Option<ErrorDetail>)>(conn)?
-            .into_iter()
-            .map(|(detail, error)| status::Info::try_from(DetailAndError(detail, error, sites)))
-            .collect()
     } else {
         let ids: Vec<_> = sites.into_iter().map(|site| site.id).collect();
 
@@ -288,10 +289,77 @@ pub(crate) fn deployment_statuses(
             .left_outer_join(e::table.on(d::fatal_error.eq(e::id.nullable())))
             .filter(d::id.eq_any(&ids))
             .load::<(DeploymentDetail, Option<ErrorDetail>)>(conn)?
-            .into_iter()
-            .map(|(detail, error)| status::Info::try_from(DetailAndError(detail, error, sites)))
-            .collect()
-    }
+    };
+    details
+        .into_iter()
+        .map(|(detail, error)| {
+            let mut info = status::Info::try_from(DetailAndError(detail, error, sites))?;
+            if let Some(site) = sites
+                .iter()
+                .find(|site| site.deployment.as_str() == info.subgraph)
+            {
+                info.copy_progress = crate::copy::status(conn, site.id)?;
+            }
+            Ok(info)
+        })
+        .collect()
+}
+
+/// Return summaries for the deployments behind `sites`, which must all
+/// live in the shard that `conn` is connected to. The size of a
+/// deployment whose namespace does not exist yet is reported as 0
+pub(crate) fn deployment_summaries(
+    conn: &PgConnection,
+    sites: &Vec<Arc<Site>>,
+) -> Result<Vec<status::Summary>, StoreError> {
+    use subgraph_deployment as d;
+
+    let ids: Vec<_> = sites.iter().map(|site| site.id).collect();
+    let details = d::table
+        .filter(d::id.eq_any(&ids))
+        .load::<DeploymentDetail>(conn)?;
+
+    let namespaces = sites
+        .iter()
+        .map(|site| site.namespace.to_string())
+        .collect();
+    let sizes = crate::catalog::namespace_sizes(conn, namespaces)?;
+
+    details
+        .into_iter()
+        .map(|detail| {
+            let site = sites
+                .iter()
+                .find(|site| site.deployment.as_str() == detail.deployment)
+                .ok_or_else(|| {
+                    constraint_violation!("missing site for subgraph `{}`", detail.deployment)
+                })?;
+            let latest_block = block(
+                &detail.deployment,
+                "latest_ethereum_block",
+                detail.latest_ethereum_block_hash,
+                detail.latest_ethereum_block_number,
+            )?
+            .map(|block| block.number());
+            let entity_count = detail.entity_count.to_u64().ok_or_else(|| {
+                constraint_violation!(
+                    "the entityCount for {} is not representable as a u64",
+                    detail.deployment
+                )
+            })?;
+            let size_bytes = sizes.get(site.namespace.as_str()).copied().unwrap_or(0);
+            Ok(status::Summary {
+                deployment: detail.deployment,
+                network: site.network.clone(),
+                shard: site.shard.to_string(),
+                synced: detail.synced,
+                health: detail.health.into(),
+                latest_block,
+                entity_count,
+                size_bytes,
+            })
+        })
+        .collect()
 }
 
 #[derive(Queryable, QueryableByName, Identifiable, Associations)]
@@ -10,6 +10,7 @@ use graph::env::env_var;
 use graph::prelude::{chrono, error, lazy_static, Logger, MetricsRegistry, StoreError};
 use graph::prometheus::Gauge;
 use graph::util::jobs::{Job, Runner};
+use graph::util::maintenance;
 
 use crate::connection_pool::ConnectionPool;
 use crate::{unused, Store, SubgraphStore};
@@ -19,6 +20,13 @@ lazy_static! {
         let interval: u32 = env_var("GRAPH_REMOVE_UNUSED_INTERVAL", 360);
         chrono::Duration::minutes(interval as i64)
     };
+
+    /// How often to sample how far each deployment is behind the head of
+    /// its chain for SLA reporting (in seconds)
+    static ref SLA_SAMPLE_INTERVAL: Duration = {
+        let interval: u64 = env_var("GRAPH_SLA_SAMPLE_INTERVAL", 60);
+        Duration::from_secs(interval)
+    };
 }
 
 pub fn register(
@@ -33,7 +41,7 @@ pub fn register(
     );
 
     runner.register(
-        Arc::new(NotificationQueueUsage::new(primary_pool, registry)),
+        Arc::new(NotificationQueueUsage::new(primary_pool.clone(), registry)),
         Duration::from_secs(60),
     );
 
@@ -42,6 +50,11 @@ pub fn register(
         Duration::from_secs(15 * 60),
     );
 
+    runner.register(
+        Arc::new(SlaSamplesJob::new(store.clone(), primary_pool)),
+        *SLA_SAMPLE_INTERVAL,
+    );
+
     // Remove unused deployments every 2 hours
     runner.register(
         Arc::new(UnusedJob::new(store.subgraph_store())),
@@ -156,6 +169,60 @@ impl Job for MirrorPrimary {
     }
 }
 
+/// A job that periodically samples how far each deployment is behind the
+/// head of its chain and folds the samples into the daily statistics in
+/// `sla_daily_stats` that back the `slaReport` query of the index node
+/// server
+struct SlaSamplesJob {
+    store: Arc<Store>,
+    primary: ConnectionPool,
+}
+
+impl SlaSamplesJob {
+    fn new(store: Arc<Store>, primary: ConnectionPool) -> SlaSamplesJob {
+        SlaSamplesJob { store, primary }
+    }
+
+    async fn sample(&self) -> Result<(), StoreError> {
+        use graph::components::store::StatusStore;
+        use graph::data::subgraph::status;
+
+        let infos = self.store.status(status::Filter::Deployments(vec![]))?;
+
+        self.primary
+            .with_conn(move |conn, _| {
+                let conn = crate::primary::Connection::new(conn);
+                for info in infos {
+                    let (head, latest) = match info.chains.first() {
+                        Some(chain) => {
+                            match (chain.chain_head_block.as_ref(), chain.latest_block.as_ref()) {
+                                (Some(head), Some(latest)) => (head.number(), latest.number()),
+                                _ => continue,
+                            }
+                        }
+                        None => continue,
+                    };
+                    conn.record_sla_sample(&info.subgraph, head - latest)?;
+                }
+                Ok(())
+            })
+            .await
+    }
+}
+
+#[async_trait]
+impl Job for SlaSamplesJob {
+    fn name(&self) -> &str {
+        "Sample deployment head lag for SLA reports"
+    }
+
+    async fn run(&self, logger: &Logger) {
+        if let Err(e) = self.sample().await {
+            error!(logger, "failed to record SLA samples"; "error" => e.to_string());
+        }
+    }
+}
+
 struct UnusedJob {
     store: Arc<SubgraphStore>,
 }
@@ -178,6 +245,15 @@ impl Job for UnusedJob {
         // Work on removing about 5 minutes
         const REMOVAL_DEADLINE: Duration = Duration::from_secs(5 * 60);
 
+        // During a maintenance window, low-priority deployments pause
+        // indexing and removals compete with much less other work, so
+        // spend longer on them
+        let deadline = if maintenance::in_window() {
+            6 * REMOVAL_DEADLINE
+        } else {
+            REMOVAL_DEADLINE
+        };
+
         let start = Instant::now();
 
         if let Err(e) = self.store.record_unused_deployments() {
@@ -208,7 +284,7 @@ impl Job for UnusedJob {
             }
             // Stop working on removing after a while to not block other
             // jobs for too long
-            if start.elapsed() > REMOVAL_DEADLINE {
+            if start.elapsed() > deadline {
                 return;
             }
         }
@@ -39,14 +39,18 @@ mod functions;
 mod jobs;
 mod jsonb;
 mod notification_listener;
+mod object_block_cache;
 mod primary;
+mod query_canceler;
 pub mod query_store;
 mod relational;
 mod relational_queries;
+mod sql;
 mod sql_value;
 mod store;
 mod store_events;
 mod subgraph_store;
+pub mod tenant;
 pub mod transaction_receipt;
 
 #[cfg(debug_assertions)]
@@ -4,8 +4,10 @@ use diesel::sql_types::Text;
 use graph::prelude::tokio::sync::mpsc::error::SendTimeoutError;
 use graph::util::backoff::ExponentialBackoff;
 use lazy_static::lazy_static;
+#[cfg(not(feature = "rustls-tls"))]
+use postgres::NoTls;
 use postgres::Notification;
-use postgres::{fallible_iterator::FallibleIterator, Client, NoTls};
+use postgres::{fallible_iterator::FallibleIterator, Client};
 use std::env;
 use std::str::FromStr;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -36,6 +38,18 @@ lazy_static! {
                 )))
             )
             .unwrap_or(Duration::from_secs(60));
+    /// How often to check that the `LISTEN` connection is still alive when
+    /// no notifications are arriving. A connection that dies without
+    /// producing an error would otherwise leave us waiting forever
+    static ref NOTIFICATION_KEEPALIVE_INTERVAL: Duration =
+        env::var("GRAPH_NOTIFICATION_KEEPALIVE_INTERVAL")
+            .ok()
+            .map(
+                |s| Duration::from_secs(u64::from_str(&s).unwrap_or_else(|_| panic!(
+                    "failed to parse env var GRAPH_NOTIFICATION_KEEPALIVE_INTERVAL"
+                )))
+            )
+            .unwrap_or(Duration::from_secs(30));
 }
 
 #[cfg(debug_assertions)]
@@ -46,6 +60,29 @@ lazy_static::lazy_static! {
     pub static ref EVENT_TAP: Mutex<Vec<StoreEvent>> = Mutex::new(Vec::new());
 }
 
+/// Open the client for the `LISTEN` connection. With the `rustls-tls`
+/// feature, the connection is encrypted with rustls when the connection
+/// URL asks for TLS through `sslmode`; the server certificate is checked
+/// against the Mozilla root store
+#[cfg(feature = "rustls-tls")]
+fn connect_client(postgres_url: &str) -> Result<Client, postgres::Error> {
+    let mut config = rustls::ClientConfig::new();
+    config
+        .root_store
+        .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+    Client::connect(
+        postgres_url,
+        tokio_postgres_rustls::MakeRustlsConnect::new(config),
+    )
+}
+
+/// Open the client for the `LISTEN` connection without TLS; the
+/// `rustls-tls` feature turns on TLS support
+#[cfg(not(feature = "rustls-tls"))]
+fn connect_client(postgres_url: &str) -> Result<Client, postgres::Error> {
+    Client::connect(postgres_url, NoTls)
+}
+
 #[derive(Clone)]
 /// This newtype exists to make it hard to misuse the `NotificationListener` API in a way that
 /// could impact security.
@@ -86,15 +123,18 @@ impl NotificationListener {
     /// The listener will handle dropping the database connection by
     /// indefinitely trying to reconnect to the database. Users of the
     /// listener have no way to find out whether the connection had been
-    /// dropped and was reestablished.
+    /// dropped and was reestablished, but the
+    /// `notification_queue_connected` gauge reflects the current health of
+    /// the connection.
     pub fn new(
         logger: &Logger,
         postgres_url: String,
         channel_name: SafeChannelName,
+        registry: Arc<dyn MetricsRegistry>,
     ) -> (Self, Receiver<JsonNotification>) {
         // Listen to Postgres notifications in a worker thread
         let (receiver, worker_handle, terminate_worker, worker_barrier) =
-            Self::listen(logger, postgres_url, channel_name);
+            Self::listen(logger, postgres_url, channel_name, registry);
 
         (
             NotificationListener {
@@ -120,6 +160,7 @@ impl NotificationListener {
         logger: &Logger,
         postgres_url: String,
         channel_name: SafeChannelName,
+        registry: Arc<dyn MetricsRegistry>,
     ) -> (
         Receiver<JsonNotification>,
         thread::JoinHandle<()>,
@@ -144,7 +185,7 @@ impl NotificationListener {
             let mut backoff =
                 ExponentialBackoff::new(Duration::from_secs(1), Duration::from_secs(30));
             loop {
-                let res = Client::connect(postgres_url, NoTls).and_then(|mut conn| {
+                let res = connect_client(postgres_url).and_then(|mut conn| {
                     conn.execute(format!("LISTEN {}", channel_name).as_str(), &[])?;
                     Ok(conn)
                 });
@@ -168,6 +209,18 @@ impl NotificationListener {
             "channel" => channel_name.0.clone()
         ));
 
+        let connected_gauge = registry
+            .global_gauge(
+                "notification_queue_connected",
+                "Whether the LISTEN connection for a channel is currently healthy",
+                {
+                    let mut labels = std::collections::HashMap::new();
+                    labels.insert("channel".to_owned(), channel_name.0.clone());
+                    labels
+                },
+            )
+            .expect("failed to create `notification_queue_connected` gauge");
+
         debug!(
             logger,
             "Cleaning up large notifications after about {}s",
@@ -194,8 +247,10 @@ impl NotificationListener {
                     &channel_name.0,
                     Some(barrier.as_ref()),
                 );
+                connected_gauge.set(1.0);
 
                 let mut max_queue_size_seen = 0;
+                let mut last_keepalive = Instant::now();
 
                 // Read notifications until the thread is to be terminated
                 while !terminate.load(Ordering::SeqCst) {
@@ -208,6 +263,8 @@ impl NotificationListener {
                         );
                         debug!(logger, "Reconnected notification listener");
                         connected = true;
+                        connected_gauge.set(1.0);
+                        last_keepalive = Instant::now();
                     }
 
                     let queue_size = conn.notifications().len();
@@ -245,6 +302,7 @@ impl NotificationListener {
                                     crit!(logger, "Error receiving message"; "error" => &msg);
                                 }
                                 connected = false;
+                                connected_gauge.set(0.0);
                                 None
                             }
                         })
@@ -299,6 +357,21 @@ impl NotificationListener {
                             }
                         }
                     }
+
+                    // If we haven't checked on the connection for a while,
+                    // send a keep-alive ping. A connection that dies
+                    // without surfacing an error in `timeout_iter` would
+                    // otherwise leave us waiting for notifications that
+                    // will never arrive
+                    if connected && last_keepalive.elapsed() >= *NOTIFICATION_KEEPALIVE_INTERVAL {
+                        if let Err(e) = conn.is_valid(Duration::from_secs(5)) {
+                            crit!(logger, "Notification listener connection is dead, reconnecting";
+                                          "error" => format!("{}", e));
+                            connected = false;
+                            connected_gauge.set(0.0);
+                        }
+                        last_keepalive = Instant::now();
+                    }
                 }
             }))
             .unwrap_or_else(|_| std::process::exit(1))
@@ -0,0 +1,194 @@
+//! An object storage tier for the block cache. Long-running archival
+//! indexers accumulate millions of cached blocks in Postgres; with this
+//! tier, `GRAPH_ETHEREUM_CLEANUP_BLOCKS` spills old blocks to an
+//! S3-compatible bucket instead of throwing them away, and ancestor
+//! lookups transparently fall back to the bucket when a block is no
+//! longer in the database. A small in-process LRU keeps recently fetched
+//! blocks hot so that repeated walks over the same ancestry do not hit
+//! the network.
+//!
+//! The tier is turned on by setting `GRAPH_BLOCK_CACHE_BUCKET` to
+//! `s3://bucket` or `s3://bucket/prefix`. Credentials come from the
+//! usual `AWS_*` environment variables; S3-compatible stores, including
+//! GCS through its interoperability endpoint
+//! `https://storage.googleapis.com`, are addressed by setting
+//! `GRAPH_BLOCK_CACHE_ENDPOINT`. `GRAPH_BLOCK_CACHE_HOT_BLOCKS` sizes
+//! the LRU and defaults to 1000 blocks.
+
+use std::env;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Mutex;
+use std::thread;
+
+use graph::prelude::{
+    anyhow::{anyhow, bail, Error},
+    serde_json as json,
+};
+use lru_time_cache::LruCache;
+use s3::{bucket::Bucket, creds::Credentials, region::Region};
+
+/// How many blocks the in-process LRU holds by default
+const DEFAULT_HOT_BLOCKS: usize = 1000;
+
+/// A request to the thread that talks to the bucket. The `rust-s3`
+/// blocking calls run a `block_on` internally and panic when they are
+/// made from a tokio worker thread, which is where the `ChainStore`
+/// methods using this cache execute. All bucket traffic therefore goes
+/// through a dedicated plain thread, and callers wait for the reply on
+/// the channel they pass in
+enum Request {
+    Get(String, Sender<Result<(Vec<u8>, u16), Error>>),
+    Put(String, Vec<u8>, Sender<Result<u16, Error>>),
+}
+
+pub struct ObjectBlockCache {
+    sender: Mutex<Sender<Request>>,
+    prefix: String,
+    chain: String,
+    hot: Mutex<LruCache<String, json::Value>>,
+}
+
+/// Start the thread that performs all bucket operations and hand back
+/// the channel for talking to it. The thread exits when the cache, and
+/// with it the sending side of the channel, is dropped
+fn start_worker(chain: &str, bucket: Bucket) -> Sender<Request> {
+    let (sender, receiver) = channel::<Request>();
+    thread::Builder::new()
+        .name(format!("blk-cache-{}", chain))
+        .spawn(move || {
+            for request in receiver {
+                match request {
+                    Request::Get(key, reply) => {
+                        let result = bucket.get_object_blocking(key).map_err(Error::from);
+                        // The caller may have gone away; nothing to do then
+                        let _ = reply.send(result);
+                    }
+                    Request::Put(key, data, reply) => {
+                        let result = bucket
+                            .put_object_blocking(key, &data)
+                            .map(|(_, code)| code)
+                            .map_err(Error::from);
+                        let _ = reply.send(result);
+                    }
+                }
+            }
+        })
+        .expect("can spawn the block cache thread");
+    sender
+}
+
+impl ObjectBlockCache {
+    /// The cache for `chain` if `GRAPH_BLOCK_CACHE_BUCKET` is set,
+    /// `None` otherwise
+    pub fn for_chain(chain: &str) -> Result<Option<Self>, Error> {
+        let url = match env::var("GRAPH_BLOCK_CACHE_BUCKET") {
+            Ok(url) => url,
+            Err(_) => return Ok(None),
+        };
+        let rest = url.strip_prefix("s3://").ok_or_else(|| {
+            anyhow!(
+                "GRAPH_BLOCK_CACHE_BUCKET must have the form `s3://bucket[/prefix]`, not `{}`",
+                url
+            )
+        })?;
+        let (bucket_name, prefix) = match rest.split_once('/') {
+            Some((bucket, prefix)) => (bucket, prefix.trim_end_matches('/')),
+            None => (rest, ""),
+        };
+        if bucket_name.is_empty() {
+            bail!("GRAPH_BLOCK_CACHE_BUCKET `{}` does not name a bucket", url);
+        }
+        let region = env::var("AWS_REGION").unwrap_or_else(|_| "us-east-1".to_string());
+        let region = match env::var("GRAPH_BLOCK_CACHE_ENDPOINT") {
+            Ok(endpoint) => Region::Custom { region, endpoint },
+            Err(_) => region.parse()?,
+        };
+        let bucket = Bucket::new(bucket_name, region, Credentials::default()?)?;
+        let hot_blocks = match env::var("GRAPH_BLOCK_CACHE_HOT_BLOCKS") {
+            Ok(size) => size.parse().map_err(|_| {
+                anyhow!(
+                    "GRAPH_BLOCK_CACHE_HOT_BLOCKS must be a number, not `{}`",
+                    size
+                )
+            })?,
+            Err(_) => DEFAULT_HOT_BLOCKS,
+        };
+        Ok(Some(ObjectBlockCache {
+            sender: Mutex::new(start_worker(chain, bucket)),
+            prefix: prefix.to_string(),
+            chain: chain.to_string(),
+            hot: Mutex::new(LruCache::with_capacity(hot_blocks)),
+        }))
+    }
+
+    /// Send `request` to the bucket thread. The thread only dies when it
+    /// panics, which makes both channels report a disconnect
+    fn request<T>(
+        &self,
+        request: Request,
+        response: std::sync::mpsc::Receiver<Result<T, Error>>,
+    ) -> Result<T, Error> {
+        self.sender
+            .lock()
+            .unwrap()
+            .send(request)
+            .map_err(|_| anyhow!("the block cache thread for `{}` died", self.chain))?;
+        response
+            .recv()
+            .map_err(|_| anyhow!("the block cache thread for `{}` died", self.chain))?
+    }
+
+    /// The object key for the block with the given hex hash (without the
+    /// `0x` prefix)
+    fn key(&self, hash: &str) -> String {
+        if self.prefix.is_empty() {
+            format!("{}/{}.json", self.chain, hash)
+        } else {
+            format!("{}/{}/{}.json", self.prefix, self.chain, hash)
+        }
+    }
+
+    /// Look the block with the given hex hash up, first in the LRU, then
+    /// in the bucket
+    pub fn get(&self, hash: &str) -> Result<Option<json::Value>, Error> {
+        if let Some(data) = self.hot.lock().unwrap().get(hash) {
+            return Ok(Some(data.clone()));
+        }
+        let (reply, response) = channel();
+        let (bytes, code) = self.request(Request::Get(self.key(hash), reply), response)?;
+        match code {
+            200 => {
+                let data: json::Value = json::from_slice(&bytes)?;
+                self.hot
+                    .lock()
+                    .unwrap()
+                    .insert(hash.to_string(), data.clone());
+                Ok(Some(data))
+            }
+            404 => Ok(None),
+            code => Err(anyhow!(
+                "fetching block `{}` from the block cache bucket failed with HTTP status {}",
+                hash,
+                code
+            )),
+        }
+    }
+
+    /// Store the block with the given hex hash in the bucket. Spilled
+    /// blocks are not added to the LRU since they are cold by definition
+    pub fn put(&self, hash: &str, data: &json::Value) -> Result<(), Error> {
+        let (reply, response) = channel();
+        let code = self.request(
+            Request::Put(self.key(hash), json::to_vec(data)?, reply),
+            response,
+        )?;
+        if code != 200 {
+            bail!(
+                "spilling block `{}` to the block cache bucket failed with HTTP status {}",
+                hash,
+                code
+            );
+        }
+        Ok(())
+    }
+}
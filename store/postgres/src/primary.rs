@@ -57,6 +57,32 @@ use crate::{
 
 #[cfg(debug_assertions)]
 use std::sync::Mutex;
+lazy_static::lazy_static! {
+    /// A fixed value for the `created_at` timestamp of new `subgraph` and
+    /// `subgraph_version` entries. Indexers that want registry metadata to
+    /// be byte-identical across nodes, e.g. to diff their databases, can
+    /// pin the timestamp with this variable; by default we use wall-clock
+    /// time
+    static ref METADATA_CREATED_AT: Option<u64> =
+        std::env::var("GRAPH_METADATA_CREATED_AT").ok().map(|s| {
+            s.parse()
+                .expect("GRAPH_METADATA_CREATED_AT must be a Unix timestamp")
+        });
+}
+
+/// The `created_at` timestamp for new registry entries. The timestamp
+/// records when this node first saw a subgraph or version; it is purely
+/// informational and never enters proofs of indexing or any other
+/// deterministic digest
+fn created_at() -> u64 {
+    METADATA_CREATED_AT.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    })
+}
+
 #[cfg(debug_assertions)]
 lazy_static::lazy_static! {
     /// Tests set this to true so that `send_store_event` will store a copy
@@ -65,6 +91,11 @@ lazy_static::lazy_static! {
     pub static ref EVENT_TAP: Mutex<Vec<StoreEvent>> = Mutex::new(Vec::new());
 }
 
+// Note that `created_at` in both the `subgraph` and `subgraph_version`
+// tables is wall-clock time on the node that first saw the subgraph; it is
+// informational only and will differ between indexers. It must never feed
+// into proofs of indexing or any other deterministic digest. See
+// `created_at()` for how to pin it
 table! {
     subgraphs.subgraph (vid) {
         vid -> BigInt,
@@ -138,6 +169,10 @@ table! {
         /// only one of them will be active. That's the one we use for
         /// querying
         active -> Bool,
+        /// The tenant owning the deployment when tenant namespaces are in
+        /// use; read access to the schema is limited to the tenant's
+        /// database role
+        tenant -> Nullable<Text>,
     }
 }
 
@@ -171,6 +206,48 @@ table! {
     }
 }
 
+table! {
+    /// Per-deployment overrides for global tunables. The recognized
+    /// names and their meaning are listed in `graph::settings`
+    public.subgraph_settings(deployment, name) {
+        deployment -> Text,
+        name -> Text,
+        value -> Text,
+    }
+}
+
+table! {
+    /// Daily head-lag distribution per deployment, fed by the SLA
+    /// sampling job and served through the `slaReport` query of the
+    /// index node server
+    public.sla_daily_stats(deployment, day) {
+        deployment -> Text,
+        day -> Date,
+        samples -> Integer,
+        /// Element i (arrays are 1-based) counts samples where the
+        /// deployment was i - 1 blocks behind the chain head; the last
+        /// element counts samples that were more than 10 blocks behind
+        behind_counts -> Array<Integer>,
+        max_behind -> Integer,
+        sum_behind -> BigInt,
+    }
+}
+
+/// The number of entries in `sla_daily_stats.behind_counts`: one bucket
+/// for each lag from 0 to 10 blocks, and one for everything above that
+pub(crate) const SLA_BUCKETS: usize = 12;
+
+/// One day of head-lag statistics for a deployment, as stored in
+/// `sla_daily_stats`
+pub struct SlaDailyStats {
+    /// The day the row covers, as `YYYY-MM-DD`
+    pub day: String,
+    pub samples: i32,
+    pub behind_counts: Vec<i32>,
+    pub max_behind: i32,
+    pub sum_behind: i64,
+}
+
 table! {
     public.db_version(version) {
         #[sql_name = "db_version"]
@@ -203,6 +280,8 @@ struct Schema {
     version: DeploymentSchemaVersion,
     pub network: String,
     pub(crate) active: bool,
+    #[allow(dead_code)]
+    pub(crate) tenant: Option<String>,
 }
 
 #[derive(Clone, Queryable, QueryableByName, Debug)]
@@ -806,10 +885,7 @@ impl<'a> Connection<'a> {
 
         let conn = self.conn.as_ref();
         let id = generate_entity_id();
-        let created_at = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        let created_at = created_at();
         let inserted = insert_into(s::table)
             .values((
                 s::id.eq(&id),
@@ -850,10 +926,7 @@ impl<'a> Connection<'a> {
 
         let conn = self.conn.as_ref();
 
-        let created_at = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        let created_at = created_at();
 
         // Check the current state of the the subgraph. If no subgraph with the
         // name exists, create one
@@ -974,6 +1047,87 @@ impl<'a> Connection<'a> {
         }
     }
 
+    /// Rename the subgraph `old` to `new`. If `alias_old` is set, also
+    /// create an alias under the old name so that existing consumers keep
+    /// resolving; the alias points at the versions that are current and
+    /// pending at the time of the rename. It is an error if no subgraph
+    /// `old` exists or if the name `new` is already taken
+    pub fn rename_subgraph(
+        &self,
+        old: &SubgraphName,
+        new: &SubgraphName,
+        alias_old: bool,
+    ) -> Result<(), StoreError> {
+        use subgraph as s;
+
+        let conn = self.conn.as_ref();
+
+        if queries::subgraph_exists(conn, new)? {
+            return Err(StoreError::Unknown(anyhow!(
+                "the subgraph name `{}` is already in use",
+                new
+            )));
+        }
+        let renamed = update(s::table.filter(s::name.eq(old.as_str())))
+            .set(s::name.eq(new.as_str()))
+            .execute(conn)?;
+        if renamed == 0 {
+            return Err(StoreError::DeploymentNotFound(old.to_string()));
+        }
+        if alias_old {
+            self.alias_subgraph(new, old)?;
+        }
+        Ok(())
+    }
+
+    /// Make `alias` another name for the subgraph `target` by creating a
+    /// subgraph entry that points at the versions that are current and
+    /// pending for `target` right now. The alias resolves like any other
+    /// name, but deploying a new version of `target` later will not move
+    /// the alias along. It is an error if no subgraph `target` exists or if
+    /// the name `alias` is already taken
+    pub fn alias_subgraph(
+        &self,
+        target: &SubgraphName,
+        alias: &SubgraphName,
+    ) -> Result<(), StoreError> {
+        use subgraph as s;
+
+        let conn = self.conn.as_ref();
+
+        let versions = s::table
+            .filter(s::name.eq(target.as_str()))
+            .select((s::current_version, s::pending_version))
+            .first::<(Option<String>, Option<String>)>(conn)
+            .optional()?;
+        let (current_version, pending_version) = match versions {
+            Some(versions) => versions,
+            None => return Err(StoreError::DeploymentNotFound(target.to_string())),
+        };
+
+        let created_at = created_at();
+        let inserted = insert_into(s::table)
+            .values((
+                s::id.eq(generate_entity_id()),
+                s::name.eq(alias.as_str()),
+                s::current_version.eq(current_version),
+                s::pending_version.eq(pending_version),
+                // using BigDecimal::from(created_at) produced a scale error
+                s::created_at.eq(sql(&format!("{}", created_at))),
+                s::block_range.eq(UNVERSIONED_RANGE),
+            ))
+            .on_conflict(s::name)
+            .do_nothing()
+            .execute(conn)?;
+        if inserted == 0 {
+            return Err(StoreError::Unknown(anyhow!(
+                "the subgraph name `{}` is already in use",
+                alias
+            )));
+        }
+        Ok(())
+    }
+
     pub fn reassign_subgraph(
         &self,
         site: &Site,
@@ -1113,6 +1267,16 @@ impl<'a> Connection<'a> {
         self.create_site(shard, src.deployment.clone(), src.network.clone(), false)
     }
 
+    /// Record that the deployment behind `site` belongs to `tenant`
+    pub fn set_tenant(&self, site: &Site, tenant: &str) -> Result<(), StoreError> {
+        use deployment_schemas as ds;
+
+        update(ds::table.filter(ds::id.eq(site.id)))
+            .set(ds::tenant.eq(tenant))
+            .execute(self.conn.as_ref())?;
+        Ok(())
+    }
+
     pub(crate) fn activate(&self, deployment: &DeploymentLocator) -> Result<(), StoreError> {
         use deployment_schemas as ds;
 
@@ -1485,6 +1649,114 @@ impl<'a> Connection<'a> {
             .map_err(|e| anyhow!("error looking up ens_name for hash {}: {}", hash, e).into())
     }
 
+    /// Persist the per-deployment override `name`, or clear it when
+    /// `value` is empty
+    pub fn set_deployment_setting(
+        &self,
+        deployment: &DeploymentHash,
+        name: &str,
+        value: &str,
+    ) -> Result<(), StoreError> {
+        use subgraph_settings as s;
+
+        if value.is_empty() {
+            delete(
+                s::table
+                    .filter(s::deployment.eq(deployment.as_str()))
+                    .filter(s::name.eq(name)),
+            )
+            .execute(self.conn.as_ref())?;
+        } else {
+            insert_into(s::table)
+                .values((
+                    s::deployment.eq(deployment.as_str()),
+                    s::name.eq(name),
+                    s::value.eq(value),
+                ))
+                .on_conflict((s::deployment, s::name))
+                .do_update()
+                .set(s::value.eq(value))
+                .execute(self.conn.as_ref())?;
+        }
+        Ok(())
+    }
+
+    /// All per-deployment setting overrides as
+    /// `(deployment, name, value)` triples
+    pub fn deployment_settings(&self) -> Result<Vec<(String, String, String)>, StoreError> {
+        use subgraph_settings as s;
+
+        Ok(s::table
+            .select((s::deployment, s::name, s::value))
+            .load(self.conn.as_ref())?)
+    }
+
+    /// Record one head-lag sample for `deployment` in today's row of
+    /// `sla_daily_stats`. `behind` is how many blocks the deployment was
+    /// behind the chain head when the sample was taken
+    pub fn record_sla_sample(&self, deployment: &str, behind: i32) -> Result<(), StoreError> {
+        const QUERY: &str = "\
+            insert into public.sla_daily_stats
+                        (deployment, day, samples, behind_counts, max_behind, sum_behind)
+                 values ($1, current_date, 1, $2, $3, $3)
+            on conflict (deployment, day) do update
+                    set samples = sla_daily_stats.samples + 1,
+                        behind_counts = (select array_agg(o + n)
+                                           from unnest(sla_daily_stats.behind_counts,
+                                                       excluded.behind_counts) as u(o, n)),
+                        max_behind = greatest(sla_daily_stats.max_behind, excluded.max_behind),
+                        sum_behind = sla_daily_stats.sum_behind + excluded.sum_behind";
+
+        let behind = behind.max(0);
+        let mut counts = vec![0; SLA_BUCKETS];
+        counts[(behind as usize).min(SLA_BUCKETS - 1)] = 1;
+
+        diesel::sql_query(QUERY)
+            .bind::<Text, _>(deployment)
+            .bind::<Array<Integer>, _>(counts)
+            .bind::<Integer, _>(behind)
+            .execute(self.conn.as_ref())?;
+        Ok(())
+    }
+
+    /// The stored daily head-lag statistics for `deployment` over the
+    /// last `days` days, including today, in ascending order by day
+    pub fn sla_daily_stats(
+        &self,
+        deployment: &str,
+        days: i32,
+    ) -> Result<Vec<SlaDailyStats>, StoreError> {
+        use diesel::sql_types::Bool;
+        use sla_daily_stats as s;
+
+        Ok(s::table
+            .filter(s::deployment.eq(deployment))
+            .filter(sql::<Bool>(&format!(
+                "day > current_date - {}",
+                days.max(1)
+            )))
+            .order_by(s::day.asc())
+            .select((
+                sql::<Text>("day::text"),
+                s::samples,
+                s::behind_counts,
+                s::max_behind,
+                s::sum_behind,
+            ))
+            .load::<(String, i32, Vec<i32>, i32, i64)>(self.conn.as_ref())?
+            .into_iter()
+            .map(
+                |(day, samples, behind_counts, max_behind, sum_behind)| SlaDailyStats {
+                    day,
+                    samples,
+                    behind_counts,
+                    max_behind,
+                    sum_behind,
+                },
+            )
+            .collect())
+    }
+
     pub fn record_active_copy(&self, src: &Site, dst: &Site) -> Result<(), StoreError> {
         use active_copies as cp;
 
@@ -0,0 +1,146 @@
+//! Cancel SQL statements in Postgres when the client that caused them
+//! goes away.
+//!
+//! When an HTTP client disconnects while a GraphQL query is running, the
+//! server drops the response future, but any SQL statement that the query
+//! is running at that point keeps going in Postgres until it finishes or
+//! hits the statement timeout. The watcher in this module knows the
+//! backend pid for every statement run on behalf of a cancelable query;
+//! when the cancel handle for such a statement fires, it asks Postgres to
+//! stop the statement with `pg_cancel_backend` from another connection,
+//! since the connection running the statement is busy.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use diesel::pg::PgConnection;
+use diesel::sql_types::Integer;
+use diesel::RunQueryDsl;
+
+use graph::prelude::{lazy_static, CancelHandle, CancelToken as _, CheapClone, StoreError};
+
+use crate::deployment_store::{DeploymentStore, ReplicaId};
+
+/// How often the watcher checks whether any cancel handles fired
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+lazy_static! {
+    static ref WATCHER: Watcher = Watcher::start();
+}
+
+/// A statement the watcher is keeping an eye on
+struct Entry {
+    /// The pid of the Postgres backend running the statement
+    pid: i32,
+    cancel: CancelHandle,
+    /// Set when the statement finished on its own
+    done: Arc<AtomicBool>,
+    /// Where to get a connection for `pg_cancel_backend`; it must go to
+    /// the same server as the connection running the statement
+    store: Arc<DeploymentStore>,
+    replica: ReplicaId,
+}
+
+impl Entry {
+    fn cancel_backend(&self) {
+        // The statement might have finished since we looked at the entry
+        if self.done.load(Ordering::SeqCst) {
+            return;
+        }
+        // There is nothing useful we can do when this fails; the
+        // statement then simply runs to completion as it would have
+        // without this module
+        if let Ok(conn) = self.store.get_replica_conn(self.replica) {
+            let _ = diesel::sql_query("select pg_cancel_backend($1)")
+                .bind::<Integer, _>(self.pid)
+                .execute(&conn);
+        }
+    }
+}
+
+struct Watcher {
+    entries: Arc<Mutex<Vec<Entry>>>,
+}
+
+impl Watcher {
+    /// Spawn a thread that periodically splits the watched entries into
+    /// finished ones, which are forgotten, canceled ones, which get their
+    /// backend canceled, and running ones, which stay under watch
+    fn start() -> Self {
+        let entries: Arc<Mutex<Vec<Entry>>> = Arc::new(Mutex::new(Vec::new()));
+        let watched = entries.cheap_clone();
+        thread::spawn(move || loop {
+            thread::sleep(POLL_INTERVAL);
+            let canceled: Vec<Entry> = {
+                let mut entries = watched.lock().unwrap();
+                entries.retain(|entry| !entry.done.load(Ordering::SeqCst));
+                let (canceled, running): (Vec<_>, Vec<_>) = entries
+                    .drain(..)
+                    .partition(|entry| entry.cancel.is_canceled());
+                *entries = running;
+                canceled
+            };
+            for entry in canceled {
+                entry.cancel_backend();
+            }
+        });
+        Watcher { entries }
+    }
+}
+
+/// The pid of the Postgres backend for `conn`. Canceling a statement
+/// running on `conn` requires telling Postgres the pid from another
+/// connection
+pub(crate) fn backend_pid(conn: &PgConnection) -> Result<i32, StoreError> {
+    #[derive(QueryableByName)]
+    struct BackendPid {
+        #[sql_type = "Integer"]
+        pid: i32,
+    }
+
+    let pid = diesel::sql_query("select pg_backend_pid() as pid")
+        .get_result::<BackendPid>(conn)?
+        .pid;
+    Ok(pid)
+}
+
+/// Guards one SQL statement run on behalf of a cancelable query. While
+/// the guard is live, the watcher cancels the backend with `pid` as soon
+/// as `cancel` fires; dropping the guard tells the watcher that the
+/// statement finished.
+///
+/// Since `pg_cancel_backend` only cancels whatever the backend is doing
+/// at that moment, the worst a stale cancelation can do is make an
+/// unrelated statement on a reused connection fail with 'canceling
+/// statement due to user request'. The `done` flag makes that window very
+/// small
+pub(crate) struct CancelableStatement {
+    done: Arc<AtomicBool>,
+}
+
+impl CancelableStatement {
+    pub(crate) fn register(
+        pid: i32,
+        store: Arc<DeploymentStore>,
+        replica: ReplicaId,
+        cancel: CancelHandle,
+    ) -> Self {
+        let done = Arc::new(AtomicBool::new(false));
+        WATCHER.entries.lock().unwrap().push(Entry {
+            pid,
+            cancel,
+            done: done.cheap_clone(),
+            store,
+            replica,
+        });
+        CancelableStatement { done }
+    }
+}
+
+impl Drop for CancelableStatement {
+    fn drop(&mut self) {
+        self.done.store(true, Ordering::SeqCst);
+    }
+}
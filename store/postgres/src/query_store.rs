@@ -1,5 +1,3 @@
-use std::collections::BTreeMap;
-
 use web3::types::H256;
 
 use crate::deployment_store::{DeploymentStore, ReplicaId};
@@ -35,13 +33,32 @@ impl QueryStore {
 impl QueryStoreTrait for QueryStore {
     fn find_query_values(
         &self,
-        query: EntityQuery,
-    ) -> Result<Vec<BTreeMap<String, r::Value>>, QueryExecutionError> {
+        mut query: EntityQuery,
+    ) -> Result<Vec<r::Object>, QueryExecutionError> {
         assert_eq!(&self.site.deployment, &query.subgraph_id);
         let conn = self
             .store
             .get_replica_conn(self.replica_id)
             .map_err(|e| QueryExecutionError::StoreError(e.into()))?;
+        // When the query carries a cancel handle, have the watcher cancel
+        // the statement in Postgres if the client goes away while the
+        // statement runs. The guard must stay alive until `execute_query`
+        // returns
+        let _statement = match query.cancel.take() {
+            Some(cancel) => {
+                cancel
+                    .check_cancel()
+                    .map_err(|_| QueryExecutionError::from(StoreError::Canceled))?;
+                let pid = crate::query_canceler::backend_pid(&conn)?;
+                Some(crate::query_canceler::CancelableStatement::register(
+                    pid,
+                    self.store.cheap_clone(),
+                    self.replica_id,
+                    cancel,
+                ))
+            }
+            None => None,
+        };
         self.store.execute_query(&conn, self.site.clone(), query)
     }
 
@@ -25,23 +25,25 @@ use std::time::{Duration, Instant};
 use crate::{
     primary::{Namespace, Site},
     relational_queries::{
-        ClampRangeQuery, ConflictingEntityQuery, EntityData, FilterCollection, FilterQuery,
-        FindManyQuery, FindQuery, InsertQuery, RevertClampQuery, RevertRemoveQuery,
+        ClampRangeQuery, ConflictingEntityQuery, EntityData, EntityIdsQuery, FilterCollection,
+        FilterQuery, FindManyQuery, FindQuery, InsertQuery, RevertClampQuery, RevertRemoveQuery,
     },
 };
 use graph::components::store::EntityType;
-use graph::data::graphql::ext::{DirectiveFinder, DocumentExt, ObjectTypeExt};
+use graph::constraint_violation;
+use graph::data::graphql::ext::{DirectiveExt, DirectiveFinder, DocumentExt, ObjectTypeExt};
 use graph::data::schema::{FulltextConfig, FulltextDefinition, Schema, SCHEMA_TYPE_NAME};
 use graph::data::store::BYTES_SCALAR;
 use graph::data::subgraph::schema::{POI_OBJECT, POI_TABLE};
 use graph::prelude::{
     anyhow, info, BlockNumber, DeploymentHash, Entity, EntityChange, EntityCollection,
     EntityFilter, EntityKey, EntityOrder, EntityRange, Logger, QueryExecutionError, StoreError,
-    StoreEvent, ValueType, BLOCK_NUMBER_MAX,
+    StoreEvent, Trace, ValueType, BLOCK_NUMBER_MAX,
 };
 
 use crate::block_range::BLOCK_RANGE_COLUMN;
 pub use crate::catalog::Catalog;
+use crate::catalog::TableStats;
 use crate::connection_pool::ForeignServer;
 use crate::{catalog, deployment};
 
@@ -89,6 +91,24 @@ lazy_static! {
             })
         }).map(|timeout| format!("set local statement_timeout={}", timeout * 1000))
     };
+
+    /// `GRAPH_STORE_HISTORY_TOAST_TUPLE_TARGET` makes Postgres move entity
+    /// versions whose row is bigger than this many bytes out of line and
+    /// compress them. The Postgres default is 2040 bytes; setting this much
+    /// lower, e.g. to 256, compresses the bulk of historical versions,
+    /// which are rarely read but dominate disk usage, at a small cost when
+    /// they do get read for time-travel queries. This only affects newly
+    /// created tables; for existing deployments, use
+    /// 'graphman stats compress'
+    static ref HISTORY_TOAST_TUPLE_TARGET: Option<usize> = {
+        env::var("GRAPH_STORE_HISTORY_TOAST_TUPLE_TARGET")
+        .ok()
+        .map(|s| {
+            usize::from_str(&s).unwrap_or_else(|_| {
+                panic!("GRAPH_STORE_HISTORY_TOAST_TUPLE_TARGET must be a number, but is `{}`", s)
+            })
+        })
+    };
 }
 
 /// A string we use as a SQL name for a table or column. The important thing
@@ -381,6 +401,8 @@ impl Layout {
                     is_reference: false,
                 },
             ],
+            indexes: vec![],
+            planner_hints: vec![],
             /// The position of this table in all the tables for this layout; this
             /// is really only needed for the tests to make the names of indexes
             /// predictable
@@ -561,6 +583,23 @@ impl Layout {
         Ok(entities_for_type)
     }
 
+    /// Return the ids of up to `first` entities of the given type that are
+    /// live at `block`, in ascending order of id
+    pub fn entity_ids(
+        &self,
+        conn: &PgConnection,
+        entity_type: &EntityType,
+        first: u32,
+        block: BlockNumber,
+    ) -> Result<Vec<String>, StoreError> {
+        let table = self.table_for_entity(entity_type)?;
+        Ok(EntityIdsQuery::new(table.as_ref(), first as i32, block)
+            .load(conn)?
+            .into_iter()
+            .map(|data| data.id)
+            .collect())
+    }
+
     pub fn insert<'a>(
         &'a self,
         conn: &PgConnection,
@@ -608,6 +647,7 @@ impl Layout {
         range: EntityRange,
         block: BlockNumber,
         query_id: Option<String>,
+        trace: Option<Arc<Trace>>,
     ) -> Result<Vec<T>, QueryExecutionError> {
         fn log_query_timing(
             logger: &Logger,
@@ -650,12 +690,19 @@ impl Layout {
         )?;
         let query_clone = query.clone();
 
+        // Planner hints for the tables the query touches; like the
+        // statement timeout, they are local to the transaction
+        let hints = filter_collection.planner_hints();
+
         let start = Instant::now();
         let values = conn
             .transaction(|| {
                 if let Some(ref timeout_sql) = *STATEMENT_TIMEOUT {
                     conn.batch_execute(timeout_sql)?;
                 }
+                for hint in &hints {
+                    conn.batch_execute(hint)?;
+                }
                 query.load::<EntityData>(conn)
             })
             .map_err(|e| match e {
@@ -671,7 +718,11 @@ impl Layout {
                     debug_query(&query_clone).to_string()
                 )),
             })?;
-        log_query_timing(logger, &query_clone, start.elapsed(), values.len());
+        let elapsed = start.elapsed();
+        log_query_timing(logger, &query_clone, elapsed, values.len());
+        if let Some(trace) = trace {
+            trace.push(debug_query(&query_clone).to_string(), elapsed, values.len());
+        }
         values
             .into_iter()
             .map(|entity_data| {
@@ -1114,6 +1165,99 @@ impl Column {
     }
 }
 
+/// Tables with at least this many rows get planner hints when their
+/// statistics suggest that the Postgres planner might pick a bad plan;
+/// see `planner_hints`
+const HUGE_TABLE_ROWS: f64 = 1_000_000.0;
+
+/// Columns with at most this many distinct values count as low-cardinality
+const LOW_CARDINALITY: f64 = 100.0;
+
+/// Derive `set local` planner hints from the statistics for a table. For
+/// huge tables with low-cardinality columns, the planner misestimates the
+/// selectivity of combining a filter on such a column with the block
+/// range constraint and falls back to scanning the whole table. Steering
+/// it away from sequential scans makes it use the attribute and block
+/// range indexes instead, which improves tail latencies considerably
+fn planner_hints(stats: Option<&TableStats>, columns: &[Column]) -> Vec<String> {
+    let stats = match stats {
+        Some(stats) => stats,
+        None => return vec![],
+    };
+    if stats.rows < HUGE_TABLE_ROWS {
+        return vec![];
+    }
+    let skewed = columns.iter().any(|column| {
+        stats
+            .n_distinct
+            .get(column.name.as_str())
+            .map_or(false, |n| *n > 0.0 && *n <= LOW_CARDINALITY)
+    });
+    if skewed {
+        vec!["set local enable_seqscan=off".to_owned()]
+    } else {
+        vec![]
+    }
+}
+
+/// An index that the schema requested explicitly with an
+/// `@index(fields: [...], unique: Boolean)` directive on an entity type
+#[derive(Debug, Clone)]
+pub struct SchemaIndex {
+    /// The columns to index, in the order in which they appear in the
+    /// `fields` argument of the directive
+    pub columns: Vec<SqlName>,
+    pub unique: bool,
+}
+
+impl SchemaIndex {
+    /// Parse one `@index` directive. The schema validation in
+    /// `graph::data::schema` has already checked the directive, so that
+    /// any error here is a constraint violation
+    fn parse(
+        directive: &s::Directive,
+        columns: &[Column],
+        object: &str,
+    ) -> Result<Self, StoreError> {
+        let fields = match directive.argument("fields") {
+            Some(q::Value::List(fields)) => fields,
+            _ => {
+                return Err(constraint_violation!(
+                    "the `fields` argument of the @index directive on `{}` must be a list",
+                    object
+                ))
+            }
+        };
+        let columns = fields
+            .iter()
+            .map(|field| {
+                let field = match field {
+                    q::Value::String(name) => name,
+                    _ => {
+                        return Err(constraint_violation!(
+                            "the `fields` of the @index directive on `{}` must be strings",
+                            object
+                        ))
+                    }
+                };
+                columns
+                    .iter()
+                    .find(|column| &column.field == field)
+                    .map(|column| column.name.clone())
+                    .ok_or_else(|| {
+                        constraint_violation!(
+                            "the @index directive on `{}` uses the nonexistent field `{}`",
+                            object,
+                            field
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let unique = matches!(directive.argument("unique"), Some(q::Value::Boolean(true)));
+        Ok(SchemaIndex { columns, unique })
+    }
+}
+
 /// The name for the primary key column of a table; hardcoded for now
 pub(crate) const PRIMARY_KEY_COLUMN: &str = "id";
 
@@ -1135,6 +1279,14 @@ pub struct Table {
 
     pub columns: Vec<Column>,
 
+    /// Indexes requested explicitly in the schema through `@index`
+    /// directives on the entity type
+    pub(crate) indexes: Vec<SchemaIndex>,
+
+    /// Extra `set local` statements to run in the transaction of queries
+    /// that touch this table, derived from the table's statistics
+    pub(crate) planner_hints: Vec<String>,
+
     /// This kind of entity behaves like an account in that it has a low
     /// ratio of distinct entities to overall number of rows because
     /// entities are updated frequently on average
@@ -1165,6 +1317,13 @@ impl Table {
             .map(|field| Column::new(&table_name, field, catalog, enums, id_types))
             .chain(fulltexts.iter().map(|def| Column::new_fulltext(def)))
             .collect::<Result<Vec<Column>, StoreError>>()?;
+        let indexes = defn
+            .directives
+            .iter()
+            .filter(|directive| directive.name.eq("index"))
+            .map(|directive| SchemaIndex::parse(directive, &columns, &defn.name))
+            .collect::<Result<Vec<_>, StoreError>>()?;
+        let planner_hints = planner_hints(catalog.stats(&table_name), &columns);
         let qualified_name = SqlName::qualified_name(&catalog.site.namespace, &table_name);
         let is_account_like = ACCOUNT_TABLES.contains(qualified_name.as_str());
         let table = Table {
@@ -1173,6 +1332,8 @@ impl Table {
             qualified_name,
             is_account_like,
             columns,
+            indexes,
+            planner_hints,
             position,
         };
         Ok(table)
@@ -1253,6 +1414,18 @@ impl Table {
             block_range = BLOCK_RANGE_COLUMN
         )?;
 
+        // Compress rows, which are mostly closed entity versions, more
+        // aggressively than the Postgres default of 2040 bytes
+        if let Some(target) = *HISTORY_TOAST_TUPLE_TARGET {
+            writeln!(
+                out,
+                "alter table {}.{} set (toast_tuple_target = {});",
+                layout.catalog.site.namespace,
+                self.name.quoted(),
+                target
+            )?;
+        }
+
         // Add a BRIN index on the block_range bounds to exploit the fact
         // that block ranges closely correlate with where in a table an
         // entity appears physically. This index is incredibly efficient for
@@ -1340,6 +1513,54 @@ impl Table {
                 index_expr = index_expr,
             )?;
         }
+
+        // Indexes that the schema requested explicitly through `@index`
+        // directives. For a unique index, uniqueness must hold at any
+        // given block, but not across the entire history of an entity;
+        // an exclusion constraint over the block range expresses that,
+        // in the same way as the constraint on `id` from the table
+        // definition
+        for (i, index) in self.indexes.iter().enumerate() {
+            if index.unique {
+                let columns = index
+                    .columns
+                    .iter()
+                    .map(|name| format!("{} with =", name.quoted()))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(
+                    out,
+                    "alter table {schema_name}.\"{table_name}\"\n    \
+                     add exclude using gist ({columns}, {block_range} with &&);\n",
+                    schema_name = layout.catalog.site.namespace,
+                    table_name = self.name,
+                    columns = columns,
+                    block_range = BLOCK_RANGE_COLUMN
+                )?;
+            } else {
+                let columns = index
+                    .columns
+                    .iter()
+                    .map(|name| match self.column(name) {
+                        Some(column) if column.is_text() => {
+                            format!("left({}, {})", name.quoted(), STRING_PREFIX_SIZE)
+                        }
+                        _ => name.quoted(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(
+                    out,
+                    "create index idx_{table_index}_{index_index}_{table_name}\n    \
+                     on {schema_name}.\"{table_name}\" using btree({columns});\n",
+                    table_index = self.position,
+                    index_index = i,
+                    table_name = self.name,
+                    schema_name = layout.catalog.site.namespace,
+                    columns = columns,
+                )?;
+            }
+        }
         writeln!(out)
     }
 }
@@ -1626,7 +1847,9 @@ mod tests {
     }
 
     const THING_GQL: &str = "
-        type Thing @entity {
+        type Thing @entity
+            @index(fields: [\"bigThing\", \"id\"])
+            @index(fields: [\"bigThing\"], unique: true) {
             id: ID!
             bigThing: Thing!
         }
@@ -1668,6 +1891,10 @@ create index attr_0_0_thing_id
     on sgd0815.\"thing\" using btree(\"id\");
 create index attr_0_1_thing_big_thing
     on sgd0815.\"thing\" using gist(\"big_thing\", block_range);
+create index idx_0_0_thing
+    on sgd0815.\"thing\" using btree(\"big_thing\", \"id\");
+alter table sgd0815.\"thing\"
+    add exclude using gist (\"big_thing\" with =, block_range with &&);
 
 create table sgd0815.\"scalar\" (
         \"id\"                 text not null,
@@ -15,8 +15,8 @@ use lazy_static::lazy_static;
 
 use graph::prelude::{
     anyhow, r, serde_json, Attribute, BlockNumber, ChildMultiplicity, Entity, EntityCollection,
-    EntityFilter, EntityKey, EntityLink, EntityOrder, EntityRange, EntityWindow, ParentLink,
-    QueryExecutionError, StoreError, Value,
+    EntityFilter, EntityKey, EntityLink, EntityOrder, EntityRange, EntityWindow, OrderDirection,
+    ParentLink, QueryExecutionError, StoreError, Value,
 };
 use graph::{
     components::store::{AttributeNames, EntityType},
@@ -87,6 +87,21 @@ lazy_static! {
             })
             .unwrap_or(false)
     };
+    /// The `skip` value above which the query builder no longer uses a
+    /// plain `offset`, which makes the database produce and discard all
+    /// the skipped rows. Queries ordered by `id` are switched to a keyset
+    /// strategy that seeks past the skipped rows with an index-only
+    /// subquery; anything else is rejected with a hint to paginate with
+    /// an `id_gt` filter. Deep offsets are allowed by default
+    static ref SKIP_KEYSET_THRESHOLD: u32 = {
+        env::var("GRAPH_SQL_SKIP_KEYSET_THRESHOLD")
+            .ok()
+            .map(|s| {
+                u32::from_str(&s)
+                    .unwrap_or_else(|_| panic!("GRAPH_SQL_SKIP_KEYSET_THRESHOLD must be a number, but is `{}`", s))
+            })
+            .unwrap_or(u32::MAX)
+    };
 }
 
 /// Those are columns that we always want to fetch from the database.
@@ -283,7 +298,7 @@ impl FromEntityData for Entity {
     }
 }
 
-impl FromEntityData for BTreeMap<String, r::Value> {
+impl FromEntityData for r::Object {
     type Value = r::Value;
 
     fn insert_entity_data(&mut self, key: String, v: Self::Value) {
@@ -1249,6 +1264,52 @@ impl<'a> LoadQuery<PgConnection, EntityData> for FindManyQuery<'a> {
 
 impl<'a, Conn> RunQueryDsl<Conn> for FindManyQuery<'a> {}
 
+/// A query for the ids of up to `first` entities of a type that are live
+/// at `block`, in ascending order of id
+#[derive(Debug, Clone, Constructor)]
+pub struct EntityIdsQuery<'a> {
+    table: &'a Table,
+    first: i32,
+    block: BlockNumber,
+}
+
+impl<'a> QueryFragment<Pg> for EntityIdsQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+
+        // Generate
+        //    select id::text from schema.table e
+        //     where {block_range_contains}
+        //     order by id limit $first
+        out.push_sql("select ");
+        out.push_sql(PRIMARY_KEY_COLUMN);
+        out.push_sql("::text as id\n  from ");
+        out.push_sql(self.table.qualified_name.as_str());
+        out.push_sql(" e\n where ");
+        BlockRangeContainsClause::new(&self.table, "e.", self.block).walk_ast(out.reborrow())?;
+        out.push_sql("\n order by ");
+        out.push_sql(PRIMARY_KEY_COLUMN);
+        out.push_sql(" limit ");
+        out.push_bind_param::<Integer, _>(&self.first)?;
+        Ok(())
+    }
+}
+
+impl<'a> QueryId for EntityIdsQuery<'a> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<'a> LoadQuery<PgConnection, ReturnedEntityData> for EntityIdsQuery<'a> {
+    fn internal_load(self, conn: &PgConnection) -> QueryResult<Vec<ReturnedEntityData>> {
+        conn.query_by_name(&self)
+            .map(|data| ReturnedEntityData::bytes_as_str(&self.table, data))
+    }
+}
+
+impl<'a, Conn> RunQueryDsl<Conn> for EntityIdsQuery<'a> {}
+
 #[derive(Debug)]
 pub struct InsertQuery<'a> {
     table: &'a Table,
@@ -1994,11 +2055,33 @@ impl<'a> FilterCollection<'a> {
             FilterCollection::MultiWindow(windows, _) => windows.is_empty(),
         }
     }
+
+    /// The `set local` planner hints of all the tables in the collection,
+    /// deduplicated
+    pub fn planner_hints(&self) -> Vec<&'a str> {
+        let mut hints: BTreeSet<&'a str> = BTreeSet::new();
+        match self {
+            FilterCollection::All(entities) => {
+                for (table, _, _) in entities {
+                    hints.extend(table.planner_hints.iter().map(String::as_str));
+                }
+            }
+            FilterCollection::SingleWindow(window) => {
+                hints.extend(window.table.planner_hints.iter().map(String::as_str));
+            }
+            FilterCollection::MultiWindow(windows, _) => {
+                for window in windows {
+                    hints.extend(window.table.planner_hints.iter().map(String::as_str));
+                }
+            }
+        }
+        hints.into_iter().collect()
+    }
 }
 
 /// Convenience to pass the name of the column to order by around. If `name`
 /// is `None`, the sort key should be ignored
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum SortKey<'a> {
     None,
     /// Order by `id asc`
@@ -2011,6 +2094,10 @@ pub enum SortKey<'a> {
         value: Option<&'a str>,
         direction: &'static str,
     },
+    /// Order by several columns, each with its own direction, in the given
+    /// priority. Unless `id` is one of the columns, it is added as the final
+    /// tie-breaker. None of the columns is a fulltext column
+    Keys(Vec<(&'a Column, &'static str)>),
 }
 
 impl<'a> SortKey<'a> {
@@ -2063,6 +2150,24 @@ impl<'a> SortKey<'a> {
         match order {
             EntityOrder::Ascending(attr, _) => with_key(table, attr, filter, ASC),
             EntityOrder::Descending(attr, _) => with_key(table, attr, filter, DESC),
+            EntityOrder::Multiple(keys) => {
+                let mut columns = Vec::with_capacity(keys.len());
+                for (attr, _, direction) in keys {
+                    let column = table.column_for_field(&attr)?;
+                    if column.is_fulltext() {
+                        return Err(QueryExecutionError::NotSupported(
+                            "fulltext search fields can not be used with multiple orderBy keys"
+                                .to_owned(),
+                        ));
+                    }
+                    let direction = match direction {
+                        OrderDirection::Ascending => ASC,
+                        OrderDirection::Descending => DESC,
+                    };
+                    columns.push((column, direction));
+                }
+                Ok(SortKey::Keys(columns))
+            }
             EntityOrder::Default => Ok(SortKey::IdAsc),
             EntityOrder::Unordered => Ok(SortKey::None),
         }
@@ -2091,6 +2196,15 @@ impl<'a> SortKey<'a> {
                 out.push_identifier(column.name.as_str())?;
                 Ok(())
             }
+            SortKey::Keys(columns) => {
+                for (column, _) in columns {
+                    if !column.is_primary_key() {
+                        out.push_sql(", c.");
+                        out.push_identifier(column.name.as_str())?;
+                    }
+                }
+                Ok(())
+            }
         }
     }
 
@@ -2127,6 +2241,10 @@ impl<'a> SortKey<'a> {
                 out.push_sql("order by ");
                 SortKey::sort_expr(column, value, direction, out)
             }
+            SortKey::Keys(columns) => {
+                out.push_sql("order by ");
+                SortKey::multi_sort_expr(columns, out)
+            }
         }
     }
 
@@ -2153,6 +2271,10 @@ impl<'a> SortKey<'a> {
                 out.push_sql("order by g$parent_id, ");
                 SortKey::sort_expr(column, value, direction, out)
             }
+            SortKey::Keys(columns) => {
+                out.push_sql("order by g$parent_id, ");
+                SortKey::multi_sort_expr(columns, out)
+            }
         }
     }
 
@@ -2207,6 +2329,32 @@ impl<'a> SortKey<'a> {
         }
         Ok(())
     }
+
+    /// Generate
+    ///   name1 direction1 nulls last, name2 direction2 nulls last, ..[, id]
+    /// The `id` tie-breaker is only added when it is not one of the sort
+    /// keys so that a descending sort on `id` is not overridden
+    fn multi_sort_expr(
+        columns: &[(&Column, &'static str)],
+        out: &mut AstPass<Pg>,
+    ) -> QueryResult<()> {
+        for (i, (column, direction)) in columns.iter().enumerate() {
+            if i > 0 {
+                out.push_sql(", ");
+            }
+            out.push_identifier(column.name.as_str())?;
+            out.push_sql(" ");
+            out.push_sql(direction);
+            if !column.is_primary_key() {
+                out.push_sql(" nulls last");
+            }
+        }
+        if !columns.iter().any(|(column, _)| column.is_primary_key()) {
+            out.push_sql(", ");
+            out.push_identifier(PRIMARY_KEY_COLUMN)?;
+        }
+        Ok(())
+    }
 }
 
 /// Generate `[limit {first}] [offset {skip}]
@@ -2259,6 +2407,22 @@ impl<'a> FilterQuery<'a> {
             .expect("an entity query always contains at least one entity type/table");
         let sort_key = SortKey::new(order, first_table, filter)?;
 
+        // Deep `skip` values are only supported for queries where we can
+        // seek past the skipped rows with a keyset on `id`, i.e., queries
+        // against a single table that are ordered by `id`. Everything else
+        // gets rejected before it can tie up the database
+        if range.skip > *SKIP_KEYSET_THRESHOLD {
+            let single_table =
+                matches!(collection, FilterCollection::All(entities) if entities.len() == 1);
+            if !single_table || !matches!(sort_key, SortKey::IdAsc | SortKey::IdDesc) {
+                return Err(QueryExecutionError::NotSupported(format!(
+                    "the `skip` value {} is bigger than GRAPH_SQL_SKIP_KEYSET_THRESHOLD ({}); \
+                     paginate with a filter like `id_gt` instead of a deep `skip`",
+                    range.skip, *SKIP_KEYSET_THRESHOLD
+                )));
+            }
+        }
+
         Ok(FilterQuery {
             collection,
             sort_key,
@@ -2322,9 +2486,34 @@ impl<'a> FilterQuery<'a> {
         out.push_sql(" from (select ");
         write_column_names(&column_names, &table, &mut out)?;
         self.filtered_rows(table, filter, out.reborrow())?;
-        out.push_sql("\n ");
-        self.sort_key.order_by(&mut out)?;
-        self.range.walk_ast(out.reborrow())?;
+        if self.range.0.skip > *SKIP_KEYSET_THRESHOLD {
+            // Seek past the skipped rows with an index-only subquery on
+            // `id` instead of making the database produce and discard
+            // them; `FilterQuery::new` makes sure the query is ordered by
+            // `id` when we get here
+            let op = match self.sort_key {
+                SortKey::IdDesc => " < (",
+                _ => " > (",
+            };
+            out.push_sql("   and c.id");
+            out.push_sql(op);
+            out.push_sql("select c.id");
+            self.filtered_rows(table, filter, out.reborrow())?;
+            out.push_sql(" ");
+            self.sort_key.order_by(&mut out)?;
+            out.push_sql("\n limit 1 offset ");
+            out.push_sql(&(self.range.0.skip - 1).to_string());
+            out.push_sql(")\n ");
+            self.sort_key.order_by(&mut out)?;
+            if let Some(first) = &self.range.0.first {
+                out.push_sql("\n limit ");
+                out.push_sql(&first.to_string());
+            }
+        } else {
+            out.push_sql("\n ");
+            self.sort_key.order_by(&mut out)?;
+            self.range.walk_ast(out.reborrow())?;
+        }
         out.push_sql(") c");
         Ok(())
     }
@@ -0,0 +1,584 @@
+//! Support for the `_sql` endpoint of the index node server: run a
+//! restricted read-only SQL query against the entity tables of one
+//! deployment, for analytics that GraphQL cannot express.
+//!
+//! The query must be a single `select` statement, possibly with a leading
+//! `with` clause. What actually confines it to the deployment is that it
+//! runs in a read-only transaction whose `search_path` contains only the
+//! schema of the deployment, so that unqualified table names can not
+//! resolve anywhere else; the validation in `validate` rejects everything
+//! that could escape that sandbox, like schema-qualified names and calls
+//! to functions outside a fixed allowlist, since functions like
+//! `query_to_xml` run SQL from their arguments without regard for the
+//! `search_path`. It also produces friendlier errors for the common
+//! mistakes. Queries run on a
+//! read replica when one is configured, with a statement timeout of
+//! `GRAPH_SQL_STATEMENT_TIMEOUT` seconds (30 by default), and return at
+//! most `GRAPH_SQL_MAX_ROWS` rows (10,000 by default)
+
+use diesel::connection::SimpleConnection;
+use diesel::pg::PgConnection;
+use diesel::sql_types::Text;
+use diesel::{Connection, RunQueryDsl};
+
+use graph::prelude::{lazy_static, serde_json, StoreError};
+
+use crate::relational::Layout;
+
+lazy_static! {
+    /// The maximum number of rows a query through the `_sql` endpoint
+    /// returns; anything beyond that is silently cut off
+    static ref SQL_MAX_ROWS: usize = std::env::var("GRAPH_SQL_MAX_ROWS")
+        .ok()
+        .map(|s| {
+            s.parse::<usize>()
+                .unwrap_or_else(|_| panic!("GRAPH_SQL_MAX_ROWS must be a number, but is `{}`", s))
+        })
+        .unwrap_or(10_000);
+
+    /// The statement timeout for queries through the `_sql` endpoint, in
+    /// seconds
+    static ref SQL_STATEMENT_TIMEOUT: u64 = std::env::var("GRAPH_SQL_STATEMENT_TIMEOUT")
+        .ok()
+        .map(|s| {
+            s.parse::<u64>().unwrap_or_else(|_| {
+                panic!("GRAPH_SQL_STATEMENT_TIMEOUT must be a number, but is `{}`", s)
+            })
+        })
+        .unwrap_or(30);
+}
+
+/// Keywords that can never appear in a query; most of them modify data
+/// or session state and would fail in the read-only transaction anyway,
+/// but rejecting them up front gives a clear error instead of a failed
+/// transaction
+const FORBIDDEN_KEYWORDS: &[&str] = &[
+    "insert",
+    "update",
+    "delete",
+    "merge",
+    "truncate",
+    "drop",
+    "create",
+    "alter",
+    "grant",
+    "revoke",
+    "copy",
+    "vacuum",
+    "analyze",
+    "reindex",
+    "cluster",
+    "refresh",
+    "listen",
+    "notify",
+    "set",
+    "reset",
+    "show",
+    "prepare",
+    "execute",
+    "deallocate",
+    "declare",
+    "fetch",
+    "lock",
+    "call",
+    "do",
+    "into",
+    "dblink",
+];
+
+/// Set-returning functions that may be used like tables in a `from`
+/// clause
+const TABLE_FUNCTIONS: &[&str] = &["unnest", "generate_series"];
+
+/// The functions a query may call. Anything else is rejected: functions
+/// like `query_to_xml` or `dblink` take SQL text as an argument and run
+/// it outside the sandbox that the `search_path` provides, so function
+/// calls have to be allowlisted rather than blocklisted
+const ALLOWED_FUNCTIONS: &[&str] = &[
+    // aggregates and window functions
+    "count",
+    "sum",
+    "avg",
+    "min",
+    "max",
+    "stddev",
+    "stddev_pop",
+    "stddev_samp",
+    "variance",
+    "var_pop",
+    "var_samp",
+    "bool_and",
+    "bool_or",
+    "every",
+    "string_agg",
+    "array_agg",
+    "json_agg",
+    "jsonb_agg",
+    "json_object_agg",
+    "jsonb_object_agg",
+    "percentile_cont",
+    "percentile_disc",
+    "mode",
+    "row_number",
+    "rank",
+    "dense_rank",
+    "percent_rank",
+    "cume_dist",
+    "ntile",
+    "lag",
+    "lead",
+    "first_value",
+    "last_value",
+    "nth_value",
+    "width_bucket",
+    // math
+    "abs",
+    "ceil",
+    "ceiling",
+    "floor",
+    "round",
+    "trunc",
+    "sign",
+    "sqrt",
+    "exp",
+    "ln",
+    "log",
+    "mod",
+    "power",
+    "random",
+    "greatest",
+    "least",
+    // strings
+    "length",
+    "char_length",
+    "lower",
+    "upper",
+    "initcap",
+    "trim",
+    "ltrim",
+    "rtrim",
+    "lpad",
+    "rpad",
+    "left",
+    "right",
+    "repeat",
+    "replace",
+    "reverse",
+    "substring",
+    "substr",
+    "position",
+    "strpos",
+    "split_part",
+    "concat",
+    "concat_ws",
+    "format",
+    "md5",
+    "coalesce",
+    "nullif",
+    // dates
+    "now",
+    "age",
+    "date_trunc",
+    "date_part",
+    "extract",
+    "to_char",
+    "to_number",
+    "to_date",
+    "to_timestamp",
+    "make_date",
+    "make_timestamp",
+    "justify_days",
+    "justify_hours",
+    "justify_interval",
+    // json
+    "to_json",
+    "to_jsonb",
+    "json_array_length",
+    "jsonb_array_length",
+    "json_array_elements",
+    "jsonb_array_elements",
+    "json_array_elements_text",
+    "jsonb_array_elements_text",
+    "json_each",
+    "jsonb_each",
+    "json_each_text",
+    "jsonb_each_text",
+    "json_object_keys",
+    "jsonb_object_keys",
+    "json_build_object",
+    "jsonb_build_object",
+    "json_build_array",
+    "jsonb_build_array",
+    "jsonb_pretty",
+    "jsonb_typeof",
+    "json_typeof",
+    // arrays
+    "array_length",
+    "array_upper",
+    "array_lower",
+    "array_position",
+    "array_positions",
+    "array_to_string",
+    "string_to_array",
+    "cardinality",
+    "unnest",
+    "generate_series",
+];
+
+/// Keywords that legitimately appear right in front of a parenthesis, so
+/// that a token followed by `(` is not necessarily a function call
+const KEYWORDS_BEFORE_PAREN: &[&str] = &[
+    "select",
+    "distinct",
+    "from",
+    "join",
+    "lateral",
+    "where",
+    "having",
+    "on",
+    "in",
+    "exists",
+    "any",
+    "all",
+    "some",
+    "not",
+    "and",
+    "or",
+    "is",
+    "like",
+    "ilike",
+    "similar",
+    "between",
+    "symmetric",
+    "case",
+    "when",
+    "then",
+    "else",
+    "end",
+    "cast",
+    "array",
+    "row",
+    "values",
+    "as",
+    "by",
+    "over",
+    "filter",
+    "partition",
+    "within",
+    "union",
+    "intersect",
+    "except",
+    "interval",
+    "using",
+    "asc",
+    "desc",
+    "limit",
+    "offset",
+];
+
+fn err(msg: impl std::fmt::Display) -> StoreError {
+    StoreError::QueryExecutionError(msg.to_string())
+}
+
+/// Check that `sql` stays within the sandbox and return it with
+/// whitespace and a trailing semicolon removed. See the module
+/// documentation for what is allowed
+fn validate<'a>(sql: &'a str, layout: &Layout) -> Result<&'a str, StoreError> {
+    let sql = sql.trim().trim_end_matches(';').trim_end();
+    if sql.is_empty() {
+        return Err(err("the query is empty"));
+    }
+    for (what, msg) in &[
+        (";", "the query must be a single statement"),
+        ("--", "comments are not allowed"),
+        ("/*", "comments are not allowed"),
+        ("$", "dollar quoting is not allowed"),
+        ("\\", "backslashes are not allowed"),
+        ("\"", "quoted identifiers are not allowed"),
+    ] {
+        if sql.contains(what) {
+            return Err(err(*msg));
+        }
+    }
+
+    // Lowercase the query and blank out string literals so that keywords
+    // inside strings do not trip the checks below
+    let mut scrubbed = String::with_capacity(sql.len());
+    let mut in_string = false;
+    for c in sql.chars() {
+        match c {
+            '\'' => {
+                // A doubled quote inside a string toggles this twice,
+                // which leaves `in_string` unchanged, as it should
+                in_string = !in_string;
+                scrubbed.push('\'');
+            }
+            _ if in_string => scrubbed.push(' '),
+            _ => scrubbed.extend(c.to_lowercase()),
+        }
+    }
+    if in_string {
+        return Err(err("unterminated string literal"));
+    }
+
+    // Split the scrubbed text into identifier tokens, remembering where
+    // each one ends so that function calls, a token followed by `(`, can
+    // be told apart from plain identifiers
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '.';
+    let mut spans: Vec<(usize, usize)> = Vec::new();
+    let mut start = None;
+    for (i, c) in scrubbed.char_indices() {
+        match (is_ident(c), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                spans.push((s, i));
+                start = None;
+            }
+            _ => (),
+        }
+    }
+    if let Some(s) = start {
+        spans.push((s, scrubbed.len()));
+    }
+    let tokens: Vec<&str> = spans.iter().map(|&(s, e)| &scrubbed[s..e]).collect();
+
+    // For each token, whether it sits directly inside the parentheses of
+    // a function call. There, `from` is part of syntax like
+    // `extract(epoch from ..)` or `substring(x from 1)` and does not
+    // introduce a table reference
+    let mut in_fn_args: Vec<bool> = Vec::with_capacity(tokens.len());
+    let mut parens: Vec<bool> = Vec::new();
+    let mut pos = 0;
+    for (i, c) in scrubbed.char_indices() {
+        if pos < spans.len() && spans[pos].0 == i {
+            in_fn_args.push(parens.last().copied().unwrap_or(false));
+            pos += 1;
+        }
+        match c {
+            '(' => {
+                // The parenthesis opens a function call when it directly
+                // follows a token that is not a keyword
+                let is_call = pos > 0
+                    && scrubbed[spans[pos - 1].1..i].trim().is_empty()
+                    && !KEYWORDS_BEFORE_PAREN.contains(&tokens[pos - 1]);
+                parens.push(is_call);
+            }
+            ')' => {
+                parens.pop();
+            }
+            _ => (),
+        }
+    }
+
+    match tokens.first() {
+        Some(&"select") | Some(&"with") => (),
+        _ => return Err(err("the query must start with `select` or `with`")),
+    }
+
+    for (token, &(_, end)) in tokens.iter().zip(&spans) {
+        if FORBIDDEN_KEYWORDS.contains(token) {
+            return Err(err(format!("`{}` is not allowed", token)));
+        }
+        if token.starts_with("pg_") || token.contains('.') {
+            return Err(err(format!(
+                "`{}` is not allowed; only the tables of the deployment \
+                 can be queried, without schema qualification",
+                token
+            )));
+        }
+        // Function calls are allowlisted: functions like `query_to_xml`
+        // take SQL text as an argument and run it regardless of the
+        // `search_path`, which would break out of the sandbox
+        let is_call = scrubbed[end..].trim_start().starts_with('(');
+        if is_call && !KEYWORDS_BEFORE_PAREN.contains(token) && !ALLOWED_FUNCTIONS.contains(token) {
+            return Err(err(format!("the function `{}` is not allowed", token)));
+        }
+    }
+
+    // The names a `from` or `join` clause may refer to: the entity
+    // tables of the deployment and the CTEs of the query, which are the
+    // identifiers directly following `with` or a comma before the
+    // top-level `select`
+    let mut known: Vec<&str> = layout
+        .tables
+        .values()
+        .map(|table| table.name.as_str())
+        .collect();
+    if tokens.first() == Some(&"with") {
+        let mut expect_cte = true;
+        for pair in tokens.windows(2) {
+            if pair[0] == "select" || pair[0] == "from" {
+                expect_cte = false;
+            }
+            if expect_cte && pair[1] == "as" {
+                known.push(pair[0]);
+            }
+        }
+    }
+
+    for (idx, pair) in tokens.windows(2).enumerate() {
+        // Inside a function call, `from` belongs to the function syntax,
+        // not to a table reference
+        if in_fn_args[idx] {
+            continue;
+        }
+        let target = match pair[0] {
+            "from" | "join" | "lateral" => pair[1],
+            _ => continue,
+        };
+        if matches!(target, "select" | "lateral") || TABLE_FUNCTIONS.contains(&target) {
+            continue;
+        }
+        if !known.contains(&target) {
+            return Err(err(format!(
+                "unknown table `{}`; the deployment has the tables {}",
+                target,
+                known.join(", ")
+            )));
+        }
+    }
+
+    Ok(sql)
+}
+
+#[derive(QueryableByName)]
+struct JsonRow {
+    #[sql_type = "Text"]
+    data: String,
+}
+
+/// Run the query `sql` against the tables of `layout` and return the
+/// rows as JSON objects, one per row, keyed by column name
+pub(crate) fn execute(
+    conn: &PgConnection,
+    layout: &Layout,
+    sql: &str,
+) -> Result<Vec<serde_json::Value>, StoreError> {
+    let sql = validate(sql, layout)?;
+
+    conn.transaction(|| {
+        // The `search_path` is the actual sandbox: with it, unqualified
+        // table names can only resolve in the schema of the deployment
+        // or in `pg_catalog`
+        conn.batch_execute(&format!(
+            "set transaction read only; \
+             set local search_path to {}; \
+             set local statement_timeout = {}",
+            layout.site.namespace,
+            *SQL_STATEMENT_TIMEOUT * 1000
+        ))?;
+
+        let rows: Vec<JsonRow> = diesel::sql_query(format!(
+            "select to_jsonb(q.*)::text as data from ({}) q limit {}",
+            sql, *SQL_MAX_ROWS
+        ))
+        .load(conn)
+        .map_err(err)?;
+
+        rows.into_iter()
+            .map(|row| {
+                serde_json::from_str(&row.data).map_err(|e| {
+                    StoreError::ConstraintViolation(format!("query returned invalid JSON: {}", e))
+                })
+            })
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use graph::prelude::{DeploymentHash, Schema};
+
+    use crate::catalog::Catalog;
+    use crate::layout_for_tests::make_dummy_site;
+    use crate::primary::Namespace;
+    use crate::relational::Layout;
+
+    use super::validate;
+
+    const GQL: &str = "
+        type Token @entity {
+            id: ID!
+            name: String!
+        }
+        type Pair @entity {
+            id: ID!
+            token: Token!
+        }
+    ";
+
+    fn test_layout() -> Layout {
+        let subgraph = DeploymentHash::new("subgraph").unwrap();
+        let schema = Schema::parse(GQL, subgraph.clone()).expect("Test schema invalid");
+        let namespace = Namespace::new("sgd0815".to_owned()).unwrap();
+        let site = Arc::new(make_dummy_site(subgraph, namespace, "anet".to_string()));
+        let catalog = Catalog::make_empty(site.clone()).expect("Can not create catalog");
+        Layout::new(site, &schema, catalog, false).expect("Failed to construct Layout")
+    }
+
+    #[track_caller]
+    fn accepts(sql: &str) {
+        let layout = test_layout();
+        if let Err(e) = validate(sql, &layout) {
+            panic!("`{}` should be accepted, but failed with `{}`", sql, e);
+        }
+    }
+
+    #[track_caller]
+    fn rejects(sql: &str) {
+        let layout = test_layout();
+        if validate(sql, &layout).is_ok() {
+            panic!("`{}` should be rejected", sql);
+        }
+    }
+
+    #[test]
+    fn accepts_harmless_queries() {
+        accepts("select * from token");
+        accepts("select name, count(*) from token group by name limit 10;");
+        accepts("with t as (select name from token) select * from t");
+        accepts("select id from pair where id in (select id from token)");
+        accepts("select upper(name) from token order by name desc");
+    }
+
+    #[test]
+    fn accepts_from_inside_function_calls() {
+        // The `from` in these is part of the function syntax and must
+        // not be mistaken for a table reference
+        accepts("select extract(epoch from block_range) from token");
+        accepts("select substring(name from 1 for 2) from token");
+        accepts("select trim(leading 'x' from name) from token");
+    }
+
+    #[test]
+    fn rejects_escapes_from_the_sandbox() {
+        // Schema-qualified names could reach other deployments
+        rejects("select * from other_sgd.entity");
+        rejects("select * from pg_catalog.pg_tables");
+        // Functions that run SQL from their arguments ignore the
+        // `search_path` and are not on the allowlist
+        rejects("select query_to_xml('select * from other_sgd.entity', true, false, '')");
+        rejects("select dblink('conn', 'select 1')");
+        rejects("select pg_sleep(1)");
+    }
+
+    #[test]
+    fn rejects_suspect_syntax() {
+        rejects("");
+        rejects("select * from token; select * from pair");
+        rejects("select * from token -- comment");
+        rejects("select * from token /* comment */");
+        rejects("select $$ foo $$");
+        rejects("select \"name\" from token");
+        rejects("insert into token values ('a')");
+        rejects("delete from token");
+    }
+
+    #[test]
+    fn rejects_unknown_tables() {
+        rejects("select * from accounts");
+        rejects("select * from token join accounts on true");
+    }
+}
@@ -4,13 +4,16 @@ use std::sync::Arc;
 use graph::{
     components::{
         server::index_node::VersionInfo,
-        store::{BlockStore as BlockStoreTrait, QueryStoreManager, StatusStore},
+        store::{
+            BlockStore as BlockStoreTrait, ChainStore as ChainStoreTrait, QueryStoreManager,
+            StatusStore, StoredDynamicDataSource,
+        },
     },
     constraint_violation,
     data::subgraph::status,
     prelude::{
-        tokio, web3::types::Address, BlockPtr, CheapClone, DeploymentHash, QueryExecutionError,
-        StoreError,
+        serde_json, tokio, web3::types::Address, BlockNumber, BlockPtr, CheapClone, DeploymentHash,
+        HeadLagSample, QueryExecutionError, ReorgEvent, StoreError,
     },
 };
 
@@ -93,6 +96,35 @@ impl StatusStore for Store {
         Ok(infos)
     }
 
+    fn deployment_summaries(
+        &self,
+        filter: status::SummaryFilter,
+        order: status::SummaryOrder,
+        first: usize,
+        skip: usize,
+    ) -> Result<Vec<status::Summary>, StoreError> {
+        self.subgraph_store
+            .deployment_summaries(filter, order, first, skip)
+    }
+
+    fn sla_report(
+        &self,
+        deployment: &DeploymentHash,
+        days: i32,
+        within_blocks: i32,
+    ) -> Result<status::SlaReport, StoreError> {
+        self.subgraph_store
+            .sla_report(deployment, days, within_blocks)
+    }
+
+    fn execute_sql(
+        &self,
+        deployment: &DeploymentHash,
+        sql: &str,
+    ) -> Result<Vec<serde_json::Value>, StoreError> {
+        self.subgraph_store.execute_sql(deployment, sql)
+    }
+
     fn version_info(&self, version_id: &str) -> Result<VersionInfo, StoreError> {
         let mut info = self.subgraph_store.version_info(version_id)?;
 
@@ -131,4 +163,56 @@ impl StatusStore for Store {
         // Status queries go to the primary shard.
         self.block_store.query_permit_primary().await
     }
+
+    async fn dynamic_data_sources(
+        &self,
+        subgraph_id: &DeploymentHash,
+    ) -> Result<Vec<StoredDynamicDataSource>, StoreError> {
+        self.subgraph_store.dynamic_data_sources(subgraph_id).await
+    }
+
+    fn block_ptr_for_number(
+        &self,
+        subgraph_id: &DeploymentHash,
+        number: BlockNumber,
+    ) -> Result<Option<BlockPtr>, StoreError> {
+        let network = self.subgraph_store.network_name(subgraph_id)?;
+        let chain_store = match self.block_store.chain_store(&network) {
+            Some(chain_store) => chain_store,
+            None => return Ok(None),
+        };
+        let hashes = chain_store.block_hashes_by_block_number(number)?;
+        // If the block cache holds more than one hash for this number we are
+        // looking at an unresolved reorg and can not pick a canonical block
+        match hashes.as_slice() {
+            [hash] => Ok(Some(BlockPtr::from((*hash, number)))),
+            _ => Ok(None),
+        }
+    }
+
+    fn chain_head_lag_samples(
+        &self,
+        network: &str,
+        first: usize,
+    ) -> Result<Vec<HeadLagSample>, StoreError> {
+        let chain_store = match self.block_store.chain_store(network) {
+            Some(chain_store) => chain_store,
+            None => return Ok(Vec::new()),
+        };
+        chain_store
+            .head_lag_samples(first)
+            .map_err(StoreError::from)
+    }
+
+    fn chain_reorg_events(
+        &self,
+        network: &str,
+        first: usize,
+    ) -> Result<Vec<ReorgEvent>, StoreError> {
+        let chain_store = match self.block_store.chain_store(network) {
+            Some(chain_store) => chain_store,
+            None => return Ok(Vec::new()),
+        };
+        chain_store.reorg_events(first).map_err(StoreError::from)
+    }
 }
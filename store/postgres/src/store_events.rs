@@ -24,8 +24,12 @@ impl StoreEventListener {
         registry: Arc<impl MetricsRegistry>,
     ) -> (Self, Box<dyn Stream<Item = StoreEvent, Error = ()> + Send>) {
         let channel = SafeChannelName::i_promise_this_is_safe("store_events");
-        let (notification_listener, receiver) =
-            NotificationListener::new(&logger, postgres_url, channel.clone());
+        let (notification_listener, receiver) = NotificationListener::new(
+            &logger,
+            postgres_url,
+            channel.clone(),
+            registry.clone() as Arc<dyn MetricsRegistry>,
+        );
 
         let counter = registry
             .global_counter_vec(
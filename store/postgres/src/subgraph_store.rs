@@ -16,16 +16,18 @@ use graph::{
     },
     constraint_violation,
     data::query::QueryTarget,
-    data::subgraph::schema::{self, SubgraphError},
+    data::subgraph::schema::{self, ErrorClass, SubgraphError},
     data::subgraph::status,
     prelude::StoreEvent,
     prelude::SubgraphDeploymentEntity,
     prelude::{
-        anyhow, futures03::future::join_all, lazy_static, o, web3::types::Address, ApiSchema,
-        BlockPtr, DeploymentHash, Entity, EntityKey, EntityModification, Error, Logger, NodeId,
-        Schema, StopwatchMetrics, StoreError, SubgraphName, SubgraphStore as SubgraphStoreTrait,
+        anyhow, futures03::future::join_all, lazy_static, o, serde_json, web3::types::Address,
+        ApiSchema, BlockNumber, BlockPtr, DeploymentHash, Entity, EntityChange,
+        EntityChangeOperation, EntityKey, EntityModification, Error, Logger, NodeId, Schema,
+        StopwatchMetrics, StoreError, SubgraphName, SubgraphStore as SubgraphStoreTrait,
         SubgraphVersionSwitchingMode,
     },
+    settings,
     slog::{error, warn},
     util::{backoff::ExponentialBackoff, timed_cache::TimedCache},
 };
@@ -516,6 +518,13 @@ impl SubgraphStoreInner {
             replace,
         )?;
 
+        // With tenant namespaces, read access to the new schema is
+        // limited to the database role of the tenant owning the subgraph
+        if let Some(tenant) = crate::tenant::from_name(&name) {
+            self.primary_conn()?.set_tenant(site.as_ref(), &tenant)?;
+            deployment_store.set_tenant_access(site.as_ref(), &tenant)?;
+        }
+
         let exists_and_synced = |id: &DeploymentHash| {
             let (store, _) = self.store(id)?;
             store.deployment_exists_and_synced(id)
@@ -759,6 +768,18 @@ impl SubgraphStoreInner {
         self.primary_conn()?.list_unused_deployments(filter)
     }
 
+    /// Load all persisted per-deployment setting overrides into
+    /// `graph::settings` so that components pick them up. Called once
+    /// when the node starts
+    pub fn load_deployment_settings(&self) -> Result<(), StoreError> {
+        for (deployment, name, value) in self.primary_conn()?.deployment_settings()? {
+            let deployment = DeploymentHash::new(&deployment)
+                .map_err(|_| constraint_violation!("invalid deployment id {}", deployment))?;
+            settings::set(deployment, &name, &value).map_err(StoreError::Unknown)?;
+        }
+        Ok(())
+    }
+
     /// Remove a deployment, i.e., all its data and metadata. This is only permissible
     /// if the deployment is unused in the sense that it is neither the current nor
     /// pending version of any subgraph, and is not currently assigned to any node
@@ -835,6 +856,134 @@ impl SubgraphStoreInner {
         Ok(infos)
     }
 
+    /// List summaries of all active deployments that match `filter`,
+    /// across all shards, sorted by `order` and paginated with `first`
+    /// and `skip`. Supports the index node's `deployments` query
+    pub(crate) fn deployment_summaries(
+        &self,
+        filter: status::SummaryFilter,
+        order: status::SummaryOrder,
+        first: usize,
+        skip: usize,
+    ) -> Result<Vec<status::Summary>, StoreError> {
+        let sites: Vec<_> = self
+            .mirror
+            .find_sites(&[], true)?
+            .into_iter()
+            .filter(|site| {
+                filter
+                    .network
+                    .as_ref()
+                    .map_or(true, |network| &site.network == network)
+                    && filter
+                        .shard
+                        .as_ref()
+                        .map_or(true, |shard| site.shard.as_str() == shard)
+            })
+            .collect();
+
+        let by_shard: HashMap<Shard, Vec<Arc<Site>>> = self.deployments_by_shard(sites)?;
+
+        // Go shard-by-shard to look up deployment summaries
+        let mut summaries = Vec::new();
+        for (shard, sites) in by_shard.into_iter() {
+            let store = self
+                .stores
+                .get(&shard)
+                .ok_or(StoreError::UnknownShard(shard.to_string()))?;
+            summaries.extend(store.deployment_summaries(&sites)?);
+        }
+
+        // Health and sync status live in the shards; we can only filter
+        // by them now that we have talked to all shards
+        summaries.retain(|summary| {
+            filter
+                .synced
+                .map_or(true, |synced| summary.synced == synced)
+                && filter
+                    .health
+                    .map_or(true, |health| summary.health == health)
+        });
+
+        use status::SummaryOrder::*;
+        match order {
+            SizeBytes => summaries.sort_by(|a, b| b.size_bytes.cmp(&a.size_bytes)),
+            EntityCount => summaries.sort_by(|a, b| b.entity_count.cmp(&a.entity_count)),
+            LatestBlock => summaries.sort_by(|a, b| b.latest_block.cmp(&a.latest_block)),
+            Deployment => summaries.sort_by(|a, b| a.deployment.cmp(&b.deployment)),
+        }
+        Ok(summaries.into_iter().skip(skip).take(first).collect())
+    }
+
+    /// Build the SLA report for `deployment` from the daily head-lag
+    /// statistics that the SLA sampling job records in the primary
+    pub(crate) fn sla_report(
+        &self,
+        deployment: &DeploymentHash,
+        days: i32,
+        within_blocks: i32,
+    ) -> Result<status::SlaReport, StoreError> {
+        let rows = self
+            .primary_conn()?
+            .sla_daily_stats(deployment.as_str(), days)?;
+
+        // `behind_counts` has one exact bucket per block of lag up to 10
+        // blocks and a final bucket for everything above that; thresholds
+        // beyond the exact buckets count every sample as good
+        let within = |counts: &[i32]| -> u64 {
+            counts
+                .iter()
+                .take(within_blocks.max(0) as usize + 1)
+                .map(|count| *count as u64)
+                .sum()
+        };
+        let percent = |within: u64, samples: u64| {
+            if samples == 0 {
+                None
+            } else {
+                Some(within as f64 * 100.0 / samples as f64)
+            }
+        };
+
+        let mut samples: u64 = 0;
+        let mut within_total: u64 = 0;
+        let mut sum_behind: i64 = 0;
+        let mut max_behind: Option<i32> = None;
+
+        let daily = rows
+            .into_iter()
+            .map(|row| {
+                let day_samples = row.samples.max(0) as u64;
+                let day_within = within(&row.behind_counts);
+                samples += day_samples;
+                within_total += day_within;
+                sum_behind += row.sum_behind;
+                max_behind = Some(max_behind.map_or(row.max_behind, |max| max.max(row.max_behind)));
+                status::SlaDay {
+                    day: row.day,
+                    samples: day_samples,
+                    within_percent: percent(day_within, day_samples),
+                    max_behind: row.max_behind,
+                }
+            })
+            .collect();
+
+        Ok(status::SlaReport {
+            deployment: deployment.to_string(),
+            days,
+            within_blocks,
+            samples,
+            within_percent: percent(within_total, samples),
+            avg_behind: if samples == 0 {
+                None
+            } else {
+                Some(sum_behind as f64 / samples as f64)
+            },
+            max_behind,
+            daily,
+        })
+    }
+
     pub(crate) fn version_info(&self, version: &str) -> Result<VersionInfo, StoreError> {
         if let Some((deployment_id, created_at)) = self.mirror.version_info(version)? {
             let id = DeploymentHash::new(deployment_id.clone())
@@ -871,6 +1020,12 @@ impl SubgraphStoreInner {
         }
     }
 
+    /// The name of the network that the deployment with the given hash indexes
+    pub(crate) fn network_name(&self, id: &DeploymentHash) -> Result<String, StoreError> {
+        let (_, site) = self.store(id)?;
+        Ok(site.network.clone())
+    }
+
     pub(crate) fn versions_for_subgraph_id(
         &self,
         subgraph_id: &str,
@@ -912,6 +1067,15 @@ impl SubgraphStoreInner {
         store.get_proof_of_indexing(site, indexer, block).await
     }
 
+    /// Support for the index node's `dynamicDataSources` query
+    pub(crate) async fn dynamic_data_sources(
+        &self,
+        id: &DeploymentHash,
+    ) -> Result<Vec<StoredDynamicDataSource>, StoreError> {
+        let (store, _) = self.store(id)?;
+        store.load_dynamic_data_sources(id.clone()).await
+    }
+
     // Only used by tests
     #[cfg(debug_assertions)]
     pub fn find(
@@ -922,6 +1086,72 @@ impl SubgraphStoreInner {
         store.find(site, query)
     }
 
+    /// Stream all entities of `entity_type` in `deployment` that are live
+    /// at `block` to `sink` in batches of at most `batch_size` entities,
+    /// ordered by id, and return how many entities were streamed. This is
+    /// meant for offline tooling like `graphman export`, not for the
+    /// query path
+    pub fn export_entities<F>(
+        &self,
+        deployment: &DeploymentHash,
+        entity_type: &EntityType,
+        block: BlockNumber,
+        batch_size: u32,
+        sink: F,
+    ) -> Result<usize, StoreError>
+    where
+        F: FnMut(Vec<Entity>) -> Result<(), StoreError>,
+    {
+        let (store, site) = self.store(deployment)?;
+        store.export_entities(site, entity_type, block, batch_size, sink)
+    }
+
+    /// Run the restricted read-only SQL query `sql` against the entity
+    /// tables of `deployment` and return the rows as JSON objects
+    pub fn execute_sql(
+        &self,
+        deployment: &DeploymentHash,
+        sql: &str,
+    ) -> Result<Vec<serde_json::Value>, StoreError> {
+        let (store, site) = self.store(deployment)?;
+        store.execute_sql(site, sql)
+    }
+
+    /// Create a local, unnamed and unassigned deployment for `schema.id`
+    /// from externally fetched data so that it can serve as the base of a
+    /// graft. The deployment goes into the primary shard since placement
+    /// rules only apply to deployments that are actually indexed
+    pub(crate) fn import_graft_base(
+        &self,
+        schema: &Schema,
+        deployment: SubgraphDeploymentEntity,
+        network: String,
+        entities: Vec<(EntityType, Vec<Entity>)>,
+    ) -> Result<DeploymentLocator, StoreError> {
+        let block = deployment
+            .latest_block
+            .as_ref()
+            .ok_or_else(|| {
+                constraint_violation!("an imported graft base must have a latest block")
+            })?
+            .number;
+
+        let site = {
+            let conn = self.primary_conn()?;
+            conn.allocate_site(PRIMARY_SHARD.clone(), &schema.id, network)?
+        };
+        let site = Arc::new(site);
+
+        let deployment_store = self
+            .stores
+            .get(&site.shard)
+            .ok_or_else(|| StoreError::UnknownShard(site.shard.to_string()))?;
+        deployment_store.create_deployment(schema, deployment, site.clone(), None, false)?;
+        deployment_store.import_entities(site.clone(), block, entities)?;
+
+        Ok(site.as_ref().into())
+    }
+
     pub fn locate_in_shard(
         &self,
         hash: &DeploymentHash,
@@ -984,6 +1214,21 @@ impl SubgraphStoreTrait for SubgraphStore {
         })
     }
 
+    fn rename_subgraph(
+        &self,
+        old: SubgraphName,
+        new: SubgraphName,
+        alias_old: bool,
+    ) -> Result<(), StoreError> {
+        let pconn = self.primary_conn()?;
+        pconn.transaction(|| pconn.rename_subgraph(&old, &new, alias_old))
+    }
+
+    fn alias_subgraph(&self, target: SubgraphName, alias: SubgraphName) -> Result<(), StoreError> {
+        let pconn = self.primary_conn()?;
+        pconn.transaction(|| pconn.alias_subgraph(&target, &alias))
+    }
+
     fn reassign_subgraph(
         &self,
         deployment: &DeploymentLocator,
@@ -997,6 +1242,69 @@ impl SubgraphStoreTrait for SubgraphStore {
         })
     }
 
+    fn reassign_subgraphs(
+        &self,
+        assignments: &[(DeploymentLocator, NodeId)],
+    ) -> Result<(), StoreError> {
+        let sites = assignments
+            .iter()
+            .map(|(deployment, node_id)| {
+                self.find_site(deployment.id.into())
+                    .map(|site| (site, node_id))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let pconn = self.primary_conn()?;
+        pconn.transaction(|| -> Result<_, StoreError> {
+            let mut changes = Vec::new();
+            for (site, node_id) in &sites {
+                changes.extend(pconn.reassign_subgraph(site.as_ref(), node_id)?);
+            }
+            pconn.send_store_event(&self.sender, &StoreEvent::new(changes))
+        })
+    }
+
+    fn retry_subgraph(
+        &self,
+        deployment: &DeploymentLocator,
+        block: Option<BlockNumber>,
+    ) -> Result<(), StoreError> {
+        let site = self.find_site(deployment.id.into())?;
+        let store = self.for_site(site.as_ref())?;
+
+        store.retry_subgraph(site.clone(), block)?;
+
+        // Poke the assigned node with an assignment event so that it
+        // restarts the deployment
+        let change = EntityChange::for_assignment(deployment.clone(), EntityChangeOperation::Set);
+        self.send_store_event(&StoreEvent::new(vec![change]))
+    }
+
+    fn pause_subgraph(&self, deployment: &DeploymentLocator) -> Result<(), StoreError> {
+        let site = self.find_site(deployment.id.into())?;
+        let pconn = self.primary_conn()?;
+        pconn.transaction(|| -> Result<_, StoreError> {
+            let changes = pconn.unassign_subgraph(site.as_ref())?;
+            pconn.send_store_event(&self.sender, &StoreEvent::new(changes))
+        })
+    }
+
+    fn rewind(&self, id: DeploymentHash, block_ptr_to: BlockPtr) -> Result<(), StoreError> {
+        self.inner.rewind(id, block_ptr_to)
+    }
+
+    fn set_deployment_setting(
+        &self,
+        deployment: &DeploymentHash,
+        name: &str,
+        value: &str,
+    ) -> Result<(), StoreError> {
+        self.primary_conn()?
+            .set_deployment_setting(deployment, name, value)?;
+        // Apply the change on this node right away; other nodes load it
+        // on their next restart
+        settings::set(deployment.clone(), name, value).map_err(StoreError::Unknown)
+    }
+
     fn assigned_node(&self, deployment: &DeploymentLocator) -> Result<Option<NodeId>, StoreError> {
         let site = self.find_site(deployment.id.into())?;
         self.mirror.assigned_node(site.as_ref())
@@ -1075,6 +1383,31 @@ impl SubgraphStoreTrait for SubgraphStore {
             .map(|site| site.into())
             .collect())
     }
+
+    fn export_entities(
+        &self,
+        deployment: &DeploymentHash,
+        entity_type: &EntityType,
+        block: BlockNumber,
+        batch_size: u32,
+        sink: &mut dyn FnMut(Vec<Entity>) -> Result<(), StoreError>,
+    ) -> Result<usize, StoreError> {
+        self.inner
+            .export_entities(deployment, entity_type, block, batch_size, |batch| {
+                sink(batch)
+            })
+    }
+
+    fn import_graft_base(
+        &self,
+        schema: &Schema,
+        deployment: SubgraphDeploymentEntity,
+        network: String,
+        entities: Vec<(EntityType, Vec<Entity>)>,
+    ) -> Result<DeploymentLocator, StoreError> {
+        self.inner
+            .import_graft_base(schema, deployment, network, entities)
+    }
 }
 
 /// A wrapper around `SubgraphStore` that only exposes functions that are
@@ -1124,14 +1457,19 @@ impl WritableStore {
         })
     }
 
-    fn log_backoff_warning(&self, op: &str, backoff: &ExponentialBackoff) {
+    fn log_backoff_warning(&self, op: &str, error: &StoreError, backoff: &ExponentialBackoff) {
         warn!(self.logger,
             "database unavailable, will retry";
             "operation" => op,
+            "error" => error.to_string(),
             "attempt" => backoff.attempt,
             "delay_ms" => backoff.delay().as_millis());
     }
 
+    // Note that retrying when the connection was lost mid-operation is safe
+    // because all write operations run in a transaction that did not commit
+    // when the error happened; when the database fails over to a new
+    // primary, we will simply rerun the transaction there
     fn retry<T, F>(&self, op: &str, f: F) -> Result<T, StoreError>
     where
         F: Fn() -> Result<T, StoreError>,
@@ -1140,8 +1478,8 @@ impl WritableStore {
         loop {
             match f() {
                 Ok(v) => return Ok(v),
-                Err(StoreError::DatabaseUnavailable) => {
-                    self.log_backoff_warning(op, &backoff);
+                Err(e) if e.is_unavailable() => {
+                    self.log_backoff_warning(op, &e, &backoff);
                 }
                 Err(e) => return Err(e),
             }
@@ -1158,8 +1496,8 @@ impl WritableStore {
         loop {
             match f().await {
                 Ok(v) => return Ok(v),
-                Err(StoreError::DatabaseUnavailable) => {
-                    self.log_backoff_warning(op, &backoff);
+                Err(e) if e.is_unavailable() => {
+                    self.log_backoff_warning(op, &e, &backoff);
                 }
                 Err(e) => return Err(e),
             }
@@ -1234,13 +1572,17 @@ impl WritableStoreTrait for WritableStore {
         })
     }
 
-    async fn fail_subgraph(&self, error: SubgraphError) -> Result<(), StoreError> {
+    async fn fail_subgraph(
+        &self,
+        error: SubgraphError,
+        class: ErrorClass,
+    ) -> Result<(), StoreError> {
         self.retry_async("fail_subgraph", || {
             let error = error.clone();
-            async {
+            async move {
                 self.writable
                     .clone()
-                    .fail_subgraph(self.site.deployment.clone(), error)
+                    .fail_subgraph(self.site.deployment.clone(), error, class)
                     .await
             }
         })
@@ -1299,6 +1641,13 @@ impl WritableStoreTrait for WritableStore {
         })
     }
 
+    fn entity_ids(&self, entity_type: &EntityType, first: u32) -> Result<Vec<String>, StoreError> {
+        self.retry("entity_ids", || {
+            self.writable
+                .entity_ids(self.site.cheap_clone(), entity_type, first)
+        })
+    }
+
     async fn is_deployment_synced(&self) -> Result<bool, StoreError> {
         self.retry_async("is_deployment_synced", || async {
             self.writable
@@ -0,0 +1,161 @@
+//! Support for hosting subgraphs from multiple tenants on one set of
+//! shards. When `GRAPH_TENANT_NAMESPACES` is set to `true`, the part of a
+//! subgraph name before the first `/` names the tenant that owns the
+//! deployment. For every tenant, the store maintains a database role in
+//! each shard and grants that role, and only that role, read access to
+//! the schemas of the tenant's deployments. A tenant that is given direct
+//! SQL access through a user that is a member of its role can therefore
+//! not read the entity tables of other tenants.
+//!
+//! The roles are created with `nologin`; to hand out direct SQL access,
+//! operators create a login user for the tenant and attach it to the role
+//! with `grant <role> to <user>`.
+
+use diesel::connection::SimpleConnection;
+use diesel::PgConnection;
+
+use graph::env::env_var;
+use graph::prelude::{lazy_static, StoreError, SubgraphName};
+
+use crate::primary::Namespace;
+
+lazy_static! {
+    /// Whether to treat the first segment of a subgraph name as the
+    /// tenant owning the deployment (`GRAPH_TENANT_NAMESPACES`, off by
+    /// default)
+    static ref TENANT_NAMESPACES: bool = env_var("GRAPH_TENANT_NAMESPACES", false);
+}
+
+/// The tenant owning `name`, the part of the name before the first `/`.
+/// Returns `None` when tenant namespaces are disabled or the name has no
+/// `/` in it
+pub fn from_name(name: &SubgraphName) -> Option<String> {
+    if !*TENANT_NAMESPACES {
+        return None;
+    }
+    let name = name.as_str();
+    name.find('/').map(|idx| name[..idx].to_string())
+}
+
+/// The longest identifier Postgres accepts; anything longer is silently
+/// truncated to this many bytes
+const MAX_IDENTIFIER_LENGTH: usize = 63;
+
+/// The name of the database role for `tenant`. The role becomes part of
+/// SQL statements, so the encoding only ever produces `[a-z0-9_]`, no
+/// matter how permissive `GRAPH_SUBGRAPH_NAME_PATTERN` is: lowercase
+/// letters and digits stand for themselves, and everything else a
+/// subgraph name can contain is escaped with a `_` in a way that keeps
+/// the encoding collision-free; simply folding `-` and `_` into `_`
+/// would make tenants like `foo-bar` and `foo_bar` share a role and
+/// therefore each other's grants. Tenant names with any other characters
+/// are rejected.
+///
+/// Since Postgres silently truncates identifiers to 63 bytes, names
+/// whose encoding goes over that limit are cut off and get a hash of the
+/// full tenant name appended so that long names that only differ past
+/// the truncation point still map to distinct roles
+pub fn role_name(tenant: &str) -> Result<String, StoreError> {
+    let mut role = String::from("graph_tenant_");
+    for c in tenant.chars() {
+        match c {
+            'a'..='z' | '0'..='9' => role.push(c),
+            'A'..='Z' => {
+                role.push_str("_c");
+                role.push(c.to_ascii_lowercase());
+            }
+            '_' => role.push_str("_u"),
+            '-' => role.push_str("_d"),
+            _ => {
+                return Err(StoreError::ConstraintViolation(format!(
+                    "the tenant name `{}` can not be mapped to a database \
+                     role since it contains the character `{}`; tenant \
+                     names can only use ASCII letters, digits, `-` and `_`",
+                    tenant, c
+                )))
+            }
+        }
+    }
+    if role.len() > MAX_IDENTIFIER_LENGTH {
+        let hash = blake3::hash(tenant.as_bytes());
+        let hash = hex::encode(&hash.as_bytes()[..8]);
+        role.truncate(MAX_IDENTIFIER_LENGTH - hash.len() - 1);
+        role.push('_');
+        role.push_str(&hash);
+    }
+    Ok(role)
+}
+
+/// Make sure the role for `tenant` exists in the database `conn` points
+/// to and give it read access to `namespace`, including any tables that
+/// get created in it later
+pub(crate) fn grant_access(
+    conn: &PgConnection,
+    namespace: &Namespace,
+    tenant: &str,
+) -> Result<(), StoreError> {
+    let role = role_name(tenant)?;
+    let query = format!(
+        "do $$
+         begin
+           if not exists (select from pg_roles where rolname = '{role}') then
+             begin
+               create role \"{role}\" nologin;
+             exception when duplicate_object then
+               -- another node created the role concurrently
+             end;
+           end if;
+         end
+         $$;
+         grant usage on schema {nsp} to \"{role}\";
+         grant select on all tables in schema {nsp} to \"{role}\";
+         alter default privileges in schema {nsp}
+           grant select on tables to \"{role}\";",
+        role = role,
+        nsp = namespace
+    );
+    conn.batch_execute(&query).map_err(StoreError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{role_name, MAX_IDENTIFIER_LENGTH};
+
+    fn role(tenant: &str) -> String {
+        role_name(tenant).unwrap()
+    }
+
+    #[test]
+    fn role_names_do_not_collide() {
+        // `foo-bar` and `foo_bar` are both valid tenant names and must
+        // not share a role
+        assert_eq!("graph_tenant_foo_dbar", role("foo-bar"));
+        assert_eq!("graph_tenant_foo_ubar", role("foo_bar"));
+        assert_ne!(role("foo-bar"), role("foo_bar"));
+        assert_eq!("graph_tenant_acme", role("acme"));
+        // Uppercase letters are encoded so that the role only uses
+        // lowercase letters, digits and underscores
+        assert_eq!("graph_tenant_a_ccme", role("aCme"));
+        assert_ne!(role("aCme"), role("acme"));
+    }
+
+    #[test]
+    fn long_role_names_do_not_collide() {
+        // Postgres truncates identifiers to 63 bytes; tenant names that
+        // only differ past that point must still get distinct roles
+        let long = "a".repeat(80);
+        let longer = format!("{}x", long);
+        assert!(role(&long).len() <= MAX_IDENTIFIER_LENGTH);
+        assert!(role(&longer).len() <= MAX_IDENTIFIER_LENGTH);
+        assert_ne!(role(&long), role(&longer));
+    }
+
+    #[test]
+    fn invalid_tenant_names_are_rejected() {
+        // Even if `GRAPH_SUBGRAPH_NAME_PATTERN` lets these through as
+        // subgraph names, they must never end up in a role name
+        for name in ["foo bar", "foo'; drop role postgres; --", "föö"] {
+            assert!(role_name(name).is_err(), "`{}` should be rejected", name);
+        }
+    }
+}
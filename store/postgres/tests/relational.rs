@@ -714,6 +714,7 @@ fn count_scalar_entities(conn: &PgConnection, layout: &Layout) -> usize {
             },
             BLOCK_NUMBER_MAX,
             None,
+            None,
         )
         .expect("Count query failed")
         .len()
@@ -903,6 +904,7 @@ impl<'a> QueryChecker<'a> {
                 query.range,
                 BLOCK_NUMBER_MAX,
                 None,
+                None,
             )
             .expect("layout.query failed to execute query");
 
@@ -1511,6 +1513,7 @@ fn text_find(expected_entity_ids: Vec<&str>, filter: EntityFilter) {
                 query.range,
                 BLOCK_NUMBER_MAX,
                 None,
+                None,
             )
             .expect("layout.query failed to execute query");
 
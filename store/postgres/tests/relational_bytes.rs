@@ -408,6 +408,7 @@ fn query() {
                 EntityRange::first(10),
                 BLOCK_NUMBER_MAX,
                 None,
+                None,
             )
             .expect("the query succeeds")
             .into_iter()
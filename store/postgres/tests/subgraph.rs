@@ -3,6 +3,7 @@ use graph::{
         server::index_node::VersionInfo,
         store::{DeploymentLocator, StatusStore},
     },
+    data::subgraph::schema::ErrorClass,
     data::subgraph::schema::SubgraphError,
     data::subgraph::schema::SubgraphHealth,
     prelude::EntityChange,
@@ -396,7 +397,7 @@ fn status() {
             .writable(LOGGER.clone(), deployment.id)
             .await
             .expect("can get writable")
-            .fail_subgraph(error)
+            .fail_subgraph(error, ErrorClass::Deterministic)
             .await
             .unwrap();
         let infos = store
@@ -541,7 +542,7 @@ fn fatal_vs_non_fatal() {
             .writable(LOGGER.clone(), deployment.id)
             .await
             .expect("can get writable")
-            .fail_subgraph(error())
+            .fail_subgraph(error(), ErrorClass::Deterministic)
             .await
             .unwrap();
 
@@ -620,7 +621,10 @@ fn fail_unfail_deterministic_error() {
             .expect("can get writable");
 
         // Fail the subgraph with a deterministic error.
-        writable.fail_subgraph(error).await.unwrap();
+        writable
+            .fail_subgraph(error, ErrorClass::Deterministic)
+            .await
+            .unwrap();
 
         // Now we have a fatal error because the subgraph failed.
         assert!(query_store.has_non_fatal_errors(None).await.unwrap());
@@ -722,7 +726,10 @@ fn fail_unfail_deterministic_error_noop() {
         };
 
         // Fail the subraph with a NON-deterministic error.
-        writable.fail_subgraph(error).await.unwrap();
+        writable
+            .fail_subgraph(error, ErrorClass::Unknown)
+            .await
+            .unwrap();
 
         // Now we have a fatal error because the subgraph failed.
         assert_eq!(count(), 1);
@@ -753,7 +760,10 @@ fn fail_unfail_deterministic_error_noop() {
         };
 
         // Fail the subgraph with an advanced block.
-        writable.fail_subgraph(error).await.unwrap();
+        writable
+            .fail_subgraph(error, ErrorClass::Deterministic)
+            .await
+            .unwrap();
 
         // Running unfail_deterministic_error won't do anything,
         // the hashes won't match and there's nothing to revert.
@@ -822,7 +832,10 @@ fn fail_unfail_non_deterministic_error() {
             .expect("can get writable");
 
         // Fail subgraph with a non-deterministic error.
-        writable.fail_subgraph(error).await.unwrap();
+        writable
+            .fail_subgraph(error, ErrorClass::Unknown)
+            .await
+            .unwrap();
 
         // Now we have a fatal error because the subgraph failed.
         assert_eq!(count(), 1);
@@ -936,7 +949,10 @@ fn fail_unfail_non_deterministic_error_noop() {
         };
 
         // Fail the subgraph with a DETERMININISTIC error.
-        writable.fail_subgraph(error).await.unwrap();
+        writable
+            .fail_subgraph(error, ErrorClass::Deterministic)
+            .await
+            .unwrap();
 
         // We now have a fatal error because the subgraph failed.
         assert_eq!(count(), 1);
@@ -964,7 +980,10 @@ fn fail_unfail_non_deterministic_error_noop() {
         };
 
         // Fail the subgraph with a non-deterministic error, but with an advanced block.
-        writable.fail_subgraph(error).await.unwrap();
+        writable
+            .fail_subgraph(error, ErrorClass::Unknown)
+            .await
+            .unwrap();
 
         // Since the block range of the block won't match the deployment head, this will be NOOP.
         writable.unfail_non_deterministic_error(&BLOCKS[1]).unwrap();
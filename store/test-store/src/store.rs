@@ -27,6 +27,7 @@ use graph_store_postgres::{
 use hex_literal::hex;
 use lazy_static::lazy_static;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Instant;
 use std::{collections::BTreeSet, env};
 use std::{marker::PhantomData, sync::Mutex};
@@ -118,6 +119,59 @@ where
     })
 }
 
+/// Counter for generating deployment hashes that are unique within this
+/// test process; see `unique_deployment_hash`
+static TEST_DEPLOYMENT_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A deployment hash that no other test in this process will use. Since
+/// each deployment gets its own database namespace, tests that only touch
+/// their own deployment are isolated from each other
+pub fn unique_deployment_hash(prefix: &str) -> DeploymentHash {
+    let id = TEST_DEPLOYMENT_COUNTER.fetch_add(1, Ordering::SeqCst);
+    DeploymentHash::new(format!("{}Test{}", prefix, id)).expect("valid deployment hash")
+}
+
+/// Run the `test` against a freshly created deployment with the given
+/// `schema`. Unlike `run_test_sequentially`, tests using this helper can run
+/// in parallel: each gets its own deployment, and with it its own database
+/// namespace, while sharing the connection pool behind the global `STORE`.
+/// The deployment is removed when the test finishes, even if it panics.
+pub fn run_test_isolated<R, F>(prefix: &str, schema: &str, test: F)
+where
+    F: FnOnce(Arc<Store>, DeploymentLocator) -> R + Send + 'static,
+    R: std::future::Future<Output = ()> + Send + 'static,
+{
+    let hash = unique_deployment_hash(prefix);
+    let deployment = create_test_subgraph(&hash, schema);
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        STORE_RUNTIME.handle().block_on(async {
+            let store = STORE.clone();
+            test(store, deployment.clone()).await
+        })
+    }));
+
+    remove_subgraph(&hash);
+
+    if let Err(panic) = result {
+        std::panic::resume_unwind(panic);
+    }
+}
+
+/// Create a fresh deployment with `schema` and insert `entities` at the
+/// genesis block. The deployment hash is generated with
+/// `unique_deployment_hash` so that callers do not step on each other.
+pub fn seed_subgraph(
+    prefix: &str,
+    schema: &str,
+    entities: Vec<(EntityType, Entity)>,
+) -> DeploymentLocator {
+    let hash = unique_deployment_hash(prefix);
+    let deployment = create_test_subgraph(&hash, schema);
+    insert_entities(&deployment, entities).expect("can insert seed entities");
+    deployment
+}
+
 /// Run a test with a connection into the primary database, not a full store
 pub fn run_test_with_conn<F>(test: F)
 where
@@ -419,7 +473,9 @@ async fn execute_subgraph_query_internal(
                 bc,
                 error_policy,
                 query.schema.id().clone(),
-                result_size_metrics()
+                result_size_metrics(),
+                None,
+                None
             )
             .await
         );